@@ -0,0 +1,106 @@
+//! 作者写法 -> CASC 规范路径的映射表
+//!
+//! Mod 作者常写 `data/global/excel/weapons.txt`、`Data\Global\...` 或完整的
+//! `data:data/...`, 而 CASC 中的同一文件只有一个规范路径。查找前先统一映射,
+//! 减少失败的 `entry.open()` 尝试, 也让同一文件的不同写法共用一个缓存项。
+
+/// 路径别名表
+///
+/// 规则按顺序匹配, 只应用第一条匹配的规则。以 `/` 或 `:` 结尾的规则替换前缀, 否则只匹配
+/// 完整路径。匹配前路径已统一为小写、正斜杠、无开头的 `/`。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathAliases {
+    rules: Vec<(String, String)>,
+}
+
+/// 内置规则: 去掉 D2R 存储命名空间和 `data/` 目录前缀 (`hd/` 资源保留自己的前缀)
+const DEFAULT_RULES: &[(&str, &str)] = &[
+    ("data:data/", ""),
+    ("data:", ""),
+    ("data/hd/", "hd/"),
+    ("data/", ""),
+];
+
+impl Default for PathAliases {
+    fn default() -> Self {
+        Self {
+            rules: DEFAULT_RULES
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl PathAliases {
+    /// 不含任何规则 (只做大小写和分隔符统一)
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// 添加一条规则, 优先于已有规则 (包括内置规则)
+    pub fn add(&mut self, from: &str, to: &str) {
+        self.rules.insert(0, (normalize(from), normalize(to)));
+    }
+
+    /// 映射为规范路径
+    pub fn canonicalize(&self, path: &str) -> String {
+        let path = normalize(path);
+        for (from, to) in &self.rules {
+            if from.ends_with(['/', ':']) {
+                if let Some(rest) = path.strip_prefix(from.as_str()) {
+                    return format!("{}{}", to, rest);
+                }
+            } else if path == *from {
+                return to.clone();
+            }
+        }
+        path
+    }
+}
+
+/// 小写、正斜杠、去掉开头的 `/`
+fn normalize(path: &str) -> String {
+    path.replace('\\', "/").trim_start_matches('/').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_aliases() {
+        let aliases = PathAliases::default();
+        for path in [
+            "global/excel/weapons.txt",
+            "Global\\Excel\\Weapons.txt",
+            "/global/excel/weapons.txt",
+            "data/global/excel/weapons.txt",
+            "data:data\\global\\excel\\weapons.txt",
+            "data:global/excel/weapons.txt",
+        ] {
+            assert_eq!(aliases.canonicalize(path), "global/excel/weapons.txt", "{}", path);
+        }
+        assert_eq!(aliases.canonicalize("data/hd/items/items.json"), "hd/items/items.json");
+        assert_eq!(aliases.canonicalize("hd/items/items.json"), "hd/items/items.json");
+    }
+
+    #[test]
+    fn test_custom_aliases() {
+        let mut aliases = PathAliases::default();
+        aliases.add("excel/", "global/excel/");
+        aliases.add("local/lng/strings/item-names.json", "local/lng/strings/item-names.json.bak");
+
+        assert_eq!(aliases.canonicalize("Excel/Weapons.txt"), "global/excel/weapons.txt");
+        assert_eq!(
+            aliases.canonicalize("local/lng/strings/item-names.json"),
+            "local/lng/strings/item-names.json.bak"
+        );
+        // Exact rules don't match longer paths
+        assert_eq!(
+            aliases.canonicalize("local/lng/strings/item-names.json.old"),
+            "local/lng/strings/item-names.json.old"
+        );
+        assert_eq!(PathAliases::empty().canonicalize("data/global/x.txt"), "data/global/x.txt");
+    }
+}
@@ -2,6 +2,8 @@
 //! 
 //! 用于从 Diablo II: Resurrected 的 CASC 存档中提取游戏数据文件。
 
+pub mod aliases;
 pub mod storage;
 
-pub use storage::{CascStorage, CascError};
+pub use aliases::PathAliases;
+pub use storage::{CascStorage, CascError, OpenRetry};
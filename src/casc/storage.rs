@@ -1,8 +1,13 @@
 //! CASC 存档操作封装
 
+use super::PathAliases;
 use anyhow::{Context, Result};
 use casclib::Storage;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 use tracing::{info, debug, warn};
 
 /// CASC 错误类型
@@ -21,15 +26,238 @@ pub enum CascError {
     InvalidPath(String),
 }
 
+/// 候选路径格式的数量 (见 `path_variant`)
+const VARIANT_COUNT: usize = 5;
+
+/// 尚未学习到成功格式时的标记值
+const NO_PREFERRED_VARIANT: usize = usize::MAX;
+
+/// 生成第 `index` 种候选路径格式
+/// D2R CASC 使用 "data:data\\" 前缀
+fn path_variant(path_str: &str, index: usize) -> String {
+    match index {
+        0 => format!("data:data\\{}", path_str),  // D2R 标准格式
+        1 => format!("data:data/{}", path_str),    // 正斜杠版本
+        2 => path_str.to_string(),                 // 原始路径
+        3 => path_str.replace('/', "\\"),
+        _ => path_str.replace('\\', "/"),
+    }
+}
+
+/// 按 file data ID 打开文件时使用的名称 (CascLib 把 `FILE%08X.dat` 形式的名称当作 ID 打开)
+fn file_data_id_name(fdid: u32) -> String {
+    format!("FILE{:08X}.dat", fdid)
+}
+
+/// 计算候选格式的尝试顺序: 上次成功的格式优先, 其余按默认顺序回退
+fn variant_order(preferred: Option<usize>) -> Vec<usize> {
+    let mut order = Vec::with_capacity(VARIANT_COUNT);
+    if let Some(index) = preferred.filter(|i| *i < VARIANT_COUNT) {
+        order.push(index);
+    }
+    order.extend((0..VARIANT_COUNT).filter(|i| Some(*i) != preferred));
+    order
+}
+
+/// 打开 CASC 存档失败时的重试策略
+///
+/// 游戏刚退出时存档可能仍被锁定, 稍等片刻后通常就能打开。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenRetry {
+    /// 首次失败后的最多重试次数
+    pub retries: u32,
+    /// 第一次重试前的等待时间, 之后每次翻倍
+    pub initial_delay: Duration,
+}
+
+impl OpenRetry {
+    /// 不重试
+    pub const NONE: Self = Self { retries: 0, initial_delay: Duration::ZERO };
+}
+
+impl Default for OpenRetry {
+    /// 重试 3 次 (等待 0.5s、1s、2s)
+    fn default() -> Self {
+        Self { retries: 3, initial_delay: Duration::from_millis(500) }
+    }
+}
+
+/// 自动探测存储根目录时向下搜索的层数
+const ROOT_SEARCH_DEPTH: usize = 2;
+
+/// 按尝试顺序列出候选存储根目录
+///
+/// 顺序: `extra_roots` (相对路径基于游戏目录), `<game>/Data`, 游戏目录本身,
+/// 然后是游戏目录下 (最多两层) 带有 CASC 标记的目录。重复项只保留第一次出现。
+pub fn casc_root_candidates(game_path: &Path, extra_roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = extra_roots.iter().map(|root| game_path.join(root)).collect();
+    candidates.push(game_path.join("Data"));
+    candidates.push(game_path.to_path_buf());
+
+    let detected = walkdir::WalkDir::new(game_path)
+        .min_depth(1)
+        .max_depth(ROOT_SEARCH_DEPTH)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_dir() && is_casc_root(entry.path()))
+        .map(|entry| entry.into_path());
+    candidates.extend(detected);
+
+    let mut unique = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        if !unique.contains(&candidate) {
+            unique.push(candidate);
+        }
+    }
+    unique
+}
+
+/// 目录是否带有 CASC 存储标记: `.build.info`, 或 `config` 目录加上 `*.idx` 索引
+/// (直接位于目录中或其 `data` 子目录中)
+fn is_casc_root(dir: &Path) -> bool {
+    let has_index = |dir: &Path| {
+        std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .any(|entry| entry.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("idx")))
+            })
+            .unwrap_or(false)
+    };
+
+    dir.join(".build.info").is_file()
+        || (dir.join("config").is_dir() && (has_index(dir) || has_index(&dir.join("data"))))
+}
+
+/// 缓存键: 小写 + 正斜杠, 同一文件的不同写法共用一个缓存项
+fn cache_key(path: &str) -> String {
+    path.replace('\\', "/").to_lowercase()
+}
+
+/// 获取互斥锁 (持锁线程 panic 后仍可继续使用)
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// 串行访问存档句柄
+///
+/// CascLib 不保证在同一个存储句柄上并发 `entry().open()` / `extract()` 是安全的,
+/// 所以所有句柄访问都经过 `source` 锁。读取的内容不在这里缓存 (内存由文件管理器的
+/// 缓存预算控制); 写入磁盘等操作在锁外进行, 并行下载/提取互不阻塞。
+struct SharedReader<S> {
+    source: Mutex<S>,
+}
+
+impl<S> SharedReader<S> {
+    fn new(source: S) -> Self {
+        Self {
+            source: Mutex::new(source),
+        }
+    }
+
+    /// 持有句柄锁执行 `f`
+    fn with_source<T>(&self, f: impl FnOnce(&S) -> T) -> T {
+        f(&lock(&self.source))
+    }
+}
+
+/// 路径查找记录: 每个路径对应的存档文件名 (或确认不存在), 以及最近成功的路径格式
+///
+/// 存档是只读的, 所以查找结果 (包括不存在) 在存档打开期间一直有效, 同一路径不必重复
+/// 尝试所有候选格式。
+struct PathLookup {
+    /// 缓存键 -> 可打开的存档文件名; `None` 表示所有格式都试过且不存在
+    resolved: Mutex<HashMap<String, Option<String>>>,
+    /// 上次成功打开文件所用的路径格式索引
+    preferred_variant: AtomicUsize,
+    /// 失败的 `entry.open()` 调用次数 (用于诊断)
+    failed_opens: AtomicU64,
+}
+
+impl PathLookup {
+    fn new() -> Self {
+        Self {
+            resolved: Mutex::new(HashMap::new()),
+            preferred_variant: AtomicUsize::new(NO_PREFERRED_VARIANT),
+            failed_opens: AtomicU64::new(0),
+        }
+    }
+
+    /// 用 `open` 打开 `path` 对应的存档文件
+    ///
+    /// 已知文件名的路径只尝试该文件名, 确认不存在的路径不再尝试; 其余按格式顺序逐个
+    /// 尝试 (上次成功的格式优先) 并记录结果。
+    fn open<T>(&self, path: &str, mut open: impl FnMut(&str) -> Option<T>) -> Option<T> {
+        let key = cache_key(path);
+        let known = lock(&self.resolved).get(&key).cloned();
+        match known {
+            Some(None) => return None,
+            Some(Some(name)) => {
+                if let Some(found) = open(&name) {
+                    return Some(found);
+                }
+                self.record_failed_open();
+            }
+            None => {}
+        }
+
+        for index in self.current_variant_order() {
+            let variant = path_variant(path, index);
+            if let Some(found) = open(&variant) {
+                self.remember_variant(index);
+                lock(&self.resolved).insert(key, Some(variant));
+                return Some(found);
+            }
+            self.record_failed_open();
+        }
+        lock(&self.resolved).insert(key, None);
+        None
+    }
+
+    /// 当前路径格式的尝试顺序
+    fn current_variant_order(&self) -> Vec<usize> {
+        let preferred = self.preferred_variant.load(Ordering::Relaxed);
+        variant_order((preferred != NO_PREFERRED_VARIANT).then_some(preferred))
+    }
+
+    /// 记住成功的路径格式, 后续文件优先尝试
+    fn remember_variant(&self, index: usize) {
+        let previous = self.preferred_variant.swap(index, Ordering::Relaxed);
+        if previous != index {
+            debug!("CASC path variant {} is now preferred", index);
+        }
+    }
+
+    /// 记录一次失败的打开尝试
+    fn record_failed_open(&self) {
+        self.failed_opens.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        lock(&self.resolved).clear();
+    }
+}
+
 /// CASC 存档管理器
+///
+/// 可在多个线程间共享 (`Arc<CascStorage>`): 存档句柄的访问是串行的。
 pub struct CascStorage {
-    storage: Storage,
+    storage: SharedReader<Storage>,
     game_path: PathBuf,
+    /// 实际打开的存储根目录
+    root: PathBuf,
+    /// 已查找过的路径及其结果
+    lookup: PathLookup,
+    /// 查找前把作者写法映射为规范路径
+    aliases: PathAliases,
 }
 
 impl CascStorage {
     /// 打开 CASC 存档
     /// 
+    /// 依次尝试 `<game>/Data`、游戏目录以及自动探测到的存储根目录
+    /// 
     /// # 参数
     /// * `game_path` - 游戏安装目录路径
     /// 
@@ -40,6 +268,15 @@ impl CascStorage {
     /// let storage = CascStorage::open("C:\\Program Files (x86)\\Diablo II Resurrected")?;
     /// ```
     pub fn open<P: AsRef<Path>>(game_path: P) -> Result<Self> {
+        Self::open_with_roots(game_path, &[])
+    }
+
+    /// 打开 CASC 存档, 依次尝试候选存储根目录 (见 [`casc_root_candidates`])
+    ///
+    /// # 参数
+    /// * `game_path` - 游戏安装目录路径
+    /// * `extra_roots` - 优先尝试的存储根目录 (相对路径基于游戏目录, 对应 `--casc-root`)
+    pub fn open_with_roots<P: AsRef<Path>>(game_path: P, extra_roots: &[PathBuf]) -> Result<Self> {
         let game_path = game_path.as_ref().to_path_buf();
         
         if !game_path.exists() {
@@ -48,53 +285,122 @@ impl CascStorage {
             ).into());
         }
         
-        info!("Opening CASC storage at: {}", game_path.display());
-        
-        // D2R 的数据通常在 Data 子目录中
-        let data_path = game_path.join("Data");
-        let storage_path = if data_path.exists() {
-            data_path
-        } else {
-            game_path.clone()
-        };
-        
-        let storage = casclib::open(storage_path.to_str().ok_or_else(|| {
-            CascError::InvalidPath("Path contains invalid UTF-8".to_string())
-        })?)
-        .map_err(|e| CascError::OpenFailed(format!("{:?}", e)))?;
-        
-        info!("CASC storage opened successfully");
+        let mut failures = Vec::new();
+        for root in casc_root_candidates(&game_path, extra_roots) {
+            info!("Opening CASC storage at: {}", root.display());
+            
+            let Some(root_str) = root.to_str() else {
+                failures.push(format!("{} (path contains invalid UTF-8)", root.display()));
+                continue;
+            };
+            
+            match casclib::open(root_str) {
+                Ok(storage) => {
+                    info!("CASC storage opened successfully from: {}", root.display());
+                    return Ok(Self {
+                        storage: SharedReader::new(storage),
+                        game_path,
+                        root,
+                        lookup: PathLookup::new(),
+                        aliases: PathAliases::default(),
+                    });
+                }
+                Err(e) => {
+                    debug!("Failed to open CASC storage at {}: {:?}", root.display(), e);
+                    failures.push(format!("{} ({:?})", root.display(), e));
+                }
+            }
+        }
         
-        Ok(Self {
-            storage,
-            game_path,
-        })
+        Err(CascError::OpenFailed(format!("tried {}", failures.join(", "))).into())
+    }
+
+    /// 同 [`open_with_roots`](Self::open_with_roots), 失败时按 `retry` 退避重试
+    ///
+    /// 游戏目录不存在时立即返回错误。重试期间阻塞当前线程。
+    pub fn open_with_retry<P: AsRef<Path>>(game_path: P, extra_roots: &[PathBuf], retry: OpenRetry) -> Result<Self> {
+        let game_path = game_path.as_ref();
+        let mut delay = retry.initial_delay;
+        let mut attempt = 0;
+        loop {
+            match Self::open_with_roots(game_path, extra_roots) {
+                Ok(storage) => return Ok(storage),
+                Err(e) if attempt >= retry.retries || matches!(e.downcast_ref(), Some(CascError::InvalidPath(_))) => {
+                    return Err(e);
+                }
+                Err(e) => {
+                    attempt += 1;
+                    warn!("{} (retrying in {:.1}s, attempt {}/{})", e, delay.as_secs_f64(), attempt, retry.retries);
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+            }
+        }
+    }
+
+    /// 成功打开的存储根目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 替换路径别名表 (默认为 [`PathAliases::default`])
+    pub fn with_path_aliases(mut self, aliases: PathAliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// 失败的 `entry.open()` 调用总数
+    pub fn failed_open_count(&self) -> u64 {
+        self.lookup.failed_opens.load(Ordering::Relaxed)
+    }
+
+    /// 忘记已查找过的路径 (包括确认不存在的), 下次查找重新尝试所有格式
+    pub fn clear_lookup_cache(&self) {
+        self.lookup.clear();
     }
     
     /// 检查文件是否存在于 CASC 存档中
     pub fn has_file<P: AsRef<Path>>(&self, path: P) -> bool {
-        let path_str = path.as_ref().to_string_lossy();
-        
-        // 尝试多种路径格式
-        // D2R CASC 使用 "data:data\\" 前缀
-        let variations = vec![
-            format!("data:data\\{}", path_str),  // D2R 标准格式
-            format!("data:data/{}", path_str),    // 正斜杠版本
-            path_str.to_string(),                 // 原始路径
-            path_str.replace("/", "\\"),
-            path_str.replace("\\", "/"),
-        ];
-        
-        for variant in variations {
-            debug!("Checking CASC file: {}", variant);
-            // casclib API: storage.entry(path) returns FileEntry directly
-            let entry = self.storage.entry(&variant);
-            if entry.open().is_ok() {
-                return true;
-            }
-        }
-        
-        false
+        let path_str = self.aliases.canonicalize(&path.as_ref().to_string_lossy());
+        self.storage.with_source(|storage| {
+            self.lookup
+                .open(&path_str, |variant| {
+                    debug!("Checking CASC file: {}", variant);
+                    // casclib API: storage.entry(path) returns FileEntry directly
+                    storage.entry(variant).open().ok()
+                })
+                .is_some()
+        })
+    }
+
+    /// 读取文件内容 (存档句柄访问串行化)
+    fn read_shared(&self, casc_path_str: &str) -> Result<Vec<u8>> {
+        let casc_path_str = &self.aliases.canonicalize(casc_path_str);
+        self.storage.with_source(|storage| {
+            let mut last_error = None;
+            let file = self.lookup.open(casc_path_str, |variant| {
+                debug!("Trying CASC path variant: {}", variant);
+                storage
+                    .entry(variant)
+                    .open()
+                    .map_err(|e| last_error = Some(format!("{:?}", e)))
+                    .ok()
+            });
+
+            let Some(file) = file else {
+                return Err(CascError::FileNotFound(format!(
+                    "{} (last error: {})",
+                    casc_path_str,
+                    last_error.unwrap_or_else(|| "not found by an earlier lookup".to_string())
+                )).into());
+            };
+            info!("✓ Found file in CASC: {}", casc_path_str);
+
+            let mut buffer = Vec::new();
+            file.extract(&mut buffer)
+                .map_err(|e| CascError::ExtractionFailed(format!("{:?}", e)))?;
+            Ok(buffer)
+        })
     }
     
     /// 从 CASC 存档中提取文件
@@ -116,61 +422,17 @@ impl CascStorage {
         
         debug!("Extracting file: {} -> {}", casc_path_str, output_path.display());
         
-        // 尝试多种路径格式
-        // D2R CASC 使用 "data:data\\" 前缀
-        let variations = vec![
-            format!("data:data\\{}", casc_path_str),  // D2R 标准格式
-            format!("data:data/{}", casc_path_str),    // 正斜杠版本
-            casc_path_str.to_string(),                 // 原始路径
-            casc_path_str.replace("/", "\\"),
-            casc_path_str.replace("\\", "/"),
-        ];
-        
-        let mut last_error = None;
+        let content = self.read_shared(&casc_path_str)?;
+        write_extracted(output_path, &content)?;
         
-        for variant in variations {
-            debug!("Trying CASC path variant: {}", variant);
-            
-            let entry = self.storage.entry(&variant);
-            match entry.open() {
-                Ok(file) => {
-                    info!("✓ Found file in CASC: {}", variant);
-                    
-                    // 创建输出目录
-                    if let Some(parent) = output_path.parent() {
-                        std::fs::create_dir_all(parent)
-                            .context("Failed to create output directory")?;
-                    }
-                    
-                    // 提取文件
-                    let mut writer = std::fs::File::create(output_path)
-                        .context("Failed to create output file")?;
-                    
-                    file.extract(&mut writer)
-                        .map_err(|e| CascError::ExtractionFailed(format!("{:?}", e)))?;
-                    
-                    let file_size = output_path.metadata()?.len() as usize;
-                    
-                    info!(
-                        "Extracted: {} ({} bytes) -> {}",
-                        casc_path_str,
-                        file_size,
-                        output_path.display()
-                    );
-                    
-                    return Ok(file_size);
-                }
-                Err(e) => {
-                    last_error = Some(format!("{:?}", e));
-                }
-            }
-        }
-        
-        Err(CascError::FileNotFound(format!(
-            "{} (last error: {})",
+        info!(
+            "Extracted: {} ({} bytes) -> {}",
             casc_path_str,
-            last_error.unwrap_or_else(|| "unknown".to_string())
-        )).into())
+            content.len(),
+            output_path.display()
+        );
+        
+        Ok(content.len())
     }
     
     /// 提取文件到内存
@@ -186,57 +448,74 @@ impl CascStorage {
         
         debug!("Extracting to memory: {}", casc_path_str);
         
-        // 尝试多种路径格式
-        // D2R CASC 使用 "data:data\\" 前缀
-        let variations = vec![
-            format!("data:data\\{}", casc_path_str),  // D2R 标准格式
-            format!("data:data/{}", casc_path_str),    // 正斜杠版本
-            casc_path_str.to_string(),                 // 原始路径
-            casc_path_str.replace("/", "\\"),
-            casc_path_str.replace("\\", "/"),
-        ];
-        
-        let mut last_error = None;
-        
-        for variant in variations {
-            let entry = self.storage.entry(&variant);
-            match entry.open() {
-                Ok(file) => {
-                    let mut buffer = Vec::new();
-                    
-                    file.extract(&mut buffer)
-                        .map_err(|e| CascError::ExtractionFailed(format!("{:?}", e)))?;
-                    
-                    info!(
-                        "Extracted to memory: {} ({} bytes)",
-                        casc_path_str,
-                        buffer.len()
-                    );
-                    
-                    return Ok(buffer);
-                }
-                Err(e) => {
-                    last_error = Some(format!("{:?}", e));
-                }
-            }
-        }
-        
-        Err(CascError::FileNotFound(format!(
-            "{} (last error: {})",
+        let content = self.read_shared(&casc_path_str)?;
+        info!(
+            "Extracted to memory: {} ({} bytes)",
             casc_path_str,
-            last_error.unwrap_or_else(|| "unknown".to_string())
-        )).into())
+            content.len()
+        );
+        
+        Ok(content)
     }
     
-    /// 列出所有文件
-    /// 
+    /// 按 file data ID 读取文件内容 (存档句柄访问串行化)
+    fn read_id_shared(&self, fdid: u32) -> Result<Vec<u8>> {
+        self.storage.with_source(|storage| {
+            let file = storage.entry(&file_data_id_name(fdid)).open().map_err(|e| {
+                self.lookup.record_failed_open();
+                CascError::FileNotFound(format!("file data ID {} ({:?})", fdid, e))
+            })?;
+
+            let mut buffer = Vec::new();
+            file.extract(&mut buffer)
+                .map_err(|e| CascError::ExtractionFailed(format!("file data ID {}: {:?}", fdid, e)))?;
+            Ok(buffer)
+        })
+    }
+
+    /// 按 file data ID 提取文件, 用于没有稳定文件名的数据
+    ///
+    /// # 返回
+    /// 成功时返回提取的字节数
+    pub fn extract_by_id(&self, fdid: u32, output_path: &Path) -> Result<usize> {
+        debug!("Extracting file data ID {} -> {}", fdid, output_path.display());
+
+        let content = self.read_id_shared(fdid)?;
+        write_extracted(output_path, &content)?;
+
+        info!("Extracted: file data ID {} ({} bytes) -> {}", fdid, content.len(), output_path.display());
+        Ok(content.len())
+    }
+
+    /// 按 file data ID 提取文件到内存
+    pub fn extract_id_to_memory(&self, fdid: u32) -> Result<Vec<u8>> {
+        let content = self.read_id_shared(fdid)?;
+        info!("Extracted to memory: file data ID {} ({} bytes)", fdid, content.len());
+        Ok(content)
+    }
+    
+    /// 列出存档中的文件
+    ///
+    /// # 参数
+    /// * `filter` - 可选的 glob 模式 (如 `global/excel/*.txt`, 也可带 `data/` 前缀), 不区分大小写
+    ///
     /// # 返回
-    /// 所有文件路径的迭代器
-    pub fn list_files(&self) -> Result<Vec<String>> {
-        // TODO: 实现文件列表功能
-        // casclib API 的 files() 方法需要进一步研究
-        warn!("list_files() not yet fully implemented");
-        Ok(Vec::new())
+    /// 脚本中使用的路径 (去掉 `data:data\` 前缀, 小写、正斜杠), 已排序去重
+    pub fn list_files(&self, filter: Option<&str>) -> Result<Vec<String>> {
+        let names = self.storage.with_source(|storage| {
+            storage
+                .files()
+                .filter_map(|entry| match entry {
+                    Ok(entry) => Some(entry.get_name().to_string()),
+                    Err(e) => {
+                        debug!("Skipping unreadable CASC entry: {:?}", e);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        info!("CASC storage lists {} files", names.len());
+        listing_paths(names, filter, &self.aliases)
     }
     
     /// 获取游戏路径
@@ -245,9 +524,51 @@ impl CascStorage {
     }
 }
 
+/// 把提取的内容写入磁盘 (不持有存档句柄锁)
+///
+/// 先写 .part 再重命名, 中途失败不会留下截断的文件。
+fn write_extracted(output_path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .context("Failed to create output directory")?;
+    }
+    crate::file_system::write_atomic(output_path, content)
+        .context("Failed to create output file")
+}
+
+/// 把存档中的文件名转换为脚本路径, 按 `filter` 过滤后排序去重
+///
+/// 文件名只套用内置规则 (自定义别名是脚本路径 -> 存档路径的方向); `filter` 按 `aliases` 映射,
+/// 所以写成 `data/global/excel/*.txt` 也能匹配。
+fn listing_paths(names: Vec<String>, filter: Option<&str>, aliases: &PathAliases) -> Result<Vec<String>> {
+    let pattern = filter
+        .map(|filter| {
+            glob::Pattern::new(&aliases.canonicalize(filter))
+                .with_context(|| format!("Invalid file filter: {}", filter))
+        })
+        .transpose()?;
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    let builtin = PathAliases::default();
+    let mut paths: Vec<String> = names
+        .iter()
+        .map(|name| builtin.canonicalize(name))
+        .filter(|path| pattern.as_ref().is_none_or(|p| p.matches_with(path, options)))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
 #[cfg(test)]
 mod tests {
     // Tests will be added as we understand the casclib API better
+    use super::*;
+    use std::sync::Arc;
     
     #[test]
     fn test_path_normalization() {
@@ -263,4 +584,141 @@ mod tests {
             assert!(forward.contains("/") || backward.contains("\\"));
         }
     }
+
+    #[test]
+    fn test_variant_order_prefers_learned_variant() {
+        assert_eq!(variant_order(None), vec![0, 1, 2, 3, 4]);
+        assert_eq!(variant_order(Some(2)), vec![2, 0, 1, 3, 4]);
+        // Out-of-range indices fall back to the default order
+        assert_eq!(variant_order(Some(VARIANT_COUNT)), vec![0, 1, 2, 3, 4]);
+
+        assert_eq!(path_variant("global/excel/misc.txt", 0), "data:data\\global/excel/misc.txt");
+        assert_eq!(path_variant("global/excel/misc.txt", 3), "global\\excel\\misc.txt");
+        assert_eq!(file_data_id_name(0x1A2B), "FILE00001A2B.dat");
+    }
+
+    #[test]
+    fn test_listing_paths() {
+        let names: Vec<String> = [
+            "data:data\\global\\excel\\Armor.txt",
+            "data:data\\global\\excel\\weapons.txt",
+            "data:data\\global\\excel\\armor.txt",
+            "data:data\\global\\excel\\desecrated\\armor.txt",
+            "data:data\\hd\\items\\items.json",
+            "data:data\\local\\lng\\strings\\item-names.json",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        let aliases = PathAliases::default();
+
+        let all = listing_paths(names.clone(), None, &aliases).unwrap();
+        assert_eq!(all.len(), 5);
+        assert_eq!(all[0], "global/excel/armor.txt");
+        assert!(all.contains(&"hd/items/items.json".to_string()));
+
+        // `*` doesn't cross directories; `data/` prefixes and case are ignored
+        for filter in ["global/excel/*.txt", "data/Global/Excel/*.TXT", "data:data\\global\\excel\\*.txt"] {
+            assert_eq!(
+                listing_paths(names.clone(), Some(filter), &aliases).unwrap(),
+                vec!["global/excel/armor.txt", "global/excel/weapons.txt"],
+                "{}",
+                filter
+            );
+        }
+        assert_eq!(listing_paths(names.clone(), Some("**/armor.txt"), &aliases).unwrap().len(), 2);
+        assert!(listing_paths(names, Some("global/[excel"), &aliases).is_err());
+    }
+
+    #[test]
+    fn test_casc_root_candidates() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let game = temp.path();
+        // Relocated storage with the usual config + data/*.idx layout
+        std::fs::create_dir_all(game.join("Storage/Main/config")).unwrap();
+        std::fs::create_dir_all(game.join("Storage/Main/data")).unwrap();
+        std::fs::write(game.join("Storage/Main/data/0000000001.idx"), "").unwrap();
+        // Not a storage: config without indices
+        std::fs::create_dir_all(game.join("Tools/config")).unwrap();
+
+        let candidates = casc_root_candidates(game, &[PathBuf::from("Custom"), PathBuf::from("Data")]);
+        assert_eq!(
+            candidates,
+            vec![
+                game.join("Custom"),
+                game.join("Data"),
+                game.to_path_buf(),
+                game.join("Storage/Main"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_lookup_caches_hits_and_misses() {
+        // 模拟存档: 只有 `data:data/` 格式 (第 1 种) 的文件名能打开
+        let archive = ["data:data/global/excel/armor.txt", "data:data/global/excel/weapons.txt"];
+        let lookup = PathLookup::new();
+        // 返回查找结果和这次打开尝试的次数
+        let open = |path: &str| {
+            let mut probes = 0;
+            let found = lookup.open(path, |name| {
+                probes += 1;
+                archive.contains(&name).then_some(())
+            });
+            (found.is_some(), probes)
+        };
+
+        assert_eq!(open("global/excel/armor.txt"), (true, 2));
+        assert_eq!(open("global/excel/armor.txt"), (true, 1));
+        assert_eq!(open("Global\\Excel\\Armor.txt"), (true, 1));
+        // The learned variant is tried first for new paths
+        assert_eq!(open("global/excel/weapons.txt"), (true, 1));
+        assert_eq!(open("global/excel/missing.txt"), (false, VARIANT_COUNT));
+        assert_eq!(open("global/excel/missing.txt"), (false, 0));
+
+        lookup.clear();
+        assert_eq!(open("global/excel/missing.txt"), (false, VARIANT_COUNT));
+        assert_eq!(lookup.failed_opens.load(Ordering::Relaxed), 1 + 2 * VARIANT_COUNT as u64);
+    }
+
+    /// 模拟存档句柄: 检测并发访问并统计读取次数
+    #[derive(Default)]
+    struct FakeArchive {
+        busy: std::sync::atomic::AtomicBool,
+        loads: AtomicUsize,
+    }
+
+    impl FakeArchive {
+        fn load(&self, key: &str) -> Result<Vec<u8>> {
+            assert!(!self.busy.swap(true, Ordering::SeqCst), "concurrent archive access");
+            std::thread::yield_now();
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            self.busy.store(false, Ordering::SeqCst);
+            Ok(key.as_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn test_shared_reader_concurrent_stress() {
+        let reader = Arc::new(SharedReader::new(FakeArchive::default()));
+
+        let threads: Vec<_> = (0..16)
+            .map(|t| {
+                let reader = Arc::clone(&reader);
+                std::thread::spawn(move || {
+                    for i in 0..500 {
+                        let key = cache_key(&format!("Global\\Excel\\file{}.txt", (i * 7 + t) % 64));
+                        let content = reader.with_source(|archive| archive.load(&key)).unwrap();
+                        assert_eq!(content.as_slice(), key.as_bytes());
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Nothing is cached: every request reaches the archive, one at a time
+        reader.with_source(|archive| assert_eq!(archive.loads.load(Ordering::SeqCst), 16 * 500));
+    }
 }
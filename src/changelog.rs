@@ -0,0 +1,188 @@
+//! Markdown summary of a build (`install --changelog`), for sharing what a build contains
+//!
+//! Generated only from what the install already loaded: each mod's `mod.json` metadata and
+//! the configuration values it ran with.
+
+use crate::install::{ModOutcome, ModStatus};
+use crate::mod_manager::{ConfigOption, LoadedMod, ENABLED_OPTION_ID};
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Render the changelog for `mods`, in load order
+///
+/// `outcomes` gives each mod's status; mods without one are listed as installed.
+pub fn render(mods: &[LoadedMod], outcomes: &[ModOutcome]) -> String {
+    let mut out = String::from("# Mods in this build\n");
+    for (index, loaded) in mods.iter().enumerate() {
+        let config = &loaded.config;
+        let _ = write!(
+            out,
+            "\n## {}. {} {}\n\n",
+            index + 1,
+            config.name,
+            config.version
+        );
+
+        let status = outcomes
+            .iter()
+            .find(|o| o.id == loaded.id)
+            .map(|o| &o.status);
+        match status {
+            Some(ModStatus::Skipped) => out.push_str("*Disabled by its configuration*\n\n"),
+            Some(ModStatus::Failed { .. }) => out.push_str("*Failed to install*\n\n"),
+            Some(ModStatus::Installed { .. }) | None => {}
+        }
+
+        if let Some(author) = &config.author {
+            let _ = writeln!(out, "- Author: {}", author);
+        }
+        if let Some(website) = &config.website {
+            let _ = writeln!(out, "- Website: <{}>", website);
+        }
+        let _ = writeln!(out, "- ID: `{}`", loaded.id);
+        if let Some(description) = config
+            .description
+            .as_deref()
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+        {
+            let _ = write!(out, "\n{}\n", description);
+        }
+
+        let values: Vec<(&str, String)> = config
+            .config
+            .iter()
+            .filter(|option| option.id() != ENABLED_OPTION_ID)
+            .filter_map(|option| {
+                let value = loaded.user_config.get(option.id())?;
+                Some((option_name(option)?, display_value(option, value)))
+            })
+            .collect();
+        if !values.is_empty() {
+            out.push_str("\n| Option | Value |\n|---|---|\n");
+            for (name, value) in values {
+                let _ = writeln!(out, "| {} | {} |", escape_cell(name), escape_cell(&value));
+            }
+        }
+    }
+    out
+}
+
+/// Write [`render`] to `path`
+pub fn write(path: &Path, mods: &[LoadedMod], outcomes: &[ModOutcome]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    std::fs::write(path, render(mods, outcomes))
+        .with_context(|| format!("Failed to write changelog: {}", path.display()))
+}
+
+/// Display name of an option that holds a value
+fn option_name(option: &ConfigOption) -> Option<&str> {
+    match option {
+        ConfigOption::CheckBox { name, .. }
+        | ConfigOption::Number { name, .. }
+        | ConfigOption::Text { name, .. }
+        | ConfigOption::Select { name, .. }
+        | ConfigOption::Color { name, .. } => Some(name),
+        ConfigOption::Section { .. } => None,
+    }
+}
+
+/// A value as the GUI shows it: checkboxes as on/off, selects by their label
+fn display_value(option: &ConfigOption, value: &serde_json::Value) -> String {
+    match (option, value) {
+        (ConfigOption::CheckBox { .. }, serde_json::Value::Bool(checked)) => {
+            if *checked { "on" } else { "off" }.to_string()
+        }
+        (ConfigOption::Select { options, .. }, serde_json::Value::String(selected)) => options
+            .iter()
+            .find(|o| o.value == *selected)
+            .map_or_else(|| selected.clone(), |o| o.label.clone()),
+        (_, serde_json::Value::String(text)) => text.clone(),
+        (_, other) => other.to_string(),
+    }
+}
+
+/// Keep a value inside its table cell
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_manager::ModConfig;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_changelog() {
+        let config: ModConfig = serde_json::from_value(serde_json::json!({
+            "name": "Loot Filter",
+            "version": "2.1",
+            "author": "someone",
+            "website": "https://example.com/loot",
+            "description": "Hides junk items.",
+            "config": [
+                { "type": "checkbox", "id": "__enabled", "name": "Enabled", "default": true },
+                { "type": "section", "id": "s", "name": "Filters" },
+                { "type": "checkbox", "id": "hideGold", "name": "Hide gold", "default": false },
+                { "type": "select", "id": "level", "name": "Level | strictness", "default": "2",
+                  "options": [{ "label": "Loose", "value": "1" }, { "label": "Strict", "value": "2" }] },
+                { "type": "number", "id": "minGold", "name": "Min gold", "default": 100 }
+            ]
+        }))
+        .unwrap();
+        let mut user_config = config.generate_default_config();
+        config.normalize_user_config(&mut user_config);
+        user_config.insert("hideGold".into(), serde_json::json!(true));
+        let loaded = LoadedMod {
+            id: "lootfilter".into(),
+            path: "mods/lootfilter".into(),
+            user_config,
+            config,
+            script_path: "mods/lootfilter/mod.lua".into(),
+            script_type: crate::runtime::ScriptType::Lua,
+            readme: None,
+        };
+        let mut other = loaded.clone();
+        other.id = "other".into();
+        other.config.author = None;
+        other.config.website = None;
+        other.config.description = None;
+        other.config.config.clear();
+
+        let outcomes = vec![
+            ModOutcome {
+                id: "lootfilter".into(),
+                name: "Loot Filter".into(),
+                version: "2.1".into(),
+                status: ModStatus::Installed {
+                    elapsed: Duration::ZERO,
+                },
+            },
+            ModOutcome {
+                id: "other".into(),
+                name: "Loot Filter".into(),
+                version: "2.1".into(),
+                status: ModStatus::Skipped,
+            },
+        ];
+        let changelog = render(&[loaded, other], &outcomes);
+
+        assert!(changelog
+            .starts_with("# Mods in this build\n\n## 1. Loot Filter 2.1\n\n- Author: someone\n"));
+        assert!(changelog.contains(
+            "- Website: <https://example.com/loot>\n- ID: `lootfilter`\n\nHides junk items.\n"
+        ));
+        assert!(changelog.contains(
+            "| Hide gold | on |\n| Level \\| strictness | Strict |\n| Min gold | 100 |\n"
+        ));
+        assert!(!changelog.contains("Enabled"));
+        assert!(changelog.ends_with(
+            "## 2. Loot Filter 2.1\n\n*Disabled by its configuration*\n\n- ID: `other`\n"
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "infinite")]
@@ -11,15 +11,77 @@ pub struct Cli {
     /// Enable verbose logging
     #[arg(short, long, global = true)]
     pub verbose: bool,
+
+    /// When to use colored output (`auto` honors NO_COLOR and disables color when not a TTY)
+    #[arg(long, global = true, value_enum, default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+
+    /// Log output format: human-readable text, or one JSON object per line (with the
+    /// current mod and file operation as span fields) for log tooling
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// GitHub download cache directory (defaults to INFINITE_CACHE_DIR, else
+    /// <config dir>/infinite/mod_cache)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub cache_dir: Option<std::path::PathBuf>,
+}
+
+/// Log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Colored output mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Resolve whether colors should be emitted on stdout
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                use std::io::IsTerminal;
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                !no_color && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// Parse a `--feature` flag: `key=value`, or a bare `key` meaning "true"
+fn parse_feature(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s.split_once('=').unwrap_or((s, "true"));
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("feature flag '{}' has an empty key", s));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Parse a `--path-alias` flag: `from=to`
+fn parse_path_alias(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() => Ok((from.trim().to_string(), to.trim().to_string())),
+        _ => Err(format!("path alias '{}' must look like FROM=TO", s)),
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Install mods
     Install {
-        /// Path to the game directory
+        /// Path to the game directory (prompted for in interactive sessions when omitted)
         #[arg(short, long)]
-        game_path: String,
+        game_path: Option<String>,
 
         /// Path to the mods directory (mutually exclusive with --mod-list)
         #[arg(short, long, conflicts_with = "mod_list")]
@@ -40,6 +102,185 @@ pub enum Commands {
         /// Clear GitHub download cache before installing
         #[arg(long)]
         clear_cache: bool,
+
+        /// Install even if the game (D2R.exe) is running
+        #[arg(long)]
+        force: bool,
+
+        /// Confine script file access to the mod directory (reads) and the output directory (writes)
+        #[arg(long)]
+        sandbox: bool,
+
+        /// Build-level feature flag passed to every script as `infinite.getFeature(key)`
+        /// (`key=value`, or just `key` for "true"; repeatable)
+        #[arg(long = "feature", value_name = "KEY=VALUE", value_parser = parse_feature)]
+        features: Vec<(String, String)>,
+
+        /// JSON file mapping GitHub sources to user config (as written by the GUI); defaults
+        /// to the file named by INFINITE_SOURCE_CONFIG
+        #[arg(long, value_name = "FILE")]
+        source_config: Option<std::path::PathBuf>,
+
+        /// Leave out files that end up identical to vanilla (extracts each written file to compare)
+        #[arg(long)]
+        prune_unchanged: bool,
+
+        /// When a script rewrites a TSV it read, keep unchanged rows byte-for-byte and only
+        /// reserialize the rows it changed
+        #[arg(long)]
+        incremental_tsv: bool,
+
+        /// Extra CASC storage root to try before the defaults (relative paths are based on
+        /// the game directory; repeatable, tried in order)
+        #[arg(long = "casc-root", value_name = "DIR")]
+        casc_roots: Vec<std::path::PathBuf>,
+
+        /// Times to retry opening CASC storage (with backoff from 0.5s) before giving up
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        casc_retries: u32,
+
+        /// Map script paths to CASC paths before lookup: a FROM ending in `/` or `:` rewrites that
+        /// prefix, otherwise only that exact path (repeatable, checked before the built-in
+        /// `data/` rules)
+        #[arg(long = "path-alias", value_name = "FROM=TO", value_parser = parse_path_alias)]
+        path_aliases: Vec<(String, String)>,
+
+        /// Install with game file extraction disabled if CASC storage can't be opened,
+        /// instead of failing
+        #[arg(long)]
+        allow_no_casc: bool,
+
+        /// Skip mods with the same name and version as an earlier one (e.g. listed both
+        /// as a local folder and as a GitHub source) instead of only warning
+        #[arg(long)]
+        dedup: bool,
+
+        /// Warn about mods that ran but read and wrote no files (often a wrong path or a
+        /// config option that is off)
+        #[arg(long)]
+        warn_empty_mods: bool,
+
+        /// Like --warn-empty-mods, but exit with an error if any mod read and wrote no files
+        #[arg(long)]
+        fail_empty_mods: bool,
+
+        /// Exit with an error if more than one mod wrote the same file
+        #[arg(long)]
+        fail_on_conflict: bool,
+
+        /// Stop a mod script that runs longer than this many seconds (0 for no limit)
+        #[arg(long, value_name = "SECS", default_value_t = 30)]
+        script_timeout: u64,
+
+        /// Keep at most this many MB of modified files in memory; past it, the least
+        /// recently written files are written to the output early
+        #[arg(long, value_name = "MB")]
+        cache_budget: Option<usize>,
+
+        /// Install even if the output volume has less than 1 GB free
+        #[arg(long)]
+        ignore_disk_space: bool,
+
+        /// After installing, write a markdown summary of the mods (name, version, author,
+        /// website, description) and their configuration values to this file
+        #[arg(long, value_name = "PATH")]
+        changelog: Option<std::path::PathBuf>,
+
+        /// Copy each game file a mod changes into `.infinite-backup` (next to the output
+        /// directory) before the first change, with a manifest listing them
+        #[arg(long)]
+        backup: bool,
+
+        /// Print only warnings, errors and the final summary (no per-mod progress or info logs)
+        #[arg(long)]
+        summary_only: bool,
+
+        /// Prompt for a missing or invalid game path and mod source (the default when stdin
+        /// is a terminal)
+        #[arg(long)]
+        interactive: bool,
+    },
+
+    /// Run the mods in memory and print the final content of one game file
+    Inspect {
+        /// Path to the game directory
+        #[arg(short, long)]
+        game_path: String,
+
+        /// Path to the mods directory (mutually exclusive with --mod-list)
+        #[arg(short, long, conflicts_with = "mod_list")]
+        mods_path: Option<String>,
+
+        /// Path to a mod list file (mutually exclusive with --mods-path)
+        #[arg(short = 'l', long, conflicts_with = "mods_path")]
+        mod_list: Option<String>,
+
+        /// Game file to show, e.g. global/excel/weapons.txt
+        file: String,
+
+        /// Write the content to this file instead of stdout
+        #[arg(long, value_name = "PATH")]
+        out: Option<std::path::PathBuf>,
+
+        /// Build-level feature flag passed to every script (repeatable)
+        #[arg(long = "feature", value_name = "KEY=VALUE", value_parser = parse_feature)]
+        features: Vec<(String, String)>,
+
+        /// JSON file mapping GitHub sources to user config (as written by the GUI); defaults
+        /// to the file named by INFINITE_SOURCE_CONFIG
+        #[arg(long, value_name = "FILE")]
+        source_config: Option<std::path::PathBuf>,
+
+        /// Extra CASC storage root to try before the defaults (repeatable)
+        #[arg(long = "casc-root", value_name = "DIR")]
+        casc_roots: Vec<std::path::PathBuf>,
+
+        /// Map script paths to CASC paths before lookup (repeatable, see `install --help`)
+        #[arg(long = "path-alias", value_name = "FROM=TO", value_parser = parse_path_alias)]
+        path_aliases: Vec<(String, String)>,
+
+        /// Run with game file extraction disabled if CASC storage can't be opened
+        #[arg(long)]
+        allow_no_casc: bool,
+    },
+
+    /// List the files in the game's CASC storage, as paths scripts can read
+    ListFiles {
+        /// Path to the game directory
+        #[arg(short, long)]
+        game_path: String,
+
+        /// Only list paths matching this glob, e.g. `global/excel/*.txt` (case-insensitive;
+        /// a `data/` prefix is ignored)
+        pattern: Option<String>,
+
+        /// Extra CASC storage root to try before the defaults (repeatable)
+        #[arg(long = "casc-root", value_name = "DIR")]
+        casc_roots: Vec<std::path::PathBuf>,
+    },
+
+    /// Extract vanilla game files from CASC storage into a folder (read-only; never
+    /// writes under the game's Mods folder)
+    Extract {
+        /// Path to the game directory
+        #[arg(short, long)]
+        game_path: String,
+
+        /// Folder to extract into (subfolders are kept)
+        #[arg(short, long)]
+        output: std::path::PathBuf,
+
+        /// Game files to extract, e.g. global/excel/misc.txt
+        #[arg(required_unless_present = "pattern")]
+        files: Vec<String>,
+
+        /// Also extract every file whose path matches this glob (see `list-files`)
+        #[arg(long, value_name = "GLOB")]
+        pattern: Option<String>,
+
+        /// Extra CASC storage root to try before the defaults (repeatable)
+        #[arg(long = "casc-root", value_name = "DIR")]
+        casc_roots: Vec<std::path::PathBuf>,
     },
 
     /// List available mods
@@ -49,10 +290,96 @@ pub enum Commands {
         mods_path: String,
     },
 
-    /// Validate a mod
+    /// Validate a mod's mod.json and check its script for syntax errors
     Validate {
         /// Path to the mod directory
         #[arg(short, long)]
         mod_path: String,
+
+        /// Also require every config option to have a unique id
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Verify an existing build against its infinite-report.json
+    Verify {
+        /// Path to the output directory that was built
+        #[arg(short, long)]
+        output_path: String,
+    },
+
+    /// Restore output files that went missing or were reverted (e.g. by a game patch)
+    /// by re-running only the mods that produced them
+    Repair {
+        /// Path to the game directory
+        #[arg(short, long)]
+        game_path: String,
+
+        /// Path to the output directory that was built (defaults to <game_path>/Mods/Infinite/Infinite.mpq/data)
+        #[arg(short, long)]
+        output_path: Option<String>,
+
+        /// Extra CASC storage root to try before the defaults (repeatable)
+        #[arg(long = "casc-root", value_name = "DIR")]
+        casc_roots: Vec<std::path::PathBuf>,
+
+        /// Repair with game file extraction disabled if CASC storage can't be opened,
+        /// instead of failing
+        #[arg(long)]
+        allow_no_casc: bool,
+    },
+
+    /// Uninstall: remove the generated `Mods/Infinite` folder so the game uses its own files
+    Restore {
+        /// Path to the game directory
+        #[arg(short, long)]
+        game_path: String,
+    },
+
+    /// Show, compact or reset the string ID manifest of an output directory
+    StringIds {
+        /// Path to the output directory that was built
+        #[arg(short, long)]
+        output_path: String,
+
+        /// Drop IDs that the most recent build did not request
+        #[arg(long, conflicts_with = "reset")]
+        compact: bool,
+
+        /// Delete the manifest so the next build assigns IDs from scratch
+        #[arg(long)]
+        reset: bool,
+    },
+
+    /// Show the size of the GitHub download cache and remove old downloads
+    CleanCache {
+        /// Only remove downloads older than this (e.g. 30d, 12h, 2w); all of them when omitted
+        #[arg(long, value_name = "AGE", value_parser = crate::mod_cache::parse_age)]
+        older_than: Option<std::time::Duration>,
+
+        /// List what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Download cached GitHub mods again whose branch has new commits (pinned commits and
+    /// up-to-date branches are left alone)
+    Update {
+        /// Only report which downloads are out of date
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Print the default output path for a game directory
+    PrintOutputPath {
+        /// Path to the game directory
+        #[arg(short, long)]
+        game_path: String,
+    },
+
+    /// Print a mod's configuration options (the `config` array of mod.json) as JSON
+    PrintConfigSchema {
+        /// Path to the mod directory (GitHub sources must be downloaded first)
+        mod_path: String,
     },
 }
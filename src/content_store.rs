@@ -0,0 +1,246 @@
+//! Content-addressed file store used to deduplicate downloads and extracted game files
+//!
+//! Every file passed to [`ContentStore::dedupe`] is hashed (BLAKE3, streamed) and
+//! hardlinked to a single object under the store root, so byte-identical files that
+//! arrive under different cache keys (branches, commits, forks) share one copy on disk.
+//! Game files extracted from CASC go through [`ContentStore::dedupe_content`], so
+//! builds in different output directories share their vanilla copies.
+//!
+//! Linked files are copy-on-write by convention: writing through one link changes every
+//! other link, so a stored file must be replaced rather than rewritten in place. Output
+//! writes do this already ([`write_atomic`](crate::file_system::write_atomic) renames a
+//! new file over the old one); other callers must [`detach`] the file first.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Running totals of a store's deduplication work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Files hashed
+    pub files: u64,
+    /// Files replaced by a link to an existing object
+    pub linked: u64,
+    /// Bytes no longer stored twice
+    pub bytes_saved: u64,
+}
+
+/// Hardlink-based content-addressed store
+#[derive(Debug)]
+pub struct ContentStore {
+    root: PathBuf,
+    files: AtomicU64,
+    linked: AtomicU64,
+    bytes_saved: AtomicU64,
+}
+
+impl ContentStore {
+    /// Create a store rooted at `root` (created lazily on first insert)
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            files: AtomicU64::new(0),
+            linked: AtomicU64::new(0),
+            bytes_saved: AtomicU64::new(0),
+        }
+    }
+
+    /// Path of the object holding content with the given hash
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.root.join(&hash[..2]).join(&hash[2..])
+    }
+
+    /// Share `path`'s content with an identical stored object, or store it as a new one
+    ///
+    /// Returns `true` if `path` now links to a pre-existing object. Failing to link
+    /// (e.g. the store is on another volume) leaves `path` untouched.
+    pub fn dedupe(&self, path: &Path) -> Result<bool> {
+        let hash = hash_file(path)?;
+        self.link(path, &hash, None)
+    }
+
+    /// Like [`dedupe`](Self::dedupe), for a file just written with `content`
+    ///
+    /// Hashes the bytes in memory instead of reading the file back, and checks that an
+    /// existing object still holds them before linking to it. An object changed in place
+    /// through some other link is replaced by `path` instead of being shared further.
+    pub fn dedupe_content(&self, path: &Path, content: &[u8]) -> Result<bool> {
+        let hash = blake3::hash(content).to_hex().to_string();
+        self.link(path, &hash, Some(content))
+    }
+
+    fn link(&self, path: &Path, hash: &str, expected: Option<&[u8]>) -> Result<bool> {
+        let object = self.object_path(hash);
+        self.files.fetch_add(1, Ordering::Relaxed);
+
+        let stale = match expected {
+            Some(content) if object.exists() => fs::read(&object).map_or(true, |stored| stored != content),
+            _ => false,
+        };
+        if stale {
+            tracing::warn!("Replacing stored object that was changed in place: {}", object.display());
+            fs::remove_file(&object)
+                .with_context(|| format!("Failed to remove stored object: {}", object.display()))?;
+        }
+
+        if !object.exists() {
+            if let Some(parent) = object.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create store directory: {}", parent.display()))?;
+            }
+            if let Err(e) = fs::hard_link(path, &object) {
+                tracing::debug!("Not storing {}: {}", path.display(), e);
+            }
+            return Ok(false);
+        }
+
+        // Link next to the target, then rename over it so `path` is never missing
+        let size = fs::metadata(path)?.len();
+        let mut staged = path.as_os_str().to_owned();
+        staged.push(".dedupe-tmp");
+        let staged = PathBuf::from(staged);
+        if let Err(e) = fs::hard_link(&object, &staged) {
+            tracing::debug!("Not deduplicating {}: {}", path.display(), e);
+            return Ok(false);
+        }
+        if let Err(e) = fs::rename(&staged, path) {
+            let _ = fs::remove_file(&staged);
+            return Err(e).with_context(|| format!("Failed to replace {} with stored copy", path.display()));
+        }
+
+        self.linked.fetch_add(1, Ordering::Relaxed);
+        self.bytes_saved.fetch_add(size, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /// Remove objects that no cached file links to any more
+    ///
+    /// Returns the number of objects removed and their size. Link counts are only
+    /// available on Unix; elsewhere nothing is removed.
+    pub fn remove_unreferenced(&self) -> Result<(u64, u64)> {
+        let mut removed = (0, 0);
+        if !self.root.exists() {
+            return Ok(removed);
+        }
+        for entry in walkdir::WalkDir::new(&self.root) {
+            let entry = entry.with_context(|| format!("Failed to read store: {}", self.root.display()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if !is_unreferenced(&metadata) {
+                continue;
+            }
+            fs::remove_file(entry.path())
+                .with_context(|| format!("Failed to remove stored object: {}", entry.path().display()))?;
+            removed.0 += 1;
+            removed.1 += metadata.len();
+        }
+        Ok(removed)
+    }
+
+    /// Totals since this store was created
+    pub fn stats(&self) -> DedupStats {
+        DedupStats {
+            files: self.files.load(Ordering::Relaxed),
+            linked: self.linked.load(Ordering::Relaxed),
+            bytes_saved: self.bytes_saved.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Whether an object's only link is the store's own
+#[cfg(unix)]
+fn is_unreferenced(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() == 1
+}
+
+#[cfg(not(unix))]
+fn is_unreferenced(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Break a file's link to the store before it is rewritten
+///
+/// Removes `path` if it exists, so the next write creates a fresh file instead of
+/// changing the shared object.
+pub fn detach(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// BLAKE3 hex digest of a file's content, read in chunks
+pub fn hash_file(path: &Path) -> Result<String> {
+    let file = fs::File::open(path)
+        .with_context(|| format!("Failed to open file for hashing: {}", path.display()))?;
+    let mut hasher = blake3::Hasher::new();
+    hasher
+        .update_reader(BufReader::new(file))
+        .with_context(|| format!("Failed to hash file: {}", path.display()))?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_dedupe_links_identical_files() {
+        let temp = TempDir::new().unwrap();
+        let store = ContentStore::new(temp.path().join(".objects"));
+
+        let a = temp.path().join("main/mod.lua");
+        let b = temp.path().join("dev/mod.lua");
+        let c = temp.path().join("dev/mod.json");
+        for (path, content) in [(&a, "print('hi')"), (&b, "print('hi')"), (&c, "{}")] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        assert!(!store.dedupe(&a).unwrap());
+        assert!(store.dedupe(&b).unwrap());
+        assert!(!store.dedupe(&c).unwrap());
+        assert_eq!(fs::read_to_string(&b).unwrap(), "print('hi')");
+        assert_eq!(
+            store.stats(),
+            DedupStats { files: 3, linked: 1, bytes_saved: 11 }
+        );
+
+        // Rewriting a detached file leaves the other copy intact
+        detach(&b).unwrap();
+        fs::write(&b, "changed").unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "print('hi')");
+    }
+
+    #[test]
+    fn test_dedupe_content_replaces_changed_objects() {
+        let temp = TempDir::new().unwrap();
+        let store = ContentStore::new(temp.path().join(".objects"));
+
+        let first = temp.path().join("first/misc.txt");
+        let second = temp.path().join("second/misc.txt");
+        let third = temp.path().join("third/misc.txt");
+        for path in [&first, &second, &third] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "vanilla").unwrap();
+        }
+
+        assert!(!store.dedupe_content(&first, b"vanilla").unwrap());
+        assert!(store.dedupe_content(&second, b"vanilla").unwrap());
+
+        // An edit written in place through a link reaches the object, which is then
+        // replaced instead of handed to the next file
+        fs::write(&second, "edited!").unwrap();
+        assert!(!store.dedupe_content(&third, b"vanilla").unwrap());
+        assert_eq!(fs::read_to_string(&third).unwrap(), "vanilla");
+        let object = store.object_path(&blake3::hash(b"vanilla").to_hex());
+        assert_eq!(fs::read_to_string(object).unwrap(), "vanilla");
+    }
+}
@@ -0,0 +1,131 @@
+//! Copies of the game files a build changed (`install --backup`), and `restore`
+//!
+//! Before a mod first changes an extracted game file, the file manager copies the
+//! extracted original into `.infinite-backup` next to the output directory and lists it
+//! in the backup manifest. `restore` removes the generated `Mods/Infinite` folder, so the
+//! game goes back to its own files.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Directory the originals are copied into, next to the output directory
+pub const BACKUP_DIR_NAME: &str = ".infinite-backup";
+
+/// File name of the manifest inside the backup directory
+pub const BACKUP_MANIFEST_FILE: &str = "manifest.json";
+
+/// Originals backed up during one build
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupManifest {
+    /// Normalized path -> ID of the mod that changed it first
+    pub files: BTreeMap<String, String>,
+}
+
+impl BackupManifest {
+    /// Backup directory for an output directory (its sibling, so clearing the output
+    /// doesn't lose it)
+    pub fn dir_for(output_path: &Path) -> PathBuf {
+        output_path
+            .parent()
+            .unwrap_or(output_path)
+            .join(BACKUP_DIR_NAME)
+    }
+
+    /// Load the manifest of a backup directory; missing means empty
+    pub fn load(backup_dir: &Path) -> Result<Self> {
+        let path = backup_dir.join(BACKUP_MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read backup manifest: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse backup manifest: {}", path.display()))
+    }
+
+    /// Write the manifest into the backup directory
+    pub fn save(&self, backup_dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(backup_dir)?;
+        let path = backup_dir.join(BACKUP_MANIFEST_FILE);
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write backup manifest: {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Result of [`restore`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    /// The removed mod folder
+    pub mod_dir: PathBuf,
+    /// Generated files removed (backups not included)
+    pub removed_files: usize,
+    /// Game files the last build changed, as listed in the backup manifest
+    pub backed_up: usize,
+}
+
+/// Remove the mod folder `install` generated for `game_path`
+///
+/// Nothing is removed (and the summary is all zeros) if there is no mod folder.
+pub fn restore(game_path: &str) -> Result<RestoreSummary> {
+    let mod_dir = PathBuf::from(crate::default_mod_dir(game_path));
+    let mut summary = RestoreSummary {
+        mod_dir: mod_dir.clone(),
+        ..Default::default()
+    };
+    if !mod_dir.exists() {
+        return Ok(summary);
+    }
+
+    let backup_dir = BackupManifest::dir_for(Path::new(&crate::default_output_path(game_path)));
+    summary.backed_up = match BackupManifest::load(&backup_dir) {
+        Ok(manifest) => manifest.files.len(),
+        Err(e) => {
+            tracing::warn!("{:#}", e);
+            0
+        }
+    };
+    for entry in walkdir::WalkDir::new(&mod_dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() && !entry.path().starts_with(&backup_dir) {
+            summary.removed_files += 1;
+        }
+    }
+
+    std::fs::remove_dir_all(&mod_dir)
+        .with_context(|| format!("Failed to remove {}", mod_dir.display()))?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore() {
+        let game = tempfile::TempDir::new().unwrap();
+        let game_path = game.path().to_string_lossy().to_string();
+        assert_eq!(restore(&game_path).unwrap().removed_files, 0);
+
+        let output = PathBuf::from(crate::default_output_path(&game_path));
+        std::fs::create_dir_all(output.join("global/excel")).unwrap();
+        std::fs::write(output.join("global/excel/misc.txt"), "changed").unwrap();
+        std::fs::write(output.parent().unwrap().join("modinfo.json"), "{}").unwrap();
+        let backup_dir = BackupManifest::dir_for(&output);
+        std::fs::create_dir_all(backup_dir.join("global/excel")).unwrap();
+        std::fs::write(backup_dir.join("global/excel/misc.txt"), "vanilla").unwrap();
+        let mut manifest = BackupManifest::default();
+        manifest.files.insert("global/excel/misc.txt".into(), "mod1".into());
+        manifest.save(&backup_dir).unwrap();
+        assert_eq!(BackupManifest::load(&backup_dir).unwrap(), manifest);
+
+        let summary = restore(&game_path).unwrap();
+        assert_eq!(summary.removed_files, 2);
+        assert_eq!(summary.backed_up, 1);
+        assert!(!summary.mod_dir.exists());
+        assert!(game.path().join("Mods").exists());
+    }
+}
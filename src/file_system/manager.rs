@@ -1,16 +1,64 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use super::backup::BackupManifest;
+use super::store::{write_atomic, DiskStore, OutputStore};
 use crate::casc::CascStorage;
+use crate::handlers::{JsonFormat, JsonHandler};
 use anyhow::Result;
 
 /// In-memory cache of file contents
 #[derive(Debug, Clone)]
 pub struct CachedFile {
-    /// File content as bytes
-    pub content: Vec<u8>,
+    /// File content
+    pub content: CachedContent,
     /// Whether this is the latest version
     pub dirty: bool,
+    /// When the entry was last written (or re-read after a spill); the lowest is spilled first
+    pub last_write: u64,
+}
+
+/// Content of a cached file
+#[derive(Debug, Clone)]
+pub enum CachedContent {
+    /// Serialized bytes
+    Bytes(Vec<u8>),
+    /// A JSON document kept unserialized until something needs its bytes, so a script that
+    /// writes the same string table many times only pays for one serialization
+    Json(serde_json::Value, JsonFormat),
+}
+
+impl CachedContent {
+    /// Serialized content
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            CachedContent::Bytes(bytes) => Ok(bytes.clone()),
+            CachedContent::Json(value, format) => JsonHandler::to_bytes_as(value, *format),
+        }
+    }
+
+    /// Size counted against the cache budget (estimated for unserialized JSON)
+    pub fn len(&self) -> usize {
+        match self {
+            CachedContent::Bytes(bytes) => bytes.len(),
+            CachedContent::Json(value, _) => estimated_json_len(value),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Roughly the compact serialized length of `value`, without serializing it
+fn estimated_json_len(value: &serde_json::Value) -> usize {
+    use serde_json::Value;
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => 8,
+        Value::String(s) => s.len() + 2,
+        Value::Array(items) => 2 + items.iter().map(|v| estimated_json_len(v) + 1).sum::<usize>(),
+        Value::Object(map) => 2 + map.iter().map(|(k, v)| k.len() + 4 + estimated_json_len(v)).sum::<usize>(),
+    }
 }
 
 /// Type of file operation
@@ -22,6 +70,8 @@ pub enum FileOperationType {
     Read,
     /// File was written/modified by a mod
     Write,
+    /// File was removed from the output by a mod
+    Delete,
 }
 
 /// A single file operation record
@@ -50,14 +100,92 @@ pub struct FileStatus {
     pub operations: Vec<FileOperation>,
 }
 
+/// A game file a mod asked for that could not be extracted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractionFailure {
+    /// Normalized file path
+    pub file_path: String,
+    /// ID of the mod that asked for the file
+    pub mod_id: String,
+    pub error: String,
+}
+
+/// Result of [`FileManager::ensure_extracted_many`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchExtraction {
+    /// Normalized paths now available in the output, in input order (each once)
+    pub extracted: Vec<String>,
+    /// Normalized paths that could not be extracted, with the reason, in input order
+    pub failed: Vec<(String, String)>,
+}
+
+/// Outcome of `--prune-unchanged` during a flush
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    /// Cached writes skipped because the content matched the vanilla file
+    pub elided_writes: usize,
+    /// Extracted game files removed because no mod changed them
+    pub removed_extracted: usize,
+}
+
+/// Counts shown in the file operations summary
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileSummary {
+    pub tracked: usize,
+    pub extracted: usize,
+    pub modified: usize,
+    /// Files written by more than one mod, as returned by [`FileManager::detect_conflicts`]
+    pub conflicts: Vec<(String, Vec<String>)>,
+}
+
+impl FileSummary {
+    /// Print the file operations summary
+    pub fn print(&self) {
+        println!("\n📊 File Operations Summary:");
+        println!("   Total files tracked: {}", self.tracked);
+        println!("   Files extracted: {}", self.extracted);
+        println!("   Files modified: {}", self.modified);
+        if !self.conflicts.is_empty() {
+            println!("\n⚠️  Conflicts (files written by more than one mod, in write order):");
+            for (path, mods) in &self.conflicts {
+                println!("   {}: {}", path, mods.join(" -> "));
+            }
+        }
+    }
+}
+
 /// File manager that tracks all file operations
 pub struct FileManager {
     files: HashMap<String, FileStatus>,
     casc_storage: Option<Arc<CascStorage>>,
-    output_path: Option<PathBuf>,
+    /// Where output files go; a [`DiskStore`] once the output path is set
+    store: Option<Box<dyn OutputStore>>,
     game_path: Option<PathBuf>,
+    /// Game files supplied in memory, consulted before CASC and the game directory
+    game_files: HashMap<String, Vec<u8>>,
     /// In-memory cache of file contents for chaining modifications
     file_cache: HashMap<String, CachedFile>,
+    /// Bytes the cache may hold before the least recently written files are spilled to
+    /// the output; unlimited when unset
+    cache_budget: Option<usize>,
+    /// Size of the cached content, tracked while a budget is set
+    cache_bytes: usize,
+    /// Counter behind [`CachedFile::last_write`]
+    write_seq: u64,
+    /// Files written to the output early because the cache went over budget
+    spilled: HashSet<String>,
+    /// Leave files identical to vanilla out of the output (`--prune-unchanged`)
+    prune_unchanged: bool,
+    prune_stats: PruneStats,
+    /// `writeTsv` keeps unchanged lines byte-for-byte (`--incremental-tsv`)
+    incremental_tsv: bool,
+    /// Failed extractions, one per file and mod, in the order they happened
+    extraction_failures: Vec<ExtractionFailure>,
+    /// Files deleted by a mod, removed from the output when the cache is flushed
+    deleted: BTreeSet<String>,
+    /// Where extracted originals are copied before their first change (`--backup`)
+    backup_dir: Option<PathBuf>,
+    backup_manifest: BackupManifest,
 }
 
 impl FileManager {
@@ -66,9 +194,21 @@ impl FileManager {
         Self {
             files: HashMap::new(),
             casc_storage: None,
-            output_path: None,
+            store: None,
             game_path: None,
+            game_files: HashMap::new(),
             file_cache: HashMap::new(),
+            cache_budget: None,
+            cache_bytes: 0,
+            write_seq: 0,
+            spilled: HashSet::new(),
+            prune_unchanged: false,
+            prune_stats: PruneStats::default(),
+            incremental_tsv: false,
+            extraction_failures: Vec::new(),
+            deleted: BTreeSet::new(),
+            backup_dir: None,
+            backup_manifest: BackupManifest::default(),
         }
     }
 
@@ -82,73 +222,225 @@ impl FileManager {
         self.game_path = Some(path.into());
     }
 
+    /// Skip writing files whose content matches the vanilla game file, and drop
+    /// extracted files no mod changed, when flushing
+    pub fn set_prune_unchanged(&mut self, prune: bool) {
+        self.prune_unchanged = prune;
+    }
+
+    /// What pruning removed so far
+    pub fn prune_stats(&self) -> PruneStats {
+        self.prune_stats
+    }
+
+    /// Rewrite only the changed rows when a script writes a TSV file it read
+    pub fn set_incremental_tsv(&mut self, incremental: bool) {
+        self.incremental_tsv = incremental;
+    }
+
+    pub fn incremental_tsv(&self) -> bool {
+        self.incremental_tsv
+    }
+
+    /// Copy each extracted game file into `dir` before a mod first changes it, and write
+    /// the list to its manifest when the cache is flushed
+    pub fn set_backup_dir<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.backup_dir = Some(dir.into());
+    }
+
+    /// Originals backed up so far
+    pub fn backup_manifest(&self) -> &BackupManifest {
+        &self.backup_manifest
+    }
+
+    /// Limit the memory held by pending writes to about `bytes`
+    ///
+    /// Past the budget, the least recently written files are written to the output and
+    /// dropped from the cache; reading one again loads it back from the output.
+    pub fn set_cache_budget(&mut self, bytes: usize) {
+        self.cache_budget = Some(bytes);
+        self.cache_bytes = self.file_cache.values().map(|cached| cached.content.len()).sum();
+        self.spill_over_budget();
+    }
+
+    /// Size of the content currently cached
+    pub fn cache_size(&self) -> usize {
+        self.file_cache.values().map(|cached| cached.content.len()).sum()
+    }
+
     /// Set the output path for extracted files
     pub fn set_output_path<P: Into<PathBuf>>(&mut self, path: P) {
-        self.output_path = Some(path.into());
+        self.store = Some(Box::new(DiskStore::new(path)));
+    }
+
+    /// Keep output files in `store` instead of an output directory
+    pub fn set_output_store<S: OutputStore + 'static>(&mut self, store: S) {
+        self.store = Some(Box::new(store));
+    }
+
+    /// Provide a game file without CASC or a game directory
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn insert_game_file(&mut self, file_path: &str, content: impl Into<Vec<u8>>) {
+        self.game_files.insert(Self::normalize_path(file_path), content.into());
+    }
+
+    fn store(&self) -> Result<&dyn OutputStore> {
+        self.store.as_deref().ok_or_else(|| anyhow::anyhow!("Output path not set"))
     }
 
-    /// Extract a file from CASC storage if needed
-    /// Returns the path to the extracted file
-    pub async fn ensure_extracted(&mut self, file_path: &str, mod_id: &str) -> Result<PathBuf> {
+    /// Extract a file from game data into the output if needed
+    /// Returns the extracted content
+    #[tracing::instrument(level = "debug", skip_all, fields(op = "extract", path = %file_path))]
+    pub async fn ensure_extracted(&mut self, file_path: &str, mod_id: &str) -> Result<Vec<u8>> {
         let normalized = Self::normalize_path(file_path);
 
-        // Check if already extracted
-        if self.is_extracted(&normalized) {
-            if let Some(output_path) = &self.output_path {
-                let file_path = output_path.join(&normalized);
-                if file_path.exists() {
-                    return Ok(file_path);
-                }
+        // Check if already extracted (a deleted file is extracted again from vanilla)
+        if self.is_extracted(&normalized) && !self.deleted.contains(&normalized) {
+            if let Some(content) = self.store()?.read(&normalized)? {
+                return Ok(content);
             }
         }
 
-        // Extract from CASC
-        if let Some(storage) = &self.casc_storage {
-            if let Some(output_path) = &self.output_path {
-                let dest_path = output_path.join(&normalized);
-
-                // Create parent directory
-                if let Some(parent) = dest_path.parent() {
-                    tokio::fs::create_dir_all(parent).await?;
-                }
-
-                // Extract file - use original path for CASC (not normalized)
-                // CASC needs backslashes, not forward slashes
-                storage.extract_file(file_path, &dest_path)?;
+        let content = self
+            .read_vanilla(file_path)
+            .unwrap_or_else(|| {
+                Err(anyhow::anyhow!(
+                    "CASC storage not configured and file not found in game directory: {}",
+                    file_path
+                ))
+            })
+            .inspect_err(|e| self.record_extraction_failure(&normalized, mod_id, e))?;
 
-                // Record extraction
-                self.record_extract(&normalized, mod_id);
+        self.store()?.write_extracted(&normalized, &content)?;
+        self.record_extract(&normalized, mod_id);
+        Ok(content)
+    }
 
-                return Ok(dest_path);
+    /// Make each of `paths` available in the output, extracting the ones that aren't yet
+    ///
+    /// Paths are deduplicated after normalization. Files already in the output (extracted
+    /// earlier or written by a mod) are left as they are. A failure is collected in the
+    /// result and doesn't stop the rest of the batch.
+    pub async fn ensure_extracted_many(&mut self, paths: &[&str], mod_id: &str) -> Result<BatchExtraction> {
+        let mut batch = BatchExtraction::default();
+        let mut seen = BTreeSet::new();
+        for path in paths {
+            let normalized = Self::normalize_path(path);
+            if !seen.insert(normalized.clone()) {
+                continue;
+            }
+            if self.file_cache.contains_key(&normalized)
+                || (!self.deleted.contains(&normalized) && self.store()?.exists(&normalized)?)
+            {
+                batch.extracted.push(normalized);
+                continue;
+            }
+            match self.ensure_extracted(path, mod_id).await {
+                Ok(_) => batch.extracted.push(normalized),
+                Err(e) => batch.failed.push((normalized, format!("{:#}", e))),
             }
         }
+        Ok(batch)
+    }
 
-        // If CASC is not available, try to read from game_path directly
-        if let Some(game_path) = &self.game_path {
-            if let Some(output_path) = &self.output_path {
-                let source_path = game_path.join(&normalized);
+    fn record_extraction_failure(&mut self, file_path: &str, mod_id: &str, error: &anyhow::Error) {
+        let seen = self
+            .extraction_failures
+            .iter()
+            .any(|f| f.file_path == file_path && f.mod_id == mod_id);
+        if !seen {
+            self.extraction_failures.push(ExtractionFailure {
+                file_path: file_path.to_string(),
+                mod_id: mod_id.to_string(),
+                error: format!("{:#}", error),
+            });
+        }
+    }
 
-                // Check if file exists in game directory
-                if source_path.exists() {
-                    let dest_path = output_path.join(&normalized);
+    /// Game files that could not be extracted, with the mod that asked for each
+    pub fn extraction_failures(&self) -> &[ExtractionFailure] {
+        &self.extraction_failures
+    }
 
-                    // Create parent directory
-                    if let Some(parent) = dest_path.parent() {
-                        tokio::fs::create_dir_all(parent).await?;
-                    }
+    /// Copy the extracted original of `normalized` into the backup directory, once
+    ///
+    /// A failed copy is logged and doesn't stop the write that triggered it.
+    fn backup_original(&mut self, normalized: &str, mod_id: &str) {
+        let Some(backup_dir) = &self.backup_dir else {
+            return;
+        };
+        if self.backup_manifest.files.contains_key(normalized) || !self.is_extracted(normalized) {
+            return;
+        }
 
-                    // Copy file from game directory to output
-                    tokio::fs::copy(&source_path, &dest_path).await?;
+        let backup_path = backup_dir.join(normalized);
+        let result = self.store().and_then(|store| store.read(normalized)).and_then(|original| {
+            let Some(original) = original else {
+                return Ok(false);
+            };
+            if let Some(parent) = backup_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_atomic(&backup_path, &original)?;
+            Ok(true)
+        });
+        match result {
+            Ok(true) => {
+                self.backup_manifest.files.insert(normalized.to_string(), mod_id.to_string());
+                tracing::debug!("Backed up: {}", normalized);
+            }
+            Ok(false) => {}
+            Err(e) => tracing::warn!("Failed to back up {}: {:#}", backup_path.display(), e),
+        }
+    }
 
-                    // Record extraction
-                    self.record_extract(&normalized, mod_id);
+    /// Read the unmodified game version of a file, if any source has it
+    fn read_vanilla(&self, file_path: &str) -> Option<Result<Vec<u8>>> {
+        let normalized = Self::normalize_path(file_path);
+        if let Some(content) = self.game_files.get(&normalized) {
+            return Some(Ok(content.clone()));
+        }
 
-                    return Ok(dest_path);
-                }
-            }
+        // Use original path for CASC (not normalized)
+        if let Some(storage) = &self.casc_storage {
+            return Some(storage.extract_to_memory(file_path));
+        }
+
+        // If CASC is not available, try to read from game_path directly
+        let source_path = self.game_path.as_ref()?.join(&normalized);
+        source_path.is_file().then(|| std::fs::read(&source_path).map_err(Into::into))
+    }
+
+    /// Whether a file is a game asset (from CASC or the game directory) rather than one
+    /// that only exists because a mod wrote or copied it
+    ///
+    /// Uses the tracked `game_file` flag when known, otherwise probes the game sources
+    /// without extracting anything.
+    pub fn is_game_file(&self, file_path: &str) -> bool {
+        let normalized = Self::normalize_path(file_path);
+        if let Some(game_file) = self.files.get(&normalized).and_then(|s| s.game_file) {
+            return game_file;
+        }
+        if self.game_files.contains_key(&normalized) {
+            return true;
         }
+        if let Some(storage) = &self.casc_storage {
+            return storage.has_file(file_path);
+        }
+        self.game_path
+            .as_ref()
+            .is_some_and(|game_path| game_path.join(&normalized).is_file())
+    }
 
-        Err(anyhow::anyhow!("CASC storage not configured and file not found in game directory: {}", file_path))
+    /// Content of the game file with CASC file data ID `fdid`
+    ///
+    /// Such files have no path, so nothing is tracked or written to the output.
+    pub fn read_game_file_by_id(&self, fdid: u32) -> Result<Vec<u8>> {
+        let storage = self
+            .casc_storage
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("CASC storage not configured; can't read file data ID {}", fdid))?;
+        storage.extract_id_to_memory(fdid)
     }
 
     /// Get or create file status for a given path
@@ -166,7 +458,7 @@ impl FileManager {
     }
 
     /// Normalize a file path (lowercase, forward slashes)
-    fn normalize_path(path: &str) -> String {
+    pub fn normalize_path(path: &str) -> String {
         path.replace('\\', "/").to_lowercase()
     }
 
@@ -199,6 +491,7 @@ impl FileManager {
 
     /// Record that a file was extracted
     pub fn record_extract(&mut self, file_path: &str, mod_id: &str) {
+        self.deleted.remove(&Self::normalize_path(file_path));
         let status = self.get_or_create(file_path);
         status.extracted = true;
         status.exists = true;
@@ -225,6 +518,7 @@ impl FileManager {
 
     /// Record that a file was written
     pub fn record_write(&mut self, file_path: &str, mod_id: &str) {
+        self.deleted.remove(&Self::normalize_path(file_path));
         let status = self.get_or_create(file_path);
         status.exists = true;
         status.modified = true;
@@ -236,6 +530,20 @@ impl FileManager {
         tracing::debug!("Wrote: {} (by {})", file_path, mod_id);
     }
 
+    /// Record that a file was deleted
+    pub fn record_delete(&mut self, file_path: &str, mod_id: &str) {
+        self.deleted.insert(Self::normalize_path(file_path));
+        let status = self.get_or_create(file_path);
+        status.exists = false;
+        status.modified = true;
+        status.operations.push(FileOperation {
+            op_type: FileOperationType::Delete,
+            mod_id: mod_id.to_string(),
+        });
+
+        tracing::debug!("Deleted: {} (by {})", file_path, mod_id);
+    }
+
     /// Get file status for a given path
     pub fn get_status(&self, file_path: &str) -> Option<&FileStatus> {
         let normalized = Self::normalize_path(file_path);
@@ -259,6 +567,13 @@ impl FileManager {
             .collect()
     }
 
+    /// Whether a mod read, extracted or wrote any file
+    pub fn has_operations_by(&self, mod_id: &str) -> bool {
+        self.files
+            .values()
+            .any(|status| status.operations.iter().any(|op| op.mod_id == mod_id))
+    }
+
     /// Check if file needs extraction
     pub async fn extract_if_needed(
         &mut self,
@@ -288,80 +603,318 @@ impl FileManager {
 
     /// Print a summary of file operations
     pub fn print_summary(&self) {
-        let total_files = self.files.len();
-        let modified_files = self.files.values().filter(|s| s.modified).count();
-        let extracted_files = self.files.values().filter(|s| s.extracted).count();
+        self.summary().print();
+    }
 
-        println!("\n📊 File Operations Summary:");
-        println!("   Total files tracked: {}", total_files);
-        println!("   Files extracted: {}", extracted_files);
-        println!("   Files modified: {}", modified_files);
+    /// Counts of tracked, extracted and modified files
+    pub fn summary(&self) -> FileSummary {
+        FileSummary {
+            tracked: self.files.len(),
+            extracted: self.files.values().filter(|s| s.extracted).count(),
+            modified: self.files.values().filter(|s| s.modified).count(),
+            conflicts: self.detect_conflicts(),
+        }
     }
 
     /// Read file content, preferring cached version if available
     /// This allows multiple mods to chain their modifications
+    #[tracing::instrument(level = "debug", skip_all, fields(op = "read", path = %file_path))]
     pub async fn read_file_with_cache(&mut self, file_path: &str, mod_id: &str) -> Result<Vec<u8>> {
         let normalized = Self::normalize_path(file_path);
 
         // Check if we have a cached (modified) version
-        if let Some(cached) = self.file_cache.get(&normalized).cloned() {
+        if let Some(cached) = self.file_cache.get_mut(&normalized) {
             tracing::debug!("Reading cached version of: {} (for {})", file_path, mod_id);
+            // Keep the serialized form so later byte reads don't serialize again
+            if let CachedContent::Json(..) = cached.content {
+                let bytes = cached.content.to_bytes()?;
+                if self.cache_budget.is_some() {
+                    self.cache_bytes = self.cache_bytes - cached.content.len() + bytes.len();
+                }
+                cached.content = CachedContent::Bytes(bytes);
+            }
+            let content = cached.content.to_bytes()?;
             self.record_read(&normalized, mod_id);
-            return Ok(cached.content);
+            self.spill_over_budget();
+            return Ok(content);
         }
 
-        // Otherwise, read from disk
-        let output_path = self.output_path.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Output path not set"))?
-            .clone();
-        let full_path = output_path.join(&normalized);
-
-        if !full_path.exists() {
-            anyhow::bail!("File not found: {}", full_path.display());
+        // Otherwise, read from the output
+        if self.deleted.contains(&normalized) {
+            anyhow::bail!("File was deleted from output: {}", normalized);
         }
-
-        let content = tokio::fs::read(&full_path).await?;
+        let content = self.store()?.read(&normalized)?
+            .ok_or_else(|| anyhow::anyhow!("File not found in output: {}", normalized))?;
         self.record_read(&normalized, mod_id);
 
+        // A spilled file is likely to be changed again, so keep it at hand; it is already
+        // in the output, so it stays clean until then
+        if self.spilled.contains(&normalized) {
+            self.cache_insert(normalized, CachedContent::Bytes(content.clone()), false);
+        }
+
         Ok(content)
     }
 
+    /// Write a file straight to the output, bypassing the cache
+    #[tracing::instrument(level = "debug", skip_all, fields(op = "write", path = %file_path))]
+    pub fn write_file_direct(&mut self, file_path: &str, content: &[u8], mod_id: &str) -> Result<()> {
+        let normalized = Self::normalize_path(file_path);
+        self.backup_original(&normalized, mod_id);
+        self.store()?.write(&normalized, content)?;
+        self.record_write(&normalized, mod_id);
+        Ok(())
+    }
+
+    /// Whether the output already has `file_path`, pending or written
+    pub fn output_contains(&self, file_path: &str) -> Result<bool> {
+        Ok(self.peek_file(file_path)?.is_some())
+    }
+
+    /// Current output content of `file_path` (cached or written), without recording a read
+    pub fn peek_file(&self, file_path: &str) -> Result<Option<Vec<u8>>> {
+        let normalized = Self::normalize_path(file_path);
+        if let Some(cached) = self.file_cache.get(&normalized) {
+            return cached.content.to_bytes().map(Some);
+        }
+        if self.deleted.contains(&normalized) {
+            return Ok(None);
+        }
+        self.store()?.read(&normalized)
+    }
+
+    /// Remove `file_path` from the output
+    ///
+    /// Pending writes are dropped right away; a copy already in the output directory is
+    /// removed when the cache is flushed, unless a later mod writes the file again.
+    /// Returns false (and records nothing) if the output doesn't have the file.
+    #[tracing::instrument(level = "debug", skip_all, fields(op = "delete", path = %file_path))]
+    pub fn delete_file(&mut self, file_path: &str, mod_id: &str) -> Result<bool> {
+        let normalized = Self::normalize_path(file_path);
+        let cached = self.cache_remove(&normalized).is_some();
+        if !cached && (self.deleted.contains(&normalized) || !self.store()?.exists(&normalized)?) {
+            return Ok(false);
+        }
+
+        self.backup_original(&normalized, mod_id);
+        self.record_delete(&normalized, mod_id);
+        Ok(true)
+    }
+
     /// Write file content to cache (not to disk yet)
     /// This allows multiple mods to modify the same file
+    #[tracing::instrument(level = "debug", skip_all, fields(op = "write", path = %file_path))]
     pub fn write_file_to_cache(&mut self, file_path: &str, content: Vec<u8>, mod_id: &str) {
         let normalized = Self::normalize_path(file_path);
-
-        self.file_cache.insert(normalized.clone(), CachedFile {
-            content,
-            dirty: true,
-        });
+        self.backup_original(&normalized, mod_id);
 
         self.record_write(&normalized, mod_id);
+        self.cache_insert(normalized, CachedContent::Bytes(content), true);
         tracing::debug!("Cached write: {} (by {})", file_path, mod_id);
     }
 
+    /// Write a JSON document to cache without serializing it
+    ///
+    /// It is serialized once, when the cache is flushed or its bytes are read, however
+    /// many times it is rewritten before that.
+    #[tracing::instrument(level = "debug", skip_all, fields(op = "write", path = %file_path))]
+    pub fn write_json_to_cache(&mut self, file_path: &str, value: serde_json::Value, format: JsonFormat, mod_id: &str) {
+        let normalized = Self::normalize_path(file_path);
+        self.backup_original(&normalized, mod_id);
+
+        self.record_write(&normalized, mod_id);
+        self.cache_insert(normalized, CachedContent::Json(value, format), true);
+        tracing::debug!("Cached JSON write: {} (by {})", file_path, mod_id);
+    }
+
+    /// The cached JSON document written by [`Self::write_json_to_cache`], if it hasn't been
+    /// serialized yet; records a read like [`Self::read_file_with_cache`]
+    pub fn read_cached_json(&mut self, file_path: &str, mod_id: &str) -> Option<serde_json::Value> {
+        let normalized = Self::normalize_path(file_path);
+        let value = match &self.file_cache.get(&normalized)?.content {
+            CachedContent::Json(value, _) => value.clone(),
+            CachedContent::Bytes(_) => return None,
+        };
+        self.record_read(&normalized, mod_id);
+        Some(value)
+    }
+
+    /// Normalized path -> IDs of the mods that wrote it, in first-write order
+    pub fn writers(&self) -> BTreeMap<String, Vec<String>> {
+        let mut writers = BTreeMap::new();
+        for status in self.files.values() {
+            let mut mods: Vec<String> = Vec::new();
+            for op in &status.operations {
+                if op.op_type == FileOperationType::Write && !mods.contains(&op.mod_id) {
+                    mods.push(op.mod_id.clone());
+                }
+            }
+            if !mods.is_empty() {
+                writers.insert(status.file_path.clone(), mods);
+            }
+        }
+        writers
+    }
+
+    /// Files written by more than one distinct mod, sorted by path, with the mods in
+    /// first-write order (the content of the last one is what ends up in the output)
+    pub fn detect_conflicts(&self) -> Vec<(String, Vec<String>)> {
+        self.writers()
+            .into_iter()
+            .filter(|(_, mods)| mods.len() > 1)
+            .collect()
+    }
+
+    /// Put `content` in the cache, then spill if that went over budget
+    fn cache_insert(&mut self, normalized: String, content: CachedContent, dirty: bool) {
+        self.write_seq += 1;
+        if self.cache_budget.is_some() {
+            self.cache_bytes += content.len();
+        }
+        let cached = CachedFile { content, dirty, last_write: self.write_seq };
+        if let Some(old) = self.file_cache.insert(normalized, cached) {
+            if self.cache_budget.is_some() {
+                self.cache_bytes -= old.content.len();
+            }
+        }
+        self.spill_over_budget();
+    }
+
+    fn cache_remove(&mut self, normalized: &str) -> Option<CachedFile> {
+        let cached = self.file_cache.remove(normalized)?;
+        if self.cache_budget.is_some() {
+            self.cache_bytes -= cached.content.len();
+        }
+        Some(cached)
+    }
+
+    /// Write out and drop the least recently written files until the cache fits its budget
+    ///
+    /// Clean entries are dropped without writing. A file that can't be written stays
+    /// cached (and is retried on the next flush).
+    fn spill_over_budget(&mut self) {
+        let Some(budget) = self.cache_budget else {
+            return;
+        };
+        while self.cache_bytes > budget {
+            let Some(oldest) = self
+                .file_cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_write)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            let Some(cached) = self.cache_remove(&oldest) else {
+                break;
+            };
+            if !cached.dirty {
+                continue;
+            }
+            match self.write_out(&oldest, &cached.content) {
+                Ok(()) => {
+                    tracing::debug!("Cache over budget, spilled: {}", oldest);
+                    self.spilled.insert(oldest);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to spill {} to the output: {:#}", oldest, e);
+                    self.cache_bytes += cached.content.len();
+                    self.file_cache.insert(oldest, cached);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Write a cached file to the output (or leave it out with `--prune-unchanged`)
+    fn write_out(&mut self, file_path: &str, content: &CachedContent) -> Result<()> {
+        let content = content.to_bytes()?;
+        if self.prune_unchanged && self.matches_vanilla(file_path, &content) {
+            // Also drop the vanilla copy extracted when the file was first read
+            self.store()?.remove(file_path)?;
+            self.prune_stats.elided_writes += 1;
+            tracing::debug!("Unchanged from vanilla, not written: {}", file_path);
+            return Ok(());
+        }
+
+        self.store()?.write(file_path, &content)?;
+        tracing::info!("Flushed to disk: {}", file_path);
+        Ok(())
+    }
+
     /// Flush all cached files to disk
     pub async fn flush_cache(&mut self) -> Result<()> {
-        let output_path = self.output_path.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Output path not set"))?;
+        self.store()?;
 
-        for (file_path, cached) in self.file_cache.drain() {
+        let cached: Vec<(String, CachedFile)> = self.file_cache.drain().collect();
+        self.cache_bytes = 0;
+        for (file_path, cached) in cached {
             if cached.dirty {
-                let full_path = output_path.join(&file_path);
+                self.write_out(&file_path, &cached.content)?;
+            }
+        }
 
-                // Create parent directory
-                if let Some(parent) = full_path.parent() {
-                    tokio::fs::create_dir_all(parent).await?;
-                }
+        for file_path in std::mem::take(&mut self.deleted) {
+            if self.store()?.remove(&file_path)? {
+                tracing::info!("Deleted from disk: {}", file_path);
+            }
+        }
 
-                tokio::fs::write(&full_path, &cached.content).await?;
-                tracing::info!("Flushed to disk: {}", file_path);
+        if let Some(backup_dir) = &self.backup_dir {
+            if !self.backup_manifest.files.is_empty() {
+                self.backup_manifest.save(backup_dir)?;
+            }
+        }
+
+        if self.prune_unchanged {
+            let untouched: Vec<String> = self.files.values()
+                .filter(|status| status.extracted && !status.modified)
+                .map(|status| status.file_path.clone())
+                .collect();
+            for file_path in untouched {
+                if self.store()?.remove(&file_path)? {
+                    self.prune_stats.removed_extracted += 1;
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Whether `content` is equivalent to the vanilla version of `file_path`
+    ///
+    /// Compares against the copy extracted into the output when available, otherwise
+    /// reads the game file. JSON is compared by value and text ignores line endings, so
+    /// re-serializing an untouched file still counts as unchanged.
+    fn matches_vanilla(&self, file_path: &str, content: &[u8]) -> bool {
+        // A spilled file has already replaced the extracted copy
+        let extracted = (self.is_extracted(file_path) && !self.spilled.contains(file_path))
+            .then(|| self.store().ok()?.read(file_path).ok().flatten())
+            .flatten();
+
+        let vanilla = match extracted {
+            Some(content) => Some(content),
+            None => self.read_vanilla(file_path).and_then(Result::ok),
+        };
+
+        vanilla.is_some_and(|vanilla| contents_equivalent(file_path, &vanilla, content))
+    }
+
+    /// List output files whose normalized path starts with `prefix`
+    ///
+    /// Merges files already on disk with pending cache writes, so files written by
+    /// earlier mods show up before the cache is flushed. Paths are normalized and sorted.
+    pub fn list_output_files(&self, prefix: &str) -> Result<Vec<String>> {
+        let prefix = Self::normalize_path(prefix);
+        let prefix = prefix.trim_start_matches('/');
+        let mut files: BTreeSet<String> = self.file_cache.keys().cloned().collect();
+        if let Some(store) = &self.store {
+            files.extend(store.list()?.into_iter().filter(|path| !self.deleted.contains(path)));
+        }
+
+        Ok(files.into_iter().filter(|path| path.starts_with(prefix)).collect())
+    }
+
     /// Check if a file is in cache
     pub fn is_cached(&self, file_path: &str) -> bool {
         let normalized = Self::normalize_path(file_path);
@@ -369,6 +922,22 @@ impl FileManager {
     }
 }
 
+/// Byte equality, or equality after normalization (JSON by value, text without `\r`)
+fn contents_equivalent(file_path: &str, a: &[u8], b: &[u8]) -> bool {
+    if a == b {
+        return true;
+    }
+    if file_path.ends_with(".json") {
+        use crate::handlers::JsonHandler;
+        return matches!(
+            (JsonHandler::parse_from_bytes(a), JsonHandler::parse_from_bytes(b)),
+            (Ok(a), Ok(b)) if a == b
+        );
+    }
+    let strip_cr = |bytes: &[u8]| bytes.iter().copied().filter(|&byte| byte != b'\r').collect::<Vec<u8>>();
+    strip_cr(a) == strip_cr(b)
+}
+
 impl Default for FileManager {
     fn default() -> Self {
         Self::new()
@@ -395,6 +964,29 @@ mod tests {
 
         let status = fm.get_status("test.json").unwrap();
         assert_eq!(status.operations.len(), 2);
+
+        fm.record_write("TEST.json", "mod3");
+        fm.record_write("test.json", "mod2");
+        assert_eq!(fm.writers()["test.json"], vec!["mod2", "mod3"]);
+    }
+
+    #[test]
+    fn test_detect_conflicts() {
+        let mut fm = FileManager::new();
+        fm.record_write("b.json", "mod1");
+        fm.record_write("b.json", "mod1");
+        fm.record_extract("a.txt", "mod1");
+        fm.record_write("a.txt", "mod2");
+        fm.record_write("A.txt", "mod1");
+        fm.record_read("c.txt", "mod3");
+        fm.record_write("c.txt", "mod2");
+
+        let conflicts = fm.detect_conflicts();
+        assert_eq!(
+            conflicts,
+            vec![("a.txt".to_string(), vec!["mod2".to_string(), "mod1".to_string()])]
+        );
+        assert_eq!(fm.summary().conflicts, conflicts);
     }
 
     #[test]
@@ -406,4 +998,235 @@ mod tests {
         assert!(fm.exists("path/to/file.json"));
         assert!(fm.exists("PATH\\TO\\FILE.JSON"));
     }
+
+    #[tokio::test]
+    async fn test_delete_file() {
+        use crate::file_system::MemoryStore;
+
+        let store = MemoryStore::new();
+        let mut fm = FileManager::new();
+        fm.set_output_store(store.clone());
+        fm.write_file_direct("hd/overlay.json", b"{}", "mod1").unwrap();
+        fm.write_file_to_cache("global/excel/a.txt", b"a".to_vec(), "mod1");
+
+        assert!(!fm.delete_file("never/written.txt", "mod2").unwrap());
+        assert!(fm.get_status("never/written.txt").is_none());
+
+        assert!(fm.delete_file("HD\\Overlay.json", "mod2").unwrap());
+        assert!(fm.delete_file("global/excel/a.txt", "mod2").unwrap());
+        assert!(!fm.delete_file("hd/overlay.json", "mod2").unwrap());
+        assert!(!fm.exists("hd/overlay.json"));
+        assert_eq!(
+            fm.get_status("hd/overlay.json").unwrap().operations.last().unwrap().op_type,
+            FileOperationType::Delete
+        );
+        assert!(fm.peek_file("hd/overlay.json").unwrap().is_none());
+        assert!(fm.read_file_with_cache("hd/overlay.json", "mod3").await.is_err());
+        assert!(fm.list_output_files("").unwrap().is_empty());
+        // Still on disk until the flush
+        assert!(store.get("hd/overlay.json").is_some());
+
+        fm.flush_cache().await.unwrap();
+        assert!(store.get("hd/overlay.json").is_none());
+        assert!(store.get("global/excel/a.txt").is_none());
+
+        // Writing again after a delete keeps the file
+        fm.write_file_direct("hd/overlay.json", b"{}", "mod1").unwrap();
+        fm.delete_file("hd/overlay.json", "mod2").unwrap();
+        fm.write_file_to_cache("hd/overlay.json", b"[]".to_vec(), "mod3");
+        fm.flush_cache().await.unwrap();
+        assert_eq!(store.get_string("hd/overlay.json").unwrap(), "[]");
+    }
+
+    #[tokio::test]
+    async fn test_cache_budget_spills_to_output() {
+        use crate::file_system::MemoryStore;
+
+        let store = MemoryStore::new();
+        let mut fm = FileManager::new();
+        fm.set_output_store(store.clone());
+        fm.set_cache_budget(10);
+
+        fm.write_file_to_cache("a.txt", b"aaaa".to_vec(), "mod1");
+        fm.write_file_to_cache("b.txt", b"bbbb".to_vec(), "mod1");
+        assert!(store.get("a.txt").is_none());
+        // Over budget: the least recently written file goes to the output
+        fm.write_file_to_cache("c.txt", b"cccc".to_vec(), "mod1");
+        assert_eq!(store.get_string("a.txt").unwrap(), "aaaa");
+        assert!(!fm.is_cached("a.txt"));
+        assert!(fm.is_cached("b.txt") && fm.is_cached("c.txt"));
+        assert!(fm.cache_size() <= 10);
+
+        // Reading a spilled file loads it back, clean, spilling the next oldest
+        assert_eq!(fm.read_file_with_cache("a.txt", "mod2").await.unwrap(), b"aaaa");
+        assert!(fm.is_cached("a.txt"));
+        assert_eq!(store.get_string("b.txt").unwrap(), "bbbb");
+
+        // A clean entry is dropped without another write
+        store.clone().write("a.txt", b"marker").unwrap();
+        fm.write_file_to_cache("d.txt", b"dddd".to_vec(), "mod2");
+        assert!(!fm.is_cached("c.txt"));
+        fm.write_file_to_cache("e.txt", b"eeee".to_vec(), "mod2");
+        assert!(!fm.is_cached("a.txt"));
+        assert_eq!(store.get_string("a.txt").unwrap(), "marker");
+
+        // Rewriting a spilled file makes it dirty again
+        fm.write_file_to_cache("b.txt", b"BBBB".to_vec(), "mod3");
+        fm.flush_cache().await.unwrap();
+        for (path, content) in [("b.txt", "BBBB"), ("c.txt", "cccc"), ("d.txt", "dddd"), ("e.txt", "eeee")] {
+            assert_eq!(store.get_string(path).unwrap(), content, "{}", path);
+        }
+        assert_eq!(fm.cache_size(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_backup_originals() {
+        use crate::file_system::MemoryStore;
+
+        let backup = tempfile::TempDir::new().unwrap();
+        let mut fm = FileManager::new();
+        fm.set_output_store(MemoryStore::new());
+        fm.set_backup_dir(backup.path());
+        fm.insert_game_file("global/excel/misc.txt", "vanilla");
+
+        fm.ensure_extracted("global/excel/misc.txt", "mod1").await.unwrap();
+        fm.write_file_to_cache("global/excel/misc.txt", b"first".to_vec(), "mod1");
+        fm.write_file_to_cache("global/excel/misc.txt", b"second".to_vec(), "mod2");
+        // Files no mod extracted have no original to keep
+        fm.write_file_to_cache("hd/new.json", b"{}".to_vec(), "mod2");
+        fm.flush_cache().await.unwrap();
+
+        let backed_up = backup.path().join("global/excel/misc.txt");
+        assert_eq!(std::fs::read_to_string(backed_up).unwrap(), "vanilla");
+        assert!(!backup.path().join("hd/new.json").exists());
+        let manifest = BackupManifest::load(backup.path()).unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files["global/excel/misc.txt"], "mod1");
+    }
+
+    #[test]
+    fn test_list_output_files_merges_cache() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let strings = temp.path().join("local/lng/strings");
+        std::fs::create_dir_all(&strings).unwrap();
+        std::fs::write(strings.join("ui.json"), "[]").unwrap();
+        std::fs::write(temp.path().join("other.txt"), "").unwrap();
+
+        let mut fm = FileManager::new();
+        fm.set_output_path(temp.path());
+        fm.write_file_to_cache("Local\\Lng\\Strings\\Mod.json", b"[]".to_vec(), "mod1");
+        fm.write_file_to_cache("local/lng/strings/ui.json", b"[]".to_vec(), "mod1");
+
+        let files = fm.list_output_files("local/lng/strings").unwrap();
+        assert_eq!(files, vec!["local/lng/strings/mod.json", "local/lng/strings/ui.json"]);
+        assert_eq!(fm.list_output_files("").unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cached_json_writes() {
+        use crate::file_system::MemoryStore;
+
+        let mut fm = FileManager::new();
+        fm.set_output_store(MemoryStore::new());
+
+        let path = "local/lng/strings/item-names.json";
+        let mut strings = Vec::new();
+        for i in 0..100 {
+            strings.push(serde_json::json!({ "id": i, "Key": format!("k{}", i) }));
+            let value = serde_json::Value::Array(strings.clone());
+            fm.write_json_to_cache(path, value, JsonFormat::Compact, "mod1");
+            assert_eq!(fm.read_cached_json(path, "mod1").unwrap().as_array().unwrap().len(), i + 1);
+        }
+        assert_eq!(fm.get_status(path).unwrap().operations.len(), 200);
+
+        // Reading the bytes serializes once and keeps the result
+        let bytes = fm.read_file_with_cache(path, "mod2").await.unwrap();
+        assert!(bytes.starts_with(br#"[{"id":0,"Key":"k0"}"#));
+        assert!(fm.read_cached_json(path, "mod2").is_none());
+        assert_eq!(fm.peek_file(path).unwrap().unwrap(), bytes);
+
+        fm.write_json_to_cache(path, serde_json::json!([]), JsonFormat::Pretty, "mod2");
+        fm.flush_cache().await.unwrap();
+        assert_eq!(fm.peek_file(path).unwrap().unwrap(), b"[]");
+    }
+
+    #[tokio::test]
+    async fn test_prune_unchanged_elides_vanilla_writes() {
+        let game = tempfile::TempDir::new().unwrap();
+        let output = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(game.path().join("global/excel")).unwrap();
+        std::fs::write(game.path().join("global/excel/same.json"), r#"{ "a": 1, "b": [1, 2] }"#).unwrap();
+        std::fs::write(game.path().join("global/excel/misc.txt"), "a\tb\r\n").unwrap();
+        std::fs::write(game.path().join("global/excel/read.txt"), "x\n").unwrap();
+
+        let mut fm = FileManager::new();
+        fm.set_game_path(game.path());
+        fm.set_output_path(output.path());
+        fm.set_prune_unchanged(true);
+
+        // Read-only file: extracted, never modified
+        fm.ensure_extracted("global/excel/read.txt", "mod1").await.unwrap();
+        // Re-serialized but equal JSON, and text differing only in line endings
+        fm.write_file_to_cache("global/excel/same.json", b"{\"b\":[1,2],\"a\":1}".to_vec(), "mod1");
+        fm.write_file_to_cache("global/excel/misc.txt", b"a\tb\n".to_vec(), "mod1");
+        fm.write_file_to_cache("global/excel/new.txt", b"new".to_vec(), "mod1");
+        fm.flush_cache().await.unwrap();
+
+        let excel = output.path().join("global/excel");
+        assert!(!excel.join("same.json").exists());
+        assert!(!excel.join("misc.txt").exists());
+        assert!(!excel.join("read.txt").exists());
+        assert!(excel.join("new.txt").exists());
+        assert_eq!(fm.prune_stats(), PruneStats { elided_writes: 2, removed_extracted: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_extraction_failures() {
+        use crate::file_system::MemoryStore;
+
+        let mut fm = FileManager::new();
+        fm.set_output_store(MemoryStore::new());
+        fm.insert_game_file("global/excel/found.txt", "x");
+
+        fm.ensure_extracted("global/excel/found.txt", "mod1").await.unwrap();
+        assert!(fm.ensure_extracted("Global\\Excel\\Renamed.txt", "mod1").await.is_err());
+        assert!(fm.ensure_extracted("global/excel/renamed.txt", "mod1").await.is_err());
+        assert!(fm.ensure_extracted("global/excel/renamed.txt", "mod2").await.is_err());
+
+        let failures: Vec<(&str, &str)> = fm
+            .extraction_failures()
+            .iter()
+            .map(|f| (f.file_path.as_str(), f.mod_id.as_str()))
+            .collect();
+        assert_eq!(failures, [("global/excel/renamed.txt", "mod1"), ("global/excel/renamed.txt", "mod2")]);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_extracted_many() {
+        use crate::file_system::MemoryStore;
+
+        let mut fm = FileManager::new();
+        fm.set_output_store(MemoryStore::new());
+        fm.insert_game_file("global/excel/armor.txt", "vanilla");
+        fm.insert_game_file("global/excel/weapons.txt", "vanilla");
+        fm.insert_game_file("global/excel/misc.txt", "vanilla");
+        fm.write_file_to_cache("global/excel/misc.txt", b"modded".to_vec(), "mod0");
+
+        let paths = [
+            "global/excel/weapons.txt",
+            "global/excel/missing.txt",
+            "Global\\Excel\\Weapons.txt",
+            "global/excel/misc.txt",
+            "global/excel/armor.txt",
+        ];
+        let batch = fm.ensure_extracted_many(&paths, "mod1").await.unwrap();
+        assert_eq!(batch.extracted, ["global/excel/weapons.txt", "global/excel/misc.txt", "global/excel/armor.txt"]);
+        assert_eq!(batch.failed.len(), 1);
+        assert_eq!(batch.failed[0].0, "global/excel/missing.txt");
+
+        // Files mods already wrote are not replaced with the vanilla version
+        assert_eq!(fm.peek_file("global/excel/misc.txt").unwrap().unwrap(), b"modded");
+        assert!(fm.is_extracted("global/excel/armor.txt"));
+        assert_eq!(fm.extraction_failures().len(), 1);
+    }
 }
@@ -1,3 +1,10 @@
+pub mod backup;
 pub mod manager;
+pub mod store;
 
-pub use manager::{FileManager, FileOperation, FileOperationType, FileStatus};
+pub use backup::{BackupManifest, RestoreSummary};
+
+pub use manager::{
+    BatchExtraction, ExtractionFailure, FileManager, FileOperation, FileOperationType, FileStatus, FileSummary, PruneStats,
+};
+pub use store::{write_atomic, DiskStore, MemoryStore, OutputStore};
@@ -0,0 +1,251 @@
+use crate::content_store::ContentStore;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where the file manager keeps output files
+///
+/// Paths are normalized (lowercase, forward slashes) and relative to the output root.
+pub trait OutputStore: Send + Sync {
+    /// Content of `path`, or `None` if it isn't stored
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Whether `path` is stored, without reading its content
+    fn exists(&self, path: &str) -> Result<bool>;
+
+    /// Store `content` at `path`, replacing any previous content
+    fn write(&self, path: &str, content: &[u8]) -> Result<()>;
+
+    /// Store vanilla `content` extracted from the game at `path`
+    ///
+    /// A store may share it with identical files elsewhere, as long as a later
+    /// [`write`](Self::write) to `path` leaves the shared copy untouched.
+    fn write_extracted(&self, path: &str, content: &[u8]) -> Result<()> {
+        self.write(path, content)
+    }
+
+    /// Remove `path`; returns whether it was stored
+    fn remove(&self, path: &str) -> Result<bool>;
+
+    /// Every stored path
+    fn list(&self) -> Result<Vec<String>>;
+}
+
+/// Output directory on disk
+///
+/// Writes go through [`write_atomic`], which renames a new file over the old one, so a
+/// file hardlinked into a [`ContentStore`] is replaced rather than changed in place.
+pub struct DiskStore {
+    root: PathBuf,
+    content_store: Option<Arc<ContentStore>>,
+}
+
+impl DiskStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            content_store: None,
+        }
+    }
+
+    /// Hardlink extracted game files into `store`, sharing them with other builds
+    pub fn with_content_store(mut self, store: Arc<ContentStore>) -> Self {
+        self.content_store = Some(store);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl OutputStore for DiskStore {
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let full_path = self.root.join(path);
+        if !full_path.is_file() {
+            return Ok(None);
+        }
+        std::fs::read(&full_path)
+            .map(Some)
+            .with_context(|| format!("Failed to read {}", full_path.display()))
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.root.join(path).is_file())
+    }
+
+    fn write(&self, path: &str, content: &[u8]) -> Result<()> {
+        let full_path = self.root.join(path);
+        if let Some(parent) = full_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_atomic(&full_path, content)
+            .with_context(|| format!("Failed to write {}", full_path.display()))
+    }
+
+    fn write_extracted(&self, path: &str, content: &[u8]) -> Result<()> {
+        self.write(path, content)?;
+        if let Some(store) = &self.content_store {
+            let full_path = self.root.join(path);
+            if let Err(e) = store.dedupe_content(&full_path, content) {
+                tracing::warn!("Failed to deduplicate {}: {}", full_path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> Result<bool> {
+        let full_path = self.root.join(path);
+        if !full_path.exists() {
+            return Ok(false);
+        }
+        std::fs::remove_file(&full_path)
+            .with_context(|| format!("Failed to remove {}", full_path.display()))?;
+        Ok(true)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        if self.root.exists() {
+            for entry in walkdir::WalkDir::new(&self.root).min_depth(1) {
+                let entry = entry?;
+                // Leftovers of an interrupted write_atomic are not output files
+                if entry.file_type().is_file() && !is_partial_file(entry.path()) {
+                    let relative = entry
+                        .path()
+                        .strip_prefix(&self.root)
+                        .unwrap_or(entry.path());
+                    files.push(super::FileManager::normalize_path(
+                        &relative.to_string_lossy(),
+                    ));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Suffix of the temporary file [`write_atomic`] writes before renaming it into place
+pub const PARTIAL_SUFFIX: &str = ".part";
+
+/// Write `content` to `path` so that `path` ends up either complete or untouched
+///
+/// The content goes to `<path>.part` first and is renamed over `path` once fully
+/// written; on failure the temporary file is deleted. A reader (or a later verify)
+/// therefore never sees a truncated file.
+pub fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(PARTIAL_SUFFIX);
+    let partial = PathBuf::from(partial);
+
+    let result = std::fs::write(&partial, content).and_then(|()| std::fs::rename(&partial, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+fn is_partial_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(PARTIAL_SUFFIX))
+}
+
+/// Output kept in memory, for runs that should not touch disk (tests, `inspect`)
+///
+/// Clones share the same files, so a caller can hand one clone to the file manager
+/// and inspect the other after the mods ran.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    files: std::sync::Arc<std::sync::Mutex<std::collections::BTreeMap<String, Vec<u8>>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Content of a stored file
+    pub fn get(&self, path: &str) -> Option<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(&super::FileManager::normalize_path(path))
+            .cloned()
+    }
+
+    /// Content of a stored file as UTF-8 text
+    pub fn get_string(&self, path: &str) -> Option<String> {
+        self.get(path)
+            .map(|content| String::from_utf8_lossy(&content).into_owned())
+    }
+}
+
+impl OutputStore for MemoryStore {
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.files.lock().unwrap().get(path).cloned())
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.files.lock().unwrap().contains_key(path))
+    }
+
+    fn write(&self, path: &str, content: &[u8]) -> Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), content.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> Result<bool> {
+        Ok(self.files.lock().unwrap().remove(path).is_some())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        Ok(self.files.lock().unwrap().keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = DiskStore::new(temp.path());
+        store.write("global/excel/misc.txt", b"new").unwrap();
+        store.write("global/excel/misc.txt", b"newer").unwrap();
+        assert_eq!(store.read("global/excel/misc.txt").unwrap().unwrap(), b"newer");
+        assert!(store.exists("global/excel/misc.txt").unwrap());
+        assert!(!store.exists("global/excel").unwrap());
+        assert!(!temp.path().join("global/excel/misc.txt.part").exists());
+
+        // A failed rename (the target is a directory) leaves no partial file behind
+        std::fs::create_dir_all(temp.path().join("global/excel/busy.txt/inner")).unwrap();
+        assert!(store.write("global/excel/busy.txt", b"content").is_err());
+        assert!(!temp.path().join("global/excel/busy.txt.part").exists());
+
+        // Leftovers of an interrupted write are not listed
+        std::fs::write(temp.path().join("global/excel/crashed.txt.part"), b"trunc").unwrap();
+        assert_eq!(store.list().unwrap(), vec!["global/excel/misc.txt"]);
+    }
+
+    #[test]
+    fn test_extracted_files_are_shared_until_written() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let objects = Arc::new(ContentStore::new(temp.path().join(".objects")));
+        let first = DiskStore::new(temp.path().join("first")).with_content_store(objects.clone());
+        let second = DiskStore::new(temp.path().join("second")).with_content_store(objects.clone());
+
+        first.write_extracted("global/excel/misc.txt", b"vanilla").unwrap();
+        second.write_extracted("global/excel/misc.txt", b"vanilla").unwrap();
+        assert_eq!(objects.stats().linked, 1);
+        assert_eq!(objects.stats().bytes_saved, 7);
+
+        // A mod's write replaces the shared file instead of changing it for both builds
+        second.write("global/excel/misc.txt", b"modded").unwrap();
+        assert_eq!(first.read("global/excel/misc.txt").unwrap().unwrap(), b"vanilla");
+        assert_eq!(second.read("global/excel/misc.txt").unwrap().unwrap(), b"modded");
+    }
+}
@@ -1,21 +1,47 @@
+use crate::content_store::{ContentStore, DedupStats};
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::future::Future;
+use futures::{StreamExt, TryStreamExt};
 use tokio::fs;
 
+/// Environment variable holding a GitHub token for downloads
+pub const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+
 /// Downloads mods from GitHub repositories
 pub struct GitHubDownloader {
     client: reqwest::Client,
     cache_dir: PathBuf,
     github_token: Option<String>,
+    /// Shares byte-identical files across branches, commits and repos in the cache
+    content_store: ContentStore,
+    /// Default branches already looked up in this run, keyed by `owner/repo`
+    default_branches: std::sync::Mutex<HashMap<String, String>>,
+    /// Files of one directory fetched at once by the Contents API fallback
+    concurrency: usize,
+    /// Revalidates API responses with their `ETag` (see [`GitHubDownloader::get_json_cached`])
+    api_cache: ApiCache,
 }
 
+/// Default for [`GitHubDownloader::set_concurrency`]
+pub const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 8;
+
 impl GitHubDownloader {
-    /// Create a new GitHub downloader
+    /// Create a new GitHub downloader, authenticating with `GITHUB_TOKEN` if it is set
     pub fn new(cache_dir: PathBuf) -> Self {
-        // Try to read GitHub token from environment variable
-        let github_token = std::env::var("GITHUB_TOKEN").ok();
+        Self::with_token(cache_dir, std::env::var(GITHUB_TOKEN_ENV).ok())
+    }
+
+    /// Create a GitHub downloader that sends `token` with every API and tarball request
+    ///
+    /// Raises the rate limit to 5000 requests/hour and gives access to private repositories
+    /// the token can read. An empty token counts as none.
+    pub fn with_token(cache_dir: PathBuf, token: Option<String>) -> Self {
+        let github_token = token
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
 
         if github_token.is_some() {
             tracing::info!("Using GitHub token for authentication");
@@ -31,28 +57,74 @@ impl GitHubDownloader {
 
         Self {
             client,
+            content_store: ContentStore::new(cache_dir.join(".objects")),
+            api_cache: ApiCache::new(cache_dir.join(".api")),
             cache_dir,
             github_token,
+            default_branches: std::sync::Mutex::new(HashMap::new()),
+            concurrency: DEFAULT_DOWNLOAD_CONCURRENCY,
+        }
+    }
+
+    /// Keep cached API responses in `dir` instead of `<cache dir>/.api`
+    pub fn set_api_cache_dir(&mut self, dir: PathBuf) {
+        self.api_cache = ApiCache::new(dir);
+    }
+
+    /// GET a GitHub API `url` and parse the JSON body
+    ///
+    /// The response is stored with its `ETag` and later requests send `If-None-Match`, so an
+    /// unchanged resource comes back as `304 Not Modified` (which GitHub doesn't count
+    /// against the rate limit) and the stored body is reused.
+    pub async fn get_json_cached(&self, url: &str) -> Result<serde_json::Value> {
+        let mut request = self.client.get(url);
+        if let Some(token) = &self.github_token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+
+        let response = self
+            .api_cache
+            .send(url, request)
+            .await
+            .with_context(|| format!("Failed to fetch {}", url))?;
+        if !response.status.is_success() {
+            if let Some(message) =
+                rate_limit_message(response.status, &response.headers, self.github_token.is_some(), unix_now())
+            {
+                anyhow::bail!("{}", message);
+            }
+            anyhow::bail!("GitHub API request failed with status {}: {}", response.status, response.body);
         }
+        serde_json::from_str(&response.body).with_context(|| format!("Failed to parse the response of {}", url))
+    }
+
+    /// How many files of a directory the Contents API fallback downloads at once (at least 1)
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1);
     }
 
     /// Download a mod from GitHub
     /// Returns the local path where the mod was downloaded
+    ///
+    /// A pinned `commit` takes precedence over `branch` and is cached under its own SHA.
     pub async fn download(
         &self,
         repo: &str,
         subdir: Option<&str>,
         branch: Option<&str>,
+        commit: Option<&str>,
     ) -> Result<PathBuf> {
-        // Get the actual branch to use
-        let branch = if let Some(b) = branch {
+        // Get the actual branch (or commit) to use
+        let branch = if let Some(c) = commit {
+            c.to_string()
+        } else if let Some(b) = branch {
             b.to_string()
         } else {
             // Query repository info to get default branch
             self.get_default_branch(repo).await?
         };
 
-        // Create cache directory structure: cache_dir/owner/repo/branch/subdir
+        // Create cache directory structure: cache_dir/owner/repo/branch-or-sha/subdir
         let parts: Vec<&str> = repo.split('/').collect();
         if parts.len() != 2 {
             anyhow::bail!("Invalid repo format: {}", repo);
@@ -72,58 +144,285 @@ impl GitHubDownloader {
             return Ok(target_dir);
         }
 
-        tracing::info!("Downloading from GitHub: {}/{} (branch: {})", owner, repo_name, branch);
+        // Refs are resolved to a full SHA so every request reads the exact same tree, and so
+        // `is_stale` can later tell whether the branch moved on
+        let resolved = if commit.is_some() {
+            tracing::info!("Downloading from GitHub: {}/{} (commit: {})", owner, repo_name, branch);
+            Some(self.resolve_commit(repo, &branch).await?)
+        } else {
+            tracing::info!("Downloading from GitHub: {}/{} (branch: {})", owner, repo_name, branch);
+            match self.resolve_commit(repo, &branch).await {
+                Ok(sha) => Some(sha),
+                Err(e) => {
+                    tracing::warn!("Could not resolve the head of {}@{} ({:#}); downloading the branch", repo, branch, e);
+                    None
+                }
+            }
+        };
+        let git_ref = resolved.clone().unwrap_or_else(|| branch.clone());
         if let Some(subdir) = subdir {
             tracing::info!("  Subdirectory: {}", subdir);
         }
 
-        // Download using GitHub API
-        let base_path = subdir.unwrap_or("");
-        self.download_directory(owner, repo_name, &branch, base_path, &target_dir)
-            .await
-            .context("Failed to download from GitHub")?;
+        // One tarball request for the whole tree; the Contents API (one request per file and
+        // directory) only if that fails
+        match self.download_tarball(owner, repo_name, &git_ref, resolved.is_some(), subdir, &target_dir).await {
+            Ok(files) => tracing::info!("Downloaded {} files from the repository tarball", files),
+            Err(e) => {
+                tracing::warn!("Tarball download failed ({:#}); falling back to the GitHub API", e);
+                let base_path = subdir.unwrap_or("");
+                if let Err(e) = self.download_directory(owner, repo_name, &git_ref, base_path, &target_dir).await {
+                    // A partial directory would be taken as cached by the next run
+                    let _ = fs::remove_dir_all(&target_dir).await;
+                    return Err(e.context("Failed to download from GitHub"));
+                }
+            }
+        }
+
+        let info = CacheInfo {
+            repo: repo.to_string(),
+            git_ref: branch,
+            subdir: subdir.map(str::to_string),
+            commit: resolved,
+            pinned: commit.is_some(),
+        };
+        if let Err(e) = info.save(&target_dir) {
+            tracing::warn!("Failed to record the downloaded commit: {:#}", e);
+        }
 
         Ok(target_dir)
     }
 
-    /// Get the default branch of a repository
-    async fn get_default_branch(&self, repo: &str) -> Result<String> {
-        let url = format!("https://api.github.com/repos/{}", repo);
+    /// Whether a download cached for `repo` at `branch` is behind the branch head
+    ///
+    /// Asks GitHub for the head commit and compares it with the commit each cached download
+    /// of that branch recorded; a download whose commit is unknown counts as stale. Nothing
+    /// cached (or only downloads from before commits were recorded) is not stale.
+    pub async fn is_stale(&self, repo: &str, branch: &str) -> Result<bool> {
+        let downloads: Vec<CacheInfo> = self
+            .cached_downloads()?
+            .into_iter()
+            .map(|(_, info)| info)
+            .filter(|info| !info.pinned && info.repo == repo && info.git_ref == branch)
+            .collect();
+        if downloads.is_empty() {
+            return Ok(false);
+        }
 
-        let mut request = self.client
-            .get(&url)
-            .header("User-Agent", "infinite-mod-manager");
+        let head = self.resolve_commit(repo, branch).await?;
+        Ok(downloads.iter().any(|info| info.commit.as_deref() != Some(head.as_str())))
+    }
 
-        if let Some(ref token) = self.github_token {
-            request = request.header("Authorization", format!("Bearer {}", token));
+    /// Every download in the cache that recorded a [`CacheInfo`], with its directory
+    pub fn cached_downloads(&self) -> Result<Vec<(PathBuf, CacheInfo)>> {
+        let mut downloads = Vec::new();
+        if !self.cache_dir.exists() {
+            return Ok(downloads);
+        }
+        let walker = walkdir::WalkDir::new(&self.cache_dir)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_type().is_dir() || !e.file_name().to_string_lossy().starts_with('.'));
+        for entry in walker {
+            let entry = entry.context("Failed to read the download cache")?;
+            if entry.file_name() != CACHE_INFO_FILE {
+                continue;
+            }
+            let dir = entry.path().parent().unwrap_or(&self.cache_dir).to_path_buf();
+            match CacheInfo::load(&dir) {
+                Ok(info) => downloads.push((dir, info)),
+                Err(e) => tracing::warn!("Ignoring {}: {:#}", entry.path().display(), e),
+            }
         }
+        Ok(downloads)
+    }
 
-        let response = request
-            .send()
+    /// Download a cached branch again at its current head, replacing the cached files
+    ///
+    /// The old files are kept aside until the new download succeeds and put back if it fails.
+    pub async fn redownload(&self, dir: &Path, info: &CacheInfo) -> Result<PathBuf> {
+        let mut aside = dir.as_os_str().to_owned();
+        aside.push(".old");
+        let aside = PathBuf::from(aside);
+        if aside.exists() {
+            fs::remove_dir_all(&aside).await?;
+        }
+        fs::rename(dir, &aside)
             .await
-            .context("Failed to fetch repository info")?;
+            .with_context(|| format!("Failed to move aside {}", dir.display()))?;
 
+        match self.download(&info.repo, info.subdir.as_deref(), Some(&info.git_ref), None).await {
+            Ok(path) => {
+                if let Err(e) = fs::remove_dir_all(&aside).await {
+                    tracing::warn!("Failed to remove {}: {}", aside.display(), e);
+                }
+                Ok(path)
+            }
+            Err(e) => {
+                if dir.exists() {
+                    let _ = fs::remove_dir_all(dir).await;
+                }
+                fs::rename(&aside, dir)
+                    .await
+                    .with_context(|| format!("Failed to restore {}", dir.display()))?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Download the repository tarball from codeload.github.com and unpack `subdir` (or
+    /// everything) into `target_dir`; returns the number of files written
+    ///
+    /// Files are unpacked next to `target_dir` and moved into place at the end, so a failed
+    /// download never leaves a partial directory that later runs would take as cached.
+    async fn download_tarball(
+        &self,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+        is_commit: bool,
+        subdir: Option<&str>,
+        target_dir: &Path,
+    ) -> Result<usize> {
+        let url = tarball_url(owner, repo, git_ref, is_commit);
+        tracing::debug!("Downloading tarball: {}", url);
+
+        let mut request = self.client.get(&url);
+        if let Some(token) = &self.github_token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let response = request.send().await.context("Failed to fetch tarball")?;
         if !response.status().is_success() {
-            tracing::warn!("Failed to get default branch for {}, falling back to 'main'", repo);
-            return Ok("main".to_string());
+            anyhow::bail!("tarball request failed with status {}", response.status());
         }
+        // Unpack while downloading rather than holding the whole archive in memory
+        let body = response.bytes_stream().map_err(std::io::Error::other);
+        let archive = tokio_util::io::SyncIoBridge::new(tokio_util::io::StreamReader::new(body));
 
-        let repo_info: serde_json::Value = response
-            .json()
+        let mut staging = target_dir.as_os_str().to_owned();
+        staging.push(".download");
+        let staging = PathBuf::from(staging);
+        if staging.exists() {
+            fs::remove_dir_all(&staging).await?;
+        }
+        let subdir = subdir.map(str::to_string);
+        let unpack_dir = staging.clone();
+        let unpacked = tokio::task::spawn_blocking(move || {
+            extract_tarball(archive, subdir.as_deref(), &unpack_dir)
+        })
+        .await?;
+        let files = match unpacked {
+            Ok(files) => files,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging).await;
+                return Err(e);
+            }
+        };
+
+        fs::rename(&staging, target_dir)
             .await
-            .context("Failed to parse repository info")?;
+            .with_context(|| format!("Failed to move download into {}", target_dir.display()))?;
 
-        let default_branch = repo_info
+        for entry in walkdir::WalkDir::new(target_dir).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                if let Err(e) = self.content_store.dedupe(entry.path()) {
+                    tracing::warn!("Failed to deduplicate {}: {}", entry.path().display(), e);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Get the default branch of a repository
+    ///
+    /// Looked up once per run. The answer is also saved in the cache, so a later run that
+    /// cannot reach the API (rate limit, offline) reuses it instead of guessing a branch.
+    async fn get_default_branch(&self, repo: &str) -> Result<String> {
+        if let Some(branch) = self.default_branches.lock().unwrap().get(repo) {
+            return Ok(branch.clone());
+        }
+
+        let branch = match self.fetch_default_branch(repo).await {
+            Ok(branch) => {
+                if let Err(e) = self.save_default_branch(repo, &branch) {
+                    tracing::warn!("Failed to cache default branch for {}: {}", repo, e);
+                }
+                branch
+            }
+            Err(e) => match self.load_default_branch(repo) {
+                Some(branch) => {
+                    tracing::warn!("{:#}; using last known default branch of {}: {}", e, repo, branch);
+                    branch
+                }
+                None => {
+                    return Err(e.context(format!(
+                        "Failed to get the default branch of {}; name a branch with github:{}@<branch>",
+                        repo, repo
+                    )))
+                }
+            },
+        };
+
+        self.default_branches
+            .lock()
+            .unwrap()
+            .insert(repo.to_string(), branch.clone());
+        Ok(branch)
+    }
+
+    /// Query the GitHub API for the default branch of a repository
+    async fn fetch_default_branch(&self, repo: &str) -> Result<String> {
+        let url = format!("https://api.github.com/repos/{}", repo);
+        let info = self
+            .get_json_cached(&url)
+            .await
+            .with_context(|| format!("Failed to fetch repository info for {}", repo))?;
+        let default_branch = info
             .get("default_branch")
             .and_then(|v| v.as_str())
-            .unwrap_or("main")
-            .to_string();
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("Repository info for {} has no default_branch", repo))?;
 
         tracing::info!("Detected default branch for {}: {}", repo, default_branch);
 
         Ok(default_branch)
     }
 
+    /// File remembering a repository's default branch (branch names cannot start with `.`,
+    /// so it never collides with a downloaded branch directory)
+    fn default_branch_file(&self, repo: &str) -> PathBuf {
+        self.cache_dir.join(repo).join(".default-branch")
+    }
+
+    fn load_default_branch(&self, repo: &str) -> Option<String> {
+        let branch = std::fs::read_to_string(self.default_branch_file(repo)).ok()?;
+        let branch = branch.trim();
+        (!branch.is_empty()).then(|| branch.to_string())
+    }
+
+    fn save_default_branch(&self, repo: &str, branch: &str) -> Result<()> {
+        let path = self.default_branch_file(repo);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, branch)?;
+        Ok(())
+    }
+
+    /// Resolve a (possibly abbreviated) commit SHA to the full SHA
+    async fn resolve_commit(&self, repo: &str, sha: &str) -> Result<String> {
+        let url = format!("https://api.github.com/repos/{}/commits/{}", repo, sha);
+        let commit_info = self
+            .get_json_cached(&url)
+            .await
+            .with_context(|| format!("Failed to look up '{}' in {}", sha, repo))?;
+
+        commit_info
+            .get("sha")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("Commit info for {} in {} has no sha", sha, repo))
+    }
+
     /// Download a directory from GitHub using the API
     fn download_directory<'a>(
         &'a self,
@@ -140,60 +439,38 @@ impl GitHubDownloader {
             owner, repo, path, branch
         );
 
-        let mut request = self.client.get(&url);
-
-        // Add authentication if token is available
-        if let Some(token) = &self.github_token {
-            request = request.header("Authorization", format!("Bearer {}", token));
-        }
-
-        let response = request
-            .send()
-            .await
-            .context("Failed to fetch from GitHub API")?;
-
-        if !response.status().is_success() {
-            anyhow::bail!(
-                "GitHub API request failed with status {}: {}",
-                response.status(),
-                response.text().await.unwrap_or_default()
-            );
-        }
-
-        let items: Vec<GitHubContentItem> = response
-            .json()
-            .await
-            .context("Failed to parse GitHub API response")?;
+        let listing = self.get_json_cached(&url).await?;
+        let items: Vec<GitHubContentItem> =
+            serde_json::from_value(listing).context("Failed to parse GitHub API response")?;
 
         // Create target directory
         fs::create_dir_all(target_dir)
             .await
             .context("Failed to create target directory")?;
 
-        // Download each item
+        // Files are fetched `concurrency` at a time; the first error stops the rest
+        let mut subdirs = Vec::new();
+        let mut files = Vec::new();
         for item in items {
-            let item_path = target_dir.join(&item.name);
-
             match item.item_type.as_str() {
                 "file" => {
-                    // Download file content
                     if let Some(download_url) = item.download_url {
-                        tracing::debug!("Downloading file: {}", item.name);
-                        let content = self
-                            .client
-                            .get(&download_url)
-                            .send()
-                            .await
-                            .context("Failed to download file")?
-                            .bytes()
-                            .await
-                            .context("Failed to read file content")?;
-
-                        fs::write(&item_path, content)
-                            .await
-                            .context("Failed to write file")?;
+                        files.push((download_url, target_dir.join(&item.name)));
                     }
                 }
+                _ => subdirs.push(item),
+            }
+        }
+        futures::stream::iter(files)
+            .map(|(url, path)| self.download_file(url, path))
+            .buffer_unordered(self.concurrency)
+            .try_collect::<()>()
+            .await?;
+
+        for item in subdirs {
+            let item_path = target_dir.join(&item.name);
+
+            match item.item_type.as_str() {
                 "dir" => {
                     // Recursively download subdirectory
                     tracing::debug!("Downloading directory: {}", item.name);
@@ -210,6 +487,39 @@ impl GitHubDownloader {
         })
     }
 
+    /// Download one file of the Contents API fallback; a partly written file is removed
+    async fn download_file(&self, url: String, path: PathBuf) -> Result<()> {
+        tracing::debug!("Downloading file: {}", path.display());
+        let written = async {
+            let content = self
+                .client
+                .get(&url)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .context("Failed to download file")?
+                .bytes()
+                .await
+                .context("Failed to read file content")?;
+            fs::write(&path, content).await.context("Failed to write file")
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = fs::remove_file(&path).await;
+            return Err(e.context(format!("Failed to download {}", path.display())));
+        }
+
+        if let Err(e) = self.content_store.dedupe(&path) {
+            tracing::warn!("Failed to deduplicate {}: {}", path.display(), e);
+        }
+        Ok(())
+    }
+
+    /// Deduplication totals for files downloaded by this downloader
+    pub fn dedup_stats(&self) -> DedupStats {
+        self.content_store.stats()
+    }
+
     /// Clear the download cache
     pub async fn clear_cache(&self) -> Result<()> {
         if self.cache_dir.exists() {
@@ -221,6 +531,43 @@ impl GitHubDownloader {
     }
 }
 
+/// File recording what a cached download holds, in the download's directory
+pub const CACHE_INFO_FILE: &str = ".infinite-cache.json";
+
+/// What a cached download was made from ([`CACHE_INFO_FILE`])
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CacheInfo {
+    /// `owner/repo`
+    pub repo: String,
+    /// Branch name, or the SHA for a pinned commit
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    /// Full SHA of the downloaded commit (`None` if it could not be resolved)
+    pub commit: Option<String>,
+    /// Downloaded from a pinned commit, so never out of date
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl CacheInfo {
+    /// Read the info of the download in `dir`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(CACHE_INFO_FILE);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write the info into the download directory `dir`
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(CACHE_INFO_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct GitHubContentItem {
     name: String,
@@ -229,3 +576,488 @@ struct GitHubContentItem {
     item_type: String,
     download_url: Option<String>,
 }
+
+/// A GitHub API response body stored with its `ETag`, replayed on `304 Not Modified`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CachedApiResponse {
+    pub url: String,
+    pub etag: String,
+    /// `Link` header of the cached response (pagination)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    pub body: String,
+}
+
+/// Result of a request sent through [`ApiCache`]
+#[derive(Debug)]
+pub struct ApiResponse {
+    /// Status of the live response, or `200 OK` when replayed from the cache
+    pub status: reqwest::StatusCode,
+    /// Headers of the live response (rate-limit headers are present on `304` too)
+    pub headers: reqwest::header::HeaderMap,
+    pub link: Option<String>,
+    pub body: String,
+    /// Whether the body came from the cache after a `304 Not Modified`
+    pub cached: bool,
+}
+
+/// On-disk cache of GitHub API responses keyed by URL
+///
+/// Requests are revalidated with `If-None-Match`; GitHub doesn't count `304`
+/// responses against the rate limit, so browsing the same repos again is nearly free.
+pub struct ApiCache {
+    dir: PathBuf,
+}
+
+impl ApiCache {
+    /// Create a cache storing entries under `dir`
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        use sha2::{Digest, Sha256};
+        self.dir.join(format!("{:x}.json", Sha256::digest(url.as_bytes())))
+    }
+
+    /// Cached response for `url`, if any
+    pub fn get(&self, url: &str) -> Option<CachedApiResponse> {
+        let content = std::fs::read_to_string(self.entry_path(url)).ok()?;
+        serde_json::from_str::<CachedApiResponse>(&content)
+            .ok()
+            .filter(|entry| entry.url == url)
+    }
+
+    /// Store a response
+    pub fn put(&self, entry: &CachedApiResponse) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create API cache directory: {}", self.dir.display()))?;
+        std::fs::write(self.entry_path(&entry.url), serde_json::to_string(entry)?)
+            .context("Failed to write API cache entry")?;
+        Ok(())
+    }
+
+    /// Send a GET `request` for `url`, revalidating any cached copy
+    ///
+    /// A `304 Not Modified` is returned as `200 OK` with the cached body; successful
+    /// responses carrying an `ETag` are stored for next time.
+    pub fn send_blocking(
+        &self,
+        url: &str,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::Result<ApiResponse> {
+        let cached = self.get(url);
+        let request = match &cached {
+            Some(entry) => request.header(reqwest::header::IF_NONE_MATCH, &entry.etag),
+            None => request,
+        };
+
+        let response = request.send()?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        if let Some(replayed) = Self::replay(url, status, &headers, &cached) {
+            return Ok(replayed);
+        }
+        let body = response.text()?;
+        Ok(self.store(url, status, headers, body))
+    }
+
+    /// Async version of [`ApiCache::send_blocking`]
+    pub async fn send(&self, url: &str, request: reqwest::RequestBuilder) -> reqwest::Result<ApiResponse> {
+        let cached = self.get(url);
+        let request = match &cached {
+            Some(entry) => request.header(reqwest::header::IF_NONE_MATCH, &entry.etag),
+            None => request,
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        if let Some(replayed) = Self::replay(url, status, &headers, &cached) {
+            return Ok(replayed);
+        }
+        let body = response.text().await?;
+        Ok(self.store(url, status, headers, body))
+    }
+
+    /// The cached response as `200 OK`, if the server answered `304 Not Modified`
+    fn replay(
+        url: &str,
+        status: reqwest::StatusCode,
+        headers: &reqwest::header::HeaderMap,
+        cached: &Option<CachedApiResponse>,
+    ) -> Option<ApiResponse> {
+        if status != reqwest::StatusCode::NOT_MODIFIED {
+            return None;
+        }
+        let entry = cached.clone()?;
+        tracing::debug!("GitHub API cache hit: {}", url);
+        Some(ApiResponse {
+            status: reqwest::StatusCode::OK,
+            headers: headers.clone(),
+            link: entry.link,
+            body: entry.body,
+            cached: true,
+        })
+    }
+
+    /// Cache a live response if it succeeded and carries an `ETag`
+    fn store(
+        &self,
+        url: &str,
+        status: reqwest::StatusCode,
+        headers: reqwest::header::HeaderMap,
+        body: String,
+    ) -> ApiResponse {
+        let header_str = |name: reqwest::header::HeaderName| {
+            headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+        };
+        let link = header_str(reqwest::header::LINK);
+        let etag = header_str(reqwest::header::ETAG);
+
+        if let (true, Some(etag)) = (status.is_success(), etag) {
+            let entry = CachedApiResponse {
+                url: url.to_string(),
+                etag,
+                link: link.clone(),
+                body: body.clone(),
+            };
+            if let Err(e) = self.put(&entry) {
+                tracing::warn!("Failed to cache GitHub API response for {}: {}", url, e);
+            }
+        }
+
+        ApiResponse {
+            status,
+            headers,
+            link,
+            body,
+            cached: false,
+        }
+    }
+}
+
+/// Explain a rate-limited GitHub API response, or `None` if `status`/`headers` aren't one
+///
+/// GitHub answers `403` (or `429`) with `x-ratelimit-remaining: 0` and the reset time as
+/// Unix seconds in `x-ratelimit-reset`; `now` is the current Unix time.
+pub fn rate_limit_message(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    has_token: bool,
+    now: u64,
+) -> Option<String> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+
+    let limited = status == reqwest::StatusCode::FORBIDDEN
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+    if !limited || header("x-ratelimit-remaining")? != 0 {
+        return None;
+    }
+
+    let mut message = match header("x-ratelimit-limit") {
+        Some(limit) => format!("GitHub API rate limit exceeded ({} requests/hour)", limit),
+        None => "GitHub API rate limit exceeded".to_string(),
+    };
+    if let Some(reset) = header("x-ratelimit-reset") {
+        let minutes = reset.saturating_sub(now).div_ceil(60);
+        if minutes == 0 {
+            message.push_str("; it resets now, try again");
+        } else {
+            message.push_str(&format!(
+                "; resets in {} minute{}",
+                minutes,
+                if minutes == 1 { "" } else { "s" }
+            ));
+        }
+    }
+    if !has_token {
+        message.push_str(". Set the GITHUB_TOKEN environment variable to raise the limit to 5000/hour");
+    }
+    Some(message)
+}
+
+/// codeload.github.com tarball URL of a branch or a full commit SHA
+fn tarball_url(owner: &str, repo: &str, git_ref: &str, is_commit: bool) -> String {
+    if is_commit {
+        format!("https://codeload.github.com/{}/{}/tar.gz/{}", owner, repo, git_ref)
+    } else {
+        format!("https://codeload.github.com/{}/{}/tar.gz/refs/heads/{}", owner, repo, git_ref)
+    }
+}
+
+/// Unpack the regular files of a GitHub `.tar.gz` into `target_dir`; returns how many
+///
+/// GitHub wraps the tree in a single `<repo>-<ref>/` directory, which is dropped. With
+/// `subdir`, only files below it are unpacked, relative to it. Symlinks and entries with
+/// `..` or absolute paths are skipped.
+pub fn extract_tarball(archive: impl std::io::Read, subdir: Option<&str>, target_dir: &Path) -> Result<usize> {
+    let subdir: Vec<&str> = subdir
+        .unwrap_or("")
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    let mut files = 0;
+    for entry in tar.entries().context("Failed to read tarball")? {
+        let mut entry = entry.context("Failed to read tarball entry")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().context("Invalid path in tarball")?.into_owned();
+        let parts: Option<Vec<String>> = path
+            .components()
+            .skip(1)
+            .map(|component| match component {
+                std::path::Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let Some(parts) = parts else { continue };
+        if parts.len() <= subdir.len() || parts.iter().zip(&subdir).any(|(a, b)| a != b) {
+            continue;
+        }
+
+        let dest = parts[subdir.len()..].iter().fold(target_dir.to_path_buf(), |dir, part| dir.join(part));
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        let mut file = std::fs::File::create(&dest)
+            .with_context(|| format!("Failed to write file: {}", dest.display()))?;
+        std::io::copy(&mut entry, &mut file)
+            .with_context(|| format!("Failed to write file: {}", dest.display()))?;
+        files += 1;
+    }
+
+    if files == 0 {
+        anyhow::bail!("no files found under '{}' in the tarball", subdir.join("/"));
+    }
+    Ok(files)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Read `default_branch` from a `GET /repos/{owner}/{repo}` response body
+pub fn parse_default_branch(body: &str) -> Option<String> {
+    let repo_info: serde_json::Value = serde_json::from_str(body).ok()?;
+    repo_info
+        .get("default_branch")
+        .and_then(|v| v.as_str())
+        .filter(|branch| !branch.is_empty())
+        .map(str::to_string)
+}
+
+/// Extract the `rel="next"` URL from a GitHub `Link` pagination header
+///
+/// Returns `None` when the header has no next page (i.e. this is the last page).
+pub fn parse_next_link(link_header: &str) -> Option<String> {
+    link_header.split(',').find_map(|part| {
+        let mut segments = part.split(';');
+        let url = segments.next()?.trim();
+        if segments.any(|s| s.trim() == "rel=\"next\"") {
+            Some(url.trim_start_matches('<').trim_end_matches('>').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_next_link() {
+        let header = "<https://api.github.com/repositories/1/branches?per_page=100&page=2>; rel=\"next\", \
+                      <https://api.github.com/repositories/1/branches?per_page=100&page=3>; rel=\"last\"";
+        assert_eq!(
+            parse_next_link(header).as_deref(),
+            Some("https://api.github.com/repositories/1/branches?per_page=100&page=2")
+        );
+
+        let last_page = "<https://api.github.com/repositories/1/branches?page=1>; rel=\"prev\", \
+                         <https://api.github.com/repositories/1/branches?page=1>; rel=\"first\"";
+        assert_eq!(parse_next_link(last_page), None);
+    }
+
+    #[test]
+    fn test_cached_downloads() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let downloader = GitHubDownloader::with_token(temp.path().into(), None);
+        let branch = CacheInfo {
+            repo: "owner/repo".into(),
+            git_ref: "main".into(),
+            subdir: Some("mods/loot".into()),
+            commit: Some("abc123".into()),
+            pinned: false,
+        };
+        let pinned = CacheInfo {
+            repo: "owner/other".into(),
+            git_ref: "def456".into(),
+            subdir: None,
+            commit: Some("def456".into()),
+            pinned: true,
+        };
+        let branch_dir = temp.path().join("owner/repo/main/mods/loot");
+        let pinned_dir = temp.path().join("owner/other/def456");
+        for (dir, info) in [(&branch_dir, &branch), (&pinned_dir, &pinned)] {
+            std::fs::create_dir_all(dir).unwrap();
+            info.save(dir).unwrap();
+        }
+        // Nothing is read from the deduplication store or from downloads without a record
+        std::fs::create_dir_all(temp.path().join(".objects/ab")).unwrap();
+        branch.save(&temp.path().join(".objects/ab")).unwrap();
+        std::fs::create_dir_all(temp.path().join("owner/legacy/main")).unwrap();
+
+        let mut downloads = downloader.cached_downloads().unwrap();
+        downloads.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(downloads, vec![(pinned_dir, pinned), (branch_dir, branch)]);
+
+        let json = std::fs::read_to_string(temp.path().join("owner/other/def456").join(CACHE_INFO_FILE)).unwrap();
+        assert!(json.contains(r#""ref": "def456""#) && !json.contains("subdir"), "{}", json);
+    }
+
+    #[test]
+    fn test_with_token() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let with = GitHubDownloader::with_token(temp.path().into(), Some(" ghp_abc\n".into()));
+        assert_eq!(with.github_token.as_deref(), Some("ghp_abc"));
+        let blank = GitHubDownloader::with_token(temp.path().into(), Some("  ".into()));
+        assert_eq!(blank.github_token, None);
+    }
+
+    #[test]
+    fn test_default_branch() {
+        assert_eq!(
+            parse_default_branch(r#"{"full_name":"owner/repo","default_branch":"develop"}"#),
+            Some("develop".to_string())
+        );
+        assert_eq!(parse_default_branch(r#"{"message":"Not Found"}"#), None);
+        assert_eq!(parse_default_branch("not json"), None);
+
+        // Remembered per repository for runs that cannot reach the API
+        let temp = tempfile::TempDir::new().unwrap();
+        let downloader = GitHubDownloader::new(temp.path().to_path_buf());
+        assert_eq!(downloader.load_default_branch("owner/repo"), None);
+        downloader.save_default_branch("owner/repo", "develop").unwrap();
+        assert_eq!(downloader.load_default_branch("owner/repo"), Some("develop".to_string()));
+        assert_eq!(downloader.load_default_branch("owner/other"), None);
+    }
+
+    #[test]
+    fn test_rate_limit_message() {
+        use reqwest::header::{HeaderMap, HeaderValue};
+        use reqwest::StatusCode;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("60"));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("10000"));
+
+        assert_eq!(
+            rate_limit_message(StatusCode::FORBIDDEN, &headers, false, 10000 - 22 * 60 - 5).unwrap(),
+            "GitHub API rate limit exceeded (60 requests/hour); resets in 23 minutes. \
+             Set the GITHUB_TOKEN environment variable to raise the limit to 5000/hour"
+        );
+        assert_eq!(
+            rate_limit_message(StatusCode::FORBIDDEN, &headers, true, 10000).unwrap(),
+            "GitHub API rate limit exceeded (60 requests/hour); it resets now, try again"
+        );
+
+        // A 403 that isn't about the rate limit (e.g. a private repo) keeps the generic error
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("12"));
+        assert_eq!(rate_limit_message(StatusCode::FORBIDDEN, &headers, false, 0), None);
+        assert_eq!(rate_limit_message(StatusCode::NOT_FOUND, &HeaderMap::new(), false, 0), None);
+    }
+
+    #[test]
+    fn test_extract_tarball() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast()));
+        for (path, content) in [
+            ("repo-main/README.md", "readme"),
+            ("repo-main/mods/loot/mod.json", "{}"),
+            ("repo-main/mods/loot/data/filter.lua", "return 1"),
+            ("repo-main/mods/lootplus/mod.json", "{}"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+        }
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let all = temp.path().join("all");
+        assert_eq!(extract_tarball(archive.as_slice(), None, &all).unwrap(), 4);
+        assert_eq!(std::fs::read_to_string(all.join("README.md")).unwrap(), "readme");
+
+        // `mods/lootplus` shares the prefix but is not inside `mods/loot`
+        let loot = temp.path().join("loot");
+        assert_eq!(extract_tarball(archive.as_slice(), Some("mods/loot/"), &loot).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(loot.join("data/filter.lua")).unwrap(), "return 1");
+        assert!(loot.join("mod.json").exists());
+
+        let err = extract_tarball(archive.as_slice(), Some("mods/missing"), &temp.path().join("x")).unwrap_err();
+        assert!(err.to_string().contains("no files found under 'mods/missing'"), "{}", err);
+
+        assert_eq!(
+            tarball_url("owner", "repo", "main", false),
+            "https://codeload.github.com/owner/repo/tar.gz/refs/heads/main"
+        );
+    }
+
+    #[test]
+    fn test_api_cache_round_trip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let cache = ApiCache::new(temp.path().join("api"));
+        let url = "https://api.github.com/repos/owner/repo/branches?per_page=100";
+
+        assert!(cache.get(url).is_none());
+
+        cache
+            .put(&CachedApiResponse {
+                url: url.to_string(),
+                etag: "W/\"abc\"".to_string(),
+                link: None,
+                body: "[{\"name\":\"main\"}]".to_string(),
+            })
+            .unwrap();
+
+        let entry = cache.get(url).unwrap();
+        assert_eq!(entry.etag, "W/\"abc\"");
+        assert_eq!(entry.body, "[{\"name\":\"main\"}]");
+        assert!(cache.get("https://api.github.com/repos/owner/other").is_none());
+    }
+
+    #[test]
+    fn test_api_cache_store_and_replay() {
+        use reqwest::header::{HeaderMap, HeaderValue, ETAG};
+        use reqwest::StatusCode;
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let cache = ApiCache::new(temp.path().join("api"));
+        let url = "https://api.github.com/repos/owner/repo/commits/main";
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+
+        let live = cache.store(url, StatusCode::OK, headers.clone(), "{\"sha\":\"abc\"}".into());
+        assert!(!live.cached);
+        // Errors are never stored
+        cache.store("https://api.github.com/x", StatusCode::NOT_FOUND, headers, "{}".into());
+        assert!(cache.get("https://api.github.com/x").is_none());
+
+        let cached = cache.get(url);
+        assert_eq!(cached.as_ref().unwrap().etag, "\"v1\"");
+        assert!(ApiCache::replay(url, StatusCode::OK, &HeaderMap::new(), &cached).is_none());
+        let replayed = ApiCache::replay(url, StatusCode::NOT_MODIFIED, &HeaderMap::new(), &cached).unwrap();
+        assert_eq!((replayed.status, replayed.body.as_str(), replayed.cached), (StatusCode::OK, "{\"sha\":\"abc\"}", true));
+    }
+}
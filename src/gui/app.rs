@@ -72,14 +72,24 @@ pub struct InfiniteApp {
     is_processing: Arc<Mutex<bool>>,
     // 进度信息
     progress: Arc<Mutex<Option<String>>>,
+    // 脚本通过 infinite.reportProgress 上报的进度
+    script_progress: infinite::runtime::SharedProgress,
     // GitHub对话框状态
     github_dialog: Option<GitHubDialog>,
     // GitHub Token (可选)
     github_token: Option<String>,
+    // 额外的 CASC 存储根目录 (设置对话框中每行一个)
+    casc_roots_text: String,
+    // CASC 存储打不开时仍然生成 (禁用游戏文件提取)
+    allow_no_casc: bool,
+    // 沙盒模式: 限制脚本可访问的路径
+    sandbox: bool,
     // GitHub API 速率限制信息
     github_rate_limit: Arc<Mutex<Option<GitHubRateLimit>>>,
     // 是否显示设置对话框
     show_settings: bool,
+    // 已记录过日志的无效配置选项 (mod 路径, 选项 ID),避免每帧重复记录
+    reported_invalid_options: std::collections::HashSet<(String, String)>,
 }
 
 /// GitHub API 速率限制信息
@@ -109,10 +119,26 @@ enum ConfigLoadState {
     NotLoaded,
     Loading,
     Loaded(ModConfig),
-    #[allow(dead_code)] // 保留错误信息用于将来的错误显示
-    Failed(String),
+    /// 加载失败; 记录失败时配置来源的修改时间, 来源变化后 (如重新下载) 自动重试
+    Failed {
+        reason: String,
+        source_mtime: Option<std::time::SystemTime>,
+    },
 }
 
+/// README 加载状态
+#[derive(Clone, Default)]
+enum ReadmeState {
+    #[default]
+    NotLoaded,
+    Loading,
+    /// 已加载; mod 没有 README 时为 None
+    Loaded(Option<String>),
+}
+
+/// 拖放排序时携带的 mod 下标
+struct DraggedMod(usize);
+
 #[derive(Clone, Serialize, Deserialize)]
 struct ModEntry {
     path: String,
@@ -124,6 +150,9 @@ struct ModEntry {
     /// 缓存的配置加载状态 (不持久化)
     #[serde(skip)]
     config_state: Arc<Mutex<ConfigLoadState>>,
+    /// 缓存的 README 加载状态 (不持久化)
+    #[serde(skip)]
+    readme_state: Arc<Mutex<ReadmeState>>,
 }
 
 impl ModEntry {
@@ -133,7 +162,13 @@ impl ModEntry {
         let state = self.config_state.lock().unwrap().clone();
         match state {
             ConfigLoadState::Loaded(config) => return Some(config),
-            ConfigLoadState::Failed(_) => return None, // 已经失败过,不再重试
+            ConfigLoadState::Failed { source_mtime, .. } => {
+                // 来源未变化时不再重试
+                if self.config_source_mtime() == source_mtime {
+                    return None;
+                }
+                tracing::info!("Config source for {} changed since last failure, retrying", self.path);
+            }
             ConfigLoadState::Loading => return None, // 正在加载中
             ConfigLoadState::NotLoaded => {
                 // 需要加载
@@ -163,15 +198,19 @@ impl ModEntry {
         } else {
             let mod_json_path = PathBuf::from(&self.path).join("mod.json");
             if let Ok(content) = std::fs::read_to_string(&mod_json_path) {
-                let config: Option<ModConfig> = serde_json::from_str(&content).ok();
-                if let Some(ref cfg) = config {
-                    *self.config_state.lock().unwrap() = ConfigLoadState::Loaded(cfg.clone());
-                } else {
-                    *self.config_state.lock().unwrap() = ConfigLoadState::Failed("Failed to parse config".to_string());
+                match serde_json::from_str::<ModConfig>(&content) {
+                    Ok(cfg) => {
+                        // 无效选项在配置面板中单独标出,其余选项照常显示
+                        *self.config_state.lock().unwrap() = ConfigLoadState::Loaded(cfg.clone());
+                        Some(cfg)
+                    }
+                    Err(_) => {
+                        self.fail_config("Failed to parse config");
+                        None
+                    }
                 }
-                config
             } else {
-                *self.config_state.lock().unwrap() = ConfigLoadState::Failed("Config file not found".to_string());
+                self.fail_config("Config file not found");
                 None
             }
         };
@@ -179,6 +218,39 @@ impl ModEntry {
         result
     }
 
+    /// 配置来源 (缓存目录或本地 mod 目录) 及其 mod.json 的最新修改时间
+    fn config_source_mtime(&self) -> Option<std::time::SystemTime> {
+        let dir = if self.path.starts_with("github:") {
+            self.resolve_github_path()?
+        } else {
+            PathBuf::from(&self.path)
+        };
+
+        let modified = |path: &std::path::Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        modified(&dir).max(modified(&dir.join("mod.json")))
+    }
+
+    /// 标记配置加载失败 (记录当前来源修改时间)
+    fn fail_config(&self, reason: &str) {
+        *self.config_state.lock().unwrap() = ConfigLoadState::Failed {
+            reason: reason.to_string(),
+            source_mtime: self.config_source_mtime(),
+        };
+    }
+
+    /// 手动重试加载配置
+    fn retry_config_load(&self) {
+        *self.config_state.lock().unwrap() = ConfigLoadState::NotLoaded;
+    }
+
+    /// 配置加载失败的原因 (未失败时为 None)
+    fn config_error(&self) -> Option<String> {
+        match &*self.config_state.lock().unwrap() {
+            ConfigLoadState::Failed { reason, .. } => Some(reason.clone()),
+            _ => None,
+        }
+    }
+
     /// 异步从 GitHub API 加载配置
     fn load_config_from_github_async(&self, ctx: Option<egui::Context>, github_token: Option<String>) {
         if !self.path.starts_with("github:") {
@@ -190,6 +262,7 @@ impl ModEntry {
         // 克隆必要的数据
         let path = self.path.clone();
         let config_state = self.config_state.clone();
+        let source_mtime = self.config_source_mtime();
 
         // 在后台线程中执行
         std::thread::spawn(move || {
@@ -197,7 +270,10 @@ impl ModEntry {
             let gh_path = match GitHubPath::parse(&path) {
                 Some(p) => p,
                 None => {
-                    *config_state.lock().unwrap() = ConfigLoadState::Failed("Invalid GitHub path".to_string());
+                    *config_state.lock().unwrap() = ConfigLoadState::Failed {
+                        reason: "Invalid GitHub path".to_string(),
+                        source_mtime,
+                    };
                     if let Some(ctx) = ctx {
                         ctx.request_repaint();
                     }
@@ -205,35 +281,38 @@ impl ModEntry {
                 }
             };
 
+            // API 请求经 GitHubDownloader 的 ETag 缓存发送, 内容未变化时 (304) 不消耗速率限制
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    *config_state.lock().unwrap() = ConfigLoadState::Failed {
+                        reason: format!("Failed to start runtime: {}", e),
+                        source_mtime,
+                    };
+                    if let Some(ctx) = ctx {
+                        ctx.request_repaint();
+                    }
+                    return;
+                }
+            };
+            let mut downloader = infinite::GitHubDownloader::with_token(AppConfig::cache_dir(), github_token);
+            downloader.set_api_cache_dir(AppConfig::api_cache_dir());
+
             // 如果没有指定分支,先获取仓库的默认分支
             let branch = if let Some(b) = gh_path.branch {
                 b
             } else {
                 // 查询仓库信息获取默认分支
                 let repo_url = format!("https://api.github.com/repos/{}", gh_path.repo);
-                let mut repo_request = reqwest::blocking::Client::new()
-                    .get(&repo_url)
-                    .header("User-Agent", "infinite-mod-manager");
-
-                if let Some(ref token) = github_token {
-                    repo_request = repo_request.header("Authorization", format!("Bearer {}", token));
-                }
-
-                match repo_request.send() {
-                    Ok(response) if response.status().is_success() => {
-                        if let Ok(repo_info) = response.json::<serde_json::Value>() {
-                            repo_info
-                                .get("default_branch")
-                                .and_then(|v| v.as_str())
-                                .map(|s| s.to_string())
-                                .unwrap_or_else(|| "main".to_string())
-                        } else {
-                            "main".to_string()
-                        }
-                    }
-                    _ => {
+                match runtime.block_on(downloader.get_json_cached(&repo_url)) {
+                    Ok(info) => info
+                        .get("default_branch")
+                        .and_then(|b| b.as_str())
+                        .unwrap_or("main")
+                        .to_string(),
+                    Err(e) => {
                         // 如果获取失败,回退到 main
-                        eprintln!("⚠️ Failed to get default branch, trying 'main'");
+                        eprintln!("⚠️ Failed to get default branch ({:#}), trying 'main'", e);
                         "main".to_string()
                     }
                 }
@@ -251,71 +330,124 @@ impl ModEntry {
                 gh_path.repo, file_path, branch
             );
 
-            // 构建请求
-            let mut request = reqwest::blocking::Client::new()
-                .get(&url)
-                .header("User-Agent", "infinite-mod-manager");
-
-            // 如果有 token,添加认证
-            if let Some(token) = github_token {
-                request = request.header("Authorization", format!("Bearer {}", token));
-            }
-
             // 尝试从 GitHub API 获取
-            match request.send() {
-                Ok(response) => {
-                    // 检查速率限制
-                    if let Some(remaining) = response.headers().get("x-ratelimit-remaining") {
-                        if let Ok(remaining_str) = remaining.to_str() {
-                            if let Ok(remaining_num) = remaining_str.parse::<u32>() {
-                                if remaining_num < 10 {
-                                    eprintln!("⚠️ GitHub API rate limit warning: {} requests remaining", remaining_num);
-                                }
-                            }
-                        }
-                    }
-
-                    if response.status().is_success() {
-                        if let Ok(content_json) = response.json::<serde_json::Value>() {
-                            // GitHub API 返回 base64 编码的内容
-                            if let Some(content_b64) = content_json.get("content").and_then(|c| c.as_str()) {
-                                // 移除换行符
-                                let content_b64 = content_b64.replace("\n", "");
-                                use base64::Engine;
-                                if let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(&content_b64) {
-                                    if let Ok(content_str) = String::from_utf8(decoded) {
-                                        if let Ok(config) = serde_json::from_str(&content_str) {
-                                            println!("✅ Successfully loaded mod.json from GitHub");
-                                            *config_state.lock().unwrap() = ConfigLoadState::Loaded(config);
-                                            // 请求重绘
-                                            if let Some(ctx) = ctx {
-                                                ctx.request_repaint();
-                                            }
-                                            return;
-                                        }
+            match runtime.block_on(downloader.get_json_cached(&url)) {
+                Ok(content_json) => {
+                    // GitHub API 返回 base64 编码的内容
+                    if let Some(content_b64) = content_json.get("content").and_then(|c| c.as_str()) {
+                        // 移除换行符
+                        let content_b64 = content_b64.replace("\n", "");
+                        use base64::Engine;
+                        if let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(&content_b64) {
+                            if let Ok(content_str) = String::from_utf8(decoded) {
+                                if let Ok(config) = serde_json::from_str(&content_str) {
+                                    println!("✅ Successfully loaded mod.json from GitHub");
+                                    *config_state.lock().unwrap() = ConfigLoadState::Loaded(config);
+                                    // 请求重绘
+                                    if let Some(ctx) = ctx {
+                                        ctx.request_repaint();
                                     }
+                                    return;
                                 }
                             }
                         }
-                    } else if response.status().as_u16() == 403 {
-                        eprintln!("⚠️ GitHub API rate limit exceeded. Consider adding a GitHub token in settings.");
-                    } else {
-                        eprintln!("⚠️ GitHub API error: {}", response.status());
                     }
                 }
                 Err(e) => {
-                    eprintln!("❌ Failed to fetch mod.json from GitHub: {}", e);
+                    eprintln!("❌ Failed to fetch mod.json from GitHub: {:#}", e);
                 }
             }
 
             // 失败情况
-            *config_state.lock().unwrap() = ConfigLoadState::Failed("Failed to load config from GitHub".to_string());
+            *config_state.lock().unwrap() = ConfigLoadState::Failed {
+                reason: "Failed to load config from GitHub".to_string(),
+                source_mtime,
+            };
             if let Some(ctx) = ctx {
                 ctx.request_repaint();
             }
         });
     }
 
+    /// 获取 README (本地直接读取; GitHub mod 先查下载缓存,否则后台从 API 获取)
+    fn load_readme(&self, ctx: &egui::Context, github_token: Option<String>) -> Option<String> {
+        let state = self.readme_state.lock().unwrap().clone();
+        match state {
+            ReadmeState::Loaded(readme) => return readme,
+            ReadmeState::Loading => return None,
+            ReadmeState::NotLoaded => {}
+        }
+
+        if !self.path.starts_with("github:") {
+            let readme = infinite::mod_manager::read_mod_readme(std::path::Path::new(&self.path));
+            *self.readme_state.lock().unwrap() = ReadmeState::Loaded(readme.clone());
+            return readme;
+        }
+
+        if let Some(readme) = self
+            .resolve_github_path()
+            .and_then(|dir| infinite::mod_manager::read_mod_readme(&dir))
+        {
+            *self.readme_state.lock().unwrap() = ReadmeState::Loaded(Some(readme.clone()));
+            return Some(readme);
+        }
+
+        *self.readme_state.lock().unwrap() = ReadmeState::Loading;
+        let Some(gh_path) = GitHubPath::parse(&self.path) else {
+            *self.readme_state.lock().unwrap() = ReadmeState::Loaded(None);
+            return None;
+        };
+        let readme_state = self.readme_state.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let api_cache = infinite::github_downloader::ApiCache::new(AppConfig::api_cache_dir());
+
+            // GitHub 的 readme 接口会查找目录中任意大小写的 README; 不指定 ref 时使用默认分支
+            let mut url = format!("https://api.github.com/repos/{}/readme", gh_path.repo);
+            if let Some(subdir) = &gh_path.subdir {
+                url = format!("{}/{}", url, subdir);
+            }
+            if let Some(branch) = &gh_path.branch {
+                url = format!("{}?ref={}", url, branch);
+            }
+
+            let mut request = reqwest::blocking::Client::new()
+                .get(&url)
+                .header("User-Agent", "infinite-mod-manager");
+            if let Some(token) = github_token {
+                request = request.header("Authorization", format!("Bearer {}", token));
+            }
+
+            // 404 表示没有 README; 其他失败同样按无 README 处理,不影响配置面板
+            let readme = match api_cache.send_blocking(&url, request) {
+                Ok(response) if response.status.is_success() => {
+                    serde_json::from_str::<serde_json::Value>(&response.body)
+                        .ok()
+                        .and_then(|json| json.get("content").and_then(|c| c.as_str()).map(|c| c.replace('\n', "")))
+                        .and_then(|content_b64| {
+                            use base64::Engine;
+                            base64::engine::general_purpose::STANDARD.decode(content_b64).ok()
+                        })
+                        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                }
+                Ok(response) => {
+                    if response.status.as_u16() != 404 {
+                        eprintln!("⚠️ Failed to fetch README from GitHub: {}", response.status);
+                    }
+                    None
+                }
+                Err(e) => {
+                    eprintln!("❌ Failed to fetch README from GitHub: {}", e);
+                    None
+                }
+            };
+
+            *readme_state.lock().unwrap() = ReadmeState::Loaded(readme);
+            ctx.request_repaint();
+        });
+        None
+    }
+
     /// 解析 GitHub 路径到实际的缓存路径
     fn resolve_github_path(&self) -> Option<PathBuf> {
         GitHubPath::parse(&self.path).map(|gh| gh.cache_path())
@@ -324,36 +456,37 @@ impl ModEntry {
     /// 初始化用户配置（使用默认值）
     fn init_user_config(&mut self) {
         if let Some(mod_config) = self.load_config(None, None) {
-            for option in &mod_config.config {
-                // 获取配置项的ID和默认值
-                let (id, default_value) = match option {
-                    infinite::mod_manager::config::ConfigOption::CheckBox {
-                        id, default, ..
-                    } => (id.clone(), serde_json::json!(default)),
-                    infinite::mod_manager::config::ConfigOption::Number { id, default, .. } => {
-                        (id.clone(), serde_json::json!(default))
-                    }
-                    infinite::mod_manager::config::ConfigOption::Text { id, default, .. } => {
-                        (id.clone(), serde_json::json!(default))
-                    }
-                    infinite::mod_manager::config::ConfigOption::Select { id, default, .. } => {
-                        (id.clone(), serde_json::json!(default))
-                    }
-                    infinite::mod_manager::config::ConfigOption::Section { .. } => {
-                        // Section 不需要存储值，跳过
-                        continue;
-                    }
-                };
+            // 默认值 (Section 不需要存储值), 再叠加 mod 自带的预设
+            let mut defaults = mod_config.generate_default_config();
+            let mod_dir = if self.path.starts_with("github:") {
+                self.resolve_github_path()
+            } else {
+                Some(PathBuf::from(&self.path))
+            };
+            if let Some(preset) = mod_dir
+                .map(|dir| dir.join(infinite::mod_manager::config::CONFIG_PRESET_FILE))
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .and_then(|content| serde_json::from_str(&content).ok())
+            {
+                mod_config.apply_preset(&mut defaults, preset);
+            }
 
-                // 如果用户配置中没有这个选项，使用默认值
-                if !self.user_config.contains_key(&id) {
-                    self.user_config.insert(id, default_value);
-                }
+            // 如果用户配置中没有这个选项，使用默认值
+            for (id, default_value) in defaults {
+                self.user_config.entry(id).or_insert(default_value);
             }
         }
     }
 }
 
+/// 生成时使用的设置 (来自设置对话框)
+struct BuildSettings {
+    github_token: Option<String>,
+    casc_roots: Vec<PathBuf>,
+    allow_no_casc: bool,
+    sandbox: bool,
+}
+
 /// 持久化配置
 #[derive(Serialize, Deserialize, Default)]
 struct AppConfig {
@@ -361,6 +494,12 @@ struct AppConfig {
     mods: Vec<ModEntry>,
     #[serde(default)]
     github_token: Option<String>,
+    #[serde(default)]
+    casc_roots: Vec<PathBuf>,
+    #[serde(default)]
+    allow_no_casc: bool,
+    #[serde(default)]
+    sandbox: bool,
 }
 
 impl AppConfig {
@@ -378,34 +517,74 @@ impl AppConfig {
         path
     }
 
-    /// 获取 mod 缓存目录路径
+    /// 获取 mod 缓存目录路径 (与 CLI 相同,支持 INFINITE_CACHE_DIR)
     fn cache_dir() -> PathBuf {
+        infinite::install::default_download_cache_dir()
+    }
+
+    /// 获取 GitHub API 响应缓存目录路径 (ETag 条件请求)
+    fn api_cache_dir() -> PathBuf {
         let mut path = Self::data_dir();
-        path.push("mod_cache");
+        path.push("github_api_cache");
         path
     }
 
+    /// 获取配置备份文件路径 (上一份完好的配置)
+    fn backup_path() -> PathBuf {
+        let mut path = Self::data_dir();
+        path.push("gui_config.json.bak");
+        path
+    }
+
+    /// 读取并解析配置文件, 文件不存在或已损坏时返回 None
+    fn read_from(path: &std::path::Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
     /// 从文件加载配置
     fn load() -> Self {
         let path = Self::config_path();
+        if let Some(config) = Self::read_from(&path) {
+            return config;
+        }
+
+        // 主配置损坏时回退到备份, 避免丢失整个 mod 列表
         if path.exists() {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                if let Ok(config) = serde_json::from_str(&content) {
-                    return config;
-                }
-            }
+            eprintln!("⚠️ Config file is corrupt, trying backup: {}", Self::backup_path().display());
         }
+        if let Some(config) = Self::read_from(&Self::backup_path()) {
+            return config;
+        }
+
         Self::default()
     }
 
     /// 保存配置到文件
+    ///
+    /// 先写入临时文件再原子重命名, 写入中途崩溃不会截断现有配置
     fn save(&self) -> std::io::Result<()> {
+        use std::io::Write;
+
         let path = Self::config_path();
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
         let content = serde_json::to_string_pretty(self)?;
-        std::fs::write(&path, content)?;
+
+        let tmp_path = path.with_extension("json.tmp");
+        {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+        }
+
+        // 保留上一份完好的配置作为备份
+        if Self::read_from(&path).is_some() {
+            std::fs::copy(&path, Self::backup_path())?;
+        }
+
+        std::fs::rename(&tmp_path, &path)?;
         Ok(())
     }
 }
@@ -428,10 +607,20 @@ impl InfiniteApp {
             status_message: Arc::new(Mutex::new("准备就绪".to_string())),
             is_processing: Arc::new(Mutex::new(false)),
             progress: Arc::new(Mutex::new(None)),
+            script_progress: Default::default(),
             github_dialog: None,
             github_token: config.github_token,
+            casc_roots_text: config
+                .casc_roots
+                .iter()
+                .map(|root| root.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            allow_no_casc: config.allow_no_casc,
+            sandbox: config.sandbox,
             github_rate_limit: Arc::new(Mutex::new(None)),
             show_settings: false,
+            reported_invalid_options: Default::default(),
         }
     }
 
@@ -441,6 +630,9 @@ impl InfiniteApp {
             game_path: self.game_path.clone(),
             mods: self.mods.clone(),
             github_token: self.github_token.clone(),
+            casc_roots: self.casc_roots(),
+            allow_no_casc: self.allow_no_casc,
+            sandbox: self.sandbox,
         };
 
         if let Err(e) = config.save() {
@@ -448,11 +640,33 @@ impl InfiniteApp {
         }
     }
 
+    /// 设置中填写的额外 CASC 存储根目录 (忽略空行)
+    fn casc_roots(&self) -> Vec<PathBuf> {
+        self.casc_roots_text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+
     /// 统一路径格式显示 - 将反斜杠转换为正斜杠
     fn normalize_path_display(path: &str) -> String {
         path.replace('\\', "/")
     }
 
+    /// 在系统文件管理器中打开目录
+    fn open_folder(&self, path: &std::path::Path) {
+        let display = Self::normalize_path_display(&path.to_string_lossy());
+        if !path.exists() {
+            *self.status_message.lock().unwrap() = format!("目录不存在: {}", display);
+            return;
+        }
+        if let Err(e) = open::that(path) {
+            *self.status_message.lock().unwrap() = format!("无法打开目录 {}: {}", display, e);
+        }
+    }
+
     fn select_game_path(&mut self) {
         if let Some(path) = rfd::FileDialog::new()
             .set_title("选择暗黑破坏神2重制版游戏目录")
@@ -485,6 +699,7 @@ impl InfiniteApp {
                                 name,
                                 user_config: HashMap::new(),
                                 config_state: Arc::new(Mutex::new(ConfigLoadState::NotLoaded)),
+                                readme_state: Default::default(),
                             };
                             mod_entry.init_user_config();
                             self.mods.push(mod_entry);
@@ -567,6 +782,7 @@ impl InfiniteApp {
                 name,
                 user_config: HashMap::new(),
                 config_state: Arc::new(Mutex::new(ConfigLoadState::NotLoaded)),
+                readme_state: Default::default(),
             };
             mod_entry.init_user_config();
             self.mods.push(mod_entry);
@@ -643,72 +859,95 @@ impl InfiniteApp {
 
             // 在新线程中获取分支信息
             std::thread::spawn(move || {
-                // 使用 GitHub API 获取分支列表
-                let url = format!("https://api.github.com/repos/{}/branches", repo_clone);
-
-                let mut request = reqwest::blocking::Client::new()
-                    .get(&url)
-                    .header("User-Agent", "infinite-mod-manager");
-
-                // 添加 token (如果有)
-                if let Some(token) = github_token {
-                    request = request.header("Authorization", format!("Bearer {}", token));
-                }
+                // 使用 GitHub API 获取分支列表 (跟随 Link: rel="next" 翻页)
+                let client = reqwest::blocking::Client::new();
+                let api_cache = infinite::github_downloader::ApiCache::new(AppConfig::api_cache_dir());
+                let mut next_url = Some(format!(
+                    "https://api.github.com/repos/{}/branches?per_page=100",
+                    repo_clone
+                ));
+                let mut branch_list: Vec<String> = Vec::new();
+
+                while let Some(url) = next_url.take() {
+                    let mut request = client
+                        .get(&url)
+                        .header("User-Agent", "infinite-mod-manager");
+
+                    // 添加 token (如果有)
+                    if let Some(ref token) = github_token {
+                        request = request.header("Authorization", format!("Bearer {}", token));
+                    }
 
-                match request.send() {
-                    Ok(response) => {
-                        // 更新速率限制信息
-                        if let (Some(remaining), Some(limit), Some(reset)) = (
-                            response.headers().get("x-ratelimit-remaining"),
-                            response.headers().get("x-ratelimit-limit"),
-                            response.headers().get("x-ratelimit-reset"),
+                    let response = match api_cache.send_blocking(&url, request) {
+                        Ok(response) => response,
+                        Err(e) => {
+                            *error_clone.lock().unwrap() = Some(format!("网络错误: {}", e));
+                            *is_loading_clone.lock().unwrap() = false;
+                            ctx.request_repaint();
+                            return;
+                        }
+                    };
+
+                    // 更新速率限制信息
+                    if let (Some(remaining), Some(limit), Some(reset)) = (
+                        response.headers.get("x-ratelimit-remaining"),
+                        response.headers.get("x-ratelimit-limit"),
+                        response.headers.get("x-ratelimit-reset"),
+                    ) {
+                        if let (Ok(rem_str), Ok(lim_str), Ok(reset_str)) = (
+                            remaining.to_str(),
+                            limit.to_str(),
+                            reset.to_str(),
                         ) {
-                            if let (Ok(rem_str), Ok(lim_str), Ok(reset_str)) = (
-                                remaining.to_str(),
-                                limit.to_str(),
-                                reset.to_str(),
+                            if let (Ok(rem), Ok(lim), Ok(reset_ts)) = (
+                                rem_str.parse::<u32>(),
+                                lim_str.parse::<u32>(),
+                                reset_str.parse::<u64>(),
                             ) {
-                                if let (Ok(rem), Ok(lim), Ok(reset_ts)) = (
-                                    rem_str.parse::<u32>(),
-                                    lim_str.parse::<u32>(),
-                                    reset_str.parse::<u64>(),
-                                ) {
-                                    *rate_limit_clone.lock().unwrap() = Some(GitHubRateLimit {
-                                        remaining: rem,
-                                        limit: lim,
-                                        reset_time: std::time::UNIX_EPOCH + std::time::Duration::from_secs(reset_ts),
-                                    });
-                                }
+                                *rate_limit_clone.lock().unwrap() = Some(GitHubRateLimit {
+                                    remaining: rem,
+                                    limit: lim,
+                                    reset_time: std::time::UNIX_EPOCH + std::time::Duration::from_secs(reset_ts),
+                                });
                             }
                         }
+                    }
 
-                        let status = response.status();
-                        if status.is_success() {
-                            if let Ok(branches_json) = response.json::<serde_json::Value>() {
-                                if let Some(branches_array) = branches_json.as_array() {
-                                    let branch_list: Vec<String> = branches_array
-                                        .iter()
-                                        .filter_map(|b| b.get("name")?.as_str())
-                                        .map(String::from)
-                                        .collect();
-
-                                    *branches_clone.lock().unwrap() = branch_list;
-                                    *is_loading_clone.lock().unwrap() = false;
-                                    ctx.request_repaint();
-                                    return;
-                                }
-                            }
+                    // 下一页地址 (没有则说明已是最后一页)
+                    next_url = response
+                        .link
+                        .as_deref()
+                        .and_then(infinite::github_downloader::parse_next_link);
+
+                    let status = response.status;
+                    let page: Option<Vec<String>> = if status.is_success() {
+                        serde_json::from_str::<serde_json::Value>(&response.body).ok().and_then(|branches_json| {
+                            branches_json.as_array().map(|branches_array| {
+                                branches_array
+                                    .iter()
+                                    .filter_map(|b| b.get("name")?.as_str())
+                                    .map(String::from)
+                                    .collect()
+                            })
+                        })
+                    } else {
+                        None
+                    };
+
+                    match page {
+                        Some(names) => branch_list.extend(names),
+                        None => {
+                            *error_clone.lock().unwrap() =
+                                Some(format!("无法获取仓库信息: {}", status));
+                            *is_loading_clone.lock().unwrap() = false;
+                            ctx.request_repaint();
+                            return;
                         }
-
-                        *error_clone.lock().unwrap() =
-                            Some(format!("无法获取仓库信息: {}", status));
-                        *is_loading_clone.lock().unwrap() = false;
-                    }
-                    Err(e) => {
-                        *error_clone.lock().unwrap() = Some(format!("网络错误: {}", e));
-                        *is_loading_clone.lock().unwrap() = false;
                     }
                 }
+
+                *branches_clone.lock().unwrap() = branch_list;
+                *is_loading_clone.lock().unwrap() = false;
                 ctx.request_repaint();
             });
         }
@@ -751,11 +990,12 @@ impl InfiniteApp {
                     request = request.header("Authorization", format!("Bearer {}", token));
                 }
 
-                match request.send() {
+                let api_cache = infinite::github_downloader::ApiCache::new(AppConfig::api_cache_dir());
+                match api_cache.send_blocking(&url, request) {
                     Ok(response) => {
-                        let status = response.status();
+                        let status = response.status;
                         if status.is_success() {
-                            if let Ok(tree_json) = response.json::<serde_json::Value>() {
+                            if let Ok(tree_json) = serde_json::from_str::<serde_json::Value>(&response.body) {
                                 if let Some(tree_array) =
                                     tree_json.get("tree").and_then(|t| t.as_array())
                                 {
@@ -831,6 +1071,7 @@ impl InfiniteApp {
                     name,
                     user_config: HashMap::new(),
                     config_state: Arc::new(Mutex::new(ConfigLoadState::NotLoaded)),
+                    readme_state: Default::default(),
                 };
                 mod_entry.init_user_config();
                 self.mods.push(mod_entry);
@@ -865,6 +1106,34 @@ impl InfiniteApp {
         }
     }
 
+    /// 拖放排序: 把 `from` 处的 mod 插入到原列表中 `before` 位置之前 (`before == len` 表示末尾)
+    fn move_mod_to(&mut self, from: usize, before: usize) {
+        if from >= self.mods.len() || before > self.mods.len() {
+            return;
+        }
+        let to = if before > from { before - 1 } else { before };
+        if to == from {
+            return;
+        }
+
+        let entry = self.mods.remove(from);
+        self.mods.insert(to, entry);
+
+        // 选中的配置面板跟随移动后的 mod
+        self.selected_mod_index = self.selected_mod_index.map(|selected| {
+            if selected == from {
+                to
+            } else if from < selected && selected <= to {
+                selected - 1
+            } else if to <= selected && selected < from {
+                selected + 1
+            } else {
+                selected
+            }
+        });
+        self.save_config();
+    }
+
     /// 渲染Mod配置面板
     fn render_config_panel(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         if let Some(index) = self.selected_mod_index {
@@ -892,9 +1161,30 @@ impl InfiniteApp {
 
                         // 配置选项区域 - 不需要内部滚动,外层已经有了
                         let mod_entry = &mut self.mods[index];
+                        let reported_invalid_options = &mut self.reported_invalid_options;
                         ui.set_width(ui.available_width());
 
                         for option in &config_options {
+                                    // showIf 条件不满足的选项不显示 (值仍会传给脚本)
+                                    if !mod_config.is_option_visible(option, &mod_entry.user_config) {
+                                        continue;
+                                    }
+
+                                    // 单个无效选项只显示警告,不影响其余选项
+                                    if let Err(e) = option.validate() {
+                                        let key = (mod_entry.path.clone(), option.id().to_string());
+                                        if reported_invalid_options.insert(key) {
+                                            tracing::warn!("Invalid config option in {}: {}", mod_entry.path, e);
+                                        }
+                                        ui.label(
+                                            egui::RichText::new(format!("⚠ 无效选项 '{}'", option.id()))
+                                                .color(egui::Color32::YELLOW),
+                                        )
+                                        .on_hover_text(e.to_string());
+                                        ui.add_space(8.0);
+                                        continue;
+                                    }
+
                                     match option {
                                         infinite::mod_manager::config::ConfigOption::Section {
                                             name,
@@ -912,6 +1202,7 @@ impl InfiniteApp {
                                             name,
                                             description,
                                             default,
+                                            ..
                                         } => {
                                             let mut value = mod_entry
                                                 .user_config
@@ -943,6 +1234,7 @@ impl InfiniteApp {
                                             min,
                                             max,
                                             default,
+                                            ..
                                         } => {
                                             let mut value = mod_entry
                                                 .user_config
@@ -993,6 +1285,7 @@ impl InfiniteApp {
                                             name,
                                             description,
                                             default,
+                                            ..
                                         } => {
                                             let mut value = mod_entry
                                                 .user_config
@@ -1028,6 +1321,7 @@ impl InfiniteApp {
                                             description,
                                             default,
                                             options,
+                                            ..
                                         } => {
                                             let mut value = mod_entry
                                                 .user_config
@@ -1069,6 +1363,52 @@ impl InfiniteApp {
                                             }
                                             ui.add_space(8.0);
                                         }
+
+                                        infinite::mod_manager::config::ConfigOption::Color {
+                                            id,
+                                            name,
+                                            description,
+                                            default,
+                                            ..
+                                        } => {
+                                            use infinite::mod_manager::config::{
+                                                format_hex_color, parse_hex_color,
+                                            };
+
+                                            // 值以 #rrggbb 字符串保存,传给脚本的也是这个字符串
+                                            let mut rgb = mod_entry
+                                                .user_config
+                                                .get(id)
+                                                .and_then(|v| v.as_str())
+                                                .and_then(parse_hex_color)
+                                                .or_else(|| parse_hex_color(default))
+                                                .unwrap_or([255, 255, 255]);
+
+                                            ui.horizontal(|ui| {
+                                                ui.label(name);
+                                                if ui.color_edit_button_srgb(&mut rgb).changed() {
+                                                    mod_entry.user_config.insert(
+                                                        id.clone(),
+                                                        serde_json::json!(format_hex_color(rgb)),
+                                                    );
+                                                    config_changed = true;
+                                                }
+                                                ui.label(
+                                                    egui::RichText::new(format_hex_color(rgb))
+                                                        .monospace()
+                                                        .color(egui::Color32::GRAY),
+                                                );
+                                            });
+
+                                            if let Some(desc) = description {
+                                                ui.label(
+                                                    egui::RichText::new(desc)
+                                                        .small()
+                                                        .color(egui::Color32::GRAY),
+                                                );
+                                            }
+                                            ui.add_space(8.0);
+                                        }
                                     }
                                 }
 
@@ -1090,6 +1430,40 @@ impl InfiniteApp {
         }
     }
 
+    /// 渲染选中 Mod 的 README (只处理标题行,其余按纯文本显示)
+    fn render_readme_panel(&self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let Some(mod_entry) = self.selected_mod_index.and_then(|index| self.mods.get(index)) else {
+            return;
+        };
+        let readme = mod_entry.load_readme(ctx, self.github_token.clone());
+        let loading = matches!(*mod_entry.readme_state.lock().unwrap(), ReadmeState::Loading);
+
+        if readme.is_none() && !loading {
+            return;
+        }
+
+        egui::CollapsingHeader::new("📖 说明")
+            .id_source(("readme", &mod_entry.path))
+            .default_open(false)
+            .show(ui, |ui| {
+                let Some(readme) = readme else {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("正在加载 README...");
+                    });
+                    return;
+                };
+                for line in readme.lines() {
+                    let heading = line.trim_start_matches('#');
+                    if heading.len() < line.len() && heading.starts_with(' ') {
+                        ui.label(egui::RichText::new(heading.trim()).strong());
+                    } else {
+                        ui.label(line);
+                    }
+                }
+            });
+    }
+
     fn generate_mods(&mut self, ctx: egui::Context) {
         if self.game_path.is_empty() {
             *self.status_message.lock().unwrap() = "请先选择游戏路径".to_string();
@@ -1115,7 +1489,7 @@ impl InfiniteApp {
         }
 
         // 计算输出路径
-        let output_path = format!("{}/Mods/Infinite/Infinite.mpq/data", self.game_path);
+        let output_path = infinite::default_output_path(&self.game_path);
 
         *self.status_message.lock().unwrap() = format!("正在生成 {} 个mod...", enabled_mods.len());
         *self.is_processing.lock().unwrap() = true;
@@ -1126,7 +1500,13 @@ impl InfiniteApp {
         let status_msg = self.status_message.clone();
         let is_proc = self.is_processing.clone();
         let progress = self.progress.clone();
-        let github_token = self.github_token.clone();
+        let script_progress = self.script_progress.clone();
+        let settings = BuildSettings {
+            github_token: self.github_token.clone(),
+            casc_roots: self.casc_roots(),
+            allow_no_casc: self.allow_no_casc,
+            sandbox: self.sandbox,
+        };
 
         // 在新线程中运行(使用tokio runtime)
         std::thread::spawn(move || {
@@ -1148,18 +1528,24 @@ impl InfiniteApp {
                     &game_path,
                     &output_path,
                     enabled_mods,
-                    github_token,
+                    settings,
                     progress.clone(),
+                    script_progress,
                     ctx.clone(),
                 ).await
             });
 
             match result {
-                Ok(_) => {
-                    *status_msg.lock().unwrap() = format!("✅ 成功生成到: {}", output_path);
+                Ok(outcome) => {
+                    let failed = outcome.failed().count();
+                    *status_msg.lock().unwrap() = if failed == 0 {
+                        format!("✅ 成功生成到: {}", output_path)
+                    } else {
+                        format!("⚠️ 已生成到: {}, {} 个mod失败 (详见日志)", output_path, failed)
+                    };
                 }
                 Err(e) => {
-                    *status_msg.lock().unwrap() = format!("❌ 生成失败: {}", e);
+                    *status_msg.lock().unwrap() = format!("❌ 生成失败: {:#}", e);
                 }
             }
 
@@ -1169,170 +1555,82 @@ impl InfiniteApp {
         });
     }
 
-    /// 内部mod安装函数(直接调用库代码)
+    /// 内部mod安装函数: 与命令行共用 `infinite::install` 流程
+    ///
+    /// 界面里每个mod的配置作为该条目的配置覆盖传入, 在mod加载后应用。
     async fn install_mods_internal(
         game_path: &str,
         output_path: &str,
         enabled_mods: Vec<(String, HashMap<String, serde_json::Value>)>,
-        github_token: Option<String>,
+        settings: BuildSettings,
         progress: Arc<Mutex<Option<String>>>,
+        script_progress: infinite::runtime::SharedProgress,
         ctx: egui::Context,
-    ) -> anyhow::Result<()> {
-        use infinite::{GitHubDownloader, ModSource, Context as ModContext};
-        use std::sync::Arc;
-        use tokio::sync::RwLock;
+    ) -> anyhow::Result<infinite::install::InstallOutcome> {
+        use infinite::install::{InstallEvent, InstallOptions};
+        use infinite::{ModList, ModListEntry, ModSource};
 
         *progress.lock().unwrap() = Some("解析mod源...".to_string());
         ctx.request_repaint();
 
-        // 设置GitHub token到环境变量(供GitHubDownloader使用)
-        if let Some(token) = github_token {
-            std::env::set_var("GITHUB_TOKEN", token);
-        }
-
-        // 解析mod源
-        let mut mod_sources = Vec::new();
-        for (path, _) in &enabled_mods {
-            match ModSource::parse(path) {
-                Ok(source) => mod_sources.push(source),
+        let mut entries = Vec::new();
+        for (path, user_config) in enabled_mods {
+            match ModSource::parse(&path) {
+                Ok(source) => entries.push(ModListEntry {
+                    source,
+                    config_overrides: user_config,
+                }),
                 Err(e) => {
                     eprintln!("Warning: Failed to parse mod source {}: {}", path, e);
                 }
             }
         }
 
-        // 下载GitHub mods并收集本地路径
-        let cache_dir = AppConfig::cache_dir();
-        let downloader = GitHubDownloader::new(cache_dir);
-        let mut mod_dirs = Vec::new();
-
-        for (idx, source) in mod_sources.iter().enumerate() {
-            *progress.lock().unwrap() = Some(format!("处理mod {}/{}...", idx + 1, mod_sources.len()));
+        let BuildSettings { github_token, casc_roots, allow_no_casc, sandbox } = settings;
+        let mut options = InstallOptions::new(game_path);
+        options.sources = Some(ModList { entries });
+        options.output_path = Some(output_path.to_string());
+        options.download_cache_dir = AppConfig::cache_dir();
+        options.github_token = github_token;
+        options.casc_roots = casc_roots;
+        options.allow_no_casc = allow_no_casc;
+        options.sandbox = sandbox;
+        options.script_progress = script_progress;
+
+        let set_progress = |text: String| {
+            *progress.lock().unwrap() = Some(text);
             ctx.request_repaint();
-
-            match source {
-                ModSource::Local { path } => {
-                    mod_dirs.push(path.clone());
-                }
-                ModSource::GitHub { repo, subdir, branch } => {
-                    println!("⬇️ Downloading from GitHub: {}", repo);
-                    let local_path = downloader
-                        .download(repo, subdir.as_deref(), branch.as_deref())
-                        .await?;
-
-                    // 应用用户配置
-                    let mut github_path = format!("github:{}", repo);
-                    if let Some(subdir) = subdir {
-                        github_path = format!("{}:{}", github_path, subdir);
-                    }
-                    if let Some(branch) = branch {
-                        if branch != "main" && branch != "master" {
-                            github_path = format!("{}@{}", github_path, branch);
-                        }
-                    }
-
-                    // 查找并应用用户配置
-                    if let Some((_, user_config)) = enabled_mods.iter().find(|(p, _)| p == &github_path) {
-                        if !user_config.is_empty() {
-                            let config_file = local_path.join("config.json");
-                            if let Ok(config_json) = serde_json::to_string_pretty(user_config) {
-                                let _ = std::fs::write(&config_file, config_json);
-                            }
-                        }
-                    }
-
-                    mod_dirs.push(local_path);
-                }
-            }
-        }
-
-        *progress.lock().unwrap() = Some("加载mods...".to_string());
-        ctx.request_repaint();
-
-        // 加载所有mods
-        let mut all_mods = Vec::new();
-        for mod_dir in &mod_dirs {
-            let config_path = mod_dir.join("mod.json");
-            if config_path.exists() {
-                let loader = infinite::ModLoader::new(mod_dir.parent().unwrap_or(&PathBuf::from(".")));
-                match loader.load_mod(mod_dir) {
-                    Ok(mod_data) => all_mods.push(mod_data),
-                    Err(e) => eprintln!("Warning: Failed to load mod at {:?}: {}", mod_dir, e),
-                }
-            } else {
-                let loader = infinite::ModLoader::new(mod_dir);
-                match loader.load_all() {
-                    Ok(mods) => all_mods.extend(mods),
-                    Err(e) => eprintln!("Warning: Failed to load mods from {:?}: {}", mod_dir, e),
-                }
-            }
-        }
-
-        if all_mods.is_empty() {
-            anyhow::bail!("No mods found!");
-        }
-
-        println!("📦 Found {} mod(s)", all_mods.len());
-
-        *progress.lock().unwrap() = Some("清理输出目录...".to_string());
-        ctx.request_repaint();
-
-        // 清理输出目录
-        let output_path_buf = PathBuf::from(output_path);
-        if output_path_buf.exists() {
-            std::fs::remove_dir_all(&output_path_buf)?;
-        }
-
-        // 创建文件管理器
-        let mut file_manager = infinite::FileManager::new();
-        file_manager.set_output_path(output_path);
-        file_manager.set_game_path(game_path);
-
-        // 尝试打开CASC存储
-        match infinite::CascStorage::open(game_path) {
-            Ok(casc) => {
-                println!("✅ CASC storage opened successfully");
-                file_manager.set_casc_storage(Arc::new(casc));
+        };
+        infinite::install::install_with_events(options, |event| match event {
+            InstallEvent::ResolvingSource { index, total, .. } => {
+                set_progress(format!("处理mod {}/{}...", index, total));
             }
-            Err(e) => {
-                eprintln!("⚠️ Failed to open CASC storage: {}", e);
+            InstallEvent::SourceDownloaded(path) => println!("⬇️ Downloaded: {}", path.display()),
+            InstallEvent::Deduplicated(stats) => println!(
+                "♻️ Deduplicated {} of {} downloaded file(s), saved {:.1} KB",
+                stats.linked,
+                stats.files,
+                stats.bytes_saved as f64 / 1024.0
+            ),
+            InstallEvent::ModsFound(count) => println!("📦 Found {} mod(s)", count),
+            InstallEvent::CascOpened(_) => println!("✅ CASC storage opened successfully"),
+            InstallEvent::ClearingOutput => set_progress("清理输出目录...".to_string()),
+            InstallEvent::ModStarted { index, total, name, version } => {
+                println!("⚙️ Installing: {} v{}", name, version);
+                set_progress(format!("安装mod {}/{}...", index, total));
             }
-        }
-
-        let file_manager = Arc::new(RwLock::new(file_manager));
-
-        // 安装每个mod
-        for (idx, mod_data) in all_mods.iter().enumerate() {
-            *progress.lock().unwrap() = Some(format!("安装mod {}/{}...", idx + 1, all_mods.len()));
-            ctx.request_repaint();
-
-            println!("⚙️ Installing: {} v{}", mod_data.config.name, mod_data.config.version);
-
-            // 创建执行上下文
-            let context = Arc::new(ModContext {
-                mod_id: mod_data.id.clone(),
-                mod_path: mod_data.path.clone(),
-                config: serde_json::to_value(&mod_data.user_config)?,
-                file_manager: file_manager.clone(),
-                game_path: game_path.into(),
-                output_path: output_path.into(),
-                dry_run: false,
-            });
-
-            // 执行mod
-            match infinite::ModExecutor::execute_mod(mod_data, context).await {
-                Ok(_) => {
-                    println!("   ✅ Installed successfully");
-                }
-                Err(e) => {
-                    eprintln!("   ❌ Failed: {}", e);
-                }
-            }
-        }
-
-        println!("✅ All mods installed successfully!");
-
-        Ok(())
+            InstallEvent::ModFailed { name, error } => eprintln!("   ❌ {} failed: {}", name, error),
+            InstallEvent::Flushing => set_progress("写入文件...".to_string()),
+            InstallEvent::ExtractsDeduplicated(stats) => println!(
+                "♻️ Shared {} of {} extracted game file(s) with earlier builds, saved {:.1} KB",
+                stats.linked,
+                stats.files,
+                stats.bytes_saved as f64 / 1024.0
+            ),
+            InstallEvent::Warning(message) => eprintln!("⚠️ {}", message),
+            _ => {}
+        })
+        .await
     }
 }
 
@@ -1341,6 +1639,7 @@ impl eframe::App for InfiniteApp {
         let is_processing = *self.is_processing.lock().unwrap();
         let status_message = self.status_message.lock().unwrap().clone();
         let progress = self.progress.lock().unwrap().clone();
+        let script_progress = self.script_progress.lock().unwrap().clone();
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -1431,6 +1730,7 @@ impl eframe::App for InfiniteApp {
                         let mut to_remove = None;
                         let mut to_move_up = None;
                         let mut to_move_down = None;
+                        let mut to_drop = None;
                         let mut config_changed = false;
 
                         for (index, mod_entry) in self.mods.iter_mut().enumerate() {
@@ -1442,7 +1742,20 @@ impl eframe::App for InfiniteApp {
                                 .map(|cfg| !cfg.config.is_empty())
                                 .unwrap_or(false);
 
-                            ui.horizontal(|ui| {
+                            let row = ui.horizontal(|ui| {
+                                // 拖动手柄
+                                if is_processing {
+                                    ui.label(egui::RichText::new("☰").color(egui::Color32::DARK_GRAY));
+                                } else {
+                                    ui.dnd_drag_source(
+                                        egui::Id::new(("mod_drag", index)),
+                                        DraggedMod(index),
+                                        |ui| ui.label("☰"),
+                                    )
+                                    .response
+                                    .on_hover_text("拖动以调整加载顺序");
+                                }
+
                                 // 启用/禁用复选框
                                 if ui.checkbox(&mut mod_entry.enabled, "").changed() {
                                     config_changed = true;
@@ -1478,6 +1791,13 @@ impl eframe::App for InfiniteApp {
                                             to_move_up = Some(index);
                                         }
 
+                                        // 配置加载失败时提供重试
+                                        if let Some(reason) = mod_entry.config_error() {
+                                            if ui.button("🔄").on_hover_text(format!("重试加载配置 ({})", reason)).clicked() {
+                                                mod_entry.retry_config_load();
+                                            }
+                                        }
+
                                         // 配置按钮 - 只在有配置选项时显示
                                         if has_config {
                                             if ui.button("⚙").clicked() {
@@ -1495,6 +1815,30 @@ impl eframe::App for InfiniteApp {
                                     },
                                 );
                             });
+
+                            // 拖放目标: 指针在行的上半部分时插到该行之前, 否则插到之后
+                            let row_rect = row.response.rect;
+                            if let Some(pointer) = row.response.dnd_hover_payload::<DraggedMod>()
+                                .and_then(|_| ui.ctx().pointer_interact_pos())
+                            {
+                                let y = if pointer.y < row_rect.center().y {
+                                    row_rect.top() - 2.5
+                                } else {
+                                    row_rect.bottom() + 2.5
+                                };
+                                ui.painter().hline(
+                                    row_rect.x_range(),
+                                    y,
+                                    egui::Stroke::new(2.0, ui.visuals().selection.bg_fill),
+                                );
+                            }
+                            if let Some(dragged) = row.response.dnd_release_payload::<DraggedMod>() {
+                                let before = ui.ctx().pointer_interact_pos()
+                                    .map_or(index, |pointer| {
+                                        if pointer.y < row_rect.center().y { index } else { index + 1 }
+                                    });
+                                to_drop = Some((dragged.0, before));
+                            }
                             ui.add_space(5.0);
                         }
 
@@ -1508,6 +1852,9 @@ impl eframe::App for InfiniteApp {
                         if let Some(index) = to_move_down {
                             self.move_mod_down(index);
                         }
+                        if let Some((from, before)) = to_drop {
+                            self.move_mod_to(from, before);
+                        }
 
                         // 如果复选框状态改变，保存配置
                         if config_changed {
@@ -1531,6 +1878,7 @@ impl eframe::App for InfiniteApp {
                     .max_height(max_config_height)
                     .auto_shrink([false, true])
                     .show(ui, |ui| {
+                        self.render_readme_panel(ui, ctx);
                         self.render_config_panel(ui, ctx);
                     });
                 ui.add_space(10.0);
@@ -1544,6 +1892,10 @@ impl eframe::App for InfiniteApp {
                     ui.spinner();
                     ui.label(&prog);
                 });
+                if let Some(script) = script_progress {
+                    let text = format!("{} {}/{}", script.label, script.current, script.total);
+                    ui.add(egui::ProgressBar::new(script.fraction()).text(text.trim_start()));
+                }
                 ui.add_space(5.0);
             }
 
@@ -1564,9 +1916,22 @@ impl eframe::App for InfiniteApp {
 
                 ui.add_space(20.0);
 
+                // 打开输出目录 / 缓存目录
+                ui.add_enabled_ui(!self.game_path.is_empty(), |ui| {
+                    if ui.button("📂 打开输出目录").clicked() {
+                        let output_path = infinite::default_output_path(&self.game_path);
+                        self.open_folder(std::path::Path::new(&output_path));
+                    }
+                });
+                if ui.button("📂 打开缓存目录").clicked() {
+                    self.open_folder(&AppConfig::cache_dir());
+                }
+
+                ui.add_space(10.0);
+
                 // 显示输出路径
                 if !self.game_path.is_empty() {
-                    let output_path = format!("{}/Mods/Infinite/Infinite.mpq/data", self.game_path);
+                    let output_path = infinite::default_output_path(&self.game_path);
                     ui.label(
                         egui::RichText::new(format!("输出路径: {}", Self::normalize_path_display(&output_path)))
                             .small()
@@ -1844,6 +2209,36 @@ impl eframe::App for InfiniteApp {
                             }
                         }
 
+                        ui.add_space(15.0);
+                        ui.separator();
+                        ui.heading("CASC 存储");
+                        ui.add_space(5.0);
+
+                        ui.label("额外的存储根目录 (每行一个, 优先于默认位置):");
+                        ui.add(
+                            egui::TextEdit::multiline(&mut self.casc_roots_text)
+                                .desired_rows(2)
+                                .desired_width(300.0),
+                        );
+                        ui.checkbox(&mut self.allow_no_casc, "存储打不开时仍然生成 (不提取游戏文件)");
+                        ui.label(
+                            egui::RichText::new("大多数 mod 需要读取游戏文件, 不提取时这些 mod 会失败")
+                                .small()
+                                .color(egui::Color32::GRAY),
+                        );
+
+                        ui.add_space(15.0);
+                        ui.separator();
+                        ui.heading("安全");
+                        ui.add_space(5.0);
+
+                        ui.checkbox(&mut self.sandbox, "沙盒模式 (适用于不信任的社区 mod)");
+                        ui.label(
+                            egui::RichText::new("脚本只能读写输出目录内的游戏文件, 只能从 mod 目录复制文件")
+                                .small()
+                                .color(egui::Color32::GRAY),
+                        );
+
                         ui.add_space(15.0);
                         ui.separator();
                         ui.add_space(10.0);
@@ -0,0 +1,231 @@
+use anyhow::Result;
+
+/// Bytes of context shown on each side of an invalid sequence
+const CONTEXT_BYTES: usize = 8;
+
+/// Bytes inspected when guessing whether content is UTF-16
+const SNIFF_BYTES: usize = 64;
+
+/// Decode UTF-8, explaining where and why decoding failed
+///
+/// Errors name the byte offset of the first invalid sequence with the surrounding
+/// bytes in hex, and point out UTF-16 content (BOM, or NUL bytes between ASCII
+/// characters), which is otherwise valid-looking UTF-8 garbage or an opaque failure.
+pub fn decode_utf8(content: &[u8]) -> Result<&str> {
+    if let Some(hint) = utf16_hint(content) {
+        anyhow::bail!("Content is not UTF-8: {}", hint);
+    }
+
+    std::str::from_utf8(content).map_err(|e| {
+        let offset = e.valid_up_to();
+        let bad_len = e.error_len().unwrap_or(content.len() - offset);
+        let before = &content[offset.saturating_sub(CONTEXT_BYTES)..offset];
+        let bad = &content[offset..offset + bad_len];
+        let after =
+            &content[offset + bad_len..(offset + bad_len + CONTEXT_BYTES).min(content.len())];
+
+        let problem = if e.error_len().is_none() {
+            "truncated UTF-8 sequence at end of content"
+        } else {
+            "invalid UTF-8"
+        };
+        anyhow::anyhow!(
+            "{} at byte offset {}: {} [{}] {} (re-save the file as UTF-8)",
+            problem,
+            offset,
+            hex(before),
+            hex(bad),
+            hex(after)
+        )
+    })
+}
+
+/// Owned variant of [`decode_utf8`]
+pub fn decode_utf8_owned(content: Vec<u8>) -> Result<String> {
+    decode_utf8(&content)?;
+    Ok(String::from_utf8(content).expect("validated above"))
+}
+
+/// Describe why `content` looks like UTF-16, if it does
+fn utf16_hint(content: &[u8]) -> Option<&'static str> {
+    match content {
+        [0xFF, 0xFE, ..] => {
+            return Some("it starts with a UTF-16 little-endian BOM; re-save it as UTF-8")
+        }
+        [0xFE, 0xFF, ..] => {
+            return Some("it starts with a UTF-16 big-endian BOM; re-save it as UTF-8")
+        }
+        _ => {}
+    }
+
+    // ASCII text in UTF-16 has a NUL in every other byte
+    let sample = &content[..content.len().min(SNIFF_BYTES)];
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let high_nul = sample
+        .chunks_exact(2)
+        .filter(|pair| pair[0] != 0 && pair[1] == 0)
+        .count();
+    let low_nul = sample
+        .chunks_exact(2)
+        .filter(|pair| pair[0] == 0 && pair[1] != 0)
+        .count();
+    if high_nul * 4 >= pairs * 3 {
+        Some("NUL bytes between characters suggest UTF-16 little-endian; re-save it as UTF-8")
+    } else if low_nul * 4 >= pairs * 3 {
+        Some("NUL bytes between characters suggest UTF-16 big-endian; re-save it as UTF-8")
+    } else {
+        None
+    }
+}
+
+/// Text encoding of a file, as named by its byte order mark
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// UTF-8 without a BOM
+    #[default]
+    Utf8,
+    /// UTF-8 starting with `EF BB BF`
+    Utf8Bom,
+    /// UTF-16 little-endian starting with `FF FE`
+    Utf16Le,
+    /// UTF-16 big-endian starting with `FE FF`
+    Utf16Be,
+}
+
+impl TextEncoding {
+    /// The encoding the BOM at the start of `content` names (UTF-8 without one)
+    pub fn detect(content: &[u8]) -> Self {
+        [Self::Utf8Bom, Self::Utf16Le, Self::Utf16Be]
+            .into_iter()
+            .find(|encoding| content.starts_with(encoding.bom()))
+            .unwrap_or(Self::Utf8)
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => &[],
+            Self::Utf8Bom => &[0xEF, 0xBB, 0xBF],
+            Self::Utf16Le => &[0xFF, 0xFE],
+            Self::Utf16Be => &[0xFE, 0xFF],
+        }
+    }
+
+    /// Decode `content` in this encoding, skipping its BOM if present
+    pub fn decode(self, content: &[u8]) -> Result<String> {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => {
+                let content = content.strip_prefix(Self::Utf8Bom.bom()).unwrap_or(content);
+                decode_utf8(content).map(str::to_string)
+            }
+            Self::Utf16Le | Self::Utf16Be => {
+                let content = content.strip_prefix(self.bom()).unwrap_or(content);
+                // `usize::is_multiple_of` needs Rust 1.87
+                #[allow(clippy::manual_is_multiple_of)]
+                if content.len() % 2 != 0 {
+                    anyhow::bail!("UTF-16 content has an odd number of bytes ({})", content.len());
+                }
+                let units: Vec<u16> = content
+                    .chunks_exact(2)
+                    .map(|pair| match self {
+                        Self::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                String::from_utf16(&units).map_err(|_| anyhow::anyhow!("invalid UTF-16 (unpaired surrogate)"))
+            }
+        }
+    }
+
+    /// Encode `text` in this encoding, starting with its BOM
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        let mut bytes = self.bom().to_vec();
+        match self {
+            Self::Utf8 | Self::Utf8Bom => bytes.extend_from_slice(text.as_bytes()),
+            Self::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Self::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+        }
+        bytes
+    }
+}
+
+/// Decode text in the encoding its BOM names (UTF-8 without one) and return that encoding
+///
+/// Without a BOM, content must be UTF-8; errors are those of [`decode_utf8`].
+pub fn decode_text(content: &[u8]) -> Result<(String, TextEncoding)> {
+    let encoding = TextEncoding::detect(content);
+    Ok((encoding.decode(content)?, encoding))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8_errors() {
+        assert_eq!(
+            decode_utf8("Name\tÄrger\n".as_bytes()).unwrap(),
+            "Name\tÄrger\n"
+        );
+
+        // Latin-1 'Ä' (0xC4) followed by ASCII is invalid
+        let err = decode_utf8(b"Name\t\xC4rger\n").unwrap_err().to_string();
+        assert!(err.contains("byte offset 5"), "{}", err);
+        assert!(
+            err.contains("4E 61 6D 65 09 [C4] 72 67 65 72 0A"),
+            "{}",
+            err
+        );
+
+        let err = decode_utf8(b"abc\xE2\x82").unwrap_err().to_string();
+        assert!(
+            err.contains("truncated") && err.contains("byte offset 3"),
+            "{}",
+            err
+        );
+
+        let utf16: Vec<u8> = "id\tname\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        let err = decode_utf8(&utf16).unwrap_err().to_string();
+        assert!(err.contains("UTF-16 little-endian"), "{}", err);
+
+        let err = decode_utf8(&[0xFF, 0xFE, b'a', 0]).unwrap_err().to_string();
+        assert!(err.contains("BOM"), "{}", err);
+    }
+
+    #[test]
+    fn test_decode_text_by_bom() {
+        let text = "id	name
+1	Ärger 🗡
+";
+        for encoding in [
+            TextEncoding::Utf8,
+            TextEncoding::Utf8Bom,
+            TextEncoding::Utf16Le,
+            TextEncoding::Utf16Be,
+        ] {
+            let bytes = encoding.encode(text);
+            assert_eq!(decode_text(&bytes).unwrap(), (text.to_string(), encoding));
+        }
+        assert_eq!(TextEncoding::Utf16Le.encode("a"), [0xFF, 0xFE, b'a', 0]);
+
+        // An explicit encoding also reads content without the BOM
+        assert_eq!(TextEncoding::Utf16Be.decode(&[0, b'h', 0, b'i']).unwrap(), "hi");
+        assert!(TextEncoding::Utf16Le.decode(&[0xFF, 0xFE, b'a']).is_err());
+        // BOM-less UTF-16 is still rejected with a hint
+        let err = decode_text(&[b'i', 0, b'd', 0, b'\t', 0, b'x', 0]).unwrap_err();
+        assert!(err.to_string().contains("UTF-16"), "{}", err);
+    }
+}
@@ -1,26 +1,36 @@
 use anyhow::{Context, Result};
 use std::path::Path;
 
+use super::decode_utf8;
+
+/// Sentinel string a `NaN` token is read as (and written back from)
+pub const NAN_SENTINEL: &str = "__NaN__";
+/// Sentinel string an `Infinity` token is read as (and written back from)
+pub const INFINITY_SENTINEL: &str = "__Infinity__";
+/// Sentinel string a `-Infinity` token is read as (and written back from)
+pub const NEG_INFINITY_SENTINEL: &str = "__-Infinity__";
+
+/// How JSON is laid out when written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonFormat {
+    /// Indented, one value per line (diff-friendly)
+    #[default]
+    Pretty,
+    /// No whitespace, like most vanilla game files
+    Compact,
+}
+
 /// Handler for JSON files
 pub struct JsonHandler;
 
 impl JsonHandler {
-    /// Read a JSON file
+    /// Read a JSON file (see [`JsonHandler::parse_from_bytes`])
     pub async fn read(path: &Path) -> Result<serde_json::Value> {
-        let content = tokio::fs::read_to_string(path)
+        let bytes = tokio::fs::read(path)
             .await
             .context("Failed to read JSON file")?;
-
-        // D2R's JSON files may have UTF-8 BOM
-        // Remove BOM if present
-        let content = content.trim_start_matches('\u{FEFF}');
-
-        // D2R's JSON files use JSON5 format with comments and trailing commas
-        // Use json5 crate for proper parsing
-        let value: serde_json::Value = json5::from_str(content)
-            .context("Failed to parse JSON5")?;
-
-        Ok(value)
+        Self::parse_from_bytes(&bytes)
+            .with_context(|| format!("Failed to parse JSON file {}", path.display()))
     }
 
     /// Write a JSON file with pretty formatting
@@ -34,6 +44,7 @@ impl JsonHandler {
 
         let content = serde_json::to_string_pretty(data)
             .context("Failed to serialize JSON")?;
+        let content = restore_non_finite(&content);
 
         tokio::fs::write(path, content)
             .await
@@ -43,27 +54,343 @@ impl JsonHandler {
     }
 
     /// Parse JSON from bytes
+    ///
+    /// Strict JSON goes straight through `serde_json`. Only when that fails are
+    /// NaN/Infinity and integer keys normalized; D2R files with `//` comments or trailing
+    /// commas then fall back to JSON5, which is much slower, so only those files pay for it.
     pub fn parse_from_bytes(content: &[u8]) -> Result<serde_json::Value> {
-        let text = std::str::from_utf8(content)
+        let text = decode_utf8(content)
             .context("Failed to decode UTF-8")?;
 
-        // Remove BOM if present
+        // D2R's JSON files may have a UTF-8 BOM
         let text = text.trim_start_matches('\u{FEFF}');
 
-        // Use json5 for parsing (supports comments and trailing commas)
-        let value: serde_json::Value = json5::from_str(text)
+        if let Ok(value) = serde_json::from_str(text) {
+            tracing::debug!("Parsed JSON strictly");
+            return Ok(value);
+        }
+
+        // Keep NaN/Infinity and integer keys that serde_json can't represent
+        let text = normalize_lenient_tokens(text);
+
+        if let Ok(value) = serde_json::from_str(&text) {
+            tracing::debug!("Parsed JSON strictly after normalizing tokens");
+            return Ok(value);
+        }
+        let value: serde_json::Value = json5::from_str(&text)
             .context("Failed to parse JSON5")?;
+        tracing::debug!("Parsed JSON as JSON5 (comments or trailing commas)");
+        Ok(value)
+    }
 
+    /// Read a JSON file, retrying with `//` and `/* */` comments stripped if it doesn't parse
+    pub async fn read_lenient(path: &Path) -> Result<serde_json::Value> {
+        let bytes = tokio::fs::read(path)
+            .await
+            .context("Failed to read JSON file")?;
+        let (value, stripped) = Self::parse_lenient(&bytes)
+            .with_context(|| format!("Failed to parse JSON file {}", path.display()))?;
+        if stripped {
+            tracing::info!("Stripped comments to parse {}", path.display());
+        }
         Ok(value)
     }
 
-    /// Convert JSON data to bytes
+    /// Parse JSON from bytes, retrying with comments stripped if the normal parse fails
+    ///
+    /// JSON5 already accepts well-formed comments; this also handles the ones it trips
+    /// over, such as a block comment left open at the end of a template. Returns whether
+    /// comments had to be stripped. If stripping doesn't help, the original error is kept.
+    pub fn parse_lenient(content: &[u8]) -> Result<(serde_json::Value, bool)> {
+        let err = match Self::parse_from_bytes(content) {
+            Ok(value) => return Ok((value, false)),
+            Err(err) => err,
+        };
+
+        let Some(stripped) = decode_utf8(content).ok().and_then(strip_comments) else {
+            return Err(err);
+        };
+        match Self::parse_from_bytes(stripped.as_bytes()) {
+            Ok(value) => Ok((value, true)),
+            Err(_) => Err(err),
+        }
+    }
+
+    /// Convert JSON data to bytes (pretty-printed)
     pub fn to_bytes(data: &serde_json::Value) -> Result<Vec<u8>> {
-        let content = serde_json::to_string_pretty(data)
-            .context("Failed to serialize JSON")?;
+        Self::to_bytes_as(data, JsonFormat::Pretty)
+    }
 
-        Ok(content.into_bytes())
+    /// Convert JSON data to bytes in the given layout
+    pub fn to_bytes_as(data: &serde_json::Value, format: JsonFormat) -> Result<Vec<u8>> {
+        let content = match format {
+            JsonFormat::Pretty => serde_json::to_string_pretty(data),
+            JsonFormat::Compact => serde_json::to_string(data),
+        }
+        .context("Failed to serialize JSON")?;
+
+        Ok(restore_non_finite(&content).into_bytes())
     }
+
+    /// Apply an RFC 6902 JSON Patch (array of operations) to a JSON value in place
+    ///
+    /// The patch is applied atomically: if any operation fails, `data` is left unchanged.
+    pub fn apply_patch(data: &mut serde_json::Value, operations: &serde_json::Value) -> Result<()> {
+        let patch: json_patch::Patch = serde_json::from_value(operations.clone())
+            .context("Invalid JSON Patch operations")?;
+
+        json_patch::patch(data, &patch).map_err(|e| match e.kind {
+            json_patch::PatchErrorKind::TestFailed => {
+                anyhow::anyhow!("JSON Patch test failed at '{}' (operation {})", e.path, e.operation)
+            }
+            _ => anyhow::anyhow!("JSON Patch failed: {}", e),
+        })?;
+
+        Ok(())
+    }
+
+    /// Apply an RFC 7386 JSON Merge Patch to `base`
+    ///
+    /// Objects merge recursively and a `null` in `patch` removes that key; any other value
+    /// (arrays included) replaces what is there. Keys keep their order, new ones are appended.
+    pub fn merge(base: &serde_json::Value, patch: &serde_json::Value) -> serde_json::Value {
+        let mut merged = base.clone();
+        json_patch::merge(&mut merged, patch);
+        merged
+    }
+
+    /// Remove the node addressed by an RFC 6901 JSON Pointer, returning it
+    ///
+    /// Errors if the pointer doesn't resolve to an existing node.
+    pub fn remove_pointer(data: &mut serde_json::Value, pointer: &str) -> Result<serde_json::Value> {
+        let (parent, token) = pointer
+            .rsplit_once('/')
+            .filter(|(parent, _)| parent.is_empty() || parent.starts_with('/'))
+            .ok_or_else(|| anyhow::anyhow!("Invalid JSON Pointer '{}'", pointer))?;
+        let token = token.replace("~1", "/").replace("~0", "~");
+
+        let removed = match data.pointer_mut(parent) {
+            Some(serde_json::Value::Object(map)) => map.remove(&token),
+            Some(serde_json::Value::Array(items)) => token
+                .parse::<usize>()
+                .ok()
+                .filter(|index| *index < items.len())
+                .map(|index| items.remove(index)),
+            _ => None,
+        };
+
+        removed.ok_or_else(|| anyhow::anyhow!("JSON Pointer '{}' does not resolve", pointer))
+    }
+}
+
+/// Rewrite tokens that serde_json can't hold into strings before parsing:
+/// `NaN`/`Infinity`/`-Infinity` values become sentinel strings and bare
+/// integer object keys (`{ 1: "a" }`) become quoted keys.
+fn normalize_lenient_tokens(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    // Last non-whitespace character emitted outside strings and comments
+    let mut last_significant: Option<char> = None;
+    let mut i = 0;
+
+    // Whether the next non-whitespace character from `from` is ':'
+    let followed_by_colon = |from: usize| {
+        chars[from..].iter().find(|c| !c.is_whitespace()) == Some(&':')
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '"' || c == '\'' {
+            // Copy the whole string literal verbatim
+            out.push(c);
+            i += 1;
+            while i < chars.len() {
+                let sc = chars[i];
+                out.push(sc);
+                i += 1;
+                if sc == '\\' && i < chars.len() {
+                    out.push(chars[i]);
+                    i += 1;
+                } else if sc == c {
+                    break;
+                }
+            }
+            last_significant = Some(c);
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                out.push(chars[i]);
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            out.push_str("/*");
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                out.push(chars[i]);
+                i += 1;
+            }
+            if i < chars.len() {
+                out.push_str("*/");
+                i += 2;
+            }
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+
+            if (word == "NaN" || word == "Infinity") && !followed_by_colon(i) {
+                // Fold an immediately preceding sign into the token
+                let negative = match out.chars().last() {
+                    Some('-') => {
+                        out.pop();
+                        true
+                    }
+                    Some('+') => {
+                        out.pop();
+                        false
+                    }
+                    _ => false,
+                };
+                let sentinel = match (word.as_str(), negative) {
+                    ("NaN", _) => NAN_SENTINEL,
+                    (_, false) => INFINITY_SENTINEL,
+                    (_, true) => NEG_INFINITY_SENTINEL,
+                };
+                out.push('"');
+                out.push_str(sentinel);
+                out.push('"');
+                last_significant = Some('"');
+            } else {
+                out.push_str(&word);
+                last_significant = word.chars().last();
+            }
+        } else if c.is_ascii_digit() && matches!(last_significant, Some('{') | Some(',')) {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+
+            if followed_by_colon(i) {
+                out.push('"');
+                out.push_str(&digits);
+                out.push('"');
+                last_significant = Some('"');
+            } else {
+                out.push_str(&digits);
+                last_significant = digits.chars().last();
+            }
+        } else {
+            out.push(c);
+            if !c.is_whitespace() {
+                last_significant = Some(c);
+            }
+            i += 1;
+        }
+    }
+
+    out
+}
+
+/// Remove `//` and `/* */` comments outside string literals; `None` if there are none
+///
+/// Comments become spaces (newlines are kept) so error positions still line up. A block
+/// comment that is never closed runs to the end of the text.
+fn strip_comments(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut found = false;
+    let mut i = 0;
+
+    let blank = |c: char| if c == '\n' { '\n' } else { ' ' };
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '"' || c == '\'' {
+            // Copy the whole string literal verbatim
+            out.push(c);
+            i += 1;
+            while i < chars.len() {
+                let sc = chars[i];
+                out.push(sc);
+                i += 1;
+                if sc == '\\' && i < chars.len() {
+                    out.push(chars[i]);
+                    i += 1;
+                } else if sc == c {
+                    break;
+                }
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            found = true;
+            while i < chars.len() && chars[i] != '\n' {
+                out.push(' ');
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            found = true;
+            out.push_str("  ");
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                out.push(blank(chars[i]));
+                i += 1;
+            }
+            if i < chars.len() {
+                out.push_str("  ");
+                i += 2;
+            }
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+
+    found.then_some(out)
+}
+
+/// Turn the sentinel strings produced by `normalize_lenient_tokens` back into
+/// bare `NaN`/`Infinity`/`-Infinity` tokens in serialized output
+///
+/// Only string values are restored; a sentinel used as an object key stays quoted.
+fn restore_non_finite(serialized: &str) -> String {
+    let bytes = serialized.as_bytes();
+    let mut out = String::with_capacity(serialized.len());
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'"' {
+            i += 1;
+            continue;
+        }
+
+        // Find the end of the string literal
+        let start = i;
+        i += 1;
+        while i < bytes.len() && bytes[i] != b'"' {
+            i += if bytes[i] == b'\\' { 2 } else { 1 };
+        }
+        i = (i + 1).min(bytes.len());
+
+        let token = match &serialized[start + 1..i - 1] {
+            s if s == NAN_SENTINEL => "NaN",
+            s if s == NEG_INFINITY_SENTINEL => "-Infinity",
+            s if s == INFINITY_SENTINEL => "Infinity",
+            _ => continue,
+        };
+        let is_key = bytes[i..].iter().find(|b| !b.is_ascii_whitespace()) == Some(&b':');
+        if !is_key {
+            out.push_str(&serialized[copied..start]);
+            out.push_str(token);
+            copied = i;
+        }
+    }
+
+    out.push_str(&serialized[copied..]);
+    out
 }
 
 #[cfg(test)]
@@ -87,4 +414,183 @@ mod tests {
 
         assert_eq!(data, read_data);
     }
+
+    #[test]
+    fn test_merge() {
+        let base = serde_json::json!({
+            "title": "Goodbye!",
+            "author": { "givenName": "John", "familyName": "Doe" },
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"
+        });
+        let patch = serde_json::json!({
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": { "familyName": null },
+            "tags": ["example"]
+        });
+
+        let merged = JsonHandler::merge(&base, &patch);
+        assert_eq!(
+            serde_json::to_string(&merged).unwrap(),
+            r#"{"title":"Hello!","author":{"givenName":"John"},"tags":["example"],"content":"This will be unchanged","phoneNumber":"+01-123-456-7890"}"#
+        );
+        assert_eq!(JsonHandler::merge(&base, &serde_json::json!([1])), serde_json::json!([1]));
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let mut data = serde_json::json!({
+            "name": "test",
+            "items": ["a", "b"]
+        });
+
+        let ops = serde_json::json!([
+            { "op": "test", "path": "/name", "value": "test" },
+            { "op": "replace", "path": "/name", "value": "patched" },
+            { "op": "add", "path": "/items/-", "value": "c" },
+            { "op": "remove", "path": "/items/0" }
+        ]);
+
+        JsonHandler::apply_patch(&mut data, &ops).unwrap();
+        assert_eq!(data, serde_json::json!({ "name": "patched", "items": ["b", "c"] }));
+
+        let failing = serde_json::json!([
+            { "op": "replace", "path": "/name", "value": "other" },
+            { "op": "test", "path": "/name", "value": "nope" }
+        ]);
+        let err = JsonHandler::apply_patch(&mut data, &failing).unwrap_err();
+        assert!(err.to_string().contains("/name"));
+        assert_eq!(data["name"], "patched");
+    }
+
+    #[test]
+    fn test_non_finite_and_integer_keys_round_trip() {
+        let text = r#"{
+            // NaN in a comment stays untouched
+            "a": NaN,
+            "b": -Infinity,
+            "c": +Infinity,
+            "d": "NaN",
+            1: [1, 2.5e3],
+        }"#;
+
+        let value = JsonHandler::parse_from_bytes(text.as_bytes()).unwrap();
+        assert_eq!(value["a"], NAN_SENTINEL);
+        assert_eq!(value["b"], NEG_INFINITY_SENTINEL);
+        assert_eq!(value["c"], INFINITY_SENTINEL);
+        assert_eq!(value["d"], "NaN");
+        assert_eq!(value["1"], serde_json::json!([1, 2500.0]));
+
+        let written = String::from_utf8(JsonHandler::to_bytes(&value).unwrap()).unwrap();
+        assert!(written.contains("\"a\": NaN"));
+        assert!(written.contains("\"b\": -Infinity"));
+        assert!(written.contains("\"c\": Infinity"));
+        assert!(written.contains("\"d\": \"NaN\""));
+
+        // What we write can be read back to the same value
+        assert_eq!(JsonHandler::parse_from_bytes(written.as_bytes()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_sentinel_keys_stay_quoted() {
+        let value = serde_json::json!({
+            NAN_SENTINEL: NAN_SENTINEL,
+            INFINITY_SENTINEL: ["x", INFINITY_SENTINEL],
+            "note": "say \"__NaN__\" here"
+        });
+
+        for format in [JsonFormat::Pretty, JsonFormat::Compact] {
+            let written = String::from_utf8(JsonHandler::to_bytes_as(&value, format).unwrap()).unwrap();
+            // Only the keys keep their quotes
+            assert!(written.contains("\"__NaN__\":"));
+            assert!(written.contains("\"__Infinity__\":"));
+            assert_eq!(written.matches("\"__NaN__\"").count(), 1);
+            assert_eq!(written.matches("\"__Infinity__\"").count(), 1);
+            assert!(written.contains(r#""say \"__NaN__\" here""#));
+            assert_eq!(JsonHandler::parse_from_bytes(written.as_bytes()).unwrap(), value);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_keeps_key_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = temp_dir.path().join("item-names.json");
+        let original = "[\n  {\n    \"id\": 1,\n    \"Key\": \"hp1\",\n    \"enUS\": \"Minor Healing Potion\",\n    \"deDE\": \"Schwacher Heiltrank\"\n  }\n]";
+        std::fs::write(&json_path, original).unwrap();
+
+        let value = JsonHandler::read(&json_path).await.unwrap();
+        JsonHandler::write(&json_path, &value).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&json_path).unwrap(), original);
+    }
+
+    #[tokio::test]
+    async fn test_read_comments_and_trailing_commas() {
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = temp_dir.path().join("items.json");
+        std::fs::write(
+            &json_path,
+            "\u{FEFF}[\n  // hand-edited\n  { \"id\": 1, \"names\": [\"a\", \"b\",], },\n  { \"id\": 2 }, // last\n]\n",
+        )
+        .unwrap();
+
+        let value = JsonHandler::read(&json_path).await.unwrap();
+        assert_eq!(value, serde_json::json!([{ "id": 1, "names": ["a", "b"] }, { "id": 2 }]));
+
+        // Strict output on write
+        JsonHandler::write(&json_path, &value).await.unwrap();
+        let written = std::fs::read_to_string(&json_path).unwrap();
+        assert!(!written.contains("//") && !written.contains(",\n]"));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&written).unwrap(), value);
+
+        let err = JsonHandler::parse_from_bytes(b"{ \"a\": 1,, }").unwrap_err();
+        assert!(err.to_string().contains("JSON5"), "{}", err);
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let text = "{\n  // leading\n  \"url\": \"http://example.com/*x*/\", /* inline */\n  'q': '// kept'\n}";
+        let stripped = strip_comments(text).unwrap();
+        assert!(stripped.contains("\"http://example.com/*x*/\""));
+        assert!(stripped.contains("'// kept'"));
+        assert!(!stripped.contains("leading") && !stripped.contains("inline"));
+        assert_eq!(stripped.lines().count(), text.lines().count());
+
+        assert_eq!(strip_comments(r#"{ "a": "no // comment here" }"#), None);
+    }
+
+    #[test]
+    fn test_parse_lenient_strips_comments() {
+        // Well-formed comments parse without stripping
+        let (value, stripped) = JsonHandler::parse_lenient(b"{ \"a\": 1 // note\n }").unwrap();
+        assert_eq!(value["a"], 1);
+        assert!(!stripped);
+
+        // An unclosed trailing block comment needs the fallback
+        let text = b"{ \"url\": \"http://example.com\", \"b\": [1, 2] }\n/* TODO: more entries";
+        assert!(JsonHandler::parse_from_bytes(text).is_err());
+        let (value, stripped) = JsonHandler::parse_lenient(text).unwrap();
+        assert_eq!(value["url"], "http://example.com");
+        assert!(stripped);
+
+        assert!(JsonHandler::parse_lenient(b"{ \"a\": } // broken").is_err());
+    }
+
+    #[test]
+    fn test_remove_pointer() {
+        let mut data = serde_json::json!({
+            "a/b": { "list": [1, 2, 3] },
+            "keep": true
+        });
+
+        assert_eq!(JsonHandler::remove_pointer(&mut data, "/a~1b/list/1").unwrap(), 2);
+        assert_eq!(data["a/b"]["list"], serde_json::json!([1, 3]));
+
+        assert!(JsonHandler::remove_pointer(&mut data, "/missing").is_err());
+        assert!(JsonHandler::remove_pointer(&mut data, "/a~1b/list/9").is_err());
+        assert!(JsonHandler::remove_pointer(&mut data, "no-slash").is_err());
+
+        JsonHandler::remove_pointer(&mut data, "/a~1b").unwrap();
+        assert_eq!(data, serde_json::json!({ "keep": true }));
+    }
 }
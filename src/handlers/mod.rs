@@ -1,7 +1,9 @@
+pub mod encoding;
 pub mod json;
 pub mod text;
 pub mod tsv;
 
-pub use json::JsonHandler;
-pub use text::TextHandler;
+pub use encoding::{decode_text, decode_utf8, decode_utf8_owned, TextEncoding};
+pub use json::{JsonFormat, JsonHandler};
+pub use text::{TextHandler, TrailingNewline};
 pub use tsv::TsvHandler;
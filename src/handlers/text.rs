@@ -1,19 +1,68 @@
 use anyhow::{Context, Result};
 use std::path::Path;
 
+use super::{decode_text, TextEncoding};
+
+/// What happens to the end of a text file when it is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingNewline {
+    /// Write the content exactly as given
+    #[default]
+    Preserve,
+    /// Drop trailing blank lines and whitespace, then end with one line break
+    /// (`\r\n` if the content uses CRLF, `\n` otherwise)
+    ExactlyOne,
+}
+
 /// Handler for plain text files
 pub struct TextHandler;
 
 impl TextHandler {
-    /// Read a text file
+    /// Read a text file, decoded as its BOM says (UTF-8 without one)
     pub async fn read(path: &Path) -> Result<String> {
-        tokio::fs::read_to_string(path)
+        Ok(Self::read_encoded(path).await?.0)
+    }
+
+    /// Read a text file and the encoding it was detected in, to write it back the same way
+    /// with [`TextHandler::write_with_encoding`]
+    pub async fn read_encoded(path: &Path) -> Result<(String, TextEncoding)> {
+        let bytes = tokio::fs::read(path)
             .await
-            .context("Failed to read text file")
+            .context("Failed to read text file")?;
+        decode_text(&bytes)
+            .with_context(|| format!("Failed to decode text file {}", path.display()))
+    }
+
+    /// Read a text file known to be in `encoding` (with or without its BOM)
+    pub async fn read_with_encoding(path: &Path, encoding: TextEncoding) -> Result<String> {
+        let bytes = tokio::fs::read(path)
+            .await
+            .context("Failed to read text file")?;
+        encoding
+            .decode(&bytes)
+            .with_context(|| format!("Failed to decode text file {} as {:?}", path.display(), encoding))
+    }
+
+    /// Apply a trailing newline policy to `content`
+    pub fn normalize_end(content: &str, policy: TrailingNewline) -> String {
+        match policy {
+            TrailingNewline::Preserve => content.to_string(),
+            TrailingNewline::ExactlyOne => {
+                let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+                let mut normalized = content.trim_end().to_string();
+                normalized.push_str(newline);
+                normalized
+            }
+        }
     }
 
-    /// Write a text file
+    /// Write a text file as UTF-8
     pub async fn write(path: &Path, content: &str) -> Result<()> {
+        Self::write_with_encoding(path, content, TextEncoding::Utf8).await
+    }
+
+    /// Write a text file in `encoding` (with its BOM)
+    pub async fn write_with_encoding(path: &Path, content: &str, encoding: TextEncoding) -> Result<()> {
         // Create parent directory if needed
         if let Some(parent) = path.parent() {
             tokio::fs::create_dir_all(parent)
@@ -21,7 +70,7 @@ impl TextHandler {
                 .context("Failed to create parent directory")?;
         }
 
-        tokio::fs::write(path, content)
+        tokio::fs::write(path, encoding.encode(content))
             .await
             .context("Failed to write text file")
     }
@@ -44,4 +93,35 @@ mod tests {
 
         assert_eq!(content, read_content);
     }
+
+    #[tokio::test]
+    async fn test_utf16_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let text_path = temp_dir.path().join("strings.txt");
+        std::fs::write(&text_path, TextEncoding::Utf16Le.encode("Key\tzhTW\nhp1\t治療藥劑\n")).unwrap();
+
+        let (content, encoding) = TextHandler::read_encoded(&text_path).await.unwrap();
+        assert_eq!((content.as_str(), encoding), ("Key\tzhTW\nhp1\t治療藥劑\n", TextEncoding::Utf16Le));
+
+        let edited = content.replace("治療藥劑", "大治療藥劑");
+        TextHandler::write_with_encoding(&text_path, &edited, encoding).await.unwrap();
+        assert!(std::fs::read(&text_path).unwrap().starts_with(&[0xFF, 0xFE, b'K', 0]));
+        assert_eq!(TextHandler::read(&text_path).await.unwrap(), edited);
+        assert_eq!(
+            TextHandler::read_with_encoding(&text_path, TextEncoding::Utf16Le).await.unwrap(),
+            edited
+        );
+    }
+
+    #[test]
+    fn test_normalize_end() {
+        let exactly_one = |s| TextHandler::normalize_end(s, TrailingNewline::ExactlyOne);
+        assert_eq!(exactly_one("a\nb"), "a\nb\n");
+        assert_eq!(exactly_one("a\nb\n\n  \n"), "a\nb\n");
+        assert_eq!(exactly_one("a\r\nb \t\r\n\r\n"), "a\r\nb\r\n");
+        assert_eq!(
+            TextHandler::normalize_end("a\n\n", TrailingNewline::Preserve),
+            "a\n\n"
+        );
+    }
 }
@@ -1,15 +1,21 @@
 use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::ops::Range;
 use std::path::Path;
 
+use super::decode_utf8;
+
 /// Handler for TSV (Tab-Separated Values) files
 pub struct TsvHandler;
 
 impl TsvHandler {
     /// Read a TSV file as a 2D array of strings
     pub async fn read(path: &Path) -> Result<Vec<Vec<String>>> {
-        let content = tokio::fs::read_to_string(path)
+        let bytes = tokio::fs::read(path)
             .await
             .context("Failed to read TSV file")?;
+        let content = decode_utf8(&bytes)
+            .with_context(|| format!("Failed to decode TSV file {}", path.display()))?;
 
         let mut reader = csv::ReaderBuilder::new()
             .delimiter(b'\t')
@@ -39,57 +45,169 @@ impl TsvHandler {
                 .context("Failed to create parent directory")?;
         }
 
-        // D2R TSV 文件需要特殊处理:
-        // - 包含逗号的字段需要用双引号包围
-        // - 这是 D2R 游戏引擎的要求
-        let mut content = String::new();
-
-        for row in data {
-            let formatted_row: Vec<String> = row
-                .iter()
-                .map(|field| {
-                    // 如果字段包含逗号,用双引号包围
-                    if field.contains(',') {
-                        format!("\"{}\"", field)
-                    } else {
-                        field.clone()
-                    }
-                })
-                .collect();
-
-            content.push_str(&formatted_row.join("\t"));
-            content.push('\n');
-        }
-
-        tokio::fs::write(path, content)
+        tokio::fs::write(path, Self::to_bytes(data)?)
             .await
             .context("Failed to write TSV file")?;
 
         Ok(())
     }
 
+    /// Serialize one row: fields joined by tabs, each quoted as [`TsvHandler::format_field`] does
+    pub fn format_row(row: &[String]) -> String {
+        row.iter()
+            .map(|field| Self::format_field(field))
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+
+    /// Quote a field that contains a tab, line break, comma or double quote, doubling the
+    /// quotes inside (CSV rules); other fields are written as they are
+    ///
+    /// D2R itself needs fields with commas quoted; the rest keep the row and column layout
+    /// intact when the file is read back.
+    pub fn format_field(field: &str) -> Cow<'_, str> {
+        if field.contains(['\t', '\n', '\r', ',', '"']) {
+            Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+        } else {
+            Cow::Borrowed(field)
+        }
+    }
+
     /// Parse TSV from bytes
     pub fn parse_from_bytes(content: &[u8]) -> Result<Vec<Vec<String>>> {
-        let text = std::str::from_utf8(content)
+        let text = decode_utf8(content)
             .context("Failed to decode UTF-8")?;
 
-        let mut reader = csv::ReaderBuilder::new()
+        Self::records(text)
+            .map(|record| record.map(|(_, fields)| fields))
+            .collect()
+    }
+
+    /// Iterate over the records of `text` with the byte range each was written in
+    ///
+    /// A quoted field may contain line breaks, so one record can span several lines. The
+    /// range covers the record itself; the line breaks and blank lines around it are left
+    /// out, for callers that copy unchanged records byte-for-byte.
+    pub fn records(text: &str) -> Records<'_> {
+        let reader = csv::ReaderBuilder::new()
             .delimiter(b'\t')
             .has_headers(false)
             .flexible(true)
             .quoting(true)
             .double_quote(true)
             .from_reader(text.as_bytes());
+        Records {
+            text,
+            reader,
+            record: csv::StringRecord::new(),
+        }
+    }
 
-        let mut rows = Vec::new();
+    /// Rewrite `baseline` to hold `rows`, keeping every record whose fields are unchanged
+    /// byte-for-byte (quoting, spacing, extra trailing columns and line endings included)
+    ///
+    /// Records are matched to rows by position, blank lines aside; changed rows are
+    /// serialized with `format_row`, rows past the end are appended and surplus records
+    /// are dropped. Returns `None` when the header row changed, in which case the caller
+    /// should rewrite the whole file.
+    pub fn patch_rows(
+        baseline: &[u8],
+        rows: &[Vec<String>],
+        format_row: impl Fn(&[String]) -> String,
+    ) -> Result<Option<Vec<u8>>> {
+        let text = decode_utf8(baseline).context("Failed to decode UTF-8")?;
+        let Some(header) = rows.first() else {
+            return Ok(None);
+        };
+        let width = header.len();
+        let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
 
-        for result in reader.records() {
-            let record = result.context("Failed to parse TSV record")?;
-            let row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
-            rows.push(row);
+        let mut out = String::with_capacity(text.len());
+        let mut cursor = 0;
+        let mut next_row = 0;
+        for record in Self::records(text) {
+            let (range, mut fields) = record?;
+            out.push_str(&text[cursor..range.start]);
+            cursor = range.end;
+            let Some(row) = rows.get(next_row) else {
+                // Row was removed, along with its line break
+                cursor += line_break_len(&text[cursor..]);
+                continue;
+            };
+
+            if next_row == 0 && fields != *header {
+                return Ok(None);
+            }
+            fields.resize(width.max(fields.len()), String::new());
+
+            if fields[..width] == row[..] {
+                out.push_str(&text[range]);
+            } else {
+                out.push_str(&format_row(row));
+            }
+            next_row += 1;
         }
+        out.push_str(&text[cursor..]);
 
-        Ok(rows)
+        if next_row == 0 {
+            return Ok(None);
+        }
+        for row in &rows[next_row..] {
+            let ends_with_newline = out.ends_with('\n');
+            if !ends_with_newline {
+                out.push_str(newline);
+            }
+            out.push_str(&format_row(row));
+            if ends_with_newline {
+                out.push_str(newline);
+            }
+        }
+
+        Ok(Some(out.into_bytes()))
+    }
+
+    /// Visit the data rows of `content` one record at a time, letting `visit` change each in place
+    ///
+    /// `visit` gets the header row and the row's fields, padded to the header width. Only
+    /// one row is parsed at a time, and a quoted field spanning lines stays one row. Rows `visit` leaves alone keep their original bytes;
+    /// changed rows are serialized with `format_row`. Returns the rewritten file and the
+    /// number of changed rows, or `None` when no row changed.
+    pub fn map_rows(
+        content: &[u8],
+        format_row: impl Fn(&[String]) -> String,
+        mut visit: impl FnMut(&[String], &mut Vec<String>) -> Result<()>,
+    ) -> Result<Option<(Vec<u8>, usize)>> {
+        let text = decode_utf8(content).context("Failed to decode UTF-8")?;
+
+        let mut out = String::with_capacity(text.len());
+        let mut cursor = 0;
+        let mut header: Option<Vec<String>> = None;
+        let mut changed = 0;
+        for record in Self::records(text) {
+            let (range, fields) = record?;
+            out.push_str(&text[cursor..range.start]);
+            cursor = range.end;
+            let Some(header) = &header else {
+                header = Some(fields);
+                out.push_str(&text[range]);
+                continue;
+            };
+
+            let mut original = fields;
+            original.resize(header.len().max(original.len()), String::new());
+            let mut row = original.clone();
+            visit(header, &mut row)?;
+
+            if row == original {
+                out.push_str(&text[range]);
+            } else {
+                out.push_str(&format_row(&row));
+                changed += 1;
+            }
+        }
+        out.push_str(&text[cursor..]);
+
+        Ok((changed > 0).then(|| (out.into_bytes(), changed)))
     }
 
     /// Convert TSV data to bytes
@@ -97,19 +215,7 @@ impl TsvHandler {
         let mut content = String::new();
 
         for row in data {
-            let formatted_row: Vec<String> = row
-                .iter()
-                .map(|field| {
-                    // 如果字段包含逗号,用双引号包围
-                    if field.contains(',') {
-                        format!("\"{}\"", field)
-                    } else {
-                        field.clone()
-                    }
-                })
-                .collect();
-
-            content.push_str(&formatted_row.join("\t"));
+            content.push_str(&Self::format_row(row));
             content.push('\n');
         }
 
@@ -117,11 +223,148 @@ impl TsvHandler {
     }
 }
 
+/// Records of a TSV text with their byte ranges, from [`TsvHandler::records`]
+pub struct Records<'a> {
+    text: &'a str,
+    reader: csv::Reader<&'a [u8]>,
+    record: csv::StringRecord,
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<(Range<usize>, Vec<String>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(e) => return Some(Err(anyhow::Error::new(e).context("Failed to parse TSV record"))),
+        }
+        // The reader's span can start with the previous line break and blank lines, and
+        // ends inside a CRLF
+        let start = self.record.position().map_or(0, |p| p.byte() as usize);
+        let end = self.reader.position().byte() as usize;
+        let raw = &self.text[start..end];
+        let range = end - raw.trim_start_matches(['\r', '\n']).len()
+            ..start + raw.trim_end_matches(['\r', '\n']).len();
+        Some(Ok((range, self.record.iter().map(str::to_string).collect())))
+    }
+}
+
+/// Length of the line break `text` starts with (0 if none)
+fn line_break_len(text: &str) -> usize {
+    if text.starts_with("\r\n") {
+        2
+    } else if text.starts_with(['\r', '\n']) {
+        1
+    } else {
+        0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    fn strings(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn test_map_rows() {
+        let baseline = b"Name\tLevel\r\nSword\t 5 \r\n\r\nAxe\t7\tspare\r\nBow\r\n";
+        let format = |row: &[String]| row.join("\t");
+
+        let mut seen = Vec::new();
+        let (patched, changed) = TsvHandler::map_rows(baseline, format, |header, row| {
+            assert_eq!(header, strings(&["Name", "Level"]));
+            seen.push(row[0].clone());
+            if row[0] == "Axe" || row[0] == "Bow" {
+                row[1] = "9".to_string();
+            }
+            Ok(())
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(seen, ["Sword", "Axe", "Bow"]);
+        assert_eq!(changed, 2);
+        // Untouched rows, blank lines and line endings survive; extra columns are kept
+        assert_eq!(
+            String::from_utf8(patched).unwrap(),
+            "Name\tLevel\r\nSword\t 5 \r\n\r\nAxe\t9\tspare\r\nBow\t9\r\n"
+        );
+
+        assert!(TsvHandler::map_rows(baseline, format, |_, _| Ok(())).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_patch_rows_keeps_untouched_lines() {
+        let baseline = "Name\tDesc\r\n\"Gem\"\t\"Red, shiny\"\textra\r\nRune\tEl\r\n\r\nOrb\tx\r\n";
+        let format = |row: &[String]| row.join("\t");
+
+        // Only the edited row is reserialized; quoting, extra columns and CRLF survive
+        let rows = vec![
+            strings(&["Name", "Desc"]),
+            strings(&["Gem", "Red, shiny"]),
+            strings(&["Rune", "Eld"]),
+            strings(&["Orb", "x"]),
+            strings(&["New", "y"]),
+        ];
+        let patched = TsvHandler::patch_rows(baseline.as_bytes(), &rows, format).unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8(patched).unwrap(),
+            "Name\tDesc\r\n\"Gem\"\t\"Red, shiny\"\textra\r\nRune\tEld\r\n\r\nOrb\tx\r\nNew\ty\r\n"
+        );
+
+        // Dropped rows disappear
+        let patched = TsvHandler::patch_rows(baseline.as_bytes(), &rows[..2], format).unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8(patched).unwrap(),
+            "Name\tDesc\r\n\"Gem\"\t\"Red, shiny\"\textra\r\n\r\n"
+        );
+
+        // A changed header means a full rewrite
+        let renamed = vec![strings(&["Name", "Description"])];
+        assert!(TsvHandler::patch_rows(baseline.as_bytes(), &renamed, format).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_multi_line_fields_stay_one_row() {
+        let baseline = "Name\tDesc\r\nGem\t\"one\ntwo\"\r\nRune\tEl\r\nOrb\tx\r\n";
+        let format = |row: &[String]| TsvHandler::format_row(row);
+
+        let rows = vec![
+            strings(&["Name", "Desc"]),
+            strings(&["Gem", "one\ntwo"]),
+            strings(&["Rune", "Eld"]),
+            strings(&["Orb", "x"]),
+        ];
+        let patched = TsvHandler::patch_rows(baseline.as_bytes(), &rows, format).unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8(patched).unwrap(),
+            "Name\tDesc\r\nGem\t\"one\ntwo\"\r\nRune\tEld\r\nOrb\tx\r\n"
+        );
+        let patched = TsvHandler::patch_rows(baseline.as_bytes(), &rows[..2], format).unwrap().unwrap();
+        assert_eq!(String::from_utf8(patched).unwrap(), "Name\tDesc\r\nGem\t\"one\ntwo\"\r\n");
+
+        let mut seen = Vec::new();
+        let (patched, changed) = TsvHandler::map_rows(baseline.as_bytes(), format, |_, row| {
+            seen.push(row[1].clone());
+            if row[0] == "Gem" {
+                row[1] = "three\nfour".to_string();
+            }
+            Ok(())
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(seen, ["one\ntwo", "El", "x"]);
+        assert_eq!(changed, 1);
+        assert_eq!(
+            String::from_utf8(patched).unwrap(),
+            "Name\tDesc\r\nGem\t\"three\nfour\"\r\nRune\tEl\r\nOrb\tx\r\n"
+        );
+    }
+
     #[tokio::test]
     async fn test_tsv_read_write() {
         let temp_dir = TempDir::new().unwrap();
@@ -138,4 +381,22 @@ mod tests {
 
         assert_eq!(data, read_data);
     }
+
+    #[test]
+    fn test_quoting_round_trip() {
+        let data = vec![
+            strings(&["Name", "Desc", "Code"]),
+            strings(&["Gem", "Red, shiny", "gem"]),
+            strings(&["Tab", "a\tb", "say \"hi\""]),
+            strings(&["Lines", "one\ntwo", ""]),
+        ];
+
+        let bytes = TsvHandler::to_bytes(&data).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "Name\tDesc\tCode\nGem\t\"Red, shiny\"\tgem\nTab\t\"a\tb\"\t\"say \"\"hi\"\"\"\nLines\t\"one\ntwo\"\t\n"
+        );
+        assert_eq!(TsvHandler::parse_from_bytes(&bytes).unwrap(), data);
+        assert_eq!(TsvHandler::format_field("plain"), "plain");
+    }
 }
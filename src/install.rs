@@ -0,0 +1,1428 @@
+//! In-process install
+//!
+//! [`install`] runs the whole `install` command — resolve mod sources, load and run
+//! every mod against a shared [`FileManager`], flush the output and write the build
+//! report — and returns what happened as an [`InstallOutcome`] instead of printing it.
+//! The CLI is a thin wrapper that renders [`InstallEvent`]s and the outcome.
+
+use crate::casc::{CascStorage, OpenRetry, PathAliases};
+use crate::content_store::{ContentStore, DedupStats};
+use crate::file_system::{
+    BackupManifest, DiskStore, ExtractionFailure, FileManager, FileSummary, MemoryStore, PruneStats,
+};
+use crate::github_downloader::GitHubDownloader;
+use crate::mod_manager::{LoadedMod, MissingModScript, ModLoader, UserConfig};
+use crate::mod_sources::{ModList, ModSource};
+use crate::report::BuildReport;
+use crate::runtime::{Context, ModExecutor, ScriptLimits, ScriptRuntimeUnavailable, SharedProgress};
+use crate::string_ids::{StringIdAllocator, StringIdManifest};
+use anyhow::{Context as _, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Per-source user configuration, keyed by the mod list entry (`github:owner/repo[:subdir][@ref]`)
+pub type SourceConfigs = HashMap<String, HashMap<String, serde_json::Value>>;
+
+/// Everything the `install` command accepts
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Game installation directory
+    pub game_path: String,
+    /// Directory with mods (or a single mod); used when neither `sources` nor `mod_list` is set
+    pub mods_path: Option<PathBuf>,
+    /// Mod list file with local and GitHub sources
+    pub mod_list: Option<PathBuf>,
+    /// Mod list built in memory (e.g. by the GUI); takes precedence over `mod_list`
+    pub sources: Option<ModList>,
+    /// Output directory; defaults to [`default_output_path`](crate::default_output_path)
+    pub output_path: Option<String>,
+    pub dry_run: bool,
+    /// Clear the GitHub download cache before resolving sources
+    pub clear_cache: bool,
+    /// Install even if the game is running
+    pub force: bool,
+    pub sandbox: bool,
+    pub features: HashMap<String, String>,
+    pub prune_unchanged: bool,
+    /// `writeTsv` only rewrites changed rows
+    pub incremental_tsv: bool,
+    /// Extra CASC storage roots to try before the auto-detected ones
+    pub casc_roots: Vec<PathBuf>,
+    /// How often to retry opening CASC storage (the game may still hold it right after closing)
+    pub casc_retry: OpenRetry,
+    /// How script paths map to CASC paths (the built-in `data/` prefixes plus any added rules)
+    pub path_aliases: PathAliases,
+    /// Install with extraction disabled when CASC storage can't be opened, instead of failing
+    pub allow_no_casc: bool,
+    /// Skip mods with the same name and version as an earlier one instead of only warning
+    pub dedup: bool,
+    /// Warn about installed mods that read and wrote no files
+    pub warn_empty_mods: bool,
+    /// Skip the free disk space check on the output volume
+    pub ignore_disk_space: bool,
+    /// Where GitHub sources are downloaded to
+    pub download_cache_dir: PathBuf,
+    /// GitHub token sent with downloads (anonymous requests are limited to 60/hour)
+    pub github_token: Option<String>,
+    /// User configuration written into downloaded mods before they load
+    pub source_configs: SourceConfigs,
+    /// Receives `infinite.reportProgress` updates while scripts run
+    pub script_progress: SharedProgress,
+    /// Build into this store instead of the output directory; nothing is written to disk
+    /// (no output tree, `modinfo.json`, string ID manifest or build report)
+    pub output_store: Option<MemoryStore>,
+    /// Write a markdown summary of the loaded mods and their configuration here
+    pub changelog: Option<PathBuf>,
+    /// Copy each extracted game file into `.infinite-backup` before a mod changes it
+    pub backup: bool,
+    /// Bytes of pending writes to keep in memory before spilling files to the output
+    pub cache_budget: Option<usize>,
+    /// How long each mod script may run before it is stopped as stuck
+    pub script_limits: ScriptLimits,
+}
+
+impl InstallOptions {
+    /// Options for installing into `game_path` with every flag off
+    pub fn new(game_path: impl Into<String>) -> Self {
+        Self {
+            game_path: game_path.into(),
+            mods_path: None,
+            mod_list: None,
+            sources: None,
+            output_path: None,
+            dry_run: false,
+            clear_cache: false,
+            force: false,
+            sandbox: false,
+            features: HashMap::new(),
+            prune_unchanged: false,
+            incremental_tsv: false,
+            casc_roots: Vec::new(),
+            casc_retry: OpenRetry::default(),
+            path_aliases: PathAliases::default(),
+            allow_no_casc: false,
+            dedup: false,
+            warn_empty_mods: false,
+            ignore_disk_space: false,
+            download_cache_dir: default_download_cache_dir(),
+            github_token: None,
+            source_configs: SourceConfigs::new(),
+            script_progress: SharedProgress::default(),
+            output_store: None,
+            changelog: None,
+            backup: false,
+            cache_budget: None,
+            script_limits: ScriptLimits::default(),
+        }
+    }
+
+    /// The output directory this install writes to
+    pub fn output_path(&self) -> String {
+        self.output_path
+            .clone()
+            .unwrap_or_else(|| crate::default_output_path(&self.game_path))
+    }
+}
+
+/// Environment variable naming a [`SourceConfigs`] file, used when `--source-config` isn't given
+pub const SOURCE_CONFIG_ENV: &str = "INFINITE_SOURCE_CONFIG";
+
+/// Read a [`SourceConfigs`] file handed over by a launcher such as the GUI
+pub fn read_source_configs(path: &Path) -> Result<SourceConfigs> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read source config {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse source config {}", path.display()))
+}
+
+/// Environment variable overriding the download cache directory
+pub const CACHE_DIR_ENV: &str = "INFINITE_CACHE_DIR";
+
+/// Default download cache: `$INFINITE_CACHE_DIR` if set, else `<config dir>/infinite/mod_cache`
+pub fn default_download_cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(CACHE_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("infinite");
+    path.push("mod_cache");
+    path
+}
+
+/// Progress notifications emitted while [`install_with_events`] runs
+#[derive(Debug, Clone)]
+pub enum InstallEvent {
+    ClearingDownloadCache,
+    SourcesLoaded(usize),
+    ResolvingSource {
+        index: usize,
+        total: usize,
+        source: ModSource,
+    },
+    SourceDownloaded(PathBuf),
+    SourceConfigApplied(PathBuf),
+    Deduplicated(DedupStats),
+    ModsFound(usize),
+    /// The game is running but `force` was set
+    GameRunning,
+    ClearingOutput,
+    OutputCleared,
+    CascOpened(PathBuf),
+    ModStarted {
+        index: usize,
+        total: usize,
+        name: String,
+        version: String,
+    },
+    ModSkipped {
+        name: String,
+    },
+    ModInstalled {
+        name: String,
+        elapsed: Duration,
+    },
+    ModFailed {
+        name: String,
+        error: String,
+    },
+    Flushing,
+    Flushed,
+    /// Extracted game files now share one copy with identical files from other builds
+    ExtractsDeduplicated(DedupStats),
+    ModinfoWritten(PathBuf),
+    ReportWritten(PathBuf),
+    ChangelogWritten(PathBuf),
+    /// A non-fatal problem, also collected in [`InstallOutcome::warnings`]
+    Warning(String),
+}
+
+/// What happened to one mod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModStatus {
+    Installed {
+        elapsed: Duration,
+    },
+    /// Disabled by its own configuration
+    Skipped,
+    Failed {
+        error: String,
+        reason: SkipReason,
+    },
+}
+
+/// Why a mod was not installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkipReason {
+    /// `mod.json` (or its configuration) could not be read, parsed or validated
+    ParseError,
+    /// Neither `mod.lua` nor `mod.js` was found
+    MissingScript,
+    /// The mod needs the JavaScript runtime, which this build was compiled without
+    JsRuntimeDisabled,
+    /// The script raised an error
+    ScriptError,
+    /// Disabled by its own configuration
+    Disabled,
+    /// Same name and version as a mod listed earlier (`dedup`)
+    Duplicate,
+}
+
+impl SkipReason {
+    /// Human-readable category name
+    pub fn label(self) -> &'static str {
+        match self {
+            SkipReason::ParseError => "Invalid mod.json or config",
+            SkipReason::MissingScript => "Missing script",
+            SkipReason::JsRuntimeDisabled => "JavaScript runtime disabled",
+            SkipReason::ScriptError => "Script error",
+            SkipReason::Disabled => "Disabled by configuration",
+            SkipReason::Duplicate => "Duplicate of an earlier mod",
+        }
+    }
+
+    fn of_load_error(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<MissingModScript>().is_some() {
+            SkipReason::MissingScript
+        } else {
+            SkipReason::ParseError
+        }
+    }
+
+    fn of_run_error(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<ScriptRuntimeUnavailable>().is_some() {
+            SkipReason::JsRuntimeDisabled
+        } else {
+            SkipReason::ScriptError
+        }
+    }
+}
+
+/// A mod that was not installed, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedMod {
+    /// Mod ID, or the directory name when the mod failed to load
+    pub mod_id: String,
+    pub reason: SkipReason,
+    /// The error message; empty for disabled mods
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModOutcome {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub status: ModStatus,
+}
+
+/// Result of an install
+#[derive(Debug, Clone)]
+pub struct InstallOutcome {
+    pub output_path: PathBuf,
+    /// Every loaded mod, in install order
+    pub mods: Vec<ModOutcome>,
+    /// Time spent running mods
+    pub elapsed: Duration,
+    /// Files written by more than one mod -> the writers, in write order
+    pub conflicts: BTreeMap<String, Vec<String>>,
+    pub files: FileSummary,
+    /// Set when `prune_unchanged` was requested
+    pub prune_stats: Option<PruneStats>,
+    /// The build report written to the output directory (not written on dry runs)
+    pub report: Option<BuildReport>,
+    pub warnings: Vec<String>,
+    /// Mods that failed to load, failed to run or were disabled, in the order they were seen
+    pub skipped: Vec<SkippedMod>,
+    /// IDs of mods that ran without error but read and wrote no files, in install order
+    pub empty_mods: Vec<String>,
+    /// Game files mods asked for that couldn't be extracted (e.g. renamed by a game patch)
+    pub missing_game_files: Vec<ExtractionFailure>,
+}
+
+impl InstallOutcome {
+    fn empty(output_path: PathBuf) -> Self {
+        Self {
+            output_path,
+            mods: Vec::new(),
+            elapsed: Duration::ZERO,
+            conflicts: BTreeMap::new(),
+            files: FileSummary::default(),
+            prune_stats: None,
+            report: None,
+            warnings: Vec::new(),
+            skipped: Vec::new(),
+            empty_mods: Vec::new(),
+            missing_game_files: Vec::new(),
+        }
+    }
+
+    /// Mods whose script failed
+    pub fn failed(&self) -> impl Iterator<Item = &ModOutcome> {
+        self.mods
+            .iter()
+            .filter(|m| matches!(m.status, ModStatus::Failed { .. }))
+    }
+
+    /// Mods that failed only because this build lacks the JavaScript runtime
+    pub fn needs_js_runtime(&self) -> impl Iterator<Item = &ModOutcome> {
+        self.mods.iter().filter(|m| {
+            matches!(
+                m.status,
+                ModStatus::Failed {
+                    reason: SkipReason::JsRuntimeDisabled,
+                    ..
+                }
+            )
+        })
+    }
+
+    /// Number of skipped mods per reason, in [`SkipReason`] order
+    pub fn skip_counts(&self) -> BTreeMap<SkipReason, usize> {
+        let mut counts = BTreeMap::new();
+        for skipped in &self.skipped {
+            *counts.entry(skipped.reason).or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// Install mods without progress notifications
+pub async fn install(options: InstallOptions) -> Result<InstallOutcome> {
+    install_with_events(options, |_| {}).await
+}
+
+/// Install mods, calling `on_event` as the install progresses
+///
+/// Errors are reserved for problems that stop the install before any mod runs (no mod
+/// source, a download failure, the game running without `force`, CASC storage that
+/// can't be opened without `allow_no_casc`, an output directory that can't be cleared).
+/// Failing mods and output bookkeeping problems are reported in the outcome.
+pub async fn install_with_events(
+    options: InstallOptions,
+    mut on_event: impl FnMut(InstallEvent),
+) -> Result<InstallOutcome> {
+    let output_path = options.output_path();
+    let output_path_buf = PathBuf::from(&output_path);
+    let mut outcome = InstallOutcome::empty(output_path_buf.clone());
+
+    let mod_dirs = resolve_sources(&options, &mut on_event).await?;
+
+    // Load all mods from all directories
+    let mut all_mods: Vec<LoadedMod> = Vec::new();
+    for (mod_dir, overrides) in &mod_dirs {
+        let first_new = all_mods.len();
+        // Check if this is a single mod or a mods directory
+        if mod_dir.join("mod.json").exists() {
+            let loader = ModLoader::new(mod_dir.parent().unwrap_or(Path::new(".")));
+            match loader.load_mod(mod_dir) {
+                Ok(mod_data) => all_mods.push(mod_data),
+                Err(e) => {
+                    warn(
+                        &mut outcome,
+                        &mut on_event,
+                        format!("Failed to load mod at {}: {:#}", mod_dir.display(), e),
+                    );
+                    outcome.skipped.push(load_failure(mod_dir, &e));
+                }
+            }
+        } else {
+            let (mods, failures) = ModLoader::new(mod_dir).load_all_with_failures()?;
+            for (path, e) in &failures {
+                tracing::warn!("Failed to load mod from {:?}: {}", path, e);
+                outcome.skipped.push(load_failure(path, e));
+            }
+            all_mods.extend(mods);
+        }
+
+        // `| key=value` overrides from the mod list line
+        for mod_data in &mut all_mods[first_new..] {
+            for key in apply_config_overrides(mod_data, overrides.clone()) {
+                warn(
+                    &mut outcome,
+                    &mut on_event,
+                    format!(
+                        "Ignoring mod list option '{}' for '{}': not a declared option, or the value has the wrong type",
+                        key, mod_data.config.name
+                    ),
+                );
+            }
+        }
+    }
+
+    // The same mod added as a local clone and as its GitHub source would run twice
+    let duplicates = find_duplicate_mods(&all_mods);
+    for &(first, duplicate) in &duplicates {
+        let (first, duplicate) = (&all_mods[first], &all_mods[duplicate]);
+        let action = if options.dedup {
+            "keeping the first"
+        } else {
+            "it will run twice and conflict with itself; remove one or pass --dedup"
+        };
+        warn(
+            &mut outcome,
+            &mut on_event,
+            format!(
+                "Mod '{}' {} is listed more than once ({} and {}); {}",
+                first.config.name,
+                first.config.version,
+                first.path.display(),
+                duplicate.path.display(),
+                action
+            ),
+        );
+    }
+    if options.dedup {
+        for &(first, duplicate) in duplicates.iter().rev() {
+            let first_path = all_mods[first].path.display().to_string();
+            let removed = all_mods.remove(duplicate);
+            outcome.skipped.push(SkippedMod {
+                mod_id: removed.id,
+                reason: SkipReason::Duplicate,
+                message: format!("same name and version as {}", first_path),
+            });
+        }
+    }
+
+    if all_mods.is_empty() {
+        return Ok(outcome);
+    }
+    on_event(InstallEvent::ModsFound(all_mods.len()));
+
+    // Bookkeeping files (modinfo, manifest, report) only belong next to a real output tree
+    let writes_disk = !options.dry_run && options.output_store.is_none();
+
+    // Writing while the game holds the files open fails or leaves partial output
+    if writes_disk && crate::is_game_running() {
+        if !options.force {
+            anyhow::bail!(
+                "{} is running. Close the game before installing, or pass --force to install anyway",
+                crate::GAME_PROCESS_NAME
+            );
+        }
+        on_event(InstallEvent::GameRunning);
+    }
+
+    // Open CASC before clearing the output, so a failure leaves the previous build in place
+    let game_path = options.game_path.clone();
+    let casc_roots = options.casc_roots.clone();
+    let casc_retry = options.casc_retry;
+    let path_aliases = options.path_aliases.clone();
+    let casc = tokio::task::spawn_blocking(move || {
+        CascStorage::open_with_retry(&game_path, &casc_roots, casc_retry)
+            .map(|casc| casc.with_path_aliases(path_aliases))
+    })
+    .await?;
+    let casc = match casc {
+        Ok(casc) => {
+            on_event(InstallEvent::CascOpened(casc.root().to_path_buf()));
+            Some(Arc::new(casc))
+        }
+        Err(e) if options.allow_no_casc => {
+            warn(
+                &mut outcome,
+                &mut on_event,
+                format!(
+                    "{:#}. File extraction is disabled; mods that read game files will fail",
+                    e
+                ),
+            );
+            None
+        }
+        Err(e) => {
+            return Err(e.context(
+                "Game files can't be extracted. Check the game path (and --casc-root), \
+                 or pass --allow-no-casc to install without extraction",
+            ));
+        }
+    };
+
+    // Running out of space halfway through leaves a partial output, so check before clearing it
+    if writes_disk && !options.ignore_disk_space {
+        check_free_space(&output_path_buf, MIN_FREE_SPACE)?;
+    }
+
+    // Reuse string IDs assigned by previous builds; checked before clearing the output
+    let manifest = StringIdManifest::load_for_build(&output_path_buf)?;
+
+    if options.output_store.is_none() && output_path_buf.exists() {
+        on_event(InstallEvent::ClearingOutput);
+        std::fs::remove_dir_all(&output_path_buf)
+            .with_context(|| format!("Failed to clear output directory: {}", output_path))?;
+        on_event(InstallEvent::OutputCleared);
+    }
+
+    // Extracted game files share the download cache's content store
+    let extract_store = Arc::new(ContentStore::new(options.download_cache_dir.join(".objects")));
+    let mut file_manager = FileManager::new();
+    match &options.output_store {
+        Some(store) => file_manager.set_output_store(store.clone()),
+        None => file_manager
+            .set_output_store(DiskStore::new(&output_path).with_content_store(extract_store.clone())),
+    }
+    file_manager.set_game_path(&options.game_path);
+    file_manager.set_prune_unchanged(options.prune_unchanged);
+    file_manager.set_incremental_tsv(options.incremental_tsv);
+    if let Some(budget) = options.cache_budget {
+        file_manager.set_cache_budget(budget);
+    }
+    if writes_disk && options.backup {
+        // The backup describes this build only
+        let backup_dir = BackupManifest::dir_for(&output_path_buf);
+        if backup_dir.exists() {
+            std::fs::remove_dir_all(&backup_dir)
+                .with_context(|| format!("Failed to clear backup directory: {}", backup_dir.display()))?;
+        }
+        file_manager.set_backup_dir(backup_dir);
+    }
+
+    if let Some(casc) = casc {
+        file_manager.set_casc_storage(casc);
+    }
+
+    let file_manager = Arc::new(RwLock::new(file_manager));
+
+    let string_ids = Arc::new(std::sync::Mutex::new(StringIdAllocator::new(manifest)));
+
+    let start_time = Instant::now();
+    for (idx, mod_data) in all_mods.iter().enumerate() {
+        on_event(InstallEvent::ModStarted {
+            index: idx + 1,
+            total: all_mods.len(),
+            name: mod_data.config.name.clone(),
+            version: mod_data.config.version.clone(),
+        });
+
+        let mod_start = Instant::now();
+        let status = if !mod_data.is_enabled() {
+            on_event(InstallEvent::ModSkipped {
+                name: mod_data.config.name.clone(),
+            });
+            ModStatus::Skipped
+        } else {
+            let context = Arc::new(Context {
+                mod_id: mod_data.id.clone(),
+                mod_path: mod_data.path.clone(),
+                config: serde_json::to_value(&mod_data.user_config)?,
+                file_manager: file_manager.clone(),
+                game_path: options.game_path.clone().into(),
+                output_path: output_path_buf.clone(),
+                dry_run: options.dry_run,
+                sandbox: options.sandbox,
+                features: options.features.clone(),
+                string_ids: string_ids.clone(),
+                progress: options.script_progress.clone(),
+                limits: options.script_limits,
+            });
+
+            match ModExecutor::execute_mod(mod_data, context).await {
+                Ok(_) => {
+                    let elapsed = mod_start.elapsed();
+                    on_event(InstallEvent::ModInstalled {
+                        name: mod_data.config.name.clone(),
+                        elapsed,
+                    });
+                    ModStatus::Installed { elapsed }
+                }
+                Err(e) => {
+                    on_event(InstallEvent::ModFailed {
+                        name: mod_data.config.name.clone(),
+                        error: e.to_string(),
+                    });
+                    ModStatus::Failed {
+                        error: e.to_string(),
+                        reason: SkipReason::of_run_error(&e),
+                    }
+                }
+            }
+        };
+
+        match &status {
+            ModStatus::Installed { .. } => {}
+            ModStatus::Skipped => outcome.skipped.push(SkippedMod {
+                mod_id: mod_data.id.clone(),
+                reason: SkipReason::Disabled,
+                message: String::new(),
+            }),
+            ModStatus::Failed { error, reason } => outcome.skipped.push(SkippedMod {
+                mod_id: mod_data.id.clone(),
+                reason: *reason,
+                message: error.clone(),
+            }),
+        }
+
+        outcome.mods.push(ModOutcome {
+            id: mod_data.id.clone(),
+            name: mod_data.config.name.clone(),
+            version: mod_data.config.version.clone(),
+            status,
+        });
+    }
+    outcome.elapsed = start_time.elapsed();
+
+    // A mod that touched no files usually has a wrong path or a config guard that's off.
+    // Dry runs don't record writes, so only reads count there.
+    {
+        let fm = file_manager.read().await;
+        outcome.empty_mods = outcome
+            .mods
+            .iter()
+            .filter(|m| matches!(m.status, ModStatus::Installed { .. }))
+            .filter(|m| !fm.has_operations_by(&m.id))
+            .map(|m| m.id.clone())
+            .collect();
+        outcome.missing_game_files = fm.extraction_failures().to_vec();
+    }
+    if options.warn_empty_mods {
+        for id in outcome.empty_mods.clone() {
+            let name = outcome
+                .mods
+                .iter()
+                .find(|m| m.id == id)
+                .map_or(id.as_str(), |m| m.name.as_str());
+            let message = format!(
+                "Mod '{}' ran but read and wrote no files; check its paths and configuration",
+                name
+            );
+            warn(&mut outcome, &mut on_event, message);
+        }
+    }
+
+    // Flush all cached file modifications to disk
+    on_event(InstallEvent::Flushing);
+    {
+        let mut fm = file_manager.write().await;
+        match fm.flush_cache().await {
+            Ok(()) => on_event(InstallEvent::Flushed),
+            Err(e) => warn(
+                &mut outcome,
+                &mut on_event,
+                format!("Failed to flush cache: {}", e),
+            ),
+        }
+        if options.prune_unchanged {
+            outcome.prune_stats = Some(fm.prune_stats());
+        }
+        outcome.files = fm.summary();
+    }
+    let stats = extract_store.stats();
+    if stats.linked > 0 {
+        on_event(InstallEvent::ExtractsDeduplicated(stats));
+    }
+
+    if writes_disk {
+        match write_modinfo(&output_path_buf) {
+            Ok(Some(path)) => {
+                let problems = check_modinfo(&path);
+                on_event(InstallEvent::ModinfoWritten(path));
+                for problem in problems {
+                    warn(
+                        &mut outcome,
+                        &mut on_event,
+                        format!("modinfo.json: {}", problem),
+                    );
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn(
+                &mut outcome,
+                &mut on_event,
+                format!("Failed to write modinfo.json: {:#}", e),
+            ),
+        }
+
+        // Persist string ID assignments for the next build
+        let saved = {
+            let string_ids = string_ids.lock().unwrap();
+            if string_ids.manifest().is_empty() {
+                Ok(())
+            } else {
+                string_ids.manifest().save(&output_path_buf).map(|_| ())
+            }
+        };
+        if let Err(e) = saved {
+            warn(
+                &mut outcome,
+                &mut on_event,
+                format!("Failed to write string ID manifest: {}", e),
+            );
+        }
+    }
+
+    let producers = {
+        let fm = file_manager.read().await;
+        outcome.conflicts = fm.detect_conflicts().into_iter().collect();
+        fm.writers()
+    };
+
+    // Record what this build produced so `verify` can detect drift later
+    if writes_disk {
+        let mod_ids = all_mods.iter().map(|m| m.id.clone()).collect();
+        let written =
+            BuildReport::from_output_dir(&output_path_buf, mod_ids).and_then(|mut report| {
+                report.mod_paths = all_mods
+                    .iter()
+                    .map(|m| (m.id.clone(), m.path.clone()))
+                    .collect();
+                report.producers = producers;
+                let path = report.save(&output_path_buf)?;
+                Ok((report, path))
+            });
+        match written {
+            Ok((report, path)) => {
+                on_event(InstallEvent::ReportWritten(path));
+                outcome.report = Some(report);
+            }
+            Err(e) => warn(
+                &mut outcome,
+                &mut on_event,
+                format!("Failed to write build report: {}", e),
+            ),
+        }
+    }
+
+    if let Some(path) = &options.changelog {
+        match crate::changelog::write(path, &all_mods, &outcome.mods) {
+            Ok(()) => on_event(InstallEvent::ChangelogWritten(path.clone())),
+            Err(e) => warn(&mut outcome, &mut on_event, format!("{:#}", e)),
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Free space the output volume needs before an install starts (extracted HD assets can
+/// take gigabytes)
+pub const MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+/// Fail unless the volume holding `path` (or its nearest existing ancestor) has `required` bytes free
+fn check_free_space(path: &Path, required: u64) -> Result<()> {
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return Ok(());
+    };
+    let available = match fs2::available_space(existing) {
+        Ok(available) => available,
+        Err(e) => {
+            tracing::warn!(
+                "Could not check free space on {}: {}",
+                existing.display(),
+                e
+            );
+            return Ok(());
+        }
+    };
+    if available < required {
+        anyhow::bail!(
+            "Only {} MB free on the volume of {} (at least {} MB recommended). \
+             Free some space, or pass --ignore-disk-space to install anyway",
+            available / (1024 * 1024),
+            existing.display(),
+            required / (1024 * 1024)
+        );
+    }
+    Ok(())
+}
+
+/// Skip entry for a mod directory that failed to load
+fn load_failure(mod_dir: &Path, e: &anyhow::Error) -> SkippedMod {
+    SkippedMod {
+        mod_id: mod_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| mod_dir.display().to_string()),
+        reason: SkipReason::of_load_error(e),
+        message: format!("{:#}", e),
+    }
+}
+
+/// Merge config overrides into a loaded mod; returns the IDs that were rejected
+fn apply_config_overrides(mod_data: &mut LoadedMod, overrides: UserConfig) -> Vec<String> {
+    if overrides.is_empty() {
+        return Vec::new();
+    }
+    let rejected = mod_data
+        .config
+        .apply_preset(&mut mod_data.user_config, overrides);
+    mod_data
+        .config
+        .normalize_user_config(&mut mod_data.user_config);
+    rejected
+}
+
+/// Mods with the same name and version as an earlier one, as `(first, duplicate)` indices
+///
+/// Sorted by the duplicate's index. Names are compared case-insensitively.
+fn find_duplicate_mods(mods: &[LoadedMod]) -> Vec<(usize, usize)> {
+    let mut seen: HashMap<(String, &str), usize> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (index, m) in mods.iter().enumerate() {
+        let key = (m.config.name.trim().to_lowercase(), m.config.version.trim());
+        match seen.get(&key) {
+            Some(&first) => duplicates.push((first, index)),
+            None => {
+                seen.insert(key, index);
+            }
+        }
+    }
+    duplicates
+}
+
+/// Report a non-fatal problem both as an event and in the outcome
+fn warn(outcome: &mut InstallOutcome, on_event: &mut impl FnMut(InstallEvent), message: String) {
+    on_event(InstallEvent::Warning(message.clone()));
+    outcome.warnings.push(message);
+}
+
+/// Turn `mods_path` / `mod_list` / `sources` into the directories to load mods from,
+/// each with the config overrides from its mod list entry
+async fn resolve_sources(
+    options: &InstallOptions,
+    on_event: &mut impl FnMut(InstallEvent),
+) -> Result<Vec<(PathBuf, UserConfig)>> {
+    let mod_list = if let Some(sources) = &options.sources {
+        sources.clone()
+    } else if let Some(list_path) = &options.mod_list {
+        ModList::from_file(list_path).await?
+    } else {
+        return match &options.mods_path {
+            Some(path) => Ok(vec![(path.clone(), UserConfig::new())]),
+            None => anyhow::bail!("Either --mods-path or --mod-list must be specified"),
+        };
+    };
+
+    let downloader =
+        GitHubDownloader::with_token(options.download_cache_dir.clone(), options.github_token.clone());
+    if options.clear_cache {
+        on_event(InstallEvent::ClearingDownloadCache);
+        downloader.clear_cache().await?;
+    }
+
+    on_event(InstallEvent::SourcesLoaded(mod_list.entries.len()));
+
+    let mut dirs = Vec::new();
+    for (idx, entry) in mod_list.entries.iter().enumerate() {
+        let overrides = &entry.config_overrides;
+        on_event(InstallEvent::ResolvingSource {
+            index: idx + 1,
+            total: mod_list.entries.len(),
+            source: entry.source.clone(),
+        });
+        match &entry.source {
+            ModSource::Local { path } => dirs.push((path.clone(), overrides.clone())),
+            ModSource::GitHub {
+                repo,
+                subdir,
+                branch,
+                commit,
+            } => {
+                let local_path = downloader
+                    .download(
+                        repo,
+                        subdir.as_deref(),
+                        branch.as_deref(),
+                        commit.as_deref(),
+                    )
+                    .await?;
+                on_event(InstallEvent::SourceDownloaded(local_path.clone()));
+
+                // Write the user's configuration into the downloaded mod before it loads
+                let key = source_config_key(
+                    repo,
+                    subdir.as_deref(),
+                    branch.as_deref(),
+                    commit.as_deref(),
+                );
+                if let Some(user_config) =
+                    options.source_configs.get(&key).filter(|c| !c.is_empty())
+                {
+                    let config_file = local_path.join("config.json");
+                    // The downloaded config.json may be shared with other cache entries
+                    let written = serde_json::to_string_pretty(user_config)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|json| {
+                            crate::content_store::detach(&config_file)?;
+                            std::fs::write(&config_file, json)?;
+                            Ok(())
+                        });
+                    match written {
+                        Ok(()) => {
+                            tracing::info!("Applied GUI config to: {}", config_file.display());
+                            on_event(InstallEvent::SourceConfigApplied(config_file));
+                        }
+                        Err(e) => tracing::warn!("Failed to write config for {}: {}", key, e),
+                    }
+                }
+
+                dirs.push((local_path, overrides.clone()));
+            }
+        }
+    }
+
+    let stats = downloader.dedup_stats();
+    if stats.linked > 0 {
+        on_event(InstallEvent::Deduplicated(stats));
+    }
+    Ok(dirs)
+}
+
+/// Key of a GitHub source in [`SourceConfigs`], as the GUI writes it
+pub fn source_config_key(
+    repo: &str,
+    subdir: Option<&str>,
+    branch: Option<&str>,
+    commit: Option<&str>,
+) -> String {
+    let mut key = format!("github:{}", repo);
+    if let Some(subdir) = subdir {
+        key = format!("{}:{}", key, subdir);
+    }
+    if let Some(commit) = commit {
+        if crate::mod_sources::is_commit_sha(commit) {
+            key = format!("{}@{}", key, commit);
+        } else {
+            key = format!("{}@{}{}", key, crate::mod_sources::SHA_REF_PREFIX, commit);
+        }
+    } else if let Some(branch) = branch {
+        if branch != "main" && branch != "master" {
+            key = format!("{}@{}", key, branch);
+        }
+    }
+    key
+}
+
+/// Write `modinfo.json` next to the output directory; `None` if it has no parent
+fn write_modinfo(output_path: &Path) -> Result<Option<PathBuf>> {
+    let Some(parent_dir) = output_path.parent() else {
+        return Ok(None);
+    };
+    let modinfo_path = parent_dir.join("modinfo.json");
+    let modinfo_content = serde_json::json!({
+        "name": "Infinite",
+        "savepath": "Infinite/"
+    });
+    std::fs::create_dir_all(parent_dir).context("Failed to create directory for modinfo.json")?;
+    std::fs::write(
+        &modinfo_path,
+        serde_json::to_string_pretty(&modinfo_content)?,
+    )?;
+    Ok(Some(modinfo_path))
+}
+
+/// Problems with a `modinfo.json` that would keep D2R from loading the mod
+///
+/// D2R expects `<game>/mods/<name>/<name>.mpq/modinfo.json` with a string `name` matching
+/// those folders (it is what `-mod <name>` looks for) and a relative `savepath` ending in
+/// `/`. Returns one message per problem; empty when the file looks loadable.
+pub fn check_modinfo(modinfo_path: &Path) -> Vec<String> {
+    let content = match std::fs::read_to_string(modinfo_path) {
+        Ok(content) => content,
+        Err(e) => return vec![format!("could not be read back: {}", e)],
+    };
+    let modinfo: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(value) => value,
+        Err(e) => return vec![format!("is not valid JSON: {}", e)],
+    };
+    let mut problems = Vec::new();
+
+    match modinfo.get("name").and_then(|v| v.as_str()) {
+        None => problems.push("missing string field \"name\"".to_string()),
+        Some(name) if name.trim().is_empty() || name.contains(['/', '\\']) => problems.push(
+            format!("\"name\" must be a plain folder name, got {:?}", name),
+        ),
+        Some(name) => {
+            // The game only finds the mod under mods/<name>/<name>.mpq
+            let mpq_dir = modinfo_path.parent();
+            let mpq_name = mpq_dir
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy());
+            let mod_name = mpq_dir
+                .and_then(|p| p.parent())
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy());
+            let expected_mpq = format!("{}.mpq", name);
+            let placed = mpq_name.is_some_and(|n| n.eq_ignore_ascii_case(&expected_mpq))
+                && mod_name.is_some_and(|n| n.eq_ignore_ascii_case(name));
+            if !placed {
+                problems.push(format!(
+                    "written to {}, but D2R looks for it in mods/{}/{}/modinfo.json (check --output-path)",
+                    modinfo_path.display(),
+                    name,
+                    expected_mpq
+                ));
+            }
+        }
+    }
+
+    match modinfo.get("savepath").and_then(|v| v.as_str()) {
+        None => problems.push("missing string field \"savepath\"".to_string()),
+        Some(savepath) => {
+            if !savepath.ends_with('/') {
+                problems.push(format!(
+                    "\"savepath\" must end with '/', got {:?}",
+                    savepath
+                ));
+            }
+            if savepath.contains('\\')
+                || savepath.starts_with('/')
+                || savepath.split('/').any(|part| part == "..")
+            {
+                problems.push(format!(
+                    "\"savepath\" must be a relative path with forward slashes, got {:?}",
+                    savepath
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_mod(mods_dir: &Path, id: &str, script: &str) {
+        let dir = mods_dir.join(id);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("mod.json"),
+            format!(r#"{{ "name": "{}", "version": "1.0" }}"#, id),
+        )
+        .unwrap();
+        std::fs::write(dir.join("mod.lua"), script).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install_returns_outcome() {
+        let temp = TempDir::new().unwrap();
+        let mods_dir = temp.path().join("mods");
+        write_mod(
+            &mods_dir,
+            "a",
+            "D2RMM.writeTxt('global/excel/shared.txt', 'a')",
+        );
+        write_mod(
+            &mods_dir,
+            "b",
+            "D2RMM.writeTxt('global/excel/shared.txt', 'b')",
+        );
+        write_mod(&mods_dir, "c", "D2RMM.error('broken')");
+        std::fs::create_dir_all(mods_dir.join("no-script")).unwrap();
+        std::fs::write(
+            mods_dir.join("no-script/mod.json"),
+            r#"{ "name": "x", "version": "1" }"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(mods_dir.join("bad-json")).unwrap();
+        std::fs::write(mods_dir.join("bad-json/mod.json"), "{").unwrap();
+
+        let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+        options.allow_no_casc = true;
+        options.mods_path = Some(mods_dir);
+        options.output_path = Some(
+            temp.path()
+                .join("out/Infinite.mpq/data")
+                .to_string_lossy()
+                .into_owned(),
+        );
+
+        let mut events = Vec::new();
+        let outcome = install_with_events(options, |e| events.push(e))
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.mods.len(), 3);
+        assert_eq!(
+            outcome.failed().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+            ["c"]
+        );
+
+        // Every mod that didn't install is listed with its reason
+        let mut skipped: Vec<(&str, SkipReason)> = outcome
+            .skipped
+            .iter()
+            .map(|s| (s.mod_id.as_str(), s.reason))
+            .collect();
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            [
+                ("bad-json", SkipReason::ParseError),
+                ("c", SkipReason::ScriptError),
+                ("no-script", SkipReason::MissingScript)
+            ]
+        );
+        assert_eq!(outcome.skip_counts()[&SkipReason::ScriptError], 1);
+        // The later of the two writers wins
+        let order: Vec<&str> = outcome
+            .mods
+            .iter()
+            .map(|m| m.id.as_str())
+            .filter(|id| *id != "c")
+            .collect();
+        assert_eq!(outcome.conflicts["global/excel/shared.txt"], order);
+        assert_eq!(
+            std::fs::read_to_string(outcome.output_path.join("global/excel/shared.txt")).unwrap(),
+            order[1]
+        );
+        assert!(outcome
+            .report
+            .unwrap()
+            .files
+            .contains_key("global/excel/shared.txt"));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, InstallEvent::ModFailed { name, .. } if name == "c")));
+        assert!(outcome.missing_game_files.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_missing_game_files() {
+        let temp = TempDir::new().unwrap();
+        let mods_dir = temp.path().join("mods");
+        write_mod(
+            &mods_dir,
+            "patched",
+            "D2RMM.readTsv('global/excel/renamed.txt')",
+        );
+
+        let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+        options.allow_no_casc = true;
+        options.mods_path = Some(mods_dir);
+        options.output_store = Some(MemoryStore::new());
+
+        let outcome = install(options).await.unwrap();
+        assert_eq!(outcome.failed().count(), 1);
+        assert_eq!(outcome.missing_game_files.len(), 1);
+        assert_eq!(
+            outcome.missing_game_files[0].file_path,
+            "global/excel/renamed.txt"
+        );
+        assert_eq!(outcome.missing_game_files[0].mod_id, "patched");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_corrupt_string_id_manifest_stops_install() {
+        let temp = TempDir::new().unwrap();
+        let mods_dir = temp.path().join("mods");
+        write_mod(&mods_dir, "a", "D2RMM.writeTxt('global/excel/notes.txt', 'hello')");
+        let output = temp.path().join("out/data");
+        std::fs::create_dir_all(&output).unwrap();
+        std::fs::write(output.join("previous.txt"), "kept").unwrap();
+        let manifest_path = StringIdManifest::path_for(&output);
+        std::fs::write(&manifest_path, "not json").unwrap();
+
+        let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+        options.allow_no_casc = true;
+        options.ignore_disk_space = true;
+        options.mods_path = Some(mods_dir);
+        options.output_path = Some(output.to_string_lossy().into_owned());
+
+        assert!(install(options).await.is_err());
+        assert_eq!(std::fs::read_to_string(&manifest_path).unwrap(), "not json");
+        assert!(output.join("previous.txt").exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install_into_memory_store() {
+        let temp = TempDir::new().unwrap();
+        let mods_dir = temp.path().join("mods");
+        write_mod(
+            &mods_dir,
+            "a",
+            "D2RMM.writeTxt('global/excel/notes.txt', 'hello')",
+        );
+
+        let store = MemoryStore::new();
+        let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+        options.allow_no_casc = true;
+        options.mods_path = Some(mods_dir);
+        options.output_path = Some(temp.path().join("out").to_string_lossy().into_owned());
+        options.output_store = Some(store.clone());
+
+        let outcome = install(options).await.unwrap();
+        assert_eq!(outcome.failed().count(), 0);
+        assert_eq!(store.get_string("global/excel/notes.txt").unwrap(), "hello");
+        // Nothing reaches disk, bookkeeping files included
+        assert!(!temp.path().join("out").exists());
+        assert!(outcome.report.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_duplicate_mods() {
+        let temp = TempDir::new().unwrap();
+        let local = temp.path().join("local");
+        let downloaded = temp.path().join("downloaded");
+        write_mod(&local, "loot", "D2RMM.writeTxt('loot.txt', 'local')");
+        write_mod(
+            &downloaded,
+            "owner-loot",
+            "D2RMM.writeTxt('loot.txt', 'github')",
+        );
+        // Same name and version as "loot" (ids come from directory names)
+        std::fs::write(
+            downloaded.join("owner-loot/mod.json"),
+            r#"{ "name": "Loot", "version": "1.0" }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            local.join("loot/mod.json"),
+            r#"{ "name": "loot", "version": "1.0" }"#,
+        )
+        .unwrap();
+        let list = temp.path().join("mods.txt");
+        std::fs::write(
+            &list,
+            format!(
+                "{}\n{}\n",
+                local.join("loot").display(),
+                downloaded.join("owner-loot").display()
+            ),
+        )
+        .unwrap();
+
+        let options = |dedup| {
+            let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+            options.allow_no_casc = true;
+            options.mod_list = Some(list.clone());
+            options.output_store = Some(MemoryStore::new());
+            options.dedup = dedup;
+            options
+        };
+
+        // Warned about, but both still run
+        let outcome = install(options(false)).await.unwrap();
+        assert_eq!(outcome.mods.len(), 2);
+        assert!(outcome
+            .warnings
+            .iter()
+            .any(|w| w.contains("listed more than once")));
+
+        let outcome = install(options(true)).await.unwrap();
+        assert_eq!(outcome.mods.len(), 1);
+        assert_eq!(outcome.mods[0].id, "loot");
+        assert_eq!(outcome.skipped[0].mod_id, "owner-loot");
+        assert_eq!(outcome.skipped[0].reason, SkipReason::Duplicate);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mod_list_config_overrides() {
+        let temp = TempDir::new().unwrap();
+        let mods_dir = temp.path().join("mods");
+        write_mod(
+            &mods_dir,
+            "sized",
+            "D2RMM.writeTxt('size.txt', tostring(config.value))",
+        );
+        std::fs::write(
+            mods_dir.join("sized/mod.json"),
+            r#"{ "name": "sized", "version": "1.0", "config": [{ "type": "number", "id": "value", "name": "Value", "default": 100 }] }"#,
+        )
+        .unwrap();
+        let list = temp.path().join("mods.txt");
+        std::fs::write(
+            &list,
+            format!(
+                "{} | value=50, unknown=1\n",
+                mods_dir.join("sized").display()
+            ),
+        )
+        .unwrap();
+
+        let store = MemoryStore::new();
+        let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+        options.allow_no_casc = true;
+        options.mod_list = Some(list);
+        options.output_store = Some(store.clone());
+
+        let outcome = install(options).await.unwrap();
+        assert_eq!(outcome.failed().count(), 0);
+        assert_eq!(store.get_string("size.txt").unwrap(), "50");
+        assert!(outcome
+            .warnings
+            .iter()
+            .any(|w| w.contains("Ignoring mod list option 'unknown'")));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_empty_mods() {
+        let temp = TempDir::new().unwrap();
+        let mods_dir = temp.path().join("mods");
+        write_mod(&mods_dir, "writer", "D2RMM.writeTxt('a.txt', 'a')");
+        write_mod(&mods_dir, "reader", "D2RMM.readTxt('a.txt')");
+        write_mod(
+            &mods_dir,
+            "noop",
+            "if config.enabled then D2RMM.writeTxt('b.txt', 'b') end",
+        );
+        // A list keeps the writer ahead of the reader
+        let list = temp.path().join("mods.txt");
+        let entries: Vec<String> = ["writer", "reader", "noop"]
+            .iter()
+            .map(|id| mods_dir.join(id).display().to_string())
+            .collect();
+        std::fs::write(&list, entries.join("\n")).unwrap();
+
+        let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+        options.allow_no_casc = true;
+        options.mod_list = Some(list);
+        options.output_store = Some(MemoryStore::new());
+        options.warn_empty_mods = true;
+
+        let outcome = install(options).await.unwrap();
+        assert_eq!(outcome.failed().count(), 0);
+        assert_eq!(outcome.empty_mods, ["noop"]);
+        assert!(outcome
+            .warnings
+            .iter()
+            .any(|w| w.contains("'noop' ran but read and wrote no files")));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install_requires_casc_by_default() {
+        let temp = TempDir::new().unwrap();
+        let mods_dir = temp.path().join("mods");
+        write_mod(&mods_dir, "a", "D2RMM.writeTxt('a.txt', 'a')");
+        let output = temp.path().join("out");
+        std::fs::create_dir_all(&output).unwrap();
+        std::fs::write(output.join("previous.txt"), "kept").unwrap();
+
+        let mut options = InstallOptions::new(temp.path().join("game").to_string_lossy());
+        options.mods_path = Some(mods_dir);
+        options.output_path = Some(output.to_string_lossy().into_owned());
+
+        let err = install(options).await.unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("--allow-no-casc"),
+            "{:#}",
+            err
+        );
+        // The previous build is left alone
+        assert!(output.join("previous.txt").exists());
+    }
+
+    #[test]
+    fn test_check_modinfo() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("Mods/Infinite/Infinite.mpq/data");
+        let path = write_modinfo(&output).unwrap().unwrap();
+        assert_eq!(check_modinfo(&path), Vec::<String>::new());
+
+        // A custom output path the game never looks at
+        let path = write_modinfo(&temp.path().join("out/data"))
+            .unwrap()
+            .unwrap();
+        let problems = check_modinfo(&path);
+        assert_eq!(problems.len(), 1);
+        assert!(
+            problems[0].contains("mods/Infinite/Infinite.mpq"),
+            "{}",
+            problems[0]
+        );
+
+        std::fs::write(&path, r#"{ "savepath": "C:\\Saves" }"#).unwrap();
+        let problems = check_modinfo(&path);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].contains("\"name\""));
+        assert!(problems[1].contains("end with '/'"));
+    }
+
+    #[test]
+    fn test_check_free_space() {
+        let temp = TempDir::new().unwrap();
+        // Missing directories are checked on their nearest existing ancestor
+        let output = temp.path().join("not/created/yet");
+        assert!(check_free_space(&output, 0).is_ok());
+        let err = check_free_space(&output, u64::MAX).unwrap_err();
+        assert!(err.to_string().contains("--ignore-disk-space"), "{}", err);
+    }
+
+    #[test]
+    fn test_read_source_configs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("source_config.json");
+        std::fs::write(&path, r#"{ "github:o/r": { "size": 5 } }"#).unwrap();
+        let configs = read_source_configs(&path).unwrap();
+        assert_eq!(configs["github:o/r"]["size"], serde_json::json!(5));
+        assert!(read_source_configs(&temp.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn test_source_config_key() {
+        assert_eq!(
+            source_config_key("o/r", None, Some("main"), None),
+            "github:o/r"
+        );
+        assert_eq!(
+            source_config_key("o/r", Some("mods/x"), Some("dev"), None),
+            "github:o/r:mods/x@dev"
+        );
+        assert_eq!(
+            source_config_key("o/r", None, Some("dev"), Some("abc")),
+            "github:o/r@sha:abc"
+        );
+    }
+}
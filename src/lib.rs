@@ -1,15 +1,86 @@
 pub mod casc;
+pub mod changelog;
 pub mod cli;
+pub mod content_store;
 pub mod file_system;
 pub mod github_downloader;
 pub mod handlers;
+pub mod install;
+pub mod mod_cache;
 pub mod mod_manager;
 pub mod mod_sources;
+pub mod report;
 pub mod runtime;
+pub mod string_ids;
 
 pub use casc::{CascStorage, CascError};
+pub use content_store::ContentStore;
 pub use file_system::FileManager;
 pub use github_downloader::GitHubDownloader;
+pub use install::{install, InstallEvent, InstallOptions, InstallOutcome, ModOutcome, ModStatus};
 pub use mod_manager::{LoadedMod, ModConfig, ModLoader};
-pub use mod_sources::{ModList, ModSource};
+pub use mod_sources::{ModList, ModListEntry, ModSource};
+pub use report::BuildReport;
 pub use runtime::{Context, ModExecutor};
+pub use string_ids::{StringIdAllocator, StringIdManifest};
+
+/// Default output directory for a game install: `<game_path>/Mods/Infinite/Infinite.mpq/data`
+///
+/// Shared by the CLI and GUI so both always agree on where generated files go.
+pub fn default_output_path(game_path: &str) -> String {
+    format!("{}/Infinite.mpq/data", default_mod_dir(game_path))
+}
+
+/// Folder holding everything `install` generates for a game install: `<game_path>/Mods/Infinite`
+pub fn default_mod_dir(game_path: &str) -> String {
+    format!("{}/Mods/Infinite", game_path)
+}
+
+/// Executable name of the game process
+pub const GAME_PROCESS_NAME: &str = "D2R.exe";
+
+/// Whether the game is currently running
+///
+/// Writing into `Mods/Infinite` while D2R has the files open can fail silently or leave
+/// partial files, so installs check this before clearing the output directory.
+/// Always `false` off Windows, or when the process list can't be read.
+pub fn is_game_running() -> bool {
+    #[cfg(windows)]
+    {
+        let filter = format!("IMAGENAME eq {}", GAME_PROCESS_NAME);
+        std::process::Command::new("tasklist")
+            .args(["/FI", &filter, "/FO", "CSV", "/NH"])
+            .output()
+            .map(|output| tasklist_contains(&String::from_utf8_lossy(&output.stdout), GAME_PROCESS_NAME))
+            .unwrap_or(false)
+    }
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+/// Check `tasklist /FO CSV /NH` output for a process image name
+#[cfg_attr(not(windows), allow(dead_code))]
+fn tasklist_contains(output: &str, image_name: &str) -> bool {
+    output.lines().any(|line| {
+        line.split(',')
+            .next()
+            .map(|name| name.trim().trim_matches('"'))
+            .is_some_and(|name| name.eq_ignore_ascii_case(image_name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tasklist_contains() {
+        let running = "\"D2R.exe\",\"12345\",\"Console\",\"1\",\"2,048,000 K\"\r\n";
+        assert!(tasklist_contains(running, GAME_PROCESS_NAME));
+
+        let not_running = "INFO: No tasks are running which match the specified criteria.\r\n";
+        assert!(!tasklist_contains(not_running, GAME_PROCESS_NAME));
+    }
+}
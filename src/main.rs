@@ -1,42 +1,96 @@
 use anyhow::{Result, Context as AnyhowContext};
 use clap::Parser;
 use colored::Colorize;
+use infinite::cli::commands::LogFormat;
 use infinite::cli::Cli;
-use infinite::casc::CascStorage;
-use infinite::file_system::FileManager;
-use infinite::github_downloader::GitHubDownloader;
+use infinite::casc::{CascStorage, PathAliases};
+use infinite::file_system::{FileManager, MemoryStore};
+use infinite::github_downloader::GITHUB_TOKEN_ENV;
+use infinite::install::{
+    read_source_configs, InstallEvent, InstallOptions, InstallOutcome, ModOutcome, SourceConfigs,
+    SOURCE_CONFIG_ENV,
+};
 use infinite::mod_manager::ModLoader;
-use infinite::mod_sources::{ModList, ModSource};
-use infinite::runtime::{Context, ModExecutor};
+use infinite::mod_sources::ModSource;
+use infinite::report::BuildReport;
+use infinite::string_ids::{StringIdAllocator, StringIdManifest};
+use infinite::runtime::{Context, ModExecutor, SharedProgress};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
 use tokio::sync::RwLock;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::EnvFilter;
 
-/// 获取 mod 缓存目录路径
-fn get_cache_dir() -> PathBuf {
-    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    path.push("infinite");
-    path.push("mod_cache");
-    path
+/// Set by `install --summary-only`: progress output is suppressed and only the final summary prints
+static SUMMARY_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for install progress, silenced by `--summary-only`
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if !SUMMARY_ONLY.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 读取启动方传递的配置映射 (github:... -> 用户配置)
+///
+/// 来自 `--source-config`,否则来自 INFINITE_SOURCE_CONFIG 环境变量;都没有时为空
+fn load_source_configs(path: Option<PathBuf>) -> Result<SourceConfigs> {
+    let Some(path) = path.or_else(|| std::env::var_os(SOURCE_CONFIG_ENV).map(PathBuf::from)) else {
+        return Ok(SourceConfigs::new());
+    };
+    let map = read_source_configs(&path)?;
+    tracing::info!("Loaded source config mapping with {} mod(s) from {}", map.len(), path.display());
+    Ok(map)
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    // Configure colored output for every `.bright_*()` call below
+    let use_color = cli.color.enabled();
+    colored::control::set_override(use_color);
+
+    let summary_only = matches!(
+        cli.command,
+        infinite::cli::commands::Commands::Install { summary_only: true, .. }
+    );
+    SUMMARY_ONLY.store(summary_only, Ordering::Relaxed);
+
     // Setup logging
     let filter = if cli.verbose {
         EnvFilter::new("debug")
+    } else if summary_only {
+        EnvFilter::new("warn")
     } else {
         EnvFilter::new("info")
     };
 
-    tracing_subscriber::fmt()
+    // `inspect` and `list-files` print data on stdout, so logs go to stderr there; JSON logs
+    // always do, keeping them apart from the human-readable progress
+    let json_logs = cli.log_format == LogFormat::Json;
+    let data_on_stdout = matches!(
+        cli.command,
+        infinite::cli::commands::Commands::Inspect { .. } | infinite::cli::commands::Commands::ListFiles { .. }
+    );
+    let writer = if json_logs || data_on_stdout {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+
+    let subscriber = tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(false)
-        .init();
+        .with_writer(writer);
+    if json_logs {
+        subscriber.json().with_current_span(true).with_span_list(true).init();
+    } else {
+        subscriber.with_ansi(use_color).init();
+    }
 
     // Execute command
     match cli.command {
@@ -47,319 +101,628 @@ async fn main() -> Result<()> {
             output_path,
             dry_run,
             clear_cache,
+            force,
+            sandbox,
+            features,
+            source_config,
+            prune_unchanged,
+            incremental_tsv,
+            casc_roots,
+            casc_retries,
+            path_aliases,
+            allow_no_casc,
+            dedup,
+            warn_empty_mods,
+            fail_empty_mods,
+            fail_on_conflict,
+            script_timeout,
+            cache_budget,
+            ignore_disk_space,
+            changelog,
+            backup,
+            summary_only: _,
+            interactive,
         } => {
-            // Use default output path if not specified
-            let output = output_path.unwrap_or_else(|| {
-                format!("{}/Mods/Infinite/Infinite.mpq/data", game_path)
-            });
-            install_mods(&game_path, mods_path.as_deref(), mod_list.as_deref(), &output, dry_run, clear_cache).await?;
+            use std::io::IsTerminal;
+            let interactive = interactive || std::io::stdin().is_terminal();
+            let game_path = resolve_game_path(game_path, &casc_roots, allow_no_casc, interactive)?;
+            let (mods_path, mod_list) = resolve_mod_source(mods_path, mod_list, interactive)?;
+
+            let mut options = InstallOptions::new(game_path);
+            options.mods_path = mods_path.map(PathBuf::from);
+            options.mod_list = mod_list.map(PathBuf::from);
+            options.output_path = output_path;
+            options.dry_run = dry_run;
+            options.clear_cache = clear_cache;
+            options.force = force;
+            options.sandbox = sandbox;
+            options.features = features.into_iter().collect();
+            options.prune_unchanged = prune_unchanged;
+            options.incremental_tsv = incremental_tsv;
+            options.casc_roots = casc_roots;
+            options.casc_retry.retries = casc_retries;
+            options.path_aliases = path_alias_table(path_aliases);
+            options.allow_no_casc = allow_no_casc;
+            options.dedup = dedup;
+            options.warn_empty_mods = warn_empty_mods || fail_empty_mods;
+            options.ignore_disk_space = ignore_disk_space;
+            options.changelog = changelog;
+            options.backup = backup;
+            options.cache_budget = cache_budget.map(|mb| mb.saturating_mul(1024 * 1024));
+            options.script_limits.timeout =
+                (script_timeout > 0).then(|| std::time::Duration::from_secs(script_timeout));
+            options.source_configs = load_source_configs(source_config)?;
+            if let Some(cache_dir) = cli.cache_dir {
+                options.download_cache_dir = cache_dir;
+            }
+            options.github_token = std::env::var(GITHUB_TOKEN_ENV).ok();
+            install_mods(options, fail_empty_mods, fail_on_conflict).await?;
+        }
+        infinite::cli::commands::Commands::Inspect {
+            game_path,
+            mods_path,
+            mod_list,
+            file,
+            out,
+            features,
+            source_config,
+            casc_roots,
+            path_aliases,
+            allow_no_casc,
+        } => {
+            let mut options = InstallOptions::new(game_path);
+            options.path_aliases = path_alias_table(path_aliases);
+            options.mods_path = mods_path.map(PathBuf::from);
+            options.mod_list = mod_list.map(PathBuf::from);
+            options.features = features.into_iter().collect();
+            options.casc_roots = casc_roots;
+            options.allow_no_casc = allow_no_casc;
+            options.source_configs = load_source_configs(source_config)?;
+            if let Some(cache_dir) = cli.cache_dir {
+                options.download_cache_dir = cache_dir;
+            }
+            options.github_token = std::env::var(GITHUB_TOKEN_ENV).ok();
+            inspect_file(options, &file, out.as_deref()).await?;
+        }
+        infinite::cli::commands::Commands::ListFiles { game_path, pattern, casc_roots } => {
+            list_casc_files(&game_path, pattern.as_deref(), &casc_roots)?;
+        }
+        infinite::cli::commands::Commands::Extract { game_path, output, files, pattern, casc_roots } => {
+            extract_game_files(&game_path, &output, files, pattern.as_deref(), &casc_roots)?;
         }
         infinite::cli::commands::Commands::List { mods_path } => {
             list_mods(&mods_path).await?;
         }
-        infinite::cli::commands::Commands::Validate { mod_path } => {
-            validate_mod(&mod_path).await?;
+        infinite::cli::commands::Commands::Validate { mod_path, strict } => {
+            validate_mod(&mod_path, strict).await?;
+        }
+        infinite::cli::commands::Commands::Verify { output_path } => {
+            verify_build(&output_path).await?;
+        }
+        infinite::cli::commands::Commands::Repair { game_path, output_path, casc_roots, allow_no_casc } => {
+            let output = output_path.unwrap_or_else(|| infinite::default_output_path(&game_path));
+            repair_build(&game_path, &output, &casc_roots, allow_no_casc).await?;
+        }
+        infinite::cli::commands::Commands::Restore { game_path } => {
+            restore_game(&game_path)?;
+        }
+        infinite::cli::commands::Commands::StringIds { output_path, compact, reset } => {
+            manage_string_ids(&output_path, compact, reset)?;
+        }
+        infinite::cli::commands::Commands::CleanCache { older_than, dry_run } => {
+            let cache_dir = cli.cache_dir.unwrap_or_else(infinite::install::default_download_cache_dir);
+            clean_cache(&cache_dir, older_than, dry_run)?;
+        }
+        infinite::cli::commands::Commands::Update { dry_run } => {
+            let cache_dir = cli.cache_dir.unwrap_or_else(infinite::install::default_download_cache_dir);
+            update_cache(cache_dir, dry_run).await?;
+        }
+        infinite::cli::commands::Commands::PrintOutputPath { game_path } => {
+            println!("{}", infinite::default_output_path(&game_path));
+        }
+        infinite::cli::commands::Commands::PrintConfigSchema { mod_path } => {
+            print_config_schema(&mod_path)?;
         }
     }
 
     Ok(())
 }
 
-async fn install_mods(
-    game_path: &str,
-    mods_path: Option<&str>,
-    mod_list: Option<&str>,
-    output_path: &str,
-    dry_run: bool,
-    clear_cache: bool,
-) -> Result<()> {
-    println!("\n{}", "🎮 infinite CLI - Installing Mods".bright_cyan().bold());
-    println!("{}", "═".repeat(50).bright_black());
-    println!("  {}  {}", "Game:".bright_white(), game_path);
-
-    // 尝试读取 GUI 传递的配置映射
-    let temp_config_path = std::env::temp_dir().join("infinite_gui_config.json");
-    let gui_config_map: std::collections::HashMap<String, std::collections::HashMap<String, serde_json::Value>> =
-        if temp_config_path.exists() {
-            match std::fs::read_to_string(&temp_config_path) {
-                Ok(content) => {
-                    match serde_json::from_str(&content) {
-                        Ok(map) => {
-                            tracing::info!("Loaded GUI config mapping with {} mod(s)",
-                                std::collections::HashMap::<String, std::collections::HashMap<String, serde_json::Value>>::len(&map));
-                            map
-                        }
-                        Err(e) => {
-                            tracing::warn!("Failed to parse GUI config: {}", e);
-                            std::collections::HashMap::new()
-                        }
-                    }
-                }
-                Err(_) => std::collections::HashMap::new()
-            }
-        } else {
-            std::collections::HashMap::new()
-        };
+/// The built-in path aliases plus `--path-alias` rules, which take precedence in the given order
+fn path_alias_table(rules: Vec<(String, String)>) -> PathAliases {
+    let mut aliases = PathAliases::default();
+    for (from, to) in rules.iter().rev() {
+        aliases.add(from, to);
+    }
+    aliases
+}
 
-    // Determine mod sources
-    let mod_dirs: Vec<PathBuf> = if let Some(list_path) = mod_list {
-        println!("  {}  {}", "Mod List:".bright_white(), list_path);
+/// Read one trimmed line from stdin after printing `question`
+fn prompt(question: &str) -> Result<String> {
+    use std::io::Write;
+    print!("{} ", question.bright_cyan());
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line)? == 0 {
+        anyhow::bail!("No input (stdin closed)");
+    }
+    Ok(line.trim().trim_matches('"').to_string())
+}
 
-        // Setup GitHub downloader with user data directory
-        let cache_dir = get_cache_dir();
-        let downloader = GitHubDownloader::new(cache_dir);
+/// The game path to install into, prompting until CASC storage opens when interactive
+///
+/// Without `interactive`, a missing path is an error and an invalid one is left for the
+/// install to report.
+fn resolve_game_path(
+    game_path: Option<String>,
+    casc_roots: &[PathBuf],
+    allow_no_casc: bool,
+    interactive: bool,
+) -> Result<String> {
+    if !interactive {
+        return game_path.context("--game-path is required (or pass --interactive to be prompted)");
+    }
 
-        if clear_cache {
-            println!("  {} Clearing download cache...", "🗑️".bright_yellow());
-            downloader.clear_cache().await?;
+    let mut candidate = game_path;
+    loop {
+        let path = match candidate.take() {
+            Some(path) => path,
+            None => prompt("Game directory (Diablo II Resurrected):")?,
+        };
+        if path.is_empty() {
+            anyhow::bail!("No game path given");
+        }
+        if allow_no_casc {
+            return Ok(path);
+        }
+        match CascStorage::open_with_roots(&path, casc_roots) {
+            Ok(_) => return Ok(path),
+            Err(e) => println!("{} {:#}", "❌".bright_red(), e),
         }
+    }
+}
 
-        // Load mod list
-        let mod_list = ModList::from_file(std::path::Path::new(list_path)).await?;
-        println!("  {} Loaded {} mod source(s)", "📝".bright_cyan(), mod_list.sources.len());
+/// `(mods_path, mod_list)`, asking which one to use when neither was given and `interactive`
+fn resolve_mod_source(
+    mods_path: Option<String>,
+    mod_list: Option<String>,
+    interactive: bool,
+) -> Result<(Option<String>, Option<String>)> {
+    if !interactive || mods_path.is_some() || mod_list.is_some() {
+        return Ok((mods_path, mod_list));
+    }
 
-        // Resolve all sources
-        let mut dirs = Vec::new();
-        for (idx, source) in mod_list.sources.iter().enumerate() {
-            println!("\n  {} [{}/{}] Processing source...", "⬇️".bright_blue(), idx + 1, mod_list.sources.len());
-            match source {
-                ModSource::Local { path } => {
-                    println!("    {} Local: {}", "📁".bright_green(), path.display());
-                    dirs.push(path.clone());
+    loop {
+        let choice = prompt("Install from [1] a mods directory or [2] a mod list file?")?;
+        match choice.as_str() {
+            "1" => {
+                let path = prompt("Mods directory:")?;
+                if std::path::Path::new(&path).is_dir() {
+                    return Ok((Some(path), None));
                 }
-                ModSource::GitHub { repo, subdir, branch } => {
-                    println!("    {} GitHub: {}", "🌐".bright_green(), repo);
-                    if let Some(subdir) = subdir {
-                        println!("      Subdirectory: {}", subdir);
-                    }
-                    if let Some(branch) = branch {
-                        println!("      Branch: {}", branch);
-                    }
+                println!("{} Not a directory: {}", "❌".bright_red(), path);
+            }
+            "2" => {
+                let path = prompt("Mod list file:")?;
+                if std::path::Path::new(&path).is_file() {
+                    return Ok((None, Some(path)));
+                }
+                println!("{} Not a file: {}", "❌".bright_red(), path);
+            }
+            "" => anyhow::bail!("No mod source given"),
+            _ => println!("Enter 1 or 2"),
+        }
+    }
+}
 
-                    let local_path = downloader
-                        .download(repo, subdir.as_deref(), branch.as_deref())
-                        .await?;
+async fn install_mods(options: InstallOptions, fail_empty_mods: bool, fail_on_conflict: bool) -> Result<()> {
+    let output_path = options.output_path();
 
-                    println!("    {} Downloaded to: {}", "✓".bright_green(), local_path.display());
+    progress!("\n{}", "🎮 infinite CLI - Installing Mods".bright_cyan().bold());
+    progress!("{}", "═".repeat(50).bright_black());
+    progress!("  {}  {}", "Game:".bright_white(), options.game_path);
+    if let Some(list_path) = &options.mod_list {
+        progress!("  {}  {}", "Mod List:".bright_white(), list_path.display());
+    } else if let Some(path) = &options.mods_path {
+        progress!("  {}  {}", "Mods:".bright_white(), path.display());
+    }
+    progress!("  {} {}", "Output:".bright_white(), output_path);
+    if options.dry_run {
+        progress!("  {}  {}", "Mode:".bright_white(), "DRY RUN".bright_yellow());
+    }
+    if options.sandbox {
+        progress!("  {}  {}", "Scripts:".bright_white(), "SANDBOXED".bright_yellow());
+    }
+    if !options.features.is_empty() {
+        let mut flags: Vec<String> = options.features.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        flags.sort();
+        progress!("  {} {}", "Features:".bright_white(), flags.join(", "));
+    }
+    progress!("{}\n", "═".repeat(50).bright_black());
 
-                    // 检查是否有 GUI 传递的配置需要应用
-                    // 构建 github: 格式的路径来匹配 GUI 配置
-                    let mut github_path = format!("github:{}", repo);
-                    if let Some(subdir) = subdir {
-                        github_path = format!("{}:{}", github_path, subdir);
-                    }
-                    if let Some(branch) = branch {
-                        if branch != "main" && branch != "master" {
-                            github_path = format!("{}@{}", github_path, branch);
-                        }
-                    }
+    // Show infinite.reportProgress updates while scripts run
+    let progress_ticker = tokio::spawn(print_script_progress(options.script_progress.clone()));
+    let outcome = infinite::install::install_with_events(options, print_install_event).await;
+    progress_ticker.abort();
+    let outcome = outcome?;
 
-                    // 如果有配置,立即写入到下载的 mod 目录
-                    if let Some(user_config) = gui_config_map.get(&github_path) {
-                        if !user_config.is_empty() {
-                            let config_file = local_path.join("config.json");
-                            match serde_json::to_string_pretty(user_config) {
-                                Ok(config_json) => {
-                                    match std::fs::write(&config_file, config_json) {
-                                        Ok(_) => {
-                                            tracing::info!("Applied GUI config to: {}", config_file.display());
-                                            println!("    {} Applied user configuration", "⚙️".bright_cyan());
-                                        }
-                                        Err(e) => {
-                                            tracing::warn!("Failed to write config for {}: {}", github_path, e);
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    tracing::warn!("Failed to serialize config for {}: {}", github_path, e);
-                                }
-                            }
-                        }
-                    }
+    if outcome.mods.is_empty() {
+        println!("{}", "⚠️  No mods found!".bright_yellow());
+        print_skipped_mods(&outcome);
+        return Ok(());
+    }
 
-                    dirs.push(local_path);
-                }
-            }
-        }
-        dirs
-    } else if let Some(path) = mods_path {
-        println!("  {}  {}", "Mods:".bright_white(), path);
-        vec![PathBuf::from(path)]
-    } else {
-        anyhow::bail!("Either --mods-path or --mod-list must be specified");
-    };
+    if let Some(pruned) = outcome.prune_stats {
+        println!(
+            "{} Pruned {} write(s) identical to vanilla and {} unmodified extracted file(s)",
+            "✂️".bright_cyan(),
+            pruned.elided_writes,
+            pruned.removed_extracted
+        );
+    }
 
-    println!("  {} {}", "Output:".bright_white(), output_path);
-    if dry_run {
-        println!("  {}  {}", "Mode:".bright_white(), "DRY RUN".bright_yellow());
+    // Print summary
+    println!("{}", "═".repeat(50).bright_black());
+    outcome.files.print();
+    println!("\n{}", "═".repeat(50).bright_black());
+    println!(
+        "{} All mods processed in {:.2}s",
+        "🎉".bright_green(),
+        outcome.elapsed.as_secs_f64()
+    );
+    let failed = outcome.failed().count();
+    if failed > 0 {
+        println!("{} {} of {} mod(s) failed", "❌".bright_red(), failed, outcome.mods.len());
     }
-    println!("{}\n", "═".repeat(50).bright_black());
 
-    let start_time = Instant::now();
-
-    // Load all mods from all directories
-    let mut all_mods = Vec::new();
-    for mod_dir in &mod_dirs {
-        // Check if this is a single mod or a mods directory
-        let config_path = mod_dir.join("mod.json");
-        if config_path.exists() {
-            // This is a single mod directory
-            let loader = ModLoader::new(mod_dir.parent().unwrap_or(&PathBuf::from(".")));
-            match loader.load_mod(mod_dir) {
-                Ok(mod_data) => all_mods.push(mod_data),
-                Err(e) => eprintln!("Warning: Failed to load mod at {:?}: {}", mod_dir, e),
-            }
-        } else {
-            // This is a directory containing multiple mods
-            let loader = ModLoader::new(mod_dir);
-            let mods = loader.load_all()?;
-            all_mods.extend(mods);
+    let needs_js_runtime: Vec<&ModOutcome> = outcome.needs_js_runtime().collect();
+    if !needs_js_runtime.is_empty() {
+        println!(
+            "\n{} {} mod(s) require the JavaScript runtime; rebuild with --features js-runtime",
+            "⚠️".bright_yellow(),
+            needs_js_runtime.len()
+        );
+        for m in &needs_js_runtime {
+            println!("   - {}", m.name);
         }
     }
 
-    if all_mods.is_empty() {
-        println!("{}", "⚠️  No mods found!".bright_yellow());
-        return Ok(());
+    print_skipped_mods(&outcome);
+    print_missing_game_files(&outcome);
+
+    if fail_empty_mods && !outcome.empty_mods.is_empty() {
+        anyhow::bail!(
+            "{} mod(s) read and wrote no files: {}",
+            outcome.empty_mods.len(),
+            outcome.empty_mods.join(", ")
+        );
+    }
+    if fail_on_conflict && !outcome.conflicts.is_empty() {
+        anyhow::bail!(
+            "{} file(s) were written by more than one mod: {}",
+            outcome.conflicts.len(),
+            outcome.conflicts.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
     }
 
-    println!("📦 Found {} mod(s)\n", all_mods.len());
+    Ok(())
+}
 
-    // Clear output directory if it exists
-    let output_path_buf = PathBuf::from(output_path);
-    if output_path_buf.exists() {
-        println!("  {} Clearing output directory...", "🗑️".bright_yellow());
-        std::fs::remove_dir_all(&output_path_buf)
-            .with_context(|| format!("Failed to clear output directory: {}", output_path))?;
-        println!("  {} Output directory cleared", "✅".bright_green());
+/// Print every mod that wasn't installed, grouped by reason with a count per reason
+fn print_skipped_mods(outcome: &InstallOutcome) {
+    if outcome.skipped.is_empty() {
+        return;
     }
 
-    // Create shared file manager
-    let mut file_manager = FileManager::new();
-    file_manager.set_output_path(output_path);
-    file_manager.set_game_path(game_path);
-
-    // Try to open CASC storage
-    match CascStorage::open(game_path) {
-        Ok(casc) => {
-            tracing::info!("CASC storage opened successfully");
-            file_manager.set_casc_storage(Arc::new(casc));
-        }
-        Err(e) => {
-            tracing::warn!("Failed to open CASC storage: {}. File extraction will be disabled.", e);
-            tracing::warn!("Make sure the game path is correct and the game is installed.");
+    println!("\n{} Mods not installed:", "📋".bright_yellow());
+    for (reason, count) in outcome.skip_counts() {
+        println!("   {} ({})", reason.label().bright_white(), count);
+        for skipped in outcome.skipped.iter().filter(|s| s.reason == reason) {
+            // Multi-line script errors would break up the table; the full text was printed above
+            match skipped.message.lines().next().filter(|line| !line.is_empty()) {
+                Some(line) => println!("     - {}: {}", skipped.mod_id, line.bright_black()),
+                None => println!("     - {}", skipped.mod_id),
+            }
         }
     }
+}
 
-    let file_manager = Arc::new(RwLock::new(file_manager));
+/// Print game files mods asked for that couldn't be extracted, grouped by mod
+fn print_missing_game_files(outcome: &InstallOutcome) {
+    if outcome.missing_game_files.is_empty() {
+        return;
+    }
 
-    // Install each mod
-    for (idx, mod_data) in all_mods.iter().enumerate() {
-        let mod_start = Instant::now();
+    println!(
+        "\n{} Game files not found in CASC (renamed or removed by a game patch?):",
+        "🔍".bright_yellow()
+    );
+    let mut mod_ids: Vec<&str> = outcome.missing_game_files.iter().map(|f| f.mod_id.as_str()).collect();
+    mod_ids.dedup();
+    for mod_id in mod_ids {
+        println!("   {}", mod_id.bright_white());
+        for failure in outcome.missing_game_files.iter().filter(|f| f.mod_id == mod_id) {
+            println!("     - {}", failure.file_path);
+        }
+    }
+}
 
-        println!(
-            "{} {}/{} - {} {}",
-            "⚙️".bright_blue(),
-            (idx + 1).to_string().bright_white(),
-            all_mods.len(),
-            mod_data.config.name.bright_green(),
-            format!("v{}", mod_data.config.version).bright_black()
-        );
+/// Run the mods into memory and print (or write to `out`) the final content of `file`
+///
+/// Status goes to stderr so stdout carries only the file content.
+async fn inspect_file(mut options: InstallOptions, file: &str, out: Option<&std::path::Path>) -> Result<()> {
+    let store = MemoryStore::new();
+    options.output_store = Some(store.clone());
+    let game_path = options.game_path.clone();
+    let casc_roots = options.casc_roots.clone();
+    let path_aliases = options.path_aliases.clone();
 
-        // Create execution context
-        let context = Arc::new(Context {
-            mod_id: mod_data.id.clone(),
-            mod_path: mod_data.path.clone(),
-            config: serde_json::to_value(&mod_data.user_config)?,
-            file_manager: file_manager.clone(),
-            game_path: game_path.into(),
-            output_path: output_path.into(),
-            dry_run,
-        });
+    let outcome = infinite::install(options).await?;
+    if outcome.mods.is_empty() {
+        eprintln!("{}", "⚠️  No mods found!".bright_yellow());
+    }
+    for warning in &outcome.warnings {
+        eprintln!("{} {}", "⚠️".bright_yellow(), warning);
+    }
+    for failed in outcome.failed() {
+        eprintln!("{} {} failed; its changes are not included", "❌".bright_red(), failed.name);
+    }
 
-        // Execute mod (static method now)
-        match ModExecutor::execute_mod(mod_data, context).await {
-            Ok(_) => {
-                let elapsed = mod_start.elapsed();
-                println!(
-                    "   {} Installed in {:.2}s\n",
-                    "✅".bright_green(),
-                    elapsed.as_secs_f64()
-                );
+    let content = match store.get(file) {
+        Some(content) => content,
+        None => {
+            eprintln!("{} No mod read or wrote {}; showing the vanilla file", "ℹ️".bright_cyan(), file);
+            let mut fm = FileManager::new();
+            fm.set_output_store(MemoryStore::new());
+            fm.set_game_path(&game_path);
+            if let Ok(casc) = CascStorage::open_with_roots(&game_path, &casc_roots) {
+                fm.set_casc_storage(Arc::new(casc.with_path_aliases(path_aliases)));
             }
-            Err(e) => {
-                eprintln!(
-                    "   {} Failed: {}\n",
-                    "❌".bright_red(),
-                    e.to_string().bright_red()
-                );
-                // Continue with next mod
+            fm.ensure_extracted(file, "inspect").await?
+        }
+    };
+
+    match out {
+        Some(path) => {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
             }
+            std::fs::write(path, &content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("{} Wrote {} ({} bytes)", "✓".bright_green(), path.display(), content.len());
+        }
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(&content)?;
         }
     }
 
-    let total_elapsed = start_time.elapsed();
+    Ok(())
+}
 
-    // Flush all cached file modifications to disk
-    println!("\n{}", "💾 Flushing cached modifications...".bright_cyan());
-    {
-        let mut fm = file_manager.write().await;
-        if let Err(e) = fm.flush_cache().await {
-            eprintln!(
-                "{} Failed to flush cache: {}",
+/// Render install progress the way the CLI always has
+fn print_install_event(event: InstallEvent) {
+    match event {
+        InstallEvent::ClearingDownloadCache => {
+            progress!("  {} Clearing download cache...", "🗑️".bright_yellow());
+        }
+        InstallEvent::SourcesLoaded(count) => {
+            progress!("  {} Loaded {} mod source(s)", "📝".bright_cyan(), count);
+        }
+        InstallEvent::ResolvingSource { index, total, source } => {
+            progress!("\n  {} [{}/{}] Processing source...", "⬇️".bright_blue(), index, total);
+            match source {
+                ModSource::Local { path } => {
+                    progress!("    {} Local: {}", "📁".bright_green(), path.display());
+                }
+                ModSource::GitHub { repo, subdir, branch, commit } => {
+                    progress!("    {} GitHub: {}", "🌐".bright_green(), repo);
+                    if let Some(subdir) = subdir {
+                        progress!("      Subdirectory: {}", subdir);
+                    }
+                    if let Some(commit) = commit {
+                        progress!("      Commit: {}", commit);
+                    } else if let Some(branch) = branch {
+                        progress!("      Branch: {}", branch);
+                    }
+                }
+            }
+        }
+        InstallEvent::SourceDownloaded(path) => {
+            progress!("    {} Downloaded to: {}", "✓".bright_green(), path.display());
+        }
+        InstallEvent::SourceConfigApplied(_) => {
+            progress!("    {} Applied user configuration", "⚙️".bright_cyan());
+        }
+        InstallEvent::Deduplicated(stats) => {
+            progress!(
+                "\n  {} Deduplicated {} of {} downloaded file(s), saved {:.1} KB",
+                "♻️".bright_green(),
+                stats.linked,
+                stats.files,
+                stats.bytes_saved as f64 / 1024.0
+            );
+        }
+        InstallEvent::ModsFound(count) => {
+            progress!("📦 Found {} mod(s)\n", count);
+        }
+        InstallEvent::GameRunning => {
+            println!(
+                "  {} {} is running, continuing because of --force",
                 "⚠️".bright_yellow(),
-                e.to_string().bright_red()
+                infinite::GAME_PROCESS_NAME
+            );
+        }
+        InstallEvent::ClearingOutput => {
+            progress!("  {} Clearing output directory...", "🗑️".bright_yellow());
+        }
+        InstallEvent::OutputCleared => {
+            progress!("  {} Output directory cleared", "✅".bright_green());
+        }
+        InstallEvent::CascOpened(root) => {
+            progress!("  {} CASC storage: {}", "📂".bright_cyan(), root.display());
+        }
+        InstallEvent::ModStarted { index, total, name, version } => {
+            progress!(
+                "{} {}/{} - {} {}",
+                "⚙️".bright_blue(),
+                index.to_string().bright_white(),
+                total,
+                name.bright_green(),
+                format!("v{}", version).bright_black()
+            );
+        }
+        InstallEvent::ModSkipped { .. } => {
+            progress!(
+                "   {} Disabled by its configuration, skipped\n",
+                "⏭️".bright_yellow()
+            );
+        }
+        InstallEvent::ModInstalled { elapsed, .. } => {
+            progress!(
+                "   {} Installed in {:.2}s\n",
+                "✅".bright_green(),
+                elapsed.as_secs_f64()
             );
-        } else {
-            println!("{} All modifications written to disk", "✅".bright_green());
+        }
+        InstallEvent::ModFailed { name, error } => {
+            // Without the per-mod header the failure line has to name the mod itself
+            if SUMMARY_ONLY.load(Ordering::Relaxed) {
+                eprintln!("{} {} failed: {}", "❌".bright_red(), name, error.bright_red());
+            } else {
+                eprintln!("   {} Failed: {}\n", "❌".bright_red(), error.bright_red());
+            }
+        }
+        InstallEvent::Flushing => {
+            progress!("\n{}", "💾 Flushing cached modifications...".bright_cyan());
+        }
+        InstallEvent::Flushed => {
+            progress!("{} All modifications written to disk", "✅".bright_green());
+        }
+        InstallEvent::ExtractsDeduplicated(stats) => {
+            progress!(
+                "{} Shared {} of {} extracted game file(s) with earlier builds, saved {:.1} KB",
+                "♻️".bright_green(),
+                stats.linked,
+                stats.files,
+                stats.bytes_saved as f64 / 1024.0
+            );
+        }
+        InstallEvent::ModinfoWritten(path) => {
+            progress!("{} Generated modinfo.json at: {}", "✅".bright_green(), path.display());
+        }
+        InstallEvent::ReportWritten(path) => {
+            progress!("{} Wrote build report: {}", "✅".bright_green(), path.display());
+        }
+        InstallEvent::ChangelogWritten(path) => {
+            progress!("{} Wrote changelog: {}", "✅".bright_green(), path.display());
+        }
+        InstallEvent::Warning(message) => {
+            eprintln!("{} {}", "⚠️".bright_yellow(), message.bright_red());
         }
     }
+}
 
-    // Generate modinfo.json in parent directory of output_path
-    if !dry_run {
-        if let Some(parent_dir) = std::path::Path::new(output_path).parent() {
-            let modinfo_path = parent_dir.join("modinfo.json");
-            let modinfo_content = serde_json::json!({
-                "name": "Infinite",
-                "savepath": "Infinite/"
-            });
+/// Print script-reported progress, at most once per second and only when it changed
+async fn print_script_progress(progress: SharedProgress) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+    let mut last = None;
+    loop {
+        interval.tick().await;
+        let current = progress.lock().unwrap().clone();
+        if let Some(p) = current.as_ref().filter(|_| current != last) {
+            let label = if p.label.is_empty() { "Working" } else { &p.label };
+            progress!(
+                "   {} {} {}/{} ({:.0}%)",
+                "⏳".bright_blue(),
+                label,
+                p.current,
+                p.total,
+                p.fraction() * 100.0
+            );
+        }
+        last = current;
+    }
+}
 
-            match std::fs::create_dir_all(parent_dir) {
-                Ok(_) => {
-                    match std::fs::write(&modinfo_path, serde_json::to_string_pretty(&modinfo_content)?) {
-                        Ok(_) => {
-                            println!("{} Generated modinfo.json at: {}", "✅".bright_green(), modinfo_path.display());
-                        }
-                        Err(e) => {
-                            eprintln!(
-                                "{} Failed to write modinfo.json: {}",
-                                "⚠️".bright_yellow(),
-                                e.to_string().bright_red()
-                            );
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!(
-                        "{} Failed to create directory for modinfo.json: {}",
-                        "⚠️".bright_yellow(),
-                        e.to_string().bright_red()
-                    );
-                }
+/// Print the game files in CASC storage, one script path per line
+fn list_casc_files(game_path: &str, pattern: Option<&str>, casc_roots: &[PathBuf]) -> Result<()> {
+    let casc = CascStorage::open_with_retry(game_path, casc_roots, Default::default())
+        .context("Failed to open CASC storage. Check the game path (and --casc-root)")?;
+    let files = casc.list_files(pattern)?;
+    if files.is_empty() {
+        eprintln!("{} No files found", "⚠️".bright_yellow());
+    }
+
+    use std::io::Write;
+    let mut stdout = std::io::stdout().lock();
+    for file in files {
+        // Stop quietly when piped into `head` and the like
+        if writeln!(stdout, "{}", file).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Extract `files` plus everything matching `pattern` from CASC storage into `output`
+fn extract_game_files(
+    game_path: &str,
+    output: &std::path::Path,
+    mut files: Vec<String>,
+    pattern: Option<&str>,
+    casc_roots: &[PathBuf],
+) -> Result<()> {
+    // Extracted vanilla files inside Mods/ would be loaded by the game as a mod
+    let mods_dir = std::path::absolute(std::path::Path::new(game_path).join("Mods"))?;
+    if std::path::absolute(output)?.starts_with(&mods_dir) {
+        anyhow::bail!(
+            "Refusing to extract into {}: it is inside the game's Mods folder",
+            output.display()
+        );
+    }
+
+    let casc = CascStorage::open_with_retry(game_path, casc_roots, Default::default())
+        .context("Failed to open CASC storage. Check the game path (and --casc-root)")?;
+    if let Some(pattern) = pattern {
+        let matched = casc.list_files(Some(pattern))?;
+        if matched.is_empty() {
+            eprintln!("{} No game files match '{}'", "⚠️".bright_yellow(), pattern);
+        }
+        files.extend(matched);
+    }
+
+    println!("\n{}", "📤 Extracting game files".bright_cyan().bold());
+    let mut failed = 0;
+    for file in &files {
+        let relative = file.replace('\\', "/");
+        let relative = relative.trim_start_matches('/');
+        if std::path::Path::new(relative)
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            eprintln!("  {} {}: not a relative game file path", "❌".bright_red(), file);
+            failed += 1;
+            continue;
+        }
+        match casc.extract_file(relative, output.join(relative)) {
+            Ok(size) => println!("  {} {} ({} bytes)", "✅".bright_green(), relative, size),
+            Err(e) => {
+                eprintln!("  {} {}: {}", "❌".bright_red(), relative, e.to_string().bright_red());
+                failed += 1;
             }
         }
     }
 
-    // Print summary
-    println!("{}", "═".repeat(50).bright_black());
-    let fm = file_manager.read().await;
-    fm.print_summary();
-    println!("\n{}", "═".repeat(50).bright_black());
     println!(
-        "{} All mods processed in {:.2}s",
-        "🎉".bright_green(),
-        total_elapsed.as_secs_f64()
+        "\n{} {} file(s) extracted to {}",
+        "📁".bright_cyan(),
+        files.len() - failed,
+        output.display()
     );
-
+    if failed > 0 {
+        anyhow::bail!("{} of {} file(s) could not be extracted", failed, files.len());
+    }
     Ok(())
 }
 
@@ -408,14 +771,23 @@ async fn list_mods(mods_path: &str) -> Result<()> {
     Ok(())
 }
 
-async fn validate_mod(mod_path: &str) -> Result<()> {
+async fn validate_mod(mod_path: &str, strict: bool) -> Result<()> {
     println!("\n{}", "🔍 Validating Mod".bright_cyan().bold());
     println!("{}\n", "═".repeat(50).bright_black());
 
     let loader = ModLoader::new(mod_path);
     let mod_data = loader.load_mod(std::path::Path::new(mod_path))?;
+    if strict {
+        mod_data.config.validate_strict().context("Invalid mod.json")?;
+    }
+    infinite::runtime::RuntimeFactory::check_syntax(&mod_data.script_path, mod_data.script_type)?;
 
     println!("{} Mod configuration is valid!", "✅".bright_green());
+    println!(
+        "{} {} compiles",
+        "✅".bright_green(),
+        mod_data.script_path.file_name().unwrap_or_default().to_string_lossy()
+    );
     println!();
     println!("  {}  {}", "Name:".bright_white(), mod_data.config.name);
     println!(
@@ -442,3 +814,364 @@ async fn validate_mod(mod_path: &str) -> Result<()> {
     println!();
     Ok(())
 }
+
+/// Print the `config` array of one local mod's mod.json as JSON
+fn print_config_schema(mod_path: &str) -> Result<()> {
+    let is_github = matches!(ModSource::parse(mod_path), Ok(ModSource::GitHub { .. }))
+        || mod_path.contains("github.com/");
+    if is_github {
+        anyhow::bail!(
+            "{} is a GitHub source; download it first (e.g. run `install` with it in a mod list) \
+             and pass the local mod directory",
+            mod_path
+        );
+    }
+
+    let config = infinite::mod_manager::read_mod_config(std::path::Path::new(mod_path))?;
+    println!("{}", serde_json::to_string_pretty(&config.config)?);
+    Ok(())
+}
+
+async fn repair_build(game_path: &str, output_path: &str, casc_roots: &[PathBuf], allow_no_casc: bool) -> Result<()> {
+    println!("\n{}", "🔧 Repairing Build".bright_cyan().bold());
+    println!("{}\n", "═".repeat(50).bright_black());
+
+    let output = PathBuf::from(output_path);
+    let mut report = BuildReport::load(&output)?;
+    let plan = report.repair_plan(&report.verify(&output)?);
+
+    if plan.files.is_empty() {
+        println!("{} Nothing to repair, output matches the build report", "✅".bright_green());
+        return Ok(());
+    }
+    for file in &plan.files {
+        println!("  {} {}", "🩹".bright_yellow(), file);
+    }
+    if !plan.mods.is_empty() && report.mod_paths.is_empty() {
+        anyhow::bail!("The build report does not record mod locations; run a full install instead");
+    }
+    println!(
+        "\n  {} file(s) to restore, re-running {} mod(s)\n",
+        plan.files.len(),
+        plan.mods.len()
+    );
+
+    // Without game files the re-run mods would write incomplete files over intact ones
+    let casc = match CascStorage::open_with_retry(game_path, casc_roots, Default::default()) {
+        Ok(casc) => {
+            println!("  {} CASC storage: {}\n", "📂".bright_cyan(), casc.root().display());
+            Some(Arc::new(casc))
+        }
+        Err(e) if allow_no_casc => {
+            tracing::warn!("{:#}. File extraction is disabled; mods that read game files will fail", e);
+            None
+        }
+        Err(e) => {
+            return Err(e.context(
+                "Game files can't be extracted. Check the game path (and --casc-root), \
+                 or pass --allow-no-casc to repair without extraction",
+            ));
+        }
+    };
+
+    // Re-run into a scratch directory so output files that are still intact are never touched
+    let scratch = output.parent().unwrap_or(&output).join(".infinite-repair");
+    if scratch.exists() {
+        std::fs::remove_dir_all(&scratch)?;
+    }
+
+    let mut file_manager = FileManager::new();
+    file_manager.set_output_path(&scratch);
+    file_manager.set_game_path(game_path);
+    if let Some(casc) = casc {
+        file_manager.set_casc_storage(casc);
+    }
+    let file_manager = Arc::new(RwLock::new(file_manager));
+
+    let manifest = StringIdManifest::load(&output)?;
+    let string_ids = Arc::new(std::sync::Mutex::new(StringIdAllocator::new(manifest)));
+
+    for mod_id in &plan.mods {
+        let Some(mod_path) = report.mod_paths.get(mod_id) else {
+            eprintln!("  {} {}: not in the build report, skipped", "⚠️".bright_yellow(), mod_id);
+            continue;
+        };
+        let loader = ModLoader::new(mod_path.parent().unwrap_or(mod_path));
+        let mod_data = match loader.load_mod(mod_path) {
+            Ok(mod_data) => mod_data,
+            Err(e) => {
+                eprintln!("  {} {}: {}", "❌".bright_red(), mod_id, e.to_string().bright_red());
+                continue;
+            }
+        };
+        if !mod_data.is_enabled() {
+            continue;
+        }
+
+        println!("{} {}", "⚙️".bright_blue(), mod_data.config.name.bright_green());
+        let context = Arc::new(Context {
+            mod_id: mod_data.id.clone(),
+            mod_path: mod_data.path.clone(),
+            config: serde_json::to_value(&mod_data.user_config)?,
+            file_manager: file_manager.clone(),
+            game_path: game_path.into(),
+            output_path: scratch.clone(),
+            dry_run: false,
+            sandbox: false,
+            features: Default::default(),
+            string_ids: string_ids.clone(),
+            progress: Default::default(),
+            limits: Default::default(),
+        });
+        if let Err(e) = ModExecutor::execute_mod(&mod_data, context).await {
+            eprintln!("   {} Failed: {}", "❌".bright_red(), e.to_string().bright_red());
+        }
+    }
+
+    {
+        let mut fm = file_manager.write().await;
+        fm.flush_cache().await?;
+        for file in &plan.extract {
+            if let Err(e) = fm.ensure_extracted(file, "repair").await {
+                tracing::warn!("Failed to re-extract {}: {}", file, e);
+            }
+        }
+    }
+
+    // Copy back only the files that needed repair
+    let mut unrepaired = Vec::new();
+    for file in &plan.files {
+        let source = [scratch.join(file), scratch.join(FileManager::normalize_path(file))]
+            .into_iter()
+            .find(|path| path.is_file());
+        let Some(source) = source else {
+            unrepaired.push(file.clone());
+            continue;
+        };
+        let target = output.join(file);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        infinite::content_store::detach(&target)?;
+        std::fs::copy(&source, &target)
+            .with_context(|| format!("Failed to restore {}", target.display()))?;
+        report.files.insert(file.clone(), infinite::report::hash_file(&target)?);
+    }
+
+    std::fs::remove_dir_all(&scratch).ok();
+    report.save(&output)?;
+
+    println!();
+    if unrepaired.is_empty() {
+        println!("{} Restored {} file(s)", "✅".bright_green(), plan.files.len());
+        Ok(())
+    } else {
+        for file in &unrepaired {
+            println!("  {} not regenerated: {}", "❌".bright_red(), file);
+        }
+        anyhow::bail!("{} file(s) could not be repaired; run a full install", unrepaired.len());
+    }
+}
+
+fn restore_game(game_path: &str) -> Result<()> {
+    if infinite::is_game_running() {
+        anyhow::bail!("{} is running. Close the game before restoring", infinite::GAME_PROCESS_NAME);
+    }
+
+    let mod_dir = infinite::default_mod_dir(game_path);
+    if !std::path::Path::new(&mod_dir).exists() {
+        println!("Nothing to restore: {} does not exist", mod_dir);
+        return Ok(());
+    }
+
+    let summary = infinite::file_system::backup::restore(game_path)?;
+    println!(
+        "{} Removed {} generated file(s) from {}",
+        "✅".bright_green(),
+        summary.removed_files,
+        summary.mod_dir.display()
+    );
+    if summary.backed_up > 0 {
+        println!("   The game uses its own copy of the {} file(s) the mods changed", summary.backed_up);
+    }
+    Ok(())
+}
+
+fn manage_string_ids(output_path: &str, compact: bool, reset: bool) -> Result<()> {
+    let output = PathBuf::from(output_path);
+    let manifest_path = StringIdManifest::path_for(&output);
+
+    if reset {
+        if manifest_path.exists() {
+            std::fs::remove_file(&manifest_path).with_context(|| {
+                format!("Failed to delete string ID manifest: {}", manifest_path.display())
+            })?;
+        }
+        println!("{} String ID manifest reset", "✅".bright_green());
+        return Ok(());
+    }
+
+    let mut manifest = StringIdManifest::load(&output)?;
+    if compact {
+        let removed = manifest.compact();
+        manifest.save(&output)?;
+        println!(
+            "{} Removed {} unused string ID(s), {} kept",
+            "✅".bright_green(),
+            removed,
+            manifest.len()
+        );
+        return Ok(());
+    }
+
+    for (mod_id, names) in &manifest.mods {
+        println!("{}", mod_id.bright_green());
+        for (name, entry) in names {
+            let stale = if entry.last_build < manifest.build { " (unused in last build)" } else { "" };
+            println!("  {:>8}  {}{}", entry.id, name, stale.bright_black());
+        }
+    }
+    println!("\n{} string ID(s) in {}", manifest.len(), manifest_path.display());
+    Ok(())
+}
+
+fn clean_cache(cache_dir: &std::path::Path, older_than: Option<std::time::Duration>, dry_run: bool) -> Result<()> {
+    let report = infinite::mod_cache::clean(cache_dir, older_than, dry_run)?;
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+    println!(
+        "{} download(s), {:.1} MB in {}",
+        report.entries.len(),
+        mb(report.total_size()),
+        cache_dir.display()
+    );
+    let now = std::time::SystemTime::now();
+    for entry in &report.removed {
+        println!(
+            "  {} {} ({:.1} MB, {} day(s) old)",
+            if dry_run { "would remove" } else { "removed" }.bright_black(),
+            entry.name(cache_dir),
+            mb(entry.size),
+            entry.age(now).as_secs() / 86400
+        );
+    }
+
+    let action = if dry_run { "Would remove" } else { "Removed" };
+    println!(
+        "{} {} {} download(s), {:.1} MB",
+        "✅".bright_green(),
+        action,
+        report.removed.len(),
+        mb(report.removed_size())
+    );
+    if report.objects_removed > 0 {
+        println!(
+            "   plus {} unused deduplicated file(s), {:.1} MB",
+            report.objects_removed,
+            mb(report.object_bytes_removed)
+        );
+    }
+    Ok(())
+}
+
+async fn update_cache(cache_dir: PathBuf, dry_run: bool) -> Result<()> {
+    let downloader = infinite::GitHubDownloader::with_token(cache_dir.clone(), std::env::var(GITHUB_TOKEN_ENV).ok());
+    let downloads: Vec<_> = downloader
+        .cached_downloads()?
+        .into_iter()
+        .filter(|(_, info)| !info.pinned)
+        .collect();
+    println!("{} cached branch download(s) in {}", downloads.len(), cache_dir.display());
+
+    // One head lookup per branch, however many subdirectories of it are cached
+    let mut branches: Vec<(&str, &str)> = downloads
+        .iter()
+        .map(|(_, info)| (info.repo.as_str(), info.git_ref.as_str()))
+        .collect();
+    branches.sort_unstable();
+    branches.dedup();
+
+    let mut updated = 0;
+    let mut failed = 0;
+    for (repo, branch) in branches {
+        match downloader.is_stale(repo, branch).await {
+            Ok(false) => {
+                println!("  {} {}@{}", "up to date".bright_black(), repo, branch);
+                continue;
+            }
+            Ok(true) => {}
+            Err(e) => {
+                println!("  {} {}@{}: {:#}", "❌".bright_red(), repo, branch, e);
+                failed += 1;
+                continue;
+            }
+        }
+        for (dir, info) in downloads.iter().filter(|(_, i)| i.repo == repo && i.git_ref == branch) {
+            let name = match &info.subdir {
+                Some(subdir) => format!("{}@{}:{}", repo, branch, subdir),
+                None => format!("{}@{}", repo, branch),
+            };
+            if dry_run {
+                println!("  {} {}", "out of date".bright_yellow(), name);
+                updated += 1;
+                continue;
+            }
+            match downloader.redownload(dir, info).await {
+                Ok(_) => {
+                    println!("  {} {}", "updated".bright_green(), name);
+                    updated += 1;
+                }
+                Err(e) => {
+                    println!("  {} {}: {:#}", "❌".bright_red(), name, e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    let action = if dry_run { "out of date" } else { "updated" };
+    println!("{} {} download(s) {}", "✅".bright_green(), updated, action);
+    if failed > 0 {
+        anyhow::bail!("{} download(s) could not be checked or updated", failed);
+    }
+    Ok(())
+}
+
+async fn verify_build(output_path: &str) -> Result<()> {
+    println!("\n{}", "🔍 Verifying Build".bright_cyan().bold());
+    println!("{}\n", "═".repeat(50).bright_black());
+
+    let output = PathBuf::from(output_path);
+    let report = BuildReport::load(&output)?;
+    println!(
+        "  {} {} file(s) from {} mod(s)",
+        "Report:".bright_white(),
+        report.files.len(),
+        report.mods.len()
+    );
+
+    let result = report.verify(&output)?;
+
+    for file in &result.missing {
+        println!("  {} missing:  {}", "❌".bright_red(), file);
+    }
+    for file in &result.modified {
+        println!("  {} modified: {}", "⚠️".bright_yellow(), file);
+    }
+    for file in &result.extra {
+        println!("  {} extra:    {}", "➕".bright_blue(), file);
+    }
+
+    println!();
+    if result.is_clean() {
+        println!("{} Output matches the build report", "✅".bright_green());
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "Verification failed: {} missing, {} modified, {} extra",
+            result.missing.len(),
+            result.modified.len(),
+            result.extra.len()
+        );
+    }
+}
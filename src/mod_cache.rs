@@ -0,0 +1,257 @@
+//! Inspection and cleanup of the GitHub download cache
+//!
+//! The cache holds one directory per downloaded ref, `<cache>/<owner>/<repo>/<branch-or-sha>`,
+//! plus the deduplication store under `<cache>/.objects` (see [`crate::content_store`]).
+//! An entry's age is the time it was downloaded: cached refs are reused as they are, so an
+//! old entry may be behind its branch until `infinite update` downloads it again.
+
+use crate::content_store::ContentStore;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// One downloaded ref in the cache
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// `<cache>/<owner>/<repo>/<branch-or-sha>`
+    pub path: PathBuf,
+    /// Total size of its files (files shared with other entries count in each)
+    pub size: u64,
+    /// When it was downloaded
+    pub modified: SystemTime,
+}
+
+impl CacheEntry {
+    /// `owner/repo@ref`
+    pub fn name(&self, cache_dir: &Path) -> String {
+        let relative = self.path.strip_prefix(cache_dir).unwrap_or(&self.path);
+        let parts: Vec<_> = relative.iter().map(|p| p.to_string_lossy()).collect();
+        match parts.as_slice() {
+            [owner, repo, git_ref] => format!("{}/{}@{}", owner, repo, git_ref),
+            _ => relative.display().to_string(),
+        }
+    }
+
+    /// Time since the download (zero if the clock went backwards)
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.modified).unwrap_or_default()
+    }
+}
+
+/// Result of [`clean`]
+#[derive(Debug, Clone, Default)]
+pub struct CleanReport {
+    /// Every entry found, oldest first
+    pub entries: Vec<CacheEntry>,
+    /// Entries removed (or that would be, in a dry run)
+    pub removed: Vec<CacheEntry>,
+    /// Deduplicated objects no longer used by any entry and removed with them
+    pub objects_removed: u64,
+    /// Bytes freed by removing those objects
+    pub object_bytes_removed: u64,
+}
+
+impl CleanReport {
+    /// Size of all entries before cleaning
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+
+    /// Size of the removed entries
+    pub fn removed_size(&self) -> u64 {
+        self.removed.iter().map(|e| e.size).sum()
+    }
+}
+
+/// List the downloaded refs under `cache_dir`, oldest first
+///
+/// Hidden names (the `.objects` store, `.default-branch` files) are not entries.
+pub fn cache_entries(cache_dir: &Path) -> Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    for owner in visible_dirs(cache_dir)? {
+        for repo in visible_dirs(&owner)? {
+            for path in visible_dirs(&repo)? {
+                let modified = fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .with_context(|| format!("Failed to read cache entry: {}", path.display()))?;
+                let size = dir_size(&path)?;
+                entries.push(CacheEntry {
+                    path,
+                    size,
+                    modified,
+                });
+            }
+        }
+    }
+    entries.sort_by_key(|e| e.modified);
+    Ok(entries)
+}
+
+/// Remove cache entries downloaded more than `older_than` ago (all entries when `None`)
+///
+/// Emptied `<owner>/<repo>` directories are removed too, along with deduplicated objects
+/// that no remaining entry links to. A dry run only reports what would be removed.
+pub fn clean(cache_dir: &Path, older_than: Option<Duration>, dry_run: bool) -> Result<CleanReport> {
+    let entries = cache_entries(cache_dir)?;
+    let now = SystemTime::now();
+    let removed: Vec<CacheEntry> = entries
+        .iter()
+        .filter(|e| older_than.is_none_or(|limit| e.age(now) > limit))
+        .cloned()
+        .collect();
+
+    let mut report = CleanReport {
+        entries,
+        removed,
+        ..Default::default()
+    };
+    if dry_run || report.removed.is_empty() {
+        return Ok(report);
+    }
+
+    for entry in &report.removed {
+        fs::remove_dir_all(&entry.path)
+            .with_context(|| format!("Failed to remove cache entry: {}", entry.path.display()))?;
+        // Drop `<repo>` and `<owner>` once nothing downloaded is left in them
+        for dir in entry.path.ancestors().skip(1).take(2) {
+            if visible_dirs(dir).map_or(true, |d| !d.is_empty()) {
+                break;
+            }
+            fs::remove_dir_all(dir)
+                .with_context(|| format!("Failed to remove cache directory: {}", dir.display()))?;
+        }
+    }
+
+    let (objects, bytes) = ContentStore::new(cache_dir.join(".objects")).remove_unreferenced()?;
+    report.objects_removed = objects;
+    report.object_bytes_removed = bytes;
+    Ok(report)
+}
+
+/// Parse an age such as `30d`, `12h`, `2w`, `45m` or `90s` (a bare number means days)
+pub fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| {
+        format!(
+            "invalid age '{}': expected a number and a unit, e.g. 30d",
+            s
+        )
+    })?;
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        other => return Err(format!("invalid age unit '{}': use s, m, h, d or w", other)),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid age '{}': too large", s))
+}
+
+/// Subdirectories of `dir` whose names don't start with `.` (none if `dir` is missing)
+fn visible_dirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let read = match fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read cache directory: {}", dir.display()))
+        }
+    };
+    let mut dirs = Vec::new();
+    for entry in read {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() && !entry.file_name().to_string_lossy().starts_with('.') {
+            dirs.push(entry.path());
+        }
+    }
+    Ok(dirs)
+}
+
+/// Total size of the files under `dir`
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry =
+            entry.with_context(|| format!("Failed to read cache entry: {}", dir.display()))?;
+        if entry.file_type().is_file() {
+            size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(
+            parse_age("30d").unwrap(),
+            Duration::from_secs(30 * 24 * 3600)
+        );
+        assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert_eq!(
+            parse_age("2w").unwrap(),
+            Duration::from_secs(14 * 24 * 3600)
+        );
+        assert_eq!(parse_age("7").unwrap(), Duration::from_secs(7 * 24 * 3600));
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3y").is_err());
+        assert!(parse_age("99999999999999999w").is_err());
+        assert!(parse_age(&format!("{}s", u64::MAX)).is_ok());
+    }
+
+    #[test]
+    fn test_clean_by_age() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let cache = temp.path();
+        for (entry, content) in [
+            ("owner/repo/main", "aaaa"),
+            ("owner/repo/dev", "bb"),
+            ("other/mod/main", "c"),
+        ] {
+            fs::create_dir_all(cache.join(entry).join("data")).unwrap();
+            fs::write(cache.join(entry).join("data/mod.lua"), content).unwrap();
+        }
+        fs::write(cache.join("owner/repo/.default-branch"), "main").unwrap();
+        fs::create_dir_all(cache.join(".objects/ab")).unwrap();
+        fs::hard_link(
+            cache.join("other/mod/main/data/mod.lua"),
+            cache.join(".objects/ab/cdef"),
+        )
+        .unwrap();
+
+        let report = clean(cache, None, true).unwrap();
+        assert_eq!(report.entries.len(), 3);
+        assert_eq!(report.total_size(), 7);
+        assert_eq!(report.removed.len(), 3);
+        assert!(
+            cache.join("owner/repo/main").exists(),
+            "dry run removed an entry"
+        );
+
+        // Nothing is older than a day
+        let report = clean(cache, Some(Duration::from_secs(24 * 3600)), false).unwrap();
+        assert!(report.removed.is_empty());
+        assert_eq!(cache_entries(cache).unwrap().len(), 3);
+
+        let report = clean(cache, None, false).unwrap();
+        assert_eq!(report.removed_size(), 7);
+        assert!(cache_entries(cache).unwrap().is_empty());
+        assert!(!cache.join("owner").exists());
+        assert!(!cache.join("other").exists());
+        #[cfg(unix)]
+        assert_eq!(
+            (report.objects_removed, report.object_bytes_removed),
+            (1, 1)
+        );
+    }
+}
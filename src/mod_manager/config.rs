@@ -22,11 +22,73 @@ pub struct ModConfig {
     /// Mod version
     pub version: String,
 
+    /// Whether the mod runs when no `__enabled` option value is set (defaults to true)
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "enabledByDefault")]
+    pub enabled_by_default: Option<bool>,
+
     /// Configuration options for the user
-    #[serde(default)]
+    ///
+    /// Also accepted as an object keyed by option ID, as some D2RMM mods write it
+    /// (see [`deserialize_config_options`]).
+    #[serde(default, deserialize_with = "deserialize_config_options")]
     pub config: Vec<ConfigOption>,
 }
 
+/// Read `config` as an array of options, or as an object mapping each option's ID to the
+/// rest of the option
+///
+/// In the object form the key becomes the option's `id` unless the option gives one itself.
+/// Options keep the order they are written in.
+fn deserialize_config_options<'de, D>(deserializer: D) -> Result<Vec<ConfigOption>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{self, MapAccess, SeqAccess, Visitor};
+
+    struct ConfigOptions;
+
+    impl<'de> Visitor<'de> for ConfigOptions {
+        type Value = Vec<ConfigOption>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("an array of config options or an object mapping option IDs to options")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut options = Vec::new();
+            while let Some(option) = seq.next_element()? {
+                options.push(option);
+            }
+            Ok(options)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut options = Vec::new();
+            while let Some((id, mut option)) = map.next_entry::<String, serde_json::Value>()? {
+                match option.as_object_mut() {
+                    Some(fields) => {
+                        fields.entry("id").or_insert_with(|| id.clone().into());
+                    }
+                    None => return Err(de::Error::custom(format!("config option '{}' is not an object", id))),
+                }
+                let option = ConfigOption::deserialize(option)
+                    .map_err(|e| de::Error::custom(format!("config option '{}': {}", id, e)))?;
+                options.push(option);
+            }
+            Ok(options)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(Vec::new())
+        }
+    }
+
+    deserializer.deserialize_any(ConfigOptions)
+}
+
+/// ID of the optional master-toggle checkbox; when false the mod's script is skipped
+pub const ENABLED_OPTION_ID: &str = "__enabled";
+
 /// Configuration option types
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
@@ -40,6 +102,8 @@ pub enum ConfigOption {
         description: Option<String>,
         #[serde(default, alias = "defaultValue")]
         default: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none", alias = "showIf")]
+        show_if: Option<String>,
     },
 
     /// Numeric input option
@@ -54,6 +118,8 @@ pub enum ConfigOption {
         min: Option<f64>,
         #[serde(skip_serializing_if = "Option::is_none", alias = "maxValue")]
         max: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none", alias = "showIf")]
+        show_if: Option<String>,
     },
 
     /// Text input option
@@ -64,6 +130,8 @@ pub enum ConfigOption {
         description: Option<String>,
         #[serde(default, alias = "defaultValue")]
         default: String,
+        #[serde(default, skip_serializing_if = "Option::is_none", alias = "showIf")]
+        show_if: Option<String>,
     },
 
     /// Dropdown selection option
@@ -75,6 +143,20 @@ pub enum ConfigOption {
         #[serde(alias = "defaultValue")]
         default: String,
         options: Vec<SelectOption>,
+        #[serde(default, skip_serializing_if = "Option::is_none", alias = "showIf")]
+        show_if: Option<String>,
+    },
+
+    /// Color picker option; the value is an `#rrggbb` string
+    Color {
+        id: String,
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(alias = "defaultValue")]
+        default: String,
+        #[serde(default, skip_serializing_if = "Option::is_none", alias = "showIf")]
+        show_if: Option<String>,
     },
 
     /// Section header (for UI organization, no value)
@@ -83,6 +165,8 @@ pub enum ConfigOption {
         name: String,
         #[serde(skip_serializing_if = "Option::is_none", alias = "defaultExpanded")]
         default_expanded: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none", alias = "showIf")]
+        show_if: Option<String>,
     },
 }
 
@@ -93,6 +177,84 @@ pub struct SelectOption {
     pub value: String,
 }
 
+/// Parse an `#rrggbb` color (hex digits in either case)
+pub fn parse_hex_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').filter(|h| h.len() == 6 && h.is_ascii())?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Format a color as `#rrggbb` (lowercase)
+pub fn format_hex_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Operators a [`Condition`] can use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionOp {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+}
+
+/// A parsed `showIf` expression: `<option id> <op> <value>`, e.g. `enableFeature == true`
+///
+/// The operators are `==`, `!=`, `>` and `<`. The value is read as JSON (`true`, `5`,
+/// `"text"`) and as plain text otherwise; `>` and `<` only hold between numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub option: String,
+    pub op: ConditionOp,
+    pub value: serde_json::Value,
+}
+
+impl Condition {
+    pub fn parse(expr: &str) -> anyhow::Result<Self> {
+        const OPERATORS: [(&str, ConditionOp); 4] = [
+            ("==", ConditionOp::Eq),
+            ("!=", ConditionOp::Ne),
+            (">", ConditionOp::Gt),
+            ("<", ConditionOp::Lt),
+        ];
+
+        let (index, token, op) = OPERATORS
+            .iter()
+            .filter_map(|(token, op)| expr.find(token).map(|index| (index, *token, *op)))
+            .min_by_key(|(index, _, _)| *index)
+            .ok_or_else(|| anyhow::anyhow!("Condition '{}' has no ==, !=, > or < operator", expr))?;
+        let option = expr[..index].trim();
+        let value = expr[index + token.len()..].trim();
+        if option.is_empty() || option.contains(char::is_whitespace) {
+            anyhow::bail!("Condition '{}' must start with an option id", expr);
+        }
+        if value.is_empty() || value.starts_with('=') {
+            anyhow::bail!("Condition '{}' needs a value after {} (only ==, !=, > and < are supported)", expr, token);
+        }
+
+        Ok(Self {
+            option: option.to_string(),
+            op,
+            value: serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string())),
+        })
+    }
+
+    /// Whether the condition holds for the referenced option's current value
+    pub fn evaluate(&self, actual: &serde_json::Value) -> bool {
+        let numbers = actual.as_f64().zip(self.value.as_f64());
+        let equal = match numbers {
+            Some((a, b)) => a == b,
+            None => *actual == self.value,
+        };
+        match self.op {
+            ConditionOp::Eq => equal,
+            ConditionOp::Ne => !equal,
+            ConditionOp::Gt => numbers.is_some_and(|(a, b)| a > b),
+            ConditionOp::Lt => numbers.is_some_and(|(a, b)| a < b),
+        }
+    }
+}
+
 /// User configuration values
 pub type UserConfig = HashMap<String, serde_json::Value>;
 
@@ -104,10 +266,68 @@ impl ConfigOption {
             ConfigOption::Number { default, .. } => Some(serde_json::json!(default)),
             ConfigOption::Text { default, .. } => Some(serde_json::json!(default)),
             ConfigOption::Select { default, .. } => Some(serde_json::json!(default)),
+            ConfigOption::Color { default, .. } => Some(serde_json::json!(default)),
             ConfigOption::Section { .. } => None, // Sections don't have values
         }
     }
 
+    /// Check whether a value has the right type for this option
+    /// (Select values must also be one of the declared options, Color values `#rrggbb`)
+    pub fn accepts(&self, value: &serde_json::Value) -> bool {
+        match self {
+            ConfigOption::CheckBox { .. } => value.is_boolean(),
+            ConfigOption::Number { .. } => value.is_number(),
+            ConfigOption::Text { .. } => value.is_string(),
+            ConfigOption::Select { options, .. } => value
+                .as_str()
+                .map(|v| options.iter().any(|opt| opt.value == v))
+                .unwrap_or(false),
+            ConfigOption::Color { .. } => value.as_str().and_then(parse_hex_color).is_some(),
+            ConfigOption::Section { .. } => false,
+        }
+    }
+
+    /// Check this option for mistakes that would otherwise only show up in the GUI
+    ///
+    /// Currently: a `Select` needs options, unique option values and a default that is one of them,
+    /// a `Color` default must be `#rrggbb`, and `showIf` must parse.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(expr) = self.show_if() {
+            Condition::parse(expr).map_err(|e| anyhow::anyhow!("Option '{}': {}", self.id(), e))?;
+        }
+        if let ConfigOption::Color { id, default, .. } = self {
+            if parse_hex_color(default).is_none() {
+                anyhow::bail!("Color option '{}' has default '{}', which is not an #rrggbb color", id, default);
+            }
+        }
+        if let ConfigOption::Select { id, default, options, .. } = self {
+            if options.is_empty() {
+                anyhow::bail!("Select option '{}' has no options", id);
+            }
+            let mut seen = std::collections::HashSet::new();
+            if let Some(duplicate) = options.iter().find(|opt| !seen.insert(opt.value.as_str())) {
+                anyhow::bail!("Select option '{}' declares value '{}' more than once", id, duplicate.value);
+            }
+            if !seen.contains(default.as_str()) {
+                anyhow::bail!("Select option '{}' has default '{}', which is not one of its options", id, default);
+            }
+        }
+        Ok(())
+    }
+
+    /// The option's `showIf` expression, if it has one (the GUI hides the option while it
+    /// doesn't hold, see [`Condition`])
+    pub fn show_if(&self) -> Option<&str> {
+        match self {
+            ConfigOption::CheckBox { show_if, .. }
+            | ConfigOption::Number { show_if, .. }
+            | ConfigOption::Text { show_if, .. }
+            | ConfigOption::Select { show_if, .. }
+            | ConfigOption::Color { show_if, .. }
+            | ConfigOption::Section { show_if, .. } => show_if.as_deref(),
+        }
+    }
+
     /// Get the ID of this config option
     pub fn id(&self) -> &str {
         match self {
@@ -115,12 +335,75 @@ impl ConfigOption {
             ConfigOption::Number { id, .. } => id,
             ConfigOption::Text { id, .. } => id,
             ConfigOption::Select { id, .. } => id,
+            ConfigOption::Color { id, .. } => id,
             ConfigOption::Section { id, .. } => id,
         }
     }
 }
 
+/// Whole numbers (within the exactly representable range) become integers; other values are unchanged
+pub fn normalize_number(value: &serde_json::Value) -> serde_json::Value {
+    const MAX_EXACT: f64 = 9_007_199_254_740_992.0; // 2^53
+
+    match value.as_f64() {
+        Some(f) if value.is_f64() && f.fract() == 0.0 && f.abs() <= MAX_EXACT => serde_json::json!(f as i64),
+        _ => value.clone(),
+    }
+}
+
 impl ModConfig {
+    /// Check the declared options for mistakes that would otherwise only show up in the GUI
+    ///
+    /// See [`ConfigOption::validate`]; the first problem found is returned.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.config.iter().try_for_each(ConfigOption::validate)
+    }
+
+    /// [`validate`](Self::validate), and also require every option's `id` to be unique
+    /// and every `showIf` to refer to a declared option
+    ///
+    /// Used by `validate --strict`; a repeated id makes the options share one value.
+    pub fn validate_strict(&self) -> anyhow::Result<()> {
+        self.validate()?;
+        let mut seen = std::collections::HashSet::new();
+        if let Some(duplicate) = self.config.iter().find(|opt| !seen.insert(opt.id())) {
+            anyhow::bail!("Option id '{}' is declared more than once", duplicate.id());
+        }
+        for option in &self.config {
+            let Some(condition) = option.show_if().and_then(|expr| Condition::parse(expr).ok()) else {
+                continue;
+            };
+            if !seen.contains(condition.option.as_str()) {
+                anyhow::bail!("Option '{}' has showIf on undeclared option '{}'", option.id(), condition.option);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the GUI shows `option` for the values in `config`
+    ///
+    /// Only the GUI hides options; scripts still get every value. A referenced option
+    /// without a value counts as its default, and an unparsable `showIf` shows the option.
+    pub fn is_option_visible(&self, option: &ConfigOption, config: &UserConfig) -> bool {
+        let Some(expr) = option.show_if() else {
+            return true;
+        };
+        let Ok(condition) = Condition::parse(expr) else {
+            return true;
+        };
+        let actual = config
+            .get(&condition.option)
+            .cloned()
+            .or_else(|| {
+                self.config
+                    .iter()
+                    .find(|opt| opt.id() == condition.option)
+                    .and_then(ConfigOption::get_default_value)
+            })
+            .unwrap_or(serde_json::Value::Null);
+        condition.evaluate(&actual)
+    }
+
     /// Generate default user configuration from config options
     pub fn generate_default_config(&self) -> UserConfig {
         let mut config = UserConfig::new();
@@ -134,12 +417,337 @@ impl ModConfig {
 
         config
     }
+
+    /// Coerce the values of `Number` options into one representation
+    ///
+    /// JSON doesn't distinguish `100` from `100.0`, but Lua (and QuickJS internally)
+    /// does, so whole numbers are always presented as integers and everything else as floats.
+    pub fn normalize_user_config(&self, config: &mut UserConfig) {
+        for option in &self.config {
+            if let ConfigOption::Number { id, .. } = option {
+                if let Some(value) = config.get_mut(id) {
+                    *value = normalize_number(value);
+                }
+            }
+        }
+    }
+
+    /// Check the values a script would receive against the declared options
+    ///
+    /// Every problem is reported: values of the wrong type, `Number` values outside
+    /// `min`/`max`, `Select` values that aren't one of the options and malformed colors.
+    /// Missing values and keys no option declares are left alone.
+    pub fn validate_user_config(&self, config: &UserConfig) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        for option in &self.config {
+            let Some(value) = config.get(option.id()) else {
+                continue;
+            };
+            match option {
+                ConfigOption::Number { id, min, max, .. } => match value.as_f64() {
+                    None => problems.push(format!("Option '{}' must be a number, got {}", id, value)),
+                    Some(number) => {
+                        if let Some(min) = min.filter(|min| number < *min) {
+                            problems.push(format!("Option '{}' is {}, below its minimum {}", id, value, min));
+                        }
+                        if let Some(max) = max.filter(|max| number > *max) {
+                            problems.push(format!("Option '{}' is {}, above its maximum {}", id, value, max));
+                        }
+                    }
+                },
+                ConfigOption::Select { id, options, .. } if value.is_string() && !option.accepts(value) => {
+                    let allowed: Vec<&str> = options.iter().map(|opt| opt.value.as_str()).collect();
+                    problems.push(format!(
+                        "Option '{}' is {}, which is not one of its options ({})",
+                        id,
+                        value,
+                        allowed.join(", ")
+                    ));
+                }
+                ConfigOption::Section { .. } => {}
+                _ if !option.accepts(value) => {
+                    let expected = match option {
+                        ConfigOption::CheckBox { .. } => "true or false",
+                        ConfigOption::Color { .. } => "an #rrggbb color",
+                        _ => "a string",
+                    };
+                    problems.push(format!("Option '{}' must be {}, got {}", option.id(), expected, value));
+                }
+                _ => {}
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Merge a preset (e.g. `config.default.json`) over `config`
+    ///
+    /// Only values for declared options with a valid type are applied;
+    /// the IDs of rejected entries are returned.
+    pub fn apply_preset(&self, config: &mut UserConfig, preset: UserConfig) -> Vec<String> {
+        let mut rejected = Vec::new();
+
+        for (key, value) in preset {
+            match self.config.iter().find(|opt| opt.id() == key) {
+                Some(option) if option.accepts(&value) => {
+                    config.insert(key, value);
+                }
+                _ => rejected.push(key),
+            }
+        }
+
+        rejected
+    }
 }
 
+/// Parse a user config file (`config.json`, `config.default.json`)
+///
+/// The expected shape is an object mapping option IDs to values. The legacy
+/// `[{ "id": ..., "value": ... }]` array form is converted; anything else is rejected
+/// with an explanation instead of a bare serde type error.
+pub fn parse_user_config(content: &str) -> anyhow::Result<UserConfig> {
+    const SHAPE: &str = "must be a JSON object mapping option IDs to values";
+
+    match serde_json::from_str(content)? {
+        serde_json::Value::Object(map) => Ok(map.into_iter().collect()),
+        serde_json::Value::Array(entries) => entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let id = entry.get("id").and_then(|id| id.as_str());
+                match (id, entry.get("value")) {
+                    (Some(id), Some(value)) => Ok((id.to_string(), value.clone())),
+                    _ => anyhow::bail!(
+                        "config {}; entry {} of the legacy array form is not an {{\"id\", \"value\"}} object",
+                        SHAPE,
+                        index
+                    ),
+                }
+            })
+            .collect(),
+        other => anyhow::bail!("config {}, found {}", SHAPE, json_type_name(&other)),
+    }
+}
+
+fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+/// File name of the optional author-provided config preset
+pub const CONFIG_PRESET_FILE: &str = "config.default.json";
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn select_config(default: &str, values: &[&str]) -> ModConfig {
+        let options: Vec<_> = values
+            .iter()
+            .map(|v| serde_json::json!({ "label": v, "value": v }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "name": "Select", "version": "1.0",
+            "config": [{ "type": "select", "id": "mode", "name": "Mode", "default": default, "options": options }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_user_config_shapes() {
+        let config = parse_user_config(r#"{ "size": 5, "flag": true }"#).unwrap();
+        assert_eq!(config["size"], serde_json::json!(5));
+
+        // Legacy array form is converted
+        let legacy = parse_user_config(r#"[{ "id": "size", "value": 5 }, { "id": "flag", "value": true }]"#).unwrap();
+        assert_eq!(legacy, config);
+
+        for malformed in [r#"[1, 2]"#, r#"[{ "id": "size" }]"#, r#""size""#, "null"] {
+            let err = parse_user_config(malformed).unwrap_err().to_string();
+            assert!(err.contains("must be a JSON object mapping option IDs to values"), "{}", err);
+        }
+        assert!(parse_user_config("{ not json").is_err());
+    }
+
+    #[test]
+    fn test_validate_select_options() {
+        assert!(select_config("b", &["a", "b"]).validate().is_ok());
+
+        let err = select_config("c", &["a", "b"]).validate().unwrap_err().to_string();
+        assert!(err.contains("'mode'") && err.contains("'c'"), "{}", err);
+
+        let err = select_config("a", &["a", "b", "a"]).validate().unwrap_err().to_string();
+        assert!(err.contains("'mode'") && err.contains("more than once"), "{}", err);
+
+        let err = select_config("a", &[]).validate().unwrap_err().to_string();
+        assert!(err.contains("'mode'") && err.contains("no options"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_color_option() {
+        let config: ModConfig = serde_json::from_str(
+            r##"{ "name": "Tints", "version": "1.0", "config": [
+                { "type": "color", "id": "unique", "name": "Unique items", "default": "#C7B377" },
+                { "type": "color", "id": "set", "name": "Set items", "defaultValue": "#00ff00" }
+            ] }"##,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let defaults = config.generate_default_config();
+        assert_eq!(defaults["unique"], serde_json::json!("#C7B377"));
+        assert_eq!(defaults["set"], serde_json::json!("#00ff00"));
+
+        let option = &config.config[1];
+        assert!(option.accepts(&serde_json::json!("#0a0B0c")));
+        assert!(!option.accepts(&serde_json::json!("0a0b0c")));
+        assert!(!option.accepts(&serde_json::json!("#0a0b0")));
+        assert!(!option.accepts(&serde_json::json!(255)));
+
+        let written = serde_json::to_value(&config).unwrap();
+        assert_eq!(written["config"][1], serde_json::json!({ "type": "color", "id": "set", "name": "Set items", "default": "#00ff00" }));
+
+        assert_eq!(parse_hex_color("#C7b377"), Some([0xc7, 0xb3, 0x77]));
+        assert_eq!(parse_hex_color("#ééé"), None);
+        assert_eq!(format_hex_color([0xc7, 0xb3, 0x07]), "#c7b307");
+
+        let bad: ModConfig = serde_json::from_str(
+            r#"{ "name": "M", "version": "1", "config": [{ "type": "color", "id": "c", "name": "C", "default": "red" }] }"#,
+        )
+        .unwrap();
+        let err = bad.validate().unwrap_err().to_string();
+        assert!(err.contains("'c'") && err.contains("#rrggbb"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_user_config() {
+        let config: ModConfig = serde_json::from_str(
+            r#"{ "name": "M", "version": "1", "config": [
+                { "type": "section", "id": "s", "name": "Section" },
+                { "type": "number", "id": "stack", "name": "Stack", "default": 50, "min": 1, "max": 500 },
+                { "type": "checkbox", "id": "flag", "name": "Flag" },
+                { "type": "select", "id": "mode", "name": "Mode", "default": "a",
+                  "options": [{ "label": "A", "value": "a" }, { "label": "B", "value": "b" }] }
+            ] }"#,
+        )
+        .unwrap();
+        let mut user_config = config.generate_default_config();
+        assert_eq!(config.validate_user_config(&user_config), Ok(()));
+
+        user_config.insert("stack".into(), serde_json::json!(500));
+        user_config.insert("unknown".into(), serde_json::json!("ignored"));
+        assert_eq!(config.validate_user_config(&user_config), Ok(()));
+
+        user_config.insert("stack".into(), serde_json::json!(9999));
+        user_config.insert("mode".into(), serde_json::json!("c"));
+        user_config.insert("flag".into(), serde_json::json!("yes"));
+        let problems = config.validate_user_config(&user_config).unwrap_err();
+        assert_eq!(
+            problems,
+            [
+                "Option 'stack' is 9999, above its maximum 500",
+                "Option 'flag' must be true or false, got \"yes\"",
+                "Option 'mode' is \"c\", which is not one of its options (a, b)",
+            ]
+        );
+
+        user_config = config.generate_default_config();
+        user_config.insert("stack".into(), serde_json::json!(0));
+        user_config.insert("mode".into(), serde_json::json!(2));
+        let problems = config.validate_user_config(&user_config).unwrap_err();
+        assert_eq!(
+            problems,
+            ["Option 'stack' is 0, below its minimum 1", "Option 'mode' must be a string, got 2"]
+        );
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        let condition = Condition::parse("enableFeature == true").unwrap();
+        assert_eq!(condition.option, "enableFeature");
+        assert_eq!(condition.op, ConditionOp::Eq);
+        assert_eq!(condition.value, serde_json::json!(true));
+
+        let condition = Condition::parse("mode!=fast").unwrap();
+        assert_eq!((condition.op, condition.value), (ConditionOp::Ne, serde_json::json!("fast")));
+        assert_eq!(Condition::parse(r#"mode == "a b""#).unwrap().value, serde_json::json!("a b"));
+        assert_eq!(Condition::parse("level > 2.5").unwrap().op, ConditionOp::Gt);
+        assert_eq!(Condition::parse("level<10").unwrap().value, serde_json::json!(10));
+
+        for bad in ["enableFeature", "== true", "a b == 1", "level >= 5", "level <"] {
+            assert!(Condition::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_condition() {
+        let holds = |expr: &str, actual: serde_json::Value| Condition::parse(expr).unwrap().evaluate(&actual);
+        assert!(holds("flag == true", serde_json::json!(true)));
+        assert!(!holds("flag == true", serde_json::json!(false)));
+        assert!(holds("flag != true", serde_json::Value::Null));
+        assert!(holds("stack == 100", serde_json::json!(100.0)));
+        assert!(holds("stack > 50", serde_json::json!(51)));
+        assert!(!holds("stack > 50", serde_json::json!(50)));
+        assert!(holds("stack < 50", serde_json::json!(-1.5)));
+        assert!(!holds("stack < 50", serde_json::json!("10")));
+        assert!(holds("mode == fast", serde_json::json!("fast")));
+    }
+
+    #[test]
+    fn test_option_visibility() {
+        let config: ModConfig = serde_json::from_str(
+            r#"{ "name": "M", "version": "1", "config": [
+                { "type": "checkbox", "id": "enableFeature", "name": "Enable", "default": false },
+                { "type": "number", "id": "amount", "name": "Amount", "default": 5, "showIf": "enableFeature == true" },
+                { "type": "text", "id": "label", "name": "Label", "show_if": "amount > 10" }
+            ] }"#,
+        )
+        .unwrap();
+        assert!(config.validate_strict().is_ok());
+        let [feature, amount, label] = &config.config[..] else { panic!() };
+
+        let mut values = UserConfig::new();
+        assert!(config.is_option_visible(feature, &values));
+        assert!(!config.is_option_visible(amount, &values));
+        values.insert("enableFeature".into(), serde_json::json!(true));
+        assert!(config.is_option_visible(amount, &values));
+        assert!(!config.is_option_visible(label, &values));
+        values.insert("amount".into(), serde_json::json!(11));
+        assert!(config.is_option_visible(label, &values));
+
+        let written = serde_json::to_value(&config).unwrap();
+        assert_eq!(written["config"][1]["show_if"], "enableFeature == true");
+        assert!(written["config"][0].get("show_if").is_none());
+
+        let mut broken = config.clone();
+        if let ConfigOption::Text { show_if, .. } = &mut broken.config[2] {
+            *show_if = Some("missing == 1".into());
+        }
+        assert!(broken.validate().is_ok());
+        let err = broken.validate_strict().unwrap_err().to_string();
+        assert!(err.contains("'label'") && err.contains("'missing'"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_strict_unique_ids() {
+        let mut config = select_config("a", &["a"]);
+        assert!(config.validate_strict().is_ok());
+
+        config.config.push(config.config[0].clone());
+        assert!(config.validate().is_ok());
+        let err = config.validate_strict().unwrap_err().to_string();
+        assert!(err.contains("'mode'") && err.contains("more than once"), "{}", err);
+    }
+
     #[test]
     fn test_parse_mod_config() {
         let json = r#"
@@ -176,4 +784,70 @@ mod tests {
         assert_eq!(defaults.get("enabled").unwrap(), &serde_json::json!(true));
         assert_eq!(defaults.get("value").unwrap(), &serde_json::json!(100.0));
     }
+
+    #[test]
+    fn test_parse_config_object_form() {
+        let json = r#"{
+            "name": "Test Mod",
+            "version": "1.0",
+            "config": {
+                "stackSize": { "type": "number", "name": "Stack size", "default": 50 },
+                "enabled": { "type": "checkbox", "name": "Enable Feature", "default": true },
+                "mode": { "type": "select", "id": "modeOverride", "name": "Mode", "default": "a",
+                          "options": [{ "label": "A", "value": "a" }] }
+            }
+        }"#;
+
+        let config: ModConfig = serde_json::from_str(json).unwrap();
+        let ids: Vec<&str> = config.config.iter().map(ConfigOption::id).collect();
+        assert_eq!(ids, ["stackSize", "enabled", "modeOverride"]);
+        let defaults = config.generate_default_config();
+        assert_eq!(defaults["enabled"], serde_json::json!(true));
+
+        // Written back in the array form
+        let written = serde_json::to_value(&config).unwrap();
+        assert_eq!(written["config"][0]["id"], "stackSize");
+        let array_form: ModConfig = serde_json::from_value(written).unwrap();
+        assert_eq!(array_form.config.len(), 3);
+
+        let no_config: ModConfig = serde_json::from_str(r#"{ "name": "M", "version": "1", "config": null }"#).unwrap();
+        assert!(no_config.config.is_empty());
+
+        for (bad, expected) in [
+            (r#"{ "x": 5 }"#, "config option 'x' is not an object"),
+            (r#"{ "x": { "type": "slider", "name": "X" } }"#, "config option 'x': unknown variant"),
+            (r#""x""#, "an array of config options or an object"),
+        ] {
+            let json = format!(r#"{{ "name": "M", "version": "1", "config": {} }}"#, bad);
+            let err = serde_json::from_str::<ModConfig>(&json).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_normalize_number_options() {
+        let json = r#"{
+            "name": "Test Mod",
+            "version": "1.0",
+            "config": [
+                { "type": "number", "id": "whole", "name": "Whole", "default": 100 },
+                { "type": "number", "id": "fraction", "name": "Fraction", "default": 1.5 },
+                { "type": "text", "id": "label", "name": "Label", "default": "x" }
+            ]
+        }"#;
+
+        let config: ModConfig = serde_json::from_str(json).unwrap();
+        let mut user_config = config.generate_default_config();
+        user_config.insert("label".to_string(), serde_json::json!(2.0));
+        config.normalize_user_config(&mut user_config);
+
+        assert!(user_config["whole"].is_i64());
+        assert_eq!(user_config["whole"], serde_json::json!(100));
+        assert_eq!(user_config["fraction"], serde_json::json!(1.5));
+        // Non-number options are left alone
+        assert!(user_config["label"].is_f64());
+
+        // `100` and `100.0` from config.json end up identical
+        assert_eq!(normalize_number(&serde_json::json!(100)), normalize_number(&serde_json::json!(100.0)));
+    }
 }
@@ -1,4 +1,5 @@
-use super::config::{ModConfig, UserConfig};
+use super::config::{parse_user_config, ModConfig, UserConfig, CONFIG_PRESET_FILE, ENABLED_OPTION_ID};
+use crate::runtime::ScriptType;
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
@@ -17,8 +18,111 @@ pub struct LoadedMod {
 
     /// User's configuration values
     pub user_config: UserConfig,
+
+    /// Entry script (`mod.lua` or `mod.js`)
+    pub script_path: PathBuf,
+
+    /// Runtime the entry script needs
+    pub script_type: ScriptType,
+
+    /// Contents of the mod's README, if it has one (see [`read_mod_readme`])
+    pub readme: Option<String>,
+}
+
+/// Read and validate a mod's `mod.json` without loading its script or user config
+pub fn read_mod_config(mod_path: &Path) -> Result<ModConfig> {
+    let config_path = mod_path.join("mod.json");
+
+    if !config_path.exists() {
+        anyhow::bail!("mod.json not found in {:?}", mod_path);
+    }
+
+    let config_str = std::fs::read_to_string(&config_path)
+        .context("Failed to read mod.json")?;
+
+    let config: ModConfig = serde_json::from_str(&config_str)
+        .context("Failed to parse mod.json")?;
+    config.validate().context("Invalid mod.json")?;
+
+    Ok(config)
+}
+
+/// Read a mod's `README.md`, `README.txt` or `README` (names compared case-insensitively)
+///
+/// Returns `None` when there is no README or it can't be read; invalid UTF-8 is replaced.
+pub fn read_mod_readme(mod_path: &Path) -> Option<String> {
+    const NAMES: [&str; 3] = ["readme.md", "readme.txt", "readme"];
+
+    let files: Vec<PathBuf> = std::fs::read_dir(mod_path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|entry| entry.path())
+        .collect();
+    let path = NAMES.iter().find_map(|name| {
+        files.iter().find(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        })
+    })?;
+
+    let bytes = std::fs::read(path).ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Locate a mod's entry script
+///
+/// `mod.lua` wins over `mod.js`, except that a zero-byte script is passed over when the
+/// other one has content (e.g. an empty `mod.lua` left next to a real `mod.js`).
+pub fn find_mod_script(mod_path: &Path) -> Option<(PathBuf, ScriptType)> {
+    const SCRIPTS: [(&str, ScriptType); 2] = [("mod.lua", ScriptType::Lua), ("mod.js", ScriptType::JavaScript)];
+
+    let candidates: Vec<(PathBuf, ScriptType, u64)> = SCRIPTS
+        .into_iter()
+        .filter_map(|(name, script_type)| {
+            let path = mod_path.join(name);
+            let metadata = std::fs::metadata(&path).ok().filter(|m| m.is_file())?;
+            Some((path, script_type, metadata.len()))
+        })
+        .collect();
+
+    let (path, script_type, len) = candidates
+        .iter()
+        .find(|(_, _, len)| *len > 0)
+        .or_else(|| candidates.first())
+        .cloned()?;
+
+    if len == 0 {
+        tracing::warn!("{} is empty; the mod will not change anything", path.display());
+    }
+    Some((path, script_type))
+}
+
+impl LoadedMod {
+    /// Whether the mod's master toggle allows its script to run
+    ///
+    /// Precedence: the `__enabled` value in the user config (config.json or the
+    /// option's default), then `enabledByDefault` from mod.json, then enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.user_config
+            .get(ENABLED_OPTION_ID)
+            .and_then(|v| v.as_bool())
+            .or(self.config.enabled_by_default)
+            .unwrap_or(true)
+    }
+}
+
+/// A mod directory has neither a usable `mod.lua` nor `mod.js`
+#[derive(Debug, thiserror::Error)]
+#[error("Neither mod.lua nor mod.js found in {:?}", .mod_path)]
+pub struct MissingModScript {
+    pub mod_path: PathBuf,
 }
 
+/// A mod directory that failed to load, with the error
+pub type LoadFailure = (PathBuf, anyhow::Error);
+
 /// Mod loader responsible for discovering and loading mods
 pub struct ModLoader {
     mods_dir: PathBuf,
@@ -34,11 +138,21 @@ impl ModLoader {
 
     /// Load all mods from the mods directory
     pub fn load_all(&self) -> Result<Vec<LoadedMod>> {
+        let (mods, failures) = self.load_all_with_failures()?;
+        for (path, e) in failures {
+            tracing::warn!("Failed to load mod from {:?}: {}", path, e);
+        }
+        Ok(mods)
+    }
+
+    /// Load all mods, returning the directories that failed to load alongside the mods
+    pub fn load_all_with_failures(&self) -> Result<(Vec<LoadedMod>, Vec<LoadFailure>)> {
         if !self.mods_dir.exists() {
             anyhow::bail!("Mods directory does not exist: {:?}", self.mods_dir);
         }
 
         let mut mods = Vec::new();
+        let mut failures = Vec::new();
 
         for entry in WalkDir::new(&self.mods_dir)
             .min_depth(1)
@@ -52,37 +166,21 @@ impl ModLoader {
                     tracing::debug!("Loaded mod: {} v{}", mod_data.config.name, mod_data.config.version);
                     mods.push(mod_data);
                 }
-                Err(e) => {
-                    tracing::warn!("Failed to load mod from {:?}: {}", entry.path(), e);
-                }
+                Err(e) => failures.push((entry.path().to_path_buf(), e)),
             }
         }
 
         tracing::info!("Loaded {} mods", mods.len());
-        Ok(mods)
+        Ok((mods, failures))
     }
 
     /// Load a single mod from a directory
     pub fn load_mod(&self, mod_path: &Path) -> Result<LoadedMod> {
-        let config_path = mod_path.join("mod.json");
-
-        if !config_path.exists() {
-            anyhow::bail!("mod.json not found in {:?}", mod_path);
-        }
+        let config = read_mod_config(mod_path)?;
 
-        let config_str = std::fs::read_to_string(&config_path)
-            .context("Failed to read mod.json")?;
-
-        let config: ModConfig = serde_json::from_str(&config_str)
-            .context("Failed to parse mod.json")?;
-
-        // Check if mod.lua or mod.js exists
-        let lua_path = mod_path.join("mod.lua");
-        let js_path = mod_path.join("mod.js");
-
-        if !lua_path.exists() && !js_path.exists() {
-            anyhow::bail!("Neither mod.lua nor mod.js found in {:?}", mod_path);
-        }
+        // Either mod.lua or mod.js is required
+        let (script_path, script_type) = find_mod_script(mod_path)
+            .ok_or_else(|| MissingModScript { mod_path: mod_path.to_path_buf() })?;
 
         let id = mod_path
             .file_name()
@@ -90,16 +188,40 @@ impl ModLoader {
             .context("Invalid mod directory name")?
             .to_string();
 
-        // 先生成默认配置,然后用 config.json 的值覆盖
+        // 先生成默认配置,然后用 config.default.json 和 config.json 的值依次覆盖
         let mut user_config = config.generate_default_config();
 
+        // 尝试加载 mod 作者提供的预设 config.default.json
+        let preset_path = mod_path.join(CONFIG_PRESET_FILE);
+        if preset_path.exists() {
+            match std::fs::read_to_string(&preset_path) {
+                Ok(preset_str) => {
+                    match parse_user_config(&preset_str) {
+                        Ok(preset) => {
+                            // 只接受已声明且类型匹配的选项
+                            for key in config.apply_preset(&mut user_config, preset) {
+                                tracing::warn!("Ignoring invalid or undeclared preset option '{}' in {}", key, id);
+                            }
+                            tracing::info!("Loaded config preset from {:?}", preset_path);
+                        },
+                        Err(e) => {
+                            tracing::warn!("Failed to parse {} for {}: {}", CONFIG_PRESET_FILE, id, e);
+                        }
+                    }
+                },
+                Err(e) => {
+                    tracing::warn!("Failed to read {} for {}: {}", CONFIG_PRESET_FILE, id, e);
+                }
+            }
+        }
+
         // 尝试加载用户配置文件 config.json
         let user_config_path = mod_path.join("config.json");
         if user_config_path.exists() {
             // 从 config.json 加载用户修改的值
             match std::fs::read_to_string(&user_config_path) {
                 Ok(config_str) => {
-                    match serde_json::from_str::<UserConfig>(&config_str) {
+                    match parse_user_config(&config_str) {
                         Ok(user_overrides) => {
                             // 用 config.json 中的值覆盖默认值
                             for (key, value) in user_overrides {
@@ -118,11 +240,17 @@ impl ModLoader {
             }
         }
 
+        // 数值选项统一表示 (100.0 与 100 在脚本中一致)
+        config.normalize_user_config(&mut user_config);
+
         Ok(LoadedMod {
             id,
             path: mod_path.to_path_buf(),
             config,
             user_config,
+            script_path,
+            script_type,
+            readme: read_mod_readme(mod_path),
         })
     }
 
@@ -164,5 +292,88 @@ mod tests {
         assert_eq!(mod_data.id, "TestMod");
         assert_eq!(mod_data.config.name, "Test Mod");
         assert_eq!(mod_data.config.version, "1.0");
+        assert_eq!(mod_data.script_type, ScriptType::Lua);
+        assert_eq!(mod_data.readme, None);
+
+        // README.md wins over other README names, whatever the case
+        fs::write(mod_dir.join("readme"), "plain").unwrap();
+        fs::write(mod_dir.join("ReadMe.MD"), "# Test Mod").unwrap();
+        let mod_data = loader.load_mod(&mod_dir).unwrap();
+        assert_eq!(mod_data.readme.as_deref(), Some("# Test Mod"));
+    }
+
+    #[test]
+    fn test_load_js_only_mod() {
+        let temp_dir = TempDir::new().unwrap();
+        let mod_dir = temp_dir.path().join("JsMod");
+        fs::create_dir(&mod_dir).unwrap();
+        fs::write(mod_dir.join("mod.json"), r#"{ "name": "JS", "version": "1.0" }"#).unwrap();
+        fs::write(mod_dir.join("mod.js"), "console.log('hi');").unwrap();
+
+        let loader = ModLoader::new(temp_dir.path());
+        let mod_data = loader.load_mod(&mod_dir).unwrap();
+        assert_eq!(mod_data.script_type, ScriptType::JavaScript);
+        assert_eq!(mod_data.script_path, mod_dir.join("mod.js"));
+
+        // An empty mod.lua does not shadow a real mod.js
+        fs::write(mod_dir.join("mod.lua"), "").unwrap();
+        assert_eq!(loader.load_mod(&mod_dir).unwrap().script_type, ScriptType::JavaScript);
+
+        // With no scripts at all, loading fails
+        fs::remove_file(mod_dir.join("mod.lua")).unwrap();
+        fs::remove_file(mod_dir.join("mod.js")).unwrap();
+        assert!(loader.load_mod(&mod_dir).is_err());
+    }
+
+    #[test]
+    fn test_enabled_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let mod_dir = temp_dir.path().join("GatedMod");
+        fs::create_dir(&mod_dir).unwrap();
+        fs::write(mod_dir.join("mod.lua"), "-- test").unwrap();
+        fs::write(
+            mod_dir.join("mod.json"),
+            r#"{ "name": "Gated", "version": "1.0", "enabledByDefault": false }"#,
+        )
+        .unwrap();
+
+        let loader = ModLoader::new(temp_dir.path());
+        assert!(!loader.load_mod(&mod_dir).unwrap().is_enabled());
+
+        // config.json's __enabled overrides enabledByDefault
+        fs::write(mod_dir.join("config.json"), r#"{ "__enabled": true }"#).unwrap();
+        assert!(loader.load_mod(&mod_dir).unwrap().is_enabled());
+    }
+
+    #[test]
+    fn test_config_preset_overrides_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let mod_dir = temp_dir.path().join("PresetMod");
+        fs::create_dir(&mod_dir).unwrap();
+        fs::write(mod_dir.join("mod.lua"), "-- test").unwrap();
+        fs::write(
+            mod_dir.join("mod.json"),
+            r#"{
+                "name": "Preset", "version": "1.0",
+                "config": [
+                    { "type": "number", "id": "size", "name": "Size", "default": 1 },
+                    { "type": "checkbox", "id": "flag", "name": "Flag", "default": false }
+                ]
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            mod_dir.join("config.default.json"),
+            r#"{ "size": 50, "flag": "yes", "unknown": 1 }"#,
+        )
+        .unwrap();
+        fs::write(mod_dir.join("config.json"), r#"{ "flag": true }"#).unwrap();
+
+        let loader = ModLoader::new(temp_dir.path());
+        let mod_data = loader.load_mod(&mod_dir).unwrap();
+
+        assert_eq!(mod_data.user_config["size"], serde_json::json!(50));
+        assert_eq!(mod_data.user_config["flag"], serde_json::json!(true));
+        assert!(!mod_data.user_config.contains_key("unknown"));
     }
 }
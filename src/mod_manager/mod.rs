@@ -2,6 +2,6 @@ pub mod config;
 pub mod executor;
 pub mod loader;
 
-pub use config::{ConfigOption, ModConfig, UserConfig};
+pub use config::{ConfigOption, ModConfig, UserConfig, ENABLED_OPTION_ID};
 pub use executor::ModExecutor;
-pub use loader::{LoadedMod, ModLoader};
+pub use loader::{read_mod_config, read_mod_readme, LoadedMod, MissingModScript, ModLoader};
@@ -1,3 +1,4 @@
+use crate::mod_manager::UserConfig;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -19,9 +20,12 @@ pub enum ModSource {
         /// Optional subdirectory within the repo
         #[serde(skip_serializing_if = "Option::is_none")]
         subdir: Option<String>,
-        /// Optional branch name (defaults to "main")
+        /// Optional branch name (defaults to the repository's default branch)
         #[serde(skip_serializing_if = "Option::is_none")]
         branch: Option<String>,
+        /// Optional pinned commit SHA (full or abbreviated); takes precedence over `branch`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        commit: Option<String>,
     },
 }
 
@@ -31,6 +35,9 @@ impl ModSource {
     /// Formats:
     /// - Local path: `path/to/mod` or `C:\path\to\mod`
     /// - GitHub: `github:owner/repo` or `github:owner/repo@branch` or `github:owner/repo:subdir` or `github:owner/repo:subdir@branch`
+    ///
+    /// A ref after `@` that is a full commit SHA (40 hex digits) pins that commit instead of a branch.
+    /// Abbreviated SHAs need the explicit form `@sha:3f2a9c1`, so branches named like `20240101` still work.
     pub fn parse(s: &str) -> Result<Self> {
         let s = s.trim();
 
@@ -65,23 +72,101 @@ impl ModSource {
             anyhow::bail!("GitHub repo must be in format 'owner/repo', got: {}", repo);
         }
 
+        let (branch, commit) = match branch {
+            Some(r) => match r.strip_prefix(SHA_REF_PREFIX) {
+                Some(sha) if is_abbreviated_sha(sha) => (None, Some(sha.to_string())),
+                Some(sha) => anyhow::bail!(
+                    "Expected a commit SHA (7-40 hex digits) after '@{}', got: {}",
+                    SHA_REF_PREFIX,
+                    sha
+                ),
+                None if is_commit_sha(&r) => (None, Some(r)),
+                None => (Some(r), None),
+            },
+            None => (None, None),
+        };
+
         Ok(Self::GitHub {
             repo,
             subdir,
             branch,
+            commit,
         })
     }
 }
 
+/// Prefix marking a ref after `@` as a commit SHA, needed for abbreviated ones (`@sha:3f2a9c1`)
+pub const SHA_REF_PREFIX: &str = "sha:";
+
+/// Whether a ref is a full 40-digit commit SHA, which a bare `@ref` pins without the `sha:` prefix
+pub fn is_commit_sha(s: &str) -> bool {
+    s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether a string is a full or abbreviated commit SHA (7-40 hex digits)
+fn is_abbreviated_sha(s: &str) -> bool {
+    (7..=40).contains(&s.len()) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse one mod list line: a source, optionally followed by `| key=value, key2=value2`
+///
+/// A `#` after whitespace starts a trailing comment. Values are read as JSON when they parse (`true`, `5`, `"text"`) and as plain
+/// strings otherwise. Pairs are split on commas, so values can't contain one.
+pub fn parse_list_line(line: &str) -> Result<(ModSource, UserConfig)> {
+    let line = match line.find(" #").or_else(|| line.find("\t#")) {
+        Some(pos) => &line[..pos],
+        None => line,
+    };
+    let (source, options) = match line.split_once('|') {
+        Some((source, options)) => (source, options),
+        None => (line, ""),
+    };
+
+    let mut overrides = UserConfig::new();
+    for pair in options.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .with_context(|| format!("Expected key=value, got: {}", pair))?;
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() {
+            anyhow::bail!("Missing option ID in: {}", pair);
+        }
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        overrides.insert(key.to_string(), value);
+    }
+
+    Ok((ModSource::parse(source)?, overrides))
+}
+
+/// One mod list line: a source and the config overrides given for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModListEntry {
+    pub source: ModSource,
+    /// Overrides from the line's `| key=value` segment
+    #[serde(default, skip_serializing_if = "UserConfig::is_empty")]
+    pub config_overrides: UserConfig,
+}
+
+impl From<ModSource> for ModListEntry {
+    fn from(source: ModSource) -> Self {
+        Self {
+            source,
+            config_overrides: UserConfig::new(),
+        }
+    }
+}
+
 /// Represents a list of mod sources to install
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModList {
-    pub sources: Vec<ModSource>,
+    pub entries: Vec<ModListEntry>,
 }
 
 impl ModList {
     /// Load a mod list from a text file
-    /// Each line is a mod source (local path or GitHub URL)
+    /// Each line is a mod source (local path or GitHub URL), optionally followed by
+    /// `| key=value, key2=value2` config overrides for that mod
     /// Lines starting with # are comments
     /// Empty lines are ignored
     pub async fn from_file(path: &Path) -> Result<Self> {
@@ -89,7 +174,7 @@ impl ModList {
             .await
             .context("Failed to read mod list file")?;
 
-        let mut sources = Vec::new();
+        let mut entries = Vec::new();
 
         for (line_num, line) in content.lines().enumerate() {
             let line = line.trim();
@@ -99,8 +184,11 @@ impl ModList {
                 continue;
             }
 
-            match ModSource::parse(line) {
-                Ok(source) => sources.push(source),
+            match parse_list_line(line) {
+                Ok((source, config_overrides)) => entries.push(ModListEntry {
+                    source,
+                    config_overrides,
+                }),
                 Err(e) => {
                     eprintln!(
                         "Warning: Failed to parse line {}: {} - {}",
@@ -112,12 +200,14 @@ impl ModList {
             }
         }
 
-        Ok(Self { sources })
+        Ok(Self { entries })
     }
 
-    /// Create a mod list from a vector of sources
+    /// Create a mod list from a vector of sources, without config overrides
     pub fn from_sources(sources: Vec<ModSource>) -> Self {
-        Self { sources }
+        Self {
+            entries: sources.into_iter().map(ModListEntry::from).collect(),
+        }
     }
 }
 
@@ -140,7 +230,7 @@ mod tests {
     fn test_parse_github_simple() {
         let source = ModSource::parse("github:user/repo").unwrap();
         match source {
-            ModSource::GitHub { repo, subdir, branch } => {
+            ModSource::GitHub { repo, subdir, branch, .. } => {
                 assert_eq!(repo, "user/repo");
                 assert_eq!(subdir, None);
                 assert_eq!(branch, None);
@@ -153,7 +243,7 @@ mod tests {
     fn test_parse_github_with_subdir() {
         let source = ModSource::parse("github:user/repo:mods/my_mod").unwrap();
         match source {
-            ModSource::GitHub { repo, subdir, branch } => {
+            ModSource::GitHub { repo, subdir, branch, .. } => {
                 assert_eq!(repo, "user/repo");
                 assert_eq!(subdir, Some("mods/my_mod".to_string()));
                 assert_eq!(branch, None);
@@ -166,7 +256,7 @@ mod tests {
     fn test_parse_github_with_branch() {
         let source = ModSource::parse("github:user/repo@dev").unwrap();
         match source {
-            ModSource::GitHub { repo, subdir, branch } => {
+            ModSource::GitHub { repo, subdir, branch, .. } => {
                 assert_eq!(repo, "user/repo");
                 assert_eq!(subdir, None);
                 assert_eq!(branch, Some("dev".to_string()));
@@ -179,7 +269,7 @@ mod tests {
     fn test_parse_github_full() {
         let source = ModSource::parse("github:user/repo:mods/my_mod@dev").unwrap();
         match source {
-            ModSource::GitHub { repo, subdir, branch } => {
+            ModSource::GitHub { repo, subdir, branch, .. } => {
                 assert_eq!(repo, "user/repo");
                 assert_eq!(subdir, Some("mods/my_mod".to_string()));
                 assert_eq!(branch, Some("dev".to_string()));
@@ -187,4 +277,70 @@ mod tests {
             _ => panic!("Expected GitHub source"),
         }
     }
+
+    #[test]
+    fn test_parse_list_line() {
+        let (source, overrides) =
+            parse_list_line("github:user/repo@dev | size=50, flag=true, label=Big Loot").unwrap();
+        match source {
+            ModSource::GitHub { repo, branch, .. } => {
+                assert_eq!(repo, "user/repo");
+                assert_eq!(branch.as_deref(), Some("dev"));
+            }
+            _ => panic!("Expected GitHub source"),
+        }
+        assert_eq!(overrides["size"], serde_json::json!(50));
+        assert_eq!(overrides["flag"], serde_json::json!(true));
+        assert_eq!(overrides["label"], serde_json::json!("Big Loot"));
+
+        let (source, overrides) =
+            parse_list_line("github:user/repo@sha:3f2a9c1   # pinned | not=an option").unwrap();
+        assert!(matches!(source, ModSource::GitHub { commit: Some(c), .. } if c == "3f2a9c1"));
+        assert!(overrides.is_empty());
+
+        // Plain lines are unchanged
+        let (source, overrides) = parse_list_line("./mods/my_mod").unwrap();
+        assert!(matches!(source, ModSource::Local { path } if path == Path::new("./mods/my_mod")));
+        assert!(overrides.is_empty());
+
+        assert!(parse_list_line("./mods/my_mod | size").is_err());
+        assert!(parse_list_line("./mods/my_mod | =1").is_err());
+    }
+
+    #[test]
+    fn test_parse_github_commit() {
+        let source = ModSource::parse("github:user/repo:mods/my_mod@sha:0123ABC").unwrap();
+        match source {
+            ModSource::GitHub { repo, subdir, branch, commit } => {
+                assert_eq!(repo, "user/repo");
+                assert_eq!(subdir, Some("mods/my_mod".to_string()));
+                assert_eq!(branch, None);
+                assert_eq!(commit, Some("0123ABC".to_string()));
+            }
+            _ => panic!("Expected GitHub source"),
+        }
+
+        let full = "0123456789abcdef0123456789abcdef01234567";
+        match ModSource::parse(&format!("github:user/repo@{}", full)).unwrap() {
+            ModSource::GitHub { branch, commit, .. } => {
+                assert_eq!(branch, None);
+                assert_eq!(commit.as_deref(), Some(full));
+            }
+            _ => panic!("Expected GitHub source"),
+        }
+
+        assert!(ModSource::parse("github:user/repo@sha:abc").is_err());
+        assert!(ModSource::parse("github:user/repo@sha:release-1").is_err());
+
+        // Without the prefix, anything short of a full SHA is a branch, even when it's all digits
+        for branch_name in ["abc123", "release-1", "deadbeefz", "20240101", "3f2a9c1"] {
+            match ModSource::parse(&format!("github:user/repo@{}", branch_name)).unwrap() {
+                ModSource::GitHub { branch, commit, .. } => {
+                    assert_eq!(branch.as_deref(), Some(branch_name));
+                    assert_eq!(commit, None);
+                }
+                _ => panic!("Expected GitHub source"),
+            }
+        }
+    }
 }
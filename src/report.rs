@@ -0,0 +1,243 @@
+//! Build report (`infinite-report.json`) describing the files an install produced
+
+use crate::file_system::FileManager;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// File name of the build report, written next to `modinfo.json`
+pub const REPORT_FILE_NAME: &str = "infinite-report.json";
+
+/// Record of an install's output, used by `verify` to detect drift
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildReport {
+    /// Version of infinite that produced the build
+    pub version: String,
+    /// Unix timestamp (seconds) of the build
+    pub generated_at: u64,
+    /// IDs of the mods that were installed, in order
+    pub mods: Vec<String>,
+    /// Output files (relative to the output path, forward slashes) -> SHA-256 hex digest
+    pub files: BTreeMap<String, String>,
+    /// Mod ID -> directory the mod was loaded from (used by `repair`)
+    #[serde(default)]
+    pub mod_paths: BTreeMap<String, PathBuf>,
+    /// Normalized output path -> IDs of the mods that wrote it, in write order
+    #[serde(default)]
+    pub producers: BTreeMap<String, Vec<String>>,
+}
+
+/// Result of comparing an output directory against its report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyResult {
+    /// Files listed in the report but absent on disk
+    pub missing: Vec<String>,
+    /// Files whose content no longer matches the recorded hash
+    pub modified: Vec<String>,
+    /// Files on disk that the report does not list
+    pub extra: Vec<String>,
+}
+
+/// What `repair` has to redo to restore drifted output files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairPlan {
+    /// Missing or modified files to restore
+    pub files: Vec<String>,
+    /// Mods that wrote any of those files, in build order
+    pub mods: Vec<String>,
+    /// Files no mod wrote, restored by re-extracting the game file
+    pub extract: Vec<String>,
+}
+
+impl VerifyResult {
+    /// Whether the output matches the report exactly
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl BuildReport {
+    /// Build a report by hashing every file currently under `output_path`
+    pub fn from_output_dir(output_path: &Path, mods: Vec<String>) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for relative in list_output_files(output_path)? {
+            let hash = hash_file(&output_path.join(&relative))?;
+            files.insert(relative, hash);
+        }
+
+        let generated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            generated_at,
+            mods,
+            files,
+            mod_paths: BTreeMap::new(),
+            producers: BTreeMap::new(),
+        })
+    }
+
+    /// Path of the report for a given output path (its parent directory)
+    pub fn path_for(output_path: &Path) -> PathBuf {
+        output_path
+            .parent()
+            .unwrap_or(output_path)
+            .join(REPORT_FILE_NAME)
+    }
+
+    /// Write the report next to the output directory
+    pub fn save(&self, output_path: &Path) -> Result<PathBuf> {
+        let report_path = Self::path_for(output_path);
+        if let Some(parent) = report_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&report_path, content)
+            .with_context(|| format!("Failed to write report: {}", report_path.display()))?;
+        Ok(report_path)
+    }
+
+    /// Load the report belonging to an output directory
+    pub fn load(output_path: &Path) -> Result<Self> {
+        let report_path = Self::path_for(output_path);
+        let content = std::fs::read_to_string(&report_path)
+            .with_context(|| format!("Failed to read report: {}", report_path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse report: {}", report_path.display()))
+    }
+
+    /// Work out which mods must re-run to restore the missing and modified files of `result`
+    pub fn repair_plan(&self, result: &VerifyResult) -> RepairPlan {
+        let mut plan = RepairPlan::default();
+        let mut needed = std::collections::BTreeSet::new();
+
+        for file in result.missing.iter().chain(&result.modified) {
+            plan.files.push(file.clone());
+            match self.producers.get(&FileManager::normalize_path(file)) {
+                Some(mods) => needed.extend(mods.iter().cloned()),
+                None => plan.extract.push(file.clone()),
+            }
+        }
+
+        plan.mods = self.mods.iter().filter(|id| needed.contains(*id)).cloned().collect();
+        plan
+    }
+
+    /// Compare the files on disk against this report
+    pub fn verify(&self, output_path: &Path) -> Result<VerifyResult> {
+        let mut result = VerifyResult::default();
+
+        for (relative, expected) in &self.files {
+            let full_path = output_path.join(relative);
+            if !full_path.is_file() {
+                result.missing.push(relative.clone());
+            } else if &hash_file(&full_path)? != expected {
+                result.modified.push(relative.clone());
+            }
+        }
+
+        if output_path.exists() {
+            for relative in list_output_files(output_path)? {
+                if !self.files.contains_key(&relative) {
+                    result.extra.push(relative);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// SHA-256 hex digest of a file's content
+pub fn hash_file(path: &Path) -> Result<String> {
+    let content = std::fs::read(path)
+        .with_context(|| format!("Failed to read file for hashing: {}", path.display()))?;
+    Ok(hash_bytes(&content))
+}
+
+/// SHA-256 hex digest (lowercase), as used for report entries and `infinite.hashString`
+pub fn hash_bytes(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// All files under `output_path`, relative and with forward slashes, sorted
+fn list_output_files(output_path: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(output_path).min_depth(1) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let relative = entry
+                .path()
+                .strip_prefix(output_path)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            files.push(relative);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_detects_drift() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("Infinite.mpq").join("data");
+        fs::create_dir_all(output.join("global/excel")).unwrap();
+        fs::write(output.join("global/excel/misc.txt"), "a\tb\n").unwrap();
+        fs::write(output.join("global/excel/armor.txt"), "c\td\n").unwrap();
+
+        let report = BuildReport::from_output_dir(&output, vec!["TestMod".to_string()]).unwrap();
+        report.save(&output).unwrap();
+        let report = BuildReport::load(&output).unwrap();
+        assert!(report.verify(&output).unwrap().is_clean());
+
+        fs::write(output.join("global/excel/misc.txt"), "changed\n").unwrap();
+        fs::remove_file(output.join("global/excel/armor.txt")).unwrap();
+        fs::write(output.join("global/excel/extra.txt"), "x\n").unwrap();
+
+        let result = report.verify(&output).unwrap();
+        assert_eq!(result.modified, vec!["global/excel/misc.txt"]);
+        assert_eq!(result.missing, vec!["global/excel/armor.txt"]);
+        assert_eq!(result.extra, vec!["global/excel/extra.txt"]);
+    }
+
+    #[test]
+    fn test_repair_plan_selects_producing_mods() {
+        let report = BuildReport {
+            version: "0.0.0".to_string(),
+            generated_at: 0,
+            mods: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            files: BTreeMap::new(),
+            mod_paths: BTreeMap::new(),
+            producers: [
+                ("global/excel/misc.txt".to_string(), vec!["c".to_string(), "a".to_string()]),
+                ("global/excel/armor.txt".to_string(), vec!["b".to_string()]),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let result = VerifyResult {
+            missing: vec!["global/excel/Misc.txt".to_string()],
+            modified: vec!["global/excel/weapons.txt".to_string()],
+            extra: vec!["global/excel/armor.txt".to_string()],
+        };
+
+        let plan = report.repair_plan(&result);
+        assert_eq!(plan.files, vec!["global/excel/Misc.txt", "global/excel/weapons.txt"]);
+        assert_eq!(plan.mods, vec!["a", "c"]);
+        assert_eq!(plan.extract, vec!["global/excel/weapons.txt"]);
+    }
+}
@@ -5,10 +5,51 @@
 /// 1. Converting between native types (JS/Lua) and Rust types
 /// 2. Wrapping these functions with the appropriate runtime bindings
 use super::script_runtime::ScriptServices;
+use crate::handlers::{JsonFormat, TrailingNewline};
 use anyhow::Result;
 use serde_json::Value as JsonValue;
 use std::sync::Arc;
 
+/// Functions every runtime exposes on the `infinite` global (also available as `D2RMM`)
+///
+/// This is the common script API: a function only one runtime registers is a bug. Each
+/// runtime's tests compare the functions it registers against this list. `null` is the one
+/// value: the JSON null marker for `mergeJson` patches (a Lua table can't hold `nil`).
+pub const SCRIPT_API: &[&str] = &[
+    "allocateStringId",
+    "copyFile",
+    "deleteFile",
+    "error",
+    "extractFileById",
+    "extractFiles",
+    "forEachTsvRow",
+    "getConfig",
+    "getConfigJSON",
+    "getFeature",
+    "getFullVersion",
+    "getLanguages",
+    "getNextStringID",
+    "getVersion",
+    "glob",
+    "hashFile",
+    "hashString",
+    "isGameFile",
+    "jsonPatch",
+    "listDirectory",
+    "listOutputFiles",
+    "mergeJson",
+    "null",
+    "readJson",
+    "readTsv",
+    "readTxt",
+    "removeJsonKey",
+    "reportProgress",
+    "writeJson",
+    "writeJsonCompact",
+    "writeTsv",
+    "writeTxt",
+];
+
 /// Core Infinite API implementation
 ///
 /// All methods are synchronous and use block_in_place for async operations.
@@ -27,6 +68,11 @@ impl InfiniteApiCore {
         1.5
     }
 
+    /// Get Infinite version as `[major, minor, patch]`
+    pub fn get_full_version(&self) -> [u32; 3] {
+        [1, 5, 0]
+    }
+
     /// Read JSON file
     ///
     /// Returns a serde_json::Value that can be converted to the target type
@@ -49,6 +95,111 @@ impl InfiniteApiCore {
         self.services.write_json(path, data)
     }
 
+    /// Write JSON file with a chosen layout
+    ///
+    /// Backs `writeJsonCompact` and `writeJson(path, data, { compact = true })`.
+    pub fn write_json_as(&self, path: &str, data: &JsonValue, format: JsonFormat) -> Result<()> {
+        tracing::debug!("writeJson called with path: {}, format: {:?}", path, format);
+        self.services.write_json_as(path, data, format)
+    }
+
+    /// Apply RFC 6902 JSON Patch operations to a JSON file
+    ///
+    /// Operations are applied to the cached value, so patches from multiple mods compose
+    pub fn json_patch(&self, path: &str, operations: &JsonValue) -> Result<()> {
+        tracing::debug!("jsonPatch called with path: {}", path);
+        let result = self.services.json_patch(path, operations);
+        if let Err(ref e) = result {
+            tracing::error!("[MOD ERROR] {}", e);
+        }
+        result
+    }
+
+    /// Merge an RFC 7386 JSON Merge Patch into a JSON file
+    ///
+    /// Like `jsonPatch`, the patch applies to the cached value, so mods layer their edits.
+    pub fn merge_json(&self, path: &str, patch: &JsonValue) -> Result<()> {
+        tracing::debug!("mergeJson called with path: {}", path);
+        let result = self.services.merge_json(path, patch);
+        if let Err(ref e) = result {
+            tracing::error!("[MOD ERROR] {}", e);
+        }
+        result
+    }
+
+    /// Remove the node addressed by a JSON Pointer from a JSON file
+    pub fn remove_json_key(&self, path: &str, pointer: &str) -> Result<()> {
+        tracing::debug!("removeJsonKey called with path: {}, pointer: {}", path, pointer);
+        self.services.remove_json_key(path, pointer)
+    }
+
+    /// List output files (including pending cache writes) under a path prefix
+    pub fn list_output_files(&self, prefix: &str) -> Result<Vec<String>> {
+        tracing::debug!("listOutputFiles called with prefix: {}", prefix);
+        self.services.list_output_files(prefix)
+    }
+
+    /// Whether a path is a game file (CASC or game directory) rather than a mod-written one
+    pub fn is_game_file(&self, path: &str) -> Result<bool> {
+        tracing::debug!("isGameFile called with path: {}", path);
+        self.services.is_game_file(path)
+    }
+
+    /// Extract several game files into the output at once
+    pub fn extract_files(&self, paths: &[String]) -> Result<serde_json::Value> {
+        tracing::debug!("extractFiles called with {} paths", paths.len());
+        self.services.extract_files(paths)
+    }
+
+    /// Read a game file by its CASC file data ID, as text
+    pub fn extract_file_by_id(&self, fdid: u32) -> Result<String> {
+        tracing::debug!("extractFileById called with id: {}", fdid);
+        self.services.extract_file_by_id(fdid)
+    }
+
+    /// List files in the mod directory matching a glob pattern
+    pub fn glob(&self, pattern: &str) -> Result<Vec<String>> {
+        tracing::debug!("glob called with pattern: {}", pattern);
+        self.services.glob(pattern)
+    }
+
+    /// List the files under a directory of the mod, recursively
+    pub fn list_directory(&self, path: &str) -> Result<Vec<String>> {
+        tracing::debug!("listDirectory called with path: {}", path);
+        self.services.list_directory(path)
+    }
+
+    /// Get a build-level feature flag (`--feature key=value`)
+    pub fn get_feature(&self, key: &str) -> Option<String> {
+        tracing::debug!("getFeature called with key: {}", key);
+        self.services.get_feature(key)
+    }
+
+    /// Get the next string ID not used by the string tables (not stable across builds)
+    pub fn get_next_string_id(&self) -> Result<u64> {
+        tracing::debug!("getNextStringID called");
+        self.services.next_string_id()
+    }
+
+    /// Get a string ID for a logical name, stable across builds via the manifest
+    pub fn allocate_string_id(&self, name: &str) -> Result<u64> {
+        tracing::debug!("allocateStringId called with name: {}", name);
+        self.services.allocate_string_id(name)
+    }
+
+    /// Publish the script's progress for the CLI/GUI (cheap; does not yield)
+    pub fn report_progress(&self, current: f64, total: f64, label: Option<&str>) {
+        tracing::trace!("reportProgress called: {}/{} {:?}", current, total, label);
+        // Negative and NaN values saturate to 0
+        self.services.report_progress(current as u64, total as u64, label.unwrap_or_default());
+    }
+
+    /// Get the language codes available in the string tables
+    pub fn get_languages(&self) -> Result<Vec<String>> {
+        tracing::debug!("getLanguages called");
+        self.services.get_languages()
+    }
+
     /// Read TSV file
     ///
     /// Returns TSV data structure with headers and rows
@@ -71,24 +222,57 @@ impl InfiniteApiCore {
         self.services.write_tsv(path, data)
     }
 
+    /// Visit each row of a TSV file, writing the file back once if `visit` changed any row
+    ///
+    /// Returns the number of changed rows.
+    pub fn for_each_tsv_row(&self, path: &str, visit: impl FnMut(&mut TsvRow) -> Result<()>) -> Result<usize> {
+        tracing::debug!("forEachTsvRow called with path: {}", path);
+        self.services.for_each_tsv_row(path, visit)
+    }
+
     /// Read text file
     pub fn read_txt(&self, path: &str) -> Result<String> {
         tracing::debug!("readTxt called with path: {}", path);
         self.services.read_txt(path)
     }
 
+    /// SHA-256 hex digest of a string's bytes
+    pub fn hash_string(&self, content: &[u8]) -> String {
+        crate::report::hash_bytes(content)
+    }
+
+    /// SHA-256 hex digest of a file's current content (this build's version, else vanilla)
+    pub fn hash_file(&self, path: &str) -> Result<String> {
+        tracing::debug!("hashFile called with path: {}", path);
+        self.services.hash_file(path)
+    }
+
     /// Write text file
     pub fn write_txt(&self, path: &str, content: &str) -> Result<()> {
         tracing::debug!("writeTxt called with path: {}", path);
         self.services.write_txt(path, content)
     }
 
+    /// Write text file with a trailing newline policy
+    ///
+    /// Backs `writeTxt(path, content, { trailingNewline = true })`.
+    pub fn write_txt_as(&self, path: &str, content: &str, policy: TrailingNewline) -> Result<()> {
+        tracing::debug!("writeTxt called with path: {}, policy: {:?}", path, policy);
+        self.services.write_txt_as(path, content, policy)
+    }
+
     /// Copy file (with optional directory support)
     pub fn copy_file(&self, src: &str, dst: &str, is_directory: bool) -> Result<()> {
         tracing::debug!("copyFile called: {} -> {} (is_dir: {})", src, dst, is_directory);
         self.services.copy_file(src, dst, is_directory)
     }
 
+    /// Delete a file from the output; returns whether there was one
+    pub fn delete_file(&self, path: &str) -> Result<bool> {
+        tracing::debug!("deleteFile called with path: {}", path);
+        self.services.delete_file(path)
+    }
+
     /// Throw an error (for Infinite.error())
     ///
     /// This should be converted to the appropriate error type by each runtime
@@ -105,11 +289,33 @@ pub struct TsvData {
     pub rows: Vec<TsvRow>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TsvRow {
     pub data: std::collections::HashMap<String, String>,
 }
 
+/// A row-level difference between two versions of a TSV table
+///
+/// Rows are matched by their key column value; a key that appears more than once
+/// is matched by occurrence and reported as `key#2`, `key#3`, ...
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TsvChange {
+    /// Row only present in the new table
+    Added { key: String, row: TsvRow },
+    /// Row only present in the old table
+    Removed { key: String, row: TsvRow },
+    /// Row present in both with different cell values
+    Changed { key: String, cells: Vec<TsvCellChange> },
+}
+
+/// A single cell that differs between matched rows (missing cells compare as empty)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TsvCellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
 impl TsvData {
     pub fn from_file(path: &std::path::Path) -> Result<Self> {
         // Read file content
@@ -148,6 +354,87 @@ impl TsvData {
         Ok(Self { headers, rows })
     }
 
+    /// Compare this table (old) against `other` (new), matching rows by `key_column`
+    ///
+    /// If a table has no `key_column`, its rows are keyed by their 1-based row number.
+    /// Changes are listed in old-table order, followed by added rows in new-table order.
+    pub fn diff(&self, other: &TsvData, key_column: &str) -> Vec<TsvChange> {
+        let old_keyed = self.keyed_rows(key_column);
+        let new_keyed = other.keyed_rows(key_column);
+        let new_index: std::collections::HashMap<&str, usize> = new_keyed
+            .iter()
+            .enumerate()
+            .map(|(i, (key, _))| (key.as_str(), i))
+            .collect();
+        let mut matched = vec![false; new_keyed.len()];
+
+        // Columns of both tables, in old-then-new order
+        let mut columns = self.headers.clone();
+        for header in &other.headers {
+            if !columns.contains(header) {
+                columns.push(header.clone());
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (key, old_row) in old_keyed {
+            let Some(&pos) = new_index.get(key.as_str()) else {
+                changes.push(TsvChange::Removed { key, row: old_row.clone() });
+                continue;
+            };
+            matched[pos] = true;
+            let new_row = new_keyed[pos].1;
+
+            let cells: Vec<TsvCellChange> = columns
+                .iter()
+                .filter_map(|column| {
+                    let old = old_row.data.get(column).map(String::as_str).unwrap_or_default();
+                    let new = new_row.data.get(column).map(String::as_str).unwrap_or_default();
+                    (old != new).then(|| TsvCellChange {
+                        column: column.clone(),
+                        old: old.to_string(),
+                        new: new.to_string(),
+                    })
+                })
+                .collect();
+
+            if !cells.is_empty() {
+                changes.push(TsvChange::Changed { key, cells });
+            }
+        }
+
+        changes.extend(
+            new_keyed
+                .iter()
+                .zip(matched)
+                .filter(|(_, matched)| !matched)
+                .map(|((key, row), _)| TsvChange::Added { key: key.clone(), row: (*row).clone() }),
+        );
+        changes
+    }
+
+    /// Rows paired with their diff key (see [`TsvChange`])
+    fn keyed_rows(&self, key_column: &str) -> Vec<(String, &TsvRow)> {
+        let has_key = self.headers.iter().any(|h| h == key_column);
+        let mut seen: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let base = if has_key {
+                    row.data.get(key_column).cloned().unwrap_or_default()
+                } else {
+                    (i + 1).to_string()
+                };
+                let count = seen.entry(base.clone()).or_insert(0);
+                *count += 1;
+                let key = if *count == 1 { base } else { format!("{}#{}", base, count) };
+                (key, row)
+            })
+            .collect()
+    }
+
     pub fn write_to_file(&self, path: &std::path::Path) -> Result<()> {
         // Convert back to TSV handler format
         let mut data: Vec<Vec<String>> = Vec::new();
@@ -196,3 +483,55 @@ impl ConsoleApi {
         tracing::error!("[MOD] {}", msg);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(headers: &[&str], rows: &[&[&str]]) -> TsvData {
+        TsvData {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|row| TsvRow {
+                    data: headers
+                        .iter()
+                        .zip(row.iter())
+                        .map(|(h, v)| (h.to_string(), v.to_string()))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_tsv_diff() {
+        let old = table(
+            &["name", "maxstack"],
+            &[&["tbk", "20"], &["ibk", "20"], &["", ""], &["", ""], &["key", "12"]],
+        );
+        let new = table(
+            &["name", "maxstack", "spawnable"],
+            &[&["tbk", "80", "1"], &["", ""], &["", ""], &["key", "12", ""], &["gem", "1", "1"]],
+        );
+
+        let changes = old.diff(&new, "name");
+        assert_eq!(changes.len(), 3);
+
+        assert_eq!(
+            changes[0],
+            TsvChange::Changed {
+                key: "tbk".to_string(),
+                cells: vec![
+                    TsvCellChange { column: "maxstack".to_string(), old: "20".to_string(), new: "80".to_string() },
+                    TsvCellChange { column: "spawnable".to_string(), old: String::new(), new: "1".to_string() },
+                ],
+            }
+        );
+        assert!(matches!(&changes[1], TsvChange::Removed { key, .. } if key == "ibk"));
+        assert!(matches!(&changes[2], TsvChange::Added { key, .. } if key == "gem"));
+
+        // Duplicate (blank) keys are matched by occurrence
+        assert!(old.diff(&old, "name").is_empty());
+    }
+}
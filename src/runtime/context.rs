@@ -1,10 +1,38 @@
 use crate::file_system::FileManager;
-use crate::handlers::{JsonHandler, TextHandler, TsvHandler};
-use anyhow::Result;
+use crate::handlers::{decode_utf8_owned, JsonHandler, TsvHandler};
+use crate::runtime::ScriptLimits;
+use crate::string_ids::StringIdAllocator;
+use anyhow::{Context as _, Result};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Progress reported by the running script through `infinite.reportProgress`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptProgress {
+    /// Mod that reported it
+    pub mod_id: String,
+    pub current: u64,
+    pub total: u64,
+    pub label: String,
+}
+
+impl ScriptProgress {
+    /// Completed fraction in `0.0..=1.0` (0 when `total` is 0)
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.current as f64 / self.total as f64).clamp(0.0, 1.0) as f32
+        }
+    }
+}
+
+/// Progress slot shared by the running script and whoever displays it (CLI/GUI);
+/// cleared when a mod finishes
+pub type SharedProgress = Arc<std::sync::Mutex<Option<ScriptProgress>>>;
+
 /// Execution context for a mod
 pub struct Context {
     /// Mod identifier
@@ -27,6 +55,21 @@ pub struct Context {
 
     /// Whether this is a dry run (don't write files)
     pub dry_run: bool,
+
+    /// Whether script file access is confined to the mod and output directories
+    pub sandbox: bool,
+
+    /// Build-level feature flags (`--feature key=value`), shared by every mod in the build
+    pub features: HashMap<String, String>,
+
+    /// String ID allocator shared by every mod in the build (see [`crate::string_ids`])
+    pub string_ids: Arc<std::sync::Mutex<StringIdAllocator>>,
+
+    /// Where `infinite.reportProgress` publishes the script's progress
+    pub progress: SharedProgress,
+
+    /// How long (and how many instructions) the script may run before it is stopped
+    pub limits: ScriptLimits,
 }
 
 impl Context {
@@ -53,11 +96,10 @@ impl Context {
         }
 
         // Extract file from CASC if needed
-        let full_path = fm.ensure_extracted(file_path, &self.mod_id).await?;
+        let content = fm.ensure_extracted(file_path, &self.mod_id).await?;
 
-        // Read the file
-        let value = JsonHandler::read(&full_path)
-            .await
+        // Parse the file
+        let value = JsonHandler::parse_from_bytes(&content)
             .map_err(|e| anyhow::anyhow!("Failed to read JSON file '{}': {}", file_path, e))?;
 
         // Record the read operation
@@ -96,11 +138,10 @@ impl Context {
         }
 
         // Extract file from CASC if needed
-        let full_path = fm.ensure_extracted(file_path, &self.mod_id).await?;
+        let content = fm.ensure_extracted(file_path, &self.mod_id).await?;
 
-        // Read the file
-        let rows = TsvHandler::read(&full_path)
-            .await
+        // Parse the file
+        let rows = TsvHandler::parse_from_bytes(&content)
             .map_err(|e| anyhow::anyhow!("Failed to read TSV file '{}': {}", file_path, e))?;
 
         // Record the read operation
@@ -133,18 +174,17 @@ impl Context {
         // Try to read from cache first (for chained modifications)
         if let Ok(content) = fm.read_file_with_cache(file_path, &self.mod_id).await {
             // Parse from cached content
-            let text = String::from_utf8(content)
-                .map_err(|e| anyhow::anyhow!("Failed to parse cached text '{}' as UTF-8: {}", file_path, e))?;
+            let text = decode_utf8_owned(content)
+                .with_context(|| format!("Failed to parse cached text '{}' as UTF-8", file_path))?;
             return Ok(text);
         }
 
         // Extract file from CASC if needed
-        let full_path = fm.ensure_extracted(file_path, &self.mod_id).await?;
+        let content = fm.ensure_extracted(file_path, &self.mod_id).await?;
 
-        // Read the file
-        let content = TextHandler::read(&full_path)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to read text file '{}': {}", file_path, e))?;
+        // Decode the file
+        let content = decode_utf8_owned(content)
+            .with_context(|| format!("Failed to read text file '{}'", file_path))?;
 
         // Record the read operation
         fm.record_read(file_path, &self.mod_id);
@@ -176,27 +216,19 @@ impl Context {
         }
 
         let src_path = self.mod_path.join(src);
-        let dst_path = self.output_path.join(dst);
+        let mut fm = self.file_manager.write().await;
 
         // Check if destination exists
-        if !overwrite && tokio::fs::try_exists(&dst_path).await? {
+        if !overwrite && fm.output_contains(dst)? {
             tracing::debug!("Skipping copy (file exists): {}", dst);
             return Ok(());
         }
 
-        // Create parent directory
-        if let Some(parent) = dst_path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
-        }
-
         // Copy the file
-        tokio::fs::copy(&src_path, &dst_path)
+        let content = tokio::fs::read(&src_path)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to copy file '{}' -> '{}': {}", src, dst, e))?;
-
-        // Record the write operation
-        let mut fm = self.file_manager.write().await;
-        fm.record_write(dst, &self.mod_id);
+        fm.write_file_direct(dst, &content, &self.mod_id)?;
 
         Ok(())
     }
@@ -212,9 +244,9 @@ impl Context {
         let mut fm = self.file_manager.write().await;
 
         // Use the ensure_extracted method from FileManager
-        let extracted_path = fm.ensure_extracted(file_path, &self.mod_id).await?;
+        fm.ensure_extracted(file_path, &self.mod_id).await?;
 
-        tracing::info!("Extracted: {} -> {}", file_path, extracted_path.display());
+        tracing::info!("Extracted: {}", file_path);
 
         Ok(())
     }
@@ -9,24 +9,46 @@ pub struct ModExecutor;
 impl ModExecutor {
     /// Execute a mod's script using the appropriate runtime
     pub async fn execute_mod(mod_data: &LoadedMod, context: Arc<Context>) -> Result<()> {
+        // Respect the mod's master toggle (`__enabled` / `enabledByDefault`)
+        if !mod_data.is_enabled() {
+            tracing::info!("Skipping {}: disabled by its configuration", mod_data.id);
+            return Ok(());
+        }
+
+        // Every event logged while the script runs carries the mod's id
+        let span = tracing::info_span!("mod", mod_id = %mod_data.id);
+        let _entered = span.enter();
+
+        // Values out of range or of the wrong type would reach the script unchecked
+        if let Err(problems) = mod_data.config.validate_user_config(&mod_data.user_config) {
+            anyhow::bail!(
+                "Invalid configuration for mod '{}':\n  - {}",
+                mod_data.id,
+                problems.join("\n  - ")
+            );
+        }
+
         // Create script services from context
         let services = ScriptServices::from_context(context.clone());
 
         // Create appropriate runtime (Lua or JavaScript) based on mod files
-        let mut runtime = RuntimeFactory::create_runtime(&mod_data.path, services)?;
+        let result = RuntimeFactory::create_runtime(&mod_data.path, services).and_then(|mut runtime| {
+            // Setup API
+            runtime.setup_api()?;
 
-        // Setup API
-        runtime.setup_api()?;
+            // Setup config
+            runtime.setup_config(&mod_data.user_config)?;
 
-        // Setup config
-        runtime.setup_config(&mod_data.user_config)?;
+            // Execute the script
+            runtime.execute()?;
 
-        // Execute the script
-        runtime.execute()?;
+            // Cleanup
+            runtime.cleanup()
+        });
 
-        // Cleanup
-        runtime.cleanup()?;
+        // Progress belongs to this run only
+        *context.progress.lock().unwrap() = None;
 
-        Ok(())
+        result
     }
 }
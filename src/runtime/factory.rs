@@ -1,6 +1,15 @@
 use super::script_runtime::*;
-use anyhow::{bail, Result};
-use std::path::Path;
+use anyhow::{bail, Context as _, Result};
+use std::path::{Path, PathBuf};
+
+/// A mod ships `mod.js` but this build was compiled without the `js-runtime` feature
+///
+/// Kept as a distinct type so callers can group these failures under the single fix.
+#[derive(Debug, thiserror::Error)]
+#[error("JavaScript runtime not enabled. Recompile with --features js-runtime to use mod.js files.\nFound: {}", .script_path.display())]
+pub struct ScriptRuntimeUnavailable {
+    pub script_path: PathBuf,
+}
 
 /// Script runtime factory
 pub struct RuntimeFactory;
@@ -11,32 +20,51 @@ impl RuntimeFactory {
         mod_path: &Path,
         services: ScriptServices,
     ) -> Result<Box<dyn ScriptRuntime>> {
-        let lua_script = mod_path.join("mod.lua");
-        let js_script = mod_path.join("mod.js");
-
-        if lua_script.exists() {
-            tracing::info!("Detected Lua script: {}", lua_script.display());
-            Ok(Box::new(super::lua_runtime::LuaScriptRuntime::new(
-                mod_path, services,
-            )?))
-        } else if js_script.exists() {
-            #[cfg(feature = "js-runtime")]
-            {
-                tracing::info!("Detected JavaScript script: {}", js_script.display());
-                Ok(Box::new(super::js_runtime::JavaScriptRuntime::new(
+        match crate::mod_manager::loader::find_mod_script(mod_path) {
+            Some((lua_script, ScriptType::Lua)) => {
+                tracing::info!("Detected Lua script: {}", lua_script.display());
+                Ok(Box::new(super::lua_runtime::LuaScriptRuntime::new(
                     mod_path, services,
                 )?))
             }
-            #[cfg(not(feature = "js-runtime"))]
-            {
-                bail!(
-                    "JavaScript runtime not enabled. Recompile with --features js-runtime to use mod.js files.\nFound: {}",
-                    js_script.display()
-                );
+            Some((js_script, ScriptType::JavaScript)) => {
+                #[cfg(feature = "js-runtime")]
+                {
+                    tracing::info!("Detected JavaScript script: {}", js_script.display());
+                    Ok(Box::new(super::js_runtime::JavaScriptRuntime::new(
+                        mod_path, services,
+                    )?))
+                }
+                #[cfg(not(feature = "js-runtime"))]
+                {
+                    let _ = services;
+                    Err(ScriptRuntimeUnavailable { script_path: js_script }.into())
+                }
             }
-        } else {
-            bail!("No mod.lua or mod.js found in {:?}", mod_path);
+            None => bail!("No mod.lua or mod.js found in {:?}", mod_path),
+        }
+    }
+
+    /// Compile a mod's entry script without running it and report the first syntax error
+    pub fn check_syntax(script_path: &Path, script_type: ScriptType) -> Result<()> {
+        let source = std::fs::read_to_string(script_path)
+            .with_context(|| format!("Failed to read {}", script_path.display()))?;
+        let name = script_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match script_type {
+            ScriptType::Lua => super::lua_runtime::LuaScriptRuntime::check_syntax(&source, &name),
+            #[cfg(feature = "js-runtime")]
+            ScriptType::JavaScript => super::js_runtime::JavaScriptRuntime::check_syntax(
+                &source,
+                &name,
+                script_path.parent().unwrap_or(Path::new(".")),
+            ),
+            #[cfg(not(feature = "js-runtime"))]
+            ScriptType::JavaScript => Err(ScriptRuntimeUnavailable { script_path: script_path.to_path_buf() }.into()),
         }
+        .with_context(|| format!("Syntax error in {}", script_path.display()))
     }
 
     /// Explicitly create Lua runtime
@@ -62,3 +90,136 @@ impl RuntimeFactory {
         )?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::{FileManager, MemoryStore, OutputStore as _};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_check_syntax() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        let lua = mod_dir.path().join("mod.lua");
+        std::fs::write(&lua, "local x = 1\nif x then\n  print(x)\nend\n").unwrap();
+        RuntimeFactory::check_syntax(&lua, ScriptType::Lua).unwrap();
+
+        std::fs::write(&lua, "local x = 1\nif x\n  print(x)\nend\n").unwrap();
+        let err = format!("{:#}", RuntimeFactory::check_syntax(&lua, ScriptType::Lua).unwrap_err());
+        assert!(err.contains("Syntax error in") && err.contains("mod.lua:3:"), "{}", err);
+
+        #[cfg(feature = "js-runtime")]
+        {
+            let js = mod_dir.path().join("mod.js");
+            std::fs::write(mod_dir.path().join("helper.js"), "export const x = () => 1;\n").unwrap();
+            std::fs::write(&js, "import { x } from './helper.js';\nx();\n").unwrap();
+            RuntimeFactory::check_syntax(&js, ScriptType::JavaScript).unwrap();
+
+            std::fs::write(mod_dir.path().join("helper.js"), "export const x = () =>;\n").unwrap();
+            let err = format!("{:#}", RuntimeFactory::check_syntax(&js, ScriptType::JavaScript).unwrap_err());
+            assert!(err.contains("helper.js:1:") && err.contains("SyntaxError"), "{}", err);
+
+            std::fs::write(&js, "const a = 1;\nconst b = ;\n").unwrap();
+            let err = format!("{:#}", RuntimeFactory::check_syntax(&js, ScriptType::JavaScript).unwrap_err());
+            assert!(err.contains("mod.js:2:") && err.contains("SyntaxError"), "{}", err);
+        }
+    }
+
+    /// Run one mod script against a fresh in-memory build and return the written files
+    #[cfg(feature = "js-runtime")]
+    async fn run_in_memory(
+        file_name: &str,
+        script: &str,
+        create: fn(&Path, ScriptServices) -> Result<Box<dyn ScriptRuntime>>,
+    ) -> Vec<(String, Vec<u8>)> {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(mod_dir.path().join(file_name), script).unwrap();
+
+        let output = MemoryStore::new();
+        let mut fm = FileManager::new();
+        fm.set_output_store(output.clone());
+        fm.insert_game_file("global/excel/weapons.txt", "name\tlevel\tcost\r\nSword\t5\t100\r\nAxe\t7\t250\r\n");
+        fm.insert_game_file("global/excel/skills.json", r#"{ "fire": { "level": 1, "tags": ["hot"] } }"#);
+        let fm = Arc::new(tokio::sync::RwLock::new(fm));
+
+        let services = ScriptServices::new(mod_dir.path().to_path_buf(), PathBuf::new(), PathBuf::new(), fm.clone());
+        let mut runtime = create(mod_dir.path(), services).unwrap();
+        runtime.setup_api().unwrap();
+        let config = UserConfig::from([
+            ("bonus".to_string(), serde_json::json!(3)),
+            ("suffix".to_string(), serde_json::json!("+")),
+        ]);
+        runtime.setup_config(&config).unwrap();
+        runtime.execute().unwrap();
+        fm.write().await.flush_cache().await.unwrap();
+
+        let mut files = output.list().unwrap();
+        files.sort();
+        files.into_iter().map(|path| {
+            let content = output.get(&path).unwrap();
+            (path, content)
+        }).collect()
+    }
+
+    /// The same logical mod must produce the same build in both runtimes
+    #[cfg(feature = "js-runtime")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lua_and_js_produce_identical_output() {
+        let lua = r#"
+            local weapons = infinite.readTsv('global/excel/weapons.txt')
+            for _, row in ipairs(weapons.rows) do
+                row.cost = tostring(tonumber(row.cost) * 2)
+            end
+            infinite.writeTsv('global/excel/weapons.txt', weapons)
+            infinite.forEachTsvRow('global/excel/weapons.txt', function(row)
+                row.name = row.name .. config.suffix
+            end)
+
+            local skills = infinite.readJson('global/excel/skills.json')
+            skills.fire.level = skills.fire.level + config.bonus
+            table.insert(skills.fire.tags, 'bright')
+            infinite.writeJson('global/excel/skills.json', skills)
+            infinite.writeJsonCompact('global/excel/compact.json', skills)
+            infinite.jsonPatch('global/excel/compact.json', { { op = 'add', path = '/ice', value = 2 } })
+
+            local version = infinite.getFullVersion()
+            infinite.writeTxt('notes.txt', table.concat(version, '.') .. ' ' .. infinite.readTsv('global/excel/weapons.txt').headers[1]
+                .. ' ' .. infinite.hashString('abc'), { trailingNewline = true })
+        "#;
+        let js = r#"
+            const weapons = infinite.readTsv('global/excel/weapons.txt');
+            for (const row of weapons.rows) {
+                row.cost = String(Number(row.cost) * 2);
+            }
+            infinite.writeTsv('global/excel/weapons.txt', weapons);
+            infinite.forEachTsvRow('global/excel/weapons.txt', (row) => {
+                row.name = row.name + config.suffix;
+            });
+
+            const skills = infinite.readJson('global/excel/skills.json');
+            skills.fire.level = skills.fire.level + config.bonus;
+            skills.fire.tags.push('bright');
+            infinite.writeJson('global/excel/skills.json', skills);
+            infinite.writeJsonCompact('global/excel/compact.json', skills);
+            infinite.jsonPatch('global/excel/compact.json', [{ op: 'add', path: '/ice', value: 2 }]);
+
+            const version = infinite.getFullVersion();
+            infinite.writeTxt('notes.txt', version.join('.') + ' ' + infinite.readTsv('global/excel/weapons.txt').headers[0]
+                + ' ' + infinite.hashString('abc'), { trailingNewline: true });
+        "#;
+
+        let lua_output = run_in_memory("mod.lua", lua, RuntimeFactory::create_lua_runtime).await;
+        let js_output = run_in_memory("mod.js", js, RuntimeFactory::create_js_runtime).await;
+        assert_eq!(lua_output.len(), 4);
+        for ((lua_path, lua_content), (js_path, js_content)) in lua_output.iter().zip(&js_output) {
+            assert_eq!(lua_path, js_path);
+            assert_eq!(
+                String::from_utf8_lossy(lua_content),
+                String::from_utf8_lossy(js_content),
+                "{} differs between Lua and JavaScript",
+                lua_path
+            );
+        }
+        assert_eq!(lua_output.len(), js_output.len());
+    }
+}
@@ -1,8 +1,11 @@
 use super::script_runtime::*;
 use super::api::{InfiniteApiCore, ConsoleApi};
+use crate::handlers::{JsonFormat, TrailingNewline};
 use anyhow::Result;
-use rquickjs::{Context, Runtime, Value, Function, Object, Array, Ctx};
-use rquickjs::function::Func;
+use rquickjs::{Context, Runtime, Value, Function, Object, Array, Ctx, Module, Exception};
+use rquickjs::function::{Func, Opt};
+use rquickjs::loader::{Loader, Resolver};
+use rquickjs::module::Declared;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -11,6 +14,75 @@ fn to_js_error(e: anyhow::Error) -> rquickjs::Error {
     rquickjs::Error::new_from_js_message("Error", "RuntimeError", e.to_string())
 }
 
+/// Module name of the mod's entry script; imports resolve relative to it
+const MAIN_MODULE: &str = "mod.js";
+
+/// Resolves `import` specifiers to `.js` files inside the mod directory
+struct ModResolver {
+    mod_path: PathBuf,
+}
+
+impl Resolver for ModResolver {
+    fn resolve<'js>(&mut self, _ctx: &Ctx<'js>, base: &str, name: &str) -> rquickjs::Result<String> {
+        let path = resolve_module_path(base, name).ok_or_else(|| {
+            rquickjs::Error::new_resolving_message(base, name, "imports must stay inside the mod directory")
+        })?;
+        // Symlinks could still point outside the mod
+        if let (Ok(real), Ok(root)) = (self.mod_path.join(&path).canonicalize(), self.mod_path.canonicalize()) {
+            if !real.starts_with(&root) {
+                return Err(rquickjs::Error::new_resolving_message(base, name, "resolves outside the mod directory"));
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// Loads resolved module names from the mod directory
+struct ModLoader {
+    mod_path: PathBuf,
+}
+
+impl Loader for ModLoader {
+    fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> rquickjs::Result<Module<'js, Declared>> {
+        let source = std::fs::read_to_string(self.mod_path.join(name))
+            .map_err(|e| rquickjs::Error::new_loading_message(name, e.to_string()))?;
+        Module::declare(ctx.clone(), name, source)
+    }
+}
+
+/// Resolve `name` imported from module `base` to a `/`-separated path in the mod directory
+///
+/// `./x` and `../x` are relative to the importing module, bare names to the mod directory;
+/// `.js` is appended when missing. Absolute paths, drive letters and `..` that climbs out
+/// of the mod directory give `None`.
+fn resolve_module_path(base: &str, name: &str) -> Option<String> {
+    if name.starts_with(['/', '\\']) || name.contains(':') {
+        return None;
+    }
+    let mut parts: Vec<&str> = Vec::new();
+    if name.starts_with('.') {
+        parts.extend(base.split('/'));
+        parts.pop();
+    }
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    let mut path = parts.join("/");
+    if !path.ends_with(".js") && !path.ends_with(".mjs") {
+        path.push_str(".js");
+    }
+    Some(path)
+}
+
 pub struct JavaScriptRuntime {
     _runtime: Runtime,  // Keep alive but mark as intentionally unused
     context: Context,
@@ -19,9 +91,31 @@ pub struct JavaScriptRuntime {
 }
 
 impl JavaScriptRuntime {
+    /// Compile `source` as a module without running it, to catch syntax errors early
+    ///
+    /// QuickJS resolves imports while compiling, so the modules it imports from `mod_path`
+    /// are compiled (and must exist) too. Errors read like `mod.js:3:5 SyntaxError: ...`.
+    pub fn check_syntax(source: &str, module_name: &str, mod_path: &Path) -> Result<()> {
+        let runtime = Runtime::new()?;
+        runtime.set_loader(
+            ModResolver { mod_path: mod_path.to_path_buf() },
+            ModLoader { mod_path: mod_path.to_path_buf() },
+        );
+        let context = Context::full(&runtime)?;
+        context.with(|ctx| {
+            Module::declare(ctx.clone(), module_name, source)
+                .map(|_| ())
+                .map_err(|e| anyhow::anyhow!(describe_js_error(&ctx, e)))
+        })
+    }
+
     pub fn new(mod_path: &Path, services: ScriptServices) -> Result<Self> {
         // Create QuickJS runtime
         let runtime = Runtime::new()?;
+        runtime.set_loader(
+            ModResolver { mod_path: mod_path.to_path_buf() },
+            ModLoader { mod_path: mod_path.to_path_buf() },
+        );
         let context = Context::full(&runtime)?;
         let services_arc = Arc::new(services);
         let api_core = Arc::new(InfiniteApiCore::new(services_arc));
@@ -50,38 +144,98 @@ impl JavaScriptRuntime {
             // Register writeJson
             self.register_write_json(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
 
+            // Register jsonPatch
+            self.register_json_patch(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register mergeJson
+            self.register_merge_json(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register removeJsonKey
+            self.register_remove_json_key(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register listOutputFiles
+            self.register_list_output_files(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register isGameFile
+            self.register_is_game_file(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register extractFiles
+            self.register_extract_files(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register extractFileById
+            self.register_extract_file_by_id(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register glob
+            self.register_glob(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register listDirectory
+            self.register_list_directory(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register getFeature
+            self.register_get_feature(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register getConfig / getConfigJSON
+            self.register_get_config(&d2rmm, ctx.clone())?;
+
+            // Register getNextStringID
+            self.register_get_next_string_id(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register allocateStringId
+            self.register_allocate_string_id(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register reportProgress
+            self.register_report_progress(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
+            // Register getLanguages
+            self.register_get_languages(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
             // Register readTsv
             self.register_read_tsv(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
 
             // Register writeTsv
             self.register_write_tsv(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
 
+            // Register forEachTsvRow
+            self.register_for_each_tsv_row(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
             // Register readTxt
             self.register_read_txt(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
 
+            // Register hashString / hashFile
+            self.register_hash(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
             // Register writeTxt
             self.register_write_txt(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
 
             // Register copyFile
             self.register_copy_file(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
 
+            // Register deleteFile
+            self.register_delete_file(&d2rmm, ctx.clone(), Arc::clone(&api_core))?;
+
             // Register getVersion
             let api_core_ver = Arc::clone(&api_core);
             d2rmm.set("getVersion", Function::new(ctx.clone(), move |_ctx: Ctx| -> rquickjs::Result<f64> {
                 Ok(api_core_ver.get_version())
             })?)?;
 
+            // Register getFullVersion
+            let api_core_ver = Arc::clone(&api_core);
+            d2rmm.set("getFullVersion", Function::new(ctx.clone(), move |_ctx: Ctx| -> rquickjs::Result<Vec<u32>> {
+                Ok(api_core_ver.get_full_version().to_vec())
+            })?)?;
+
             // Register error - throws an error that stops execution
             let api_core_err = Arc::clone(&api_core);
             d2rmm.set("error", Function::new(ctx.clone(), move |ctx: Ctx, msg: String| -> rquickjs::Result<()> {
                 let _ = api_core_err.throw_error(&msg);
-                // Throw a JavaScript Error
-                let error_ctor: Function = ctx.globals().get("Error")?;
-                let _error: Value = error_ctor.call((msg,))?;
-                Err(rquickjs::Error::Exception)
+                // Throw a JavaScript Error, so the message and stack reach the report
+                Err(Exception::throw_message(&ctx, &msg))
             })?)?;
 
-            globals.set("D2RMM", d2rmm)?;
+            globals.set("D2RMM", d2rmm.clone())?;
+            // Also set as "infinite", like the Lua runtime
+            globals.set("infinite", d2rmm)?;
 
             // Register console
             self.register_console(ctx.clone())?;
@@ -103,12 +257,174 @@ impl JavaScriptRuntime {
     }
 
     fn register_write_json<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
-        let func = Func::from(move |ctx: Ctx<'js>, path: String, data: Value<'js>| -> rquickjs::Result<()> {
+        let core = Arc::clone(&api_core);
+        let func = Func::from(move |ctx: Ctx<'js>, path: String, data: Value<'js>, options: Opt<Object<'js>>| -> rquickjs::Result<()> {
             let json = rquickjs_to_json(ctx, &data)?;
-            api_core.write_json(&path, &json).map_err(to_js_error)?;
+            // writeJson(path, data, { compact: true })
+            let compact = match options.0 {
+                Some(options) => options.get::<_, Option<bool>>("compact")?.unwrap_or(false),
+                None => false,
+            };
+            let format = if compact { JsonFormat::Compact } else { JsonFormat::Pretty };
+            core.write_json_as(&path, &json, format).map_err(to_js_error)?;
             Ok(())
         });
         d2rmm.set("writeJson", func)?;
+
+        let func = Func::from(move |ctx: Ctx<'js>, path: String, data: Value<'js>| -> rquickjs::Result<()> {
+            let json = rquickjs_to_json(ctx, &data)?;
+            api_core.write_json_as(&path, &json, JsonFormat::Compact).map_err(to_js_error)?;
+            Ok(())
+        });
+        d2rmm.set("writeJsonCompact", func)?;
+        Ok(())
+    }
+
+    fn register_json_patch<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |ctx: Ctx<'js>, path: String, ops: Value<'js>| -> rquickjs::Result<()> {
+            let ops = rquickjs_to_json(ctx, &ops)?;
+            api_core.json_patch(&path, &ops).map_err(to_js_error)?;
+            Ok(())
+        });
+        d2rmm.set("jsonPatch", func)?;
+        Ok(())
+    }
+
+    fn register_merge_json<'js>(&self, d2rmm: &Object<'js>, ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |ctx: Ctx<'js>, path: String, patch: Value<'js>| -> rquickjs::Result<()> {
+            let patch = rquickjs_to_json(ctx, &patch)?;
+            api_core.merge_json(&path, &patch).map_err(to_js_error)
+        });
+        d2rmm.set("mergeJson", func)?;
+        // Same marker as in Lua, where it is needed; in JS it is plain `null`
+        d2rmm.set("null", Value::new_null(ctx))?;
+        Ok(())
+    }
+
+    fn register_remove_json_key<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, path: String, pointer: String| -> rquickjs::Result<()> {
+            api_core.remove_json_key(&path, &pointer).map_err(to_js_error)
+        });
+        d2rmm.set("removeJsonKey", func)?;
+        Ok(())
+    }
+
+    fn register_list_output_files<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, prefix: Opt<String>| -> rquickjs::Result<Vec<String>> {
+            api_core.list_output_files(prefix.0.as_deref().unwrap_or("")).map_err(to_js_error)
+        });
+        d2rmm.set("listOutputFiles", func)?;
+        Ok(())
+    }
+
+    fn register_is_game_file<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, path: String| -> rquickjs::Result<bool> {
+            api_core.is_game_file(&path).map_err(to_js_error)
+        });
+        d2rmm.set("isGameFile", func)?;
+        Ok(())
+    }
+
+    fn register_extract_files<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |ctx: Ctx<'js>, paths: Vec<String>| -> rquickjs::Result<Value<'js>> {
+            let result = api_core.extract_files(&paths).map_err(to_js_error)?;
+            json_to_rquickjs(ctx, &result)
+        });
+        d2rmm.set("extractFiles", func)?;
+        Ok(())
+    }
+
+    fn register_extract_file_by_id<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, fdid: u32| -> rquickjs::Result<String> {
+            api_core.extract_file_by_id(fdid).map_err(to_js_error)
+        });
+        d2rmm.set("extractFileById", func)?;
+        Ok(())
+    }
+
+    fn register_glob<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, pattern: String| -> rquickjs::Result<Vec<String>> {
+            api_core.glob(&pattern).map_err(to_js_error)
+        });
+        d2rmm.set("glob", func)?;
+        Ok(())
+    }
+
+    fn register_list_directory<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, path: Opt<String>| -> rquickjs::Result<Vec<String>> {
+            api_core.list_directory(path.0.as_deref().unwrap_or("")).map_err(to_js_error)
+        });
+        d2rmm.set("listDirectory", func)?;
+        Ok(())
+    }
+
+    fn register_get_feature<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, key: String| -> rquickjs::Result<Option<String>> {
+            Ok(api_core.get_feature(&key))
+        });
+        d2rmm.set("getFeature", func)?;
+        Ok(())
+    }
+
+    /// `getConfig(key, default)` and `getConfigJSON()`, reading the `config` global set by
+    /// `setup_config`
+    fn register_get_config<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>) -> rquickjs::Result<()> {
+        let func = Func::from(|ctx: Ctx<'js>, key: String, default: Opt<Value<'js>>| -> rquickjs::Result<Value<'js>> {
+            let config: Value = ctx.globals().get("config")?;
+            let value = match config.as_object() {
+                Some(config) => config.get::<_, Value>(key)?,
+                None => Value::new_undefined(ctx.clone()),
+            };
+            if value.is_undefined() {
+                return Ok(default.0.unwrap_or_else(|| Value::new_undefined(ctx)));
+            }
+            Ok(value)
+        });
+        d2rmm.set("getConfig", func)?;
+
+        let func = Func::from(|ctx: Ctx<'js>| -> rquickjs::Result<String> {
+            let config: Value = ctx.globals().get("config")?;
+            let json = if config.is_object() {
+                rquickjs_to_json(ctx, &config)?
+            } else {
+                serde_json::Value::Object(Default::default())
+            };
+            Ok(json.to_string())
+        });
+        d2rmm.set("getConfigJSON", func)?;
+        Ok(())
+    }
+
+    fn register_get_next_string_id<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>| -> rquickjs::Result<f64> {
+            api_core.get_next_string_id().map(|id| id as f64).map_err(to_js_error)
+        });
+        d2rmm.set("getNextStringID", func)?;
+        Ok(())
+    }
+
+    fn register_allocate_string_id<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, name: String| -> rquickjs::Result<f64> {
+            api_core.allocate_string_id(&name).map(|id| id as f64).map_err(to_js_error)
+        });
+        d2rmm.set("allocateStringId", func)?;
+        Ok(())
+    }
+
+    fn register_report_progress<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, current: f64, total: f64, label: Opt<String>| -> rquickjs::Result<()> {
+            api_core.report_progress(current, total, label.0.as_deref());
+            Ok(())
+        });
+        d2rmm.set("reportProgress", func)?;
+        Ok(())
+    }
+
+    fn register_get_languages<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>| -> rquickjs::Result<Vec<String>> {
+            api_core.get_languages().map_err(to_js_error)
+        });
+        d2rmm.set("getLanguages", func)?;
         Ok(())
     }
 
@@ -132,6 +448,34 @@ impl JavaScriptRuntime {
         Ok(())
     }
 
+    fn register_for_each_tsv_row<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |ctx: Ctx<'js>, path: String, callback: Function<'js>| -> rquickjs::Result<usize> {
+            // Errors thrown by the callback are rethrown as they are
+            let mut callback_error = None;
+            let changed = api_core.for_each_tsv_row(&path, |row| {
+                let visited = (|| {
+                    let row_obj = Object::new(ctx.clone())?;
+                    for (key, value) in &row.data {
+                        row_obj.set(key.as_str(), value.as_str())?;
+                    }
+                    callback.call::<_, ()>((row_obj.clone(),))?;
+                    row.data = rquickjs_row_data(&row_obj)?;
+                    Ok(())
+                })();
+                visited.map_err(|e: rquickjs::Error| {
+                    callback_error = Some(e);
+                    anyhow::anyhow!("forEachTsvRow callback failed")
+                })
+            });
+            if let Some(e) = callback_error {
+                return Err(e);
+            }
+            changed.map_err(to_js_error)
+        });
+        d2rmm.set("forEachTsvRow", func)?;
+        Ok(())
+    }
+
     fn register_read_txt<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
         let func = Func::from(move |_ctx: Ctx<'js>, path: String| -> rquickjs::Result<String> {
             api_core.read_txt(&path).map_err(to_js_error)
@@ -140,9 +484,29 @@ impl JavaScriptRuntime {
         Ok(())
     }
 
+    fn register_hash<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let core = Arc::clone(&api_core);
+        let hash_string = Func::from(move |_ctx: Ctx<'js>, content: String| -> rquickjs::Result<String> {
+            Ok(core.hash_string(content.as_bytes()))
+        });
+        d2rmm.set("hashString", hash_string)?;
+
+        let hash_file = Func::from(move |_ctx: Ctx<'js>, path: String| -> rquickjs::Result<String> {
+            api_core.hash_file(&path).map_err(to_js_error)
+        });
+        d2rmm.set("hashFile", hash_file)?;
+        Ok(())
+    }
+
     fn register_write_txt<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
-        let func = Func::from(move |_ctx: Ctx<'js>, path: String, content: String| -> rquickjs::Result<()> {
-            api_core.write_txt(&path, &content).map_err(to_js_error)
+        let func = Func::from(move |_ctx: Ctx<'js>, path: String, content: String, options: Opt<Object<'js>>| -> rquickjs::Result<()> {
+            // writeTxt(path, content, { trailingNewline: true })
+            let trailing_newline = match options.0 {
+                Some(options) => options.get::<_, Option<bool>>("trailingNewline")?.unwrap_or(false),
+                None => false,
+            };
+            let policy = if trailing_newline { TrailingNewline::ExactlyOne } else { TrailingNewline::Preserve };
+            api_core.write_txt_as(&path, &content, policy).map_err(to_js_error)
         });
         d2rmm.set("writeTxt", func)?;
         Ok(())
@@ -156,6 +520,14 @@ impl JavaScriptRuntime {
         Ok(())
     }
 
+    fn register_delete_file<'js>(&self, d2rmm: &Object<'js>, _ctx: Ctx<'js>, api_core: Arc<InfiniteApiCore>) -> rquickjs::Result<()> {
+        let func = Func::from(move |_ctx: Ctx<'js>, path: String| -> rquickjs::Result<bool> {
+            api_core.delete_file(&path).map_err(to_js_error)
+        });
+        d2rmm.set("deleteFile", func)?;
+        Ok(())
+    }
+
     fn register_console<'js>(&self, ctx: Ctx<'js>) -> rquickjs::Result<()> {
         let globals = ctx.globals();
         let console = Object::new(ctx.clone())?;
@@ -214,19 +586,21 @@ impl ScriptRuntime for JavaScriptRuntime {
     }
 
     fn execute(&mut self) -> Result<()> {
-        let script_path = self.mod_path.join("mod.js");
+        let script_path = self.mod_path.join(MAIN_MODULE);
         let script_content = std::fs::read_to_string(&script_path)
             .map_err(|e| anyhow::anyhow!("Failed to read mod.js: {}", e))?;
 
         tracing::debug!("Executing JavaScript from: {:?}", script_path);
         tracing::debug!("Script length: {} bytes", script_content.len());
 
+        // Run as a module so mods can `import` their own files; `D2RMM`, `config` and
+        // `console` stay globals
         self.context.with(|ctx| {
-            ctx.eval::<(), _>(script_content.as_bytes())
+            Module::evaluate(ctx.clone(), MAIN_MODULE, script_content)
+                .and_then(|promise| promise.finish::<()>())
                 .map_err(|e| {
-                    // Try to get more detailed error information
-                    let error_msg = format!("JavaScript execution error: {:?}", e);
-                    tracing::error!("{}", error_msg);
+                    let error_msg = describe_js_error(&ctx, e);
+                    tracing::error!("JavaScript execution error: {}", error_msg);
                     anyhow::anyhow!(error_msg)
                 })?;
             Ok::<(), anyhow::Error>(())
@@ -245,6 +619,55 @@ impl ScriptRuntime for JavaScriptRuntime {
     }
 }
 
+/// Describe a failed evaluation as `mod.js:42:7 TypeError: message`, followed by the stack
+///
+/// For `Error::Exception` the thrown value is taken from the context; values that aren't
+/// Error objects (`throw "text"`) are shown as they are.
+fn describe_js_error<'js>(ctx: &Ctx<'js>, error: rquickjs::Error) -> String {
+    if !error.is_exception() {
+        return error.to_string();
+    }
+    let thrown = ctx.catch();
+    let Some(exception) = thrown.as_object().cloned().and_then(Exception::from_object) else {
+        return value_to_string(ctx.clone(), &thrown)
+            .unwrap_or_else(|_| "uncaught exception".to_string());
+    };
+
+    let name: String = exception
+        .get::<_, Option<String>>("name")
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "Error".to_string());
+    let message = exception.message().unwrap_or_default();
+    let stack = exception.stack().unwrap_or_default();
+    let headline = match stack_location(&stack) {
+        Some(location) => format!("{} {}: {}", location, name, message),
+        None => format!("{}: {}", name, message),
+    };
+    let stack = stack.trim_end();
+    if stack.is_empty() {
+        headline
+    } else {
+        format!("{}\n{}", headline, stack)
+    }
+}
+
+/// `file:line:col` of the innermost stack frame that has a source position
+fn stack_location(stack: &str) -> Option<&str> {
+    stack.lines().find_map(|frame| {
+        let frame = frame.trim().strip_prefix("at ")?;
+        let location = match frame.rfind('(') {
+            Some(start) => frame[start + 1..].strip_suffix(')')?,
+            None => frame,
+        };
+        location
+            .rsplit(':')
+            .next()
+            .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            .map(|_| location)
+    })
+}
+
 // Helper functions for type conversion
 
 /// Format console arguments by calling toString() on each value
@@ -287,8 +710,8 @@ fn json_to_rquickjs<'js>(ctx: Ctx<'js>, json: &serde_json::Value) -> rquickjs::R
         JsonValue::Null => Ok(Value::new_undefined(ctx.clone())),
         JsonValue::Bool(b) => Ok(Value::new_bool(ctx.clone(), *b)),
         JsonValue::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Ok(Value::new_int(ctx.clone(), i as i32))
+            if let Some(i) = n.as_i64().and_then(|i| i32::try_from(i).ok()) {
+                Ok(Value::new_int(ctx.clone(), i))
             } else if let Some(f) = n.as_f64() {
                 Ok(Value::new_float(ctx.clone(), f))
             } else {
@@ -412,23 +835,174 @@ fn rquickjs_to_tsv<'js>(_ctx: Ctx<'js>, val: &Value<'js>) -> rquickjs::Result<Ts
         let row_val: Value = rows_arr.get(i)?;
         let row_obj = row_val.as_object().ok_or_else(|| rquickjs::Error::new_from_js_message("Error", "TypeError", "Row must be an object"))?;
 
-        let mut data = std::collections::HashMap::new();
-        for prop in row_obj.props::<String, Value>() {
-            let (key, value) = prop?;
-            let str_val = if let Some(s) = value.as_string() {
-                s.to_string()?
-            } else if let Some(i) = value.as_int() {
-                i.to_string()
-            } else if let Some(f) = value.as_float() {
-                f.to_string()
-            } else {
-                String::new()
-            };
-            data.insert(key, str_val);
+        rows.push(TsvRow { data: rquickjs_row_data(row_obj)? });
+    }
+
+    Ok(TsvData { headers, rows })
+}
+
+/// Column values of a TSV row object; numbers are stringified, other non-strings become empty
+fn rquickjs_row_data<'js>(row_obj: &Object<'js>) -> rquickjs::Result<std::collections::HashMap<String, String>> {
+    let mut data = std::collections::HashMap::new();
+    for prop in row_obj.props::<String, Value>() {
+        let (key, value) = prop?;
+        let str_val = if let Some(s) = value.as_string() {
+            s.to_string()?
+        } else if let Some(i) = value.as_int() {
+            i.to_string()
+        } else if let Some(f) = value.as_float() {
+            f.to_string()
+        } else {
+            String::new()
+        };
+        data.insert(key, str_val);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::{FileManager, MemoryStore};
+    use crate::mod_manager::ModConfig;
+
+    type SharedFileManager = Arc<tokio::sync::RwLock<FileManager>>;
+
+    /// A runtime with the API set up, writing to a fresh in-memory output over the given game files
+    fn runtime_with_files(files: &[(&str, &str)]) -> (JavaScriptRuntime, MemoryStore, SharedFileManager) {
+        runtime_with_services(files, |_| {})
+    }
+
+    /// Like `runtime_with_files`, with a chance to adjust the services (mod path, sandbox, limits) first
+    fn runtime_with_services(
+        files: &[(&str, &str)],
+        configure: impl FnOnce(&mut ScriptServices),
+    ) -> (JavaScriptRuntime, MemoryStore, SharedFileManager) {
+        let output = MemoryStore::new();
+        let mut fm = FileManager::new();
+        fm.set_output_store(output.clone());
+        for (path, content) in files {
+            fm.insert_game_file(path, *content);
         }
+        let fm = Arc::new(tokio::sync::RwLock::new(fm));
 
-        rows.push(TsvRow { data });
+        let mut services = ScriptServices::new(PathBuf::new(), PathBuf::new(), PathBuf::new(), fm.clone());
+        configure(&mut services);
+        let mut runtime = JavaScriptRuntime::new(&services.mod_path.clone(), services).unwrap();
+        runtime.setup_api().unwrap();
+        (runtime, output, fm)
     }
 
-    Ok(TsvData { headers, rows })
+    #[test]
+    fn test_number_config_arrives_as_integer() {
+        let config: ModConfig = serde_json::from_str(
+            r#"{ "name": "Test", "version": "1.0", "config": [{ "type": "number", "id": "value", "name": "Value", "default": 100 }] }"#,
+        )
+        .unwrap();
+        let mut user_config = config.generate_default_config();
+        config.normalize_user_config(&mut user_config);
+
+        let (mut runtime, _, _) = runtime_with_files(&[]);
+        runtime.setup_config(&user_config).unwrap();
+
+        runtime.context.with(|ctx| {
+            let value: Value = ctx.eval("config.value").unwrap();
+            assert_eq!(value.as_int(), Some(100));
+            let text: String = ctx.eval("String(config.value)").unwrap();
+            assert_eq!(text, "100");
+        });
+    }
+
+    #[test]
+    fn test_get_config() {
+        let mut user_config = UserConfig::new();
+        user_config.insert("level".into(), serde_json::json!(3));
+
+        let (mut runtime, _, _) = runtime_with_files(&[]);
+        runtime.setup_config(&user_config).unwrap();
+
+        runtime.context.with(|ctx| {
+            let ok: bool = ctx
+                .eval(
+                    r#"
+                    D2RMM.getConfig('level', 1) === 3
+                        && D2RMM.getConfig('missing', 'fallback') === 'fallback'
+                        && D2RMM.getConfig('missing') === undefined
+                        && JSON.parse(D2RMM.getConfigJSON()).level === 3
+                    "#,
+                )
+                .unwrap();
+            assert!(ok);
+        });
+    }
+
+    #[test]
+    fn test_resolve_module_path() {
+        assert_eq!(resolve_module_path("mod.js", "./lib/helpers").as_deref(), Some("lib/helpers.js"));
+        assert_eq!(resolve_module_path("lib/helpers.js", "./util.js").as_deref(), Some("lib/util.js"));
+        assert_eq!(resolve_module_path("lib/helpers.js", "../data").as_deref(), Some("data.js"));
+        assert_eq!(resolve_module_path("lib/helpers.js", "common").as_deref(), Some("common.js"));
+        assert_eq!(resolve_module_path("mod.js", "../outside"), None);
+        assert_eq!(resolve_module_path("mod.js", "/etc/passwd"), None);
+        assert_eq!(resolve_module_path("mod.js", "C:/Windows/x"), None);
+    }
+
+    #[test]
+    fn test_import_mod_modules() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(mod_dir.path().join("lib")).unwrap();
+        std::fs::write(
+            mod_dir.path().join("lib/helpers.js"),
+            "export const double = (x) => x * 2;\nexport const apiVisible = typeof D2RMM.getConfig === 'function';",
+        )
+        .unwrap();
+        std::fs::write(
+            mod_dir.path().join("mod.js"),
+            r#"
+            import { double, apiVisible } from './lib/helpers.js';
+            if (double(21) !== 42 || !apiVisible) throw new Error('import failed');
+            console.log('imported');
+            "#,
+        )
+        .unwrap();
+
+        let (mut runtime, _, _) =
+            runtime_with_services(&[], |services| services.mod_path = mod_dir.path().to_path_buf());
+        runtime.execute().unwrap();
+
+        std::fs::write(mod_dir.path().join("mod.js"), "import { x } from '../outside.js';").unwrap();
+        assert!(runtime.execute().is_err());
+    }
+
+    #[test]
+    fn test_errors_report_location() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        let (mut runtime, _, _) =
+            runtime_with_services(&[], |services| services.mod_path = mod_dir.path().to_path_buf());
+
+        std::fs::write(mod_dir.path().join("mod.js"), "const x = 1;\nfunction run() {\n  x();\n}\nrun();\n").unwrap();
+        let error = runtime.execute().unwrap_err().to_string();
+        assert!(error.starts_with("mod.js:3:"), "{}", error);
+        assert!(error.contains("TypeError: "), "{}", error);
+        assert!(error.contains("at run"), "{}", error);
+
+        std::fs::write(mod_dir.path().join("mod.js"), "D2RMM.error('bad config');\n").unwrap();
+        let error = runtime.execute().unwrap_err().to_string();
+        assert!(error.contains("Error: bad config"), "{}", error);
+
+        std::fs::write(mod_dir.path().join("mod.js"), "throw 'plain';\n").unwrap();
+        assert_eq!(runtime.execute().unwrap_err().to_string(), "plain");
+    }
+
+    #[test]
+    fn test_api_surface_matches_common_api() {
+        let (runtime, _, _) = runtime_with_files(&[]);
+
+        runtime.context.with(|ctx| {
+            let names: Vec<String> = ctx.eval("Object.keys(infinite).sort()").unwrap();
+            assert_eq!(names, crate::runtime::SCRIPT_API);
+            let same: bool = ctx.eval("infinite === D2RMM").unwrap();
+            assert!(same);
+        });
+    }
 }
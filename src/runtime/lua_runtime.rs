@@ -1,7 +1,9 @@
 use super::script_runtime::*;
 use super::api::{InfiniteApiCore, ConsoleApi};
+use crate::handlers::{JsonFormat, TrailingNewline};
 use anyhow::Result;
 use mlua::{Lua, Table, Value as LuaValue};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -9,11 +11,31 @@ pub struct LuaScriptRuntime {
     lua: Lua,
     mod_path: PathBuf,
     api_core: Arc<InfiniteApiCore>,
+    sandbox: bool,
+    /// Mod ID for error messages
+    mod_id: String,
+    limits: ScriptLimits,
 }
 
+/// Instructions between checks of the script limits
+const LIMIT_CHECK_INTERVAL: u32 = 10_000;
+
+/// Standard library globals that reach the filesystem, replaced in sandbox mode
+/// (`require` is always the mod-local one from `register_require`)
+const SANDBOX_BLOCKED_GLOBALS: &[&str] = &["io", "dofile", "loadfile", "package"];
+
+/// Registry name of the table caching the results of modules loaded by `require`
+const LOADED_MODULES_REGISTRY: &str = "infinite_loaded_modules";
+
+/// `os` functions that stay available in sandbox mode
+const SANDBOX_SAFE_OS: &[&str] = &["time", "clock", "date", "difftime"];
+
 impl LuaScriptRuntime {
     pub fn new(mod_path: &Path, services: ScriptServices) -> Result<Self> {
         let lua = Lua::new();
+        let sandbox = services.sandbox;
+        let mod_id = services.mod_id.clone();
+        let limits = services.limits;
         let services_arc = Arc::new(services);
         let api_core = Arc::new(InfiniteApiCore::new(services_arc));
 
@@ -21,8 +43,143 @@ impl LuaScriptRuntime {
             lua,
             mod_path: mod_path.to_path_buf(),
             api_core,
+            sandbox,
+            mod_id,
+            limits,
         })
     }
+
+    /// Compile `source` without running it, to catch syntax errors early
+    ///
+    /// Errors read like `mod.lua:3: unexpected symbol near 'x'`.
+    pub fn check_syntax(source: &str, chunk_name: &str) -> Result<()> {
+        Lua::new()
+            .load(source)
+            .set_name(format!("={}", chunk_name))
+            .into_function()?;
+        Ok(())
+    }
+
+    /// Abort the script once it passes its time or instruction limit
+    ///
+    /// The returned slot is filled with the reason when the hook stopped the script, so a
+    /// script that catches the error with `pcall` is still reported as stopped.
+    fn install_limit_hook(&self) -> Arc<std::sync::Mutex<Option<String>>> {
+        let stopped = Arc::new(std::sync::Mutex::new(None));
+        let ScriptLimits { timeout, max_instructions } = self.limits;
+        if timeout.is_none() && max_instructions.is_none() {
+            return stopped;
+        }
+
+        let started = std::time::Instant::now();
+        let executed = std::sync::atomic::AtomicU64::new(0);
+        let slot = Arc::clone(&stopped);
+        self.lua.set_hook(
+            mlua::HookTriggers::new().every_nth_instruction(LIMIT_CHECK_INTERVAL),
+            move |_lua, _debug| {
+                let count = executed.fetch_add(LIMIT_CHECK_INTERVAL as u64, std::sync::atomic::Ordering::Relaxed)
+                    + LIMIT_CHECK_INTERVAL as u64;
+                let reason = match (timeout, max_instructions) {
+                    (Some(timeout), _) if started.elapsed() > timeout => {
+                        format!("ran longer than {}s", timeout.as_secs_f64())
+                    }
+                    (_, Some(max)) if count > max => format!("executed more than {} instructions", max),
+                    _ => return Ok(()),
+                };
+                let mut slot = slot.lock().unwrap();
+                let reason = slot.get_or_insert(reason);
+                Err(mlua::Error::RuntimeError(format!("script stopped: {}", reason)))
+            },
+        );
+        stopped
+    }
+
+    /// Replace filesystem-reaching globals with stubs that log and raise an error
+    fn apply_sandbox(&self) -> Result<()> {
+        let globals = self.lua.globals();
+
+        for &name in SANDBOX_BLOCKED_GLOBALS {
+            let blocked = self.lua.create_function(move |_, _: mlua::MultiValue| -> mlua::Result<()> {
+                tracing::warn!("Sandbox blocked call to '{}'", name);
+                Err(mlua::Error::external(format!("'{}' is disabled by --sandbox", name)))
+            })?;
+
+            let value = match globals.get::<_, LuaValue>(name)? {
+                // Library tables: every field access is blocked
+                LuaValue::Table(_) => {
+                    let stub = self.lua.create_table()?;
+                    let meta = self.lua.create_table()?;
+                    meta.set("__index", self.lua.create_function(move |_, (_, key): (Table, String)| -> mlua::Result<()> {
+                        tracing::warn!("Sandbox blocked access to '{}.{}'", name, key);
+                        Err(mlua::Error::external(format!("'{}.{}' is disabled by --sandbox", name, key)))
+                    })?)?;
+                    stub.set_metatable(Some(meta));
+                    LuaValue::Table(stub)
+                }
+                _ => LuaValue::Function(blocked),
+            };
+            globals.set(name, value)?;
+        }
+
+        let os: Table = globals.get("os")?;
+        let safe_os = self.lua.create_table()?;
+        for &name in SANDBOX_SAFE_OS {
+            safe_os.set(name, os.get::<_, LuaValue>(name)?)?;
+        }
+        globals.set("os", safe_os)?;
+
+        Ok(())
+    }
+
+    /// Replace `require` with one that only loads `.lua` files from the mod directory
+    ///
+    /// `require("lib.util")` and `require("lib/util")` both load `lib/util.lua`. A module
+    /// runs once; later calls return its cached result (`true` if it returned nothing).
+    fn register_require(&self) -> Result<()> {
+        let mod_path = self.mod_path.clone();
+        self.lua.set_named_registry_value(LOADED_MODULES_REGISTRY, self.lua.create_table()?)?;
+
+        let require = self.lua.create_function(move |lua, name: String| {
+            let (key, path) = resolve_module(&mod_path, &name).map_err(mlua::Error::external)?;
+            let loaded: Table = lua.named_registry_value(LOADED_MODULES_REGISTRY)?;
+            let cached: LuaValue = loaded.get(key.as_str())?;
+            if !cached.is_nil() {
+                return Ok(cached);
+            }
+
+            let code = std::fs::read_to_string(&path).map_err(|e| {
+                mlua::Error::external(format!("module '{}' not found ({}): {}", name, path.display(), e))
+            })?;
+            let value: LuaValue = lua.load(&code).set_name(format!("{}.lua", key)).call(name.as_str())?;
+            let value = if value.is_nil() { LuaValue::Boolean(true) } else { value };
+            loaded.set(key.as_str(), value.clone())?;
+            Ok(value)
+        })?;
+        self.lua.globals().set("require", require)?;
+        Ok(())
+    }
+}
+
+/// Resolve a `require` name to its cache key (`lib/util`) and file in the mod directory
+///
+/// Names are split on `.`, `/` and `\`; empty parts (as in `..`, `/abs` or `a//b`) and
+/// drive letters are rejected, as are files that resolve outside the mod directory.
+fn resolve_module(mod_path: &Path, name: &str) -> Result<(String, PathBuf)> {
+    let stem = name.strip_suffix(".lua").unwrap_or(name);
+    let parts: Vec<&str> = stem.split(['.', '/', '\\']).collect();
+    if parts.iter().any(|part| part.is_empty() || part.contains(':')) {
+        anyhow::bail!("require('{}'): module names must be relative paths inside the mod directory", name);
+    }
+
+    let key = parts.join("/");
+    let path = mod_path.join(format!("{}.lua", key));
+    // Symlinks could still point outside the mod
+    if let (Ok(real), Ok(base)) = (path.canonicalize(), mod_path.canonicalize()) {
+        if !real.starts_with(&base) {
+            anyhow::bail!("require('{}') resolves outside the mod directory", name);
+        }
+    }
+    Ok((key, path))
 }
 
 impl ScriptRuntime for LuaScriptRuntime {
@@ -38,6 +195,12 @@ impl ScriptRuntime for LuaScriptRuntime {
             Ok(api_core.get_version())
         })?)?;
 
+        // Register getFullVersion
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("getFullVersion", self.lua.create_function(move |lua, ()| {
+            lua.create_sequence_from(api_core.get_full_version())
+        })?)?;
+
         // Register readJson
         let api_core = Arc::clone(&self.api_core);
         d2rmm.set("readJson", self.lua.create_function(move |lua, path: String| {
@@ -49,13 +212,151 @@ impl ScriptRuntime for LuaScriptRuntime {
 
         // Register writeJson
         let api_core = Arc::clone(&self.api_core);
-        d2rmm.set("writeJson", self.lua.create_function(move |lua, (path, data): (String, LuaValue)| {
+        d2rmm.set("writeJson", self.lua.create_function(move |lua, (path, data, options): (String, LuaValue, Option<Table>)| {
             let json = lua_value_to_json(lua, data)
                 .map_err(|e| mlua::Error::external(e))?;
-            api_core.write_json(&path, &json)
+            // writeJson(path, data, { compact = true })
+            let compact = match options {
+                Some(options) => options.get::<_, Option<bool>>("compact")?.unwrap_or(false),
+                None => false,
+            };
+            let format = if compact { JsonFormat::Compact } else { JsonFormat::Pretty };
+            api_core.write_json_as(&path, &json, format)
                 .map_err(|e| mlua::Error::external(e))
         })?)?;
 
+        // Register writeJsonCompact
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("writeJsonCompact", self.lua.create_function(move |lua, (path, data): (String, LuaValue)| {
+            let json = lua_value_to_json(lua, data)
+                .map_err(mlua::Error::external)?;
+            api_core.write_json_as(&path, &json, JsonFormat::Compact)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register jsonPatch
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("jsonPatch", self.lua.create_function(move |lua, (path, ops): (String, LuaValue)| {
+            let ops = lua_value_to_json(lua, ops)
+                .map_err(mlua::Error::external)?;
+            api_core.json_patch(&path, &ops)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register mergeJson
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("mergeJson", self.lua.create_function(move |lua, (path, patch): (String, LuaValue)| {
+            let patch = lua_value_to_json(lua, patch)
+                .map_err(mlua::Error::external)?;
+            api_core.merge_json(&path, &patch)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register null (a nil can't be stored in a patch table; this converts to JSON null)
+        d2rmm.set("null", LuaValue::LightUserData(mlua::LightUserData(std::ptr::null_mut())))?;
+
+        // Register removeJsonKey
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("removeJsonKey", self.lua.create_function(move |_lua, (path, pointer): (String, String)| {
+            api_core.remove_json_key(&path, &pointer)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register listOutputFiles
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("listOutputFiles", self.lua.create_function(move |_lua, prefix: Option<String>| {
+            api_core.list_output_files(prefix.as_deref().unwrap_or(""))
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register isGameFile
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("isGameFile", self.lua.create_function(move |_lua, path: String| {
+            api_core.is_game_file(&path)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register extractFiles
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("extractFiles", self.lua.create_function(move |lua, paths: Vec<String>| {
+            let result = api_core.extract_files(&paths)
+                .map_err(mlua::Error::external)?;
+            json_to_lua_value(lua, &result)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register extractFileById
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("extractFileById", self.lua.create_function(move |_lua, fdid: u32| {
+            api_core.extract_file_by_id(fdid)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register glob
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("glob", self.lua.create_function(move |_lua, pattern: String| {
+            api_core.glob(&pattern)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register listDirectory
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("listDirectory", self.lua.create_function(move |_lua, path: Option<String>| {
+            api_core.list_directory(path.as_deref().unwrap_or(""))
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register getFeature
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("getFeature", self.lua.create_function(move |_lua, key: String| {
+            Ok(api_core.get_feature(&key))
+        })?)?;
+
+        // Register getConfig / getConfigJSON (read the `config` global set by setup_config)
+        d2rmm.set("getConfig", self.lua.create_function(|lua, (key, default): (String, LuaValue)| {
+            let value = match lua.globals().get::<_, Option<Table>>("config")? {
+                Some(config) => config.get::<_, LuaValue>(key)?,
+                None => LuaValue::Nil,
+            };
+            Ok(if value.is_nil() { default } else { value })
+        })?)?;
+        d2rmm.set("getConfigJSON", self.lua.create_function(|lua, ()| {
+            let config = match lua.globals().get::<_, Option<Table>>("config")? {
+                Some(config) => lua_value_to_json(lua, LuaValue::Table(config))
+                    .map_err(mlua::Error::external)?,
+                None => serde_json::Value::Object(Default::default()),
+            };
+            Ok(config.to_string())
+        })?)?;
+
+        // Register getNextStringID
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("getNextStringID", self.lua.create_function(move |_lua, ()| {
+            api_core.get_next_string_id()
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register allocateStringId
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("allocateStringId", self.lua.create_function(move |_lua, name: String| {
+            api_core.allocate_string_id(&name)
+                .map_err(mlua::Error::external)
+        })?)?;
+
+        // Register reportProgress
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("reportProgress", self.lua.create_function(move |_lua, (current, total, label): (f64, f64, Option<String>)| {
+            api_core.report_progress(current, total, label.as_deref());
+            Ok(())
+        })?)?;
+
+        // Register getLanguages
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("getLanguages", self.lua.create_function(move |_lua, ()| {
+            api_core.get_languages()
+                .map_err(mlua::Error::external)
+        })?)?;
+
         // Register readTsv
         let api_core = Arc::clone(&self.api_core);
         d2rmm.set("readTsv", self.lua.create_function(move |lua, path: String| {
@@ -113,6 +414,32 @@ impl ScriptRuntime for LuaScriptRuntime {
                 .map_err(|e| mlua::Error::external(e))
         })?)?;
 
+        // Register forEachTsvRow
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("forEachTsvRow", self.lua.create_function(move |lua, (path, callback): (String, mlua::Function)| {
+            // Errors raised by the callback are rethrown as they are
+            let mut callback_error = None;
+            let changed = api_core.for_each_tsv_row(&path, |row| {
+                let visited = (|| {
+                    let row_table = lua.create_table()?;
+                    for (key, value) in &row.data {
+                        row_table.set(key.as_str(), value.as_str())?;
+                    }
+                    callback.call::<_, ()>(row_table.clone())?;
+                    row.data = row_table.pairs::<String, String>().collect::<mlua::Result<_>>()?;
+                    Ok(())
+                })();
+                visited.map_err(|e: mlua::Error| {
+                    callback_error = Some(e);
+                    anyhow::anyhow!("forEachTsvRow callback failed")
+                })
+            });
+            if let Some(e) = callback_error {
+                return Err(e);
+            }
+            changed.map_err(mlua::Error::external)
+        })?)?;
+
         // Register readTxt
         let api_core = Arc::clone(&self.api_core);
         d2rmm.set("readTxt", self.lua.create_function(move |_lua, path: String| {
@@ -120,10 +447,29 @@ impl ScriptRuntime for LuaScriptRuntime {
                 .map_err(|e| mlua::Error::external(e))
         })?)?;
 
+        // Register hashString
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("hashString", self.lua.create_function(move |_lua, content: mlua::String| {
+            Ok(api_core.hash_string(content.as_bytes()))
+        })?)?;
+
+        // Register hashFile
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("hashFile", self.lua.create_function(move |_lua, path: String| {
+            api_core.hash_file(&path)
+                .map_err(mlua::Error::external)
+        })?)?;
+
         // Register writeTxt
         let api_core = Arc::clone(&self.api_core);
-        d2rmm.set("writeTxt", self.lua.create_function(move |_lua, (path, content): (String, String)| {
-            api_core.write_txt(&path, &content)
+        d2rmm.set("writeTxt", self.lua.create_function(move |_lua, (path, content, options): (String, String, Option<Table>)| {
+            // writeTxt(path, content, { trailingNewline = true })
+            let trailing_newline = match options {
+                Some(options) => options.get::<_, Option<bool>>("trailingNewline")?.unwrap_or(false),
+                None => false,
+            };
+            let policy = if trailing_newline { TrailingNewline::ExactlyOne } else { TrailingNewline::Preserve };
+            api_core.write_txt_as(&path, &content, policy)
                 .map_err(|e| mlua::Error::external(e))
         })?)?;
 
@@ -134,6 +480,12 @@ impl ScriptRuntime for LuaScriptRuntime {
                 .map_err(|e| mlua::Error::external(e))
         })?)?;
 
+        // Register deleteFile
+        let api_core = Arc::clone(&self.api_core);
+        d2rmm.set("deleteFile", self.lua.create_function(move |_lua, path: String| {
+            api_core.delete_file(&path).map_err(mlua::Error::external)
+        })?)?;
+
         // Register error function
         d2rmm.set("error", self.lua.create_function(|_lua, msg: String| {
             tracing::error!("[Lua MOD ERROR] {}", msg);
@@ -164,6 +516,11 @@ impl ScriptRuntime for LuaScriptRuntime {
         })?)?;
         globals.set("console", console)?;
 
+        self.register_require()?;
+        if self.sandbox {
+            self.apply_sandbox()?;
+        }
+
         Ok(())
     }
 
@@ -185,7 +542,19 @@ impl ScriptRuntime for LuaScriptRuntime {
         let script_path = self.mod_path.join("mod.lua");
         let script = std::fs::read_to_string(&script_path)?;
 
-        self.lua.load(&script).set_name("mod.lua").exec()?;
+        let stopped = self.install_limit_hook();
+        let result = self.lua.load(&script).set_name("mod.lua").exec();
+        self.lua.remove_hook();
+
+        if let Some(reason) = stopped.lock().unwrap().take() {
+            let name = if self.mod_id.is_empty() { self.mod_path.display().to_string() } else { self.mod_id.clone() };
+            anyhow::bail!(
+                "Mod '{}' {} and was stopped (an endless loop?); raise the limit with --script-timeout",
+                name,
+                reason
+            );
+        }
+        result?;
         Ok(())
     }
 
@@ -199,8 +568,34 @@ impl ScriptRuntime for LuaScriptRuntime {
     }
 }
 
+/// Registry name of the weak-keyed table holding the original key order of each object
+/// table made by `json_to_lua_value`
+const KEY_ORDER_REGISTRY: &str = "infinite_json_key_order";
+
+/// Lua tables have no key order, so the order of a converted JSON object is kept on the side;
+/// `lua_value_to_json` writes those keys back in that order
+fn key_order_table(lua: &Lua) -> Result<Table<'_>> {
+    if let Ok(table) = lua.named_registry_value::<Table>(KEY_ORDER_REGISTRY) {
+        return Ok(table);
+    }
+    let table = lua.create_table()?;
+    let metatable = lua.create_table()?;
+    metatable.set("__mode", "k")?;
+    table.set_metatable(Some(metatable));
+    lua.set_named_registry_value(KEY_ORDER_REGISTRY, table.clone())?;
+    Ok(table)
+}
+
 // Helper function to convert serde_json::Value to mlua::Value
 fn json_to_lua_value<'lua>(lua: &'lua Lua, json: &serde_json::Value) -> Result<LuaValue<'lua>> {
+    json_to_lua_ordered(lua, json, &key_order_table(lua)?)
+}
+
+fn json_to_lua_ordered<'lua>(
+    lua: &'lua Lua,
+    json: &serde_json::Value,
+    key_order: &Table<'lua>,
+) -> Result<LuaValue<'lua>> {
     use serde_json::Value as JV;
 
     Ok(match json {
@@ -219,15 +614,18 @@ fn json_to_lua_value<'lua>(lua: &'lua Lua, json: &serde_json::Value) -> Result<L
         JV::Array(arr) => {
             let table = lua.create_table()?;
             for (i, item) in arr.iter().enumerate() {
-                table.set(i + 1, json_to_lua_value(lua, item)?)?;
+                table.set(i + 1, json_to_lua_ordered(lua, item, key_order)?)?;
             }
             LuaValue::Table(table)
         }
         JV::Object(obj) => {
             let table = lua.create_table()?;
-            for (k, v) in obj {
-                table.set(k.as_str(), json_to_lua_value(lua, v)?)?;
+            let keys = lua.create_table_with_capacity(obj.len(), 0)?;
+            for (i, (k, v)) in obj.iter().enumerate() {
+                table.set(k.as_str(), json_to_lua_ordered(lua, v, key_order)?)?;
+                keys.set(i + 1, k.as_str())?;
             }
+            key_order.set(table.clone(), keys)?;
             LuaValue::Table(table)
         }
     })
@@ -235,6 +633,10 @@ fn json_to_lua_value<'lua>(lua: &'lua Lua, json: &serde_json::Value) -> Result<L
 
 // Helper function to convert mlua::Value to serde_json::Value
 fn lua_value_to_json<'lua>(lua: &'lua Lua, val: LuaValue<'lua>) -> Result<serde_json::Value> {
+    lua_to_json_ordered(val, &key_order_table(lua)?)
+}
+
+fn lua_to_json_ordered<'lua>(val: LuaValue<'lua>, key_order: &Table<'lua>) -> Result<serde_json::Value> {
     use serde_json::Value as JV;
 
     Ok(match val {
@@ -270,17 +672,36 @@ fn lua_value_to_json<'lua>(lua: &'lua Lua, val: LuaValue<'lua>) -> Result<serde_
                 for i in 1..=max_idx {
                     match table.get::<_, LuaValue>(i) {
                         Ok(LuaValue::Nil) => break,
-                        Ok(val) => arr.push(lua_value_to_json(lua, val)?),
+                        Ok(val) => arr.push(lua_to_json_ordered(val, key_order)?),
                         Err(_) => break,
                     }
                 }
                 JV::Array(arr)
             } else {
-                // It's an object
-                let mut obj = serde_json::Map::new();
-                for pair in table.pairs::<String, LuaValue>() {
-                    let (key, value) = pair?;
-                    obj.insert(key, lua_value_to_json(lua, value)?);
+                // It's an object: keys read from JSON keep their order, keys added by the
+                // script follow in sorted order so the output is deterministic
+                let mut entries = Vec::new();
+                for pair in table.clone().pairs::<String, LuaValue>() {
+                    entries.push(pair?);
+                }
+                let original: Vec<String> = match key_order.get::<_, Option<Table>>(table)? {
+                    Some(keys) => keys.sequence_values::<String>().collect::<mlua::Result<_>>()?,
+                    None => Vec::new(),
+                };
+                let position: HashMap<&str, usize> =
+                    original.iter().enumerate().map(|(i, k)| (k.as_str(), i)).collect();
+                entries.sort_by(|(a, _), (b, _)| {
+                    match (position.get(a.as_str()), position.get(b.as_str())) {
+                        (Some(x), Some(y)) => x.cmp(y),
+                        (Some(_), None) => std::cmp::Ordering::Less,
+                        (None, Some(_)) => std::cmp::Ordering::Greater,
+                        (None, None) => a.cmp(b),
+                    }
+                });
+
+                let mut obj = serde_json::Map::with_capacity(entries.len());
+                for (key, value) in entries {
+                    obj.insert(key, lua_to_json_ordered(value, key_order)?);
                 }
                 JV::Object(obj)
             }
@@ -288,3 +709,449 @@ fn lua_value_to_json<'lua>(lua: &'lua Lua, val: LuaValue<'lua>) -> Result<serde_
         _ => JV::Null,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::{FileManager, MemoryStore};
+    use crate::mod_manager::ModConfig;
+
+    type SharedFileManager = Arc<tokio::sync::RwLock<FileManager>>;
+
+    /// A runtime with the API set up, writing to a fresh in-memory output over the given game files
+    fn runtime_with_files(files: &[(&str, &str)]) -> (LuaScriptRuntime, MemoryStore, SharedFileManager) {
+        runtime_with_services(files, |_| {})
+    }
+
+    /// Like `runtime_with_files`, with a chance to adjust the services (mod path, sandbox, limits) first
+    fn runtime_with_services(
+        files: &[(&str, &str)],
+        configure: impl FnOnce(&mut ScriptServices),
+    ) -> (LuaScriptRuntime, MemoryStore, SharedFileManager) {
+        let output = MemoryStore::new();
+        let mut fm = FileManager::new();
+        fm.set_output_store(output.clone());
+        for (path, content) in files {
+            fm.insert_game_file(path, *content);
+        }
+        let fm = Arc::new(tokio::sync::RwLock::new(fm));
+
+        let mut services = ScriptServices::new(PathBuf::new(), PathBuf::new(), PathBuf::new(), fm.clone());
+        configure(&mut services);
+        let mut runtime = LuaScriptRuntime::new(&services.mod_path.clone(), services).unwrap();
+        runtime.setup_api().unwrap();
+        (runtime, output, fm)
+    }
+
+    #[test]
+    fn test_number_config_arrives_as_integer() {
+        let config: ModConfig = serde_json::from_str(
+            r#"{ "name": "Test", "version": "1.0", "config": [{ "type": "number", "id": "value", "name": "Value", "default": 100 }] }"#,
+        )
+        .unwrap();
+        let mut user_config = config.generate_default_config();
+        config.normalize_user_config(&mut user_config);
+
+        let (mut runtime, _, _) = runtime_with_files(&[]);
+        runtime.setup_config(&user_config).unwrap();
+
+        let (kind, text): (String, String) = runtime
+            .lua
+            .load("return math.type(config.value), tostring(config.value)")
+            .eval()
+            .unwrap();
+        assert_eq!(kind, "integer");
+        assert_eq!(text, "100");
+    }
+
+    #[test]
+    fn test_endless_loop_is_stopped() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(mod_dir.path().join("mod.lua"), "while true do end").unwrap();
+
+        let (mut runtime, _, _) = runtime_with_services(&[], |services| {
+            services.mod_path = mod_dir.path().to_path_buf();
+            services.mod_id = "looper".into();
+            services.limits.timeout = Some(std::time::Duration::from_millis(200));
+        });
+        let error = runtime.execute().unwrap_err().to_string();
+        assert!(error.contains("'looper' ran longer than 0.2s"), "{}", error);
+
+        // Catching the error doesn't hide it
+        std::fs::write(mod_dir.path().join("mod.lua"), "pcall(function() while true do end end)").unwrap();
+        let (mut runtime, _, _) = runtime_with_services(&[], |services| {
+            services.mod_path = mod_dir.path().to_path_buf();
+            services.mod_id = "looper".into();
+            services.limits = ScriptLimits { timeout: None, max_instructions: Some(100_000) };
+        });
+        let error = runtime.execute().unwrap_err().to_string();
+        assert!(error.contains("executed more than 100000 instructions"), "{}", error);
+    }
+
+    #[test]
+    fn test_get_config() {
+        let mut user_config = UserConfig::new();
+        user_config.insert("level".into(), serde_json::json!(3));
+        user_config.insert("hideGold".into(), serde_json::json!(false));
+
+        let (mut runtime, _, _) = runtime_with_files(&[]);
+        assert_eq!(runtime.lua.load("return infinite.getConfigJSON()").eval::<String>().unwrap(), "{}");
+        runtime.setup_config(&user_config).unwrap();
+
+        runtime
+            .lua
+            .load(
+                r#"
+                assert(infinite.getConfig('level', 1) == 3)
+                assert(infinite.getConfig('hideGold', true) == false)
+                assert(infinite.getConfig('missing', 'fallback') == 'fallback')
+                assert(infinite.getConfig('missing') == nil)
+                "#,
+            )
+            .exec()
+            .unwrap();
+        let json: String = runtime.lua.load("return D2RMM.getConfigJSON()").eval().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::json!({ "level": 3, "hideGold": false }));
+    }
+
+    #[test]
+    fn test_api_surface_matches_common_api() {
+        let (runtime, _, _) = runtime_with_files(&[]);
+
+        let (names, same): (Vec<String>, bool) = runtime
+            .lua
+            .load(
+                r#"
+                local names = {}
+                for name in pairs(infinite) do table.insert(names, name) end
+                table.sort(names)
+                return names, rawequal(infinite, D2RMM)
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(names, crate::runtime::SCRIPT_API);
+        assert!(same);
+    }
+
+    #[test]
+    fn test_sandbox_blocks_filesystem_globals() {
+        let (runtime, _, _) = runtime_with_services(&[], |services| services.sandbox = true);
+
+        let (io_ok, dofile_ok, has_time, has_execute): (bool, bool, bool, bool) = runtime
+            .lua
+            .load("return pcall(function() return io.open('x') end), pcall(dofile, 'x'), os.time ~= nil, os.execute ~= nil")
+            .eval()
+            .unwrap();
+        assert!(!io_ok);
+        assert!(!dofile_ok);
+        assert!(has_time);
+        assert!(!has_execute);
+    }
+
+    #[test]
+    fn test_require_mod_modules() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(mod_dir.path().join("lib")).unwrap();
+        std::fs::write(
+            mod_dir.path().join("lib/helpers.lua"),
+            "loads = (loads or 0) + 1\nlocal M = {}\nfunction M.double(x) return x * 2 end\nreturn M",
+        )
+        .unwrap();
+        std::fs::write(mod_dir.path().join("setup.lua"), "configured = true").unwrap();
+        std::fs::write(
+            mod_dir.path().join("mod.lua"),
+            r#"
+            local helpers = require('lib.helpers')
+            assert(helpers.double(21) == 42)
+            assert(require('lib/helpers') == helpers)
+            assert(loads == 1)
+            assert(require('setup') == true and configured)
+            assert(not pcall(require, '../outside'))
+            assert(not pcall(require, '/etc/passwd'))
+            assert(not pcall(require, 'C:/Windows/x'))
+            assert(not pcall(require, 'missing'))
+            "#,
+        )
+        .unwrap();
+
+        let (mut runtime, _, _) = runtime_with_services(&[], |services| {
+            services.mod_path = mod_dir.path().to_path_buf();
+            services.sandbox = true;
+        });
+        runtime.execute().unwrap();
+    }
+
+    #[test]
+    fn test_report_progress_updates_shared_state() {
+        // A clone of the services shares their progress state
+        let mut services = None;
+        let (runtime, _, _) = runtime_with_services(&[], |s| services = Some(s.clone()));
+        let services = services.unwrap();
+
+        runtime
+            .lua
+            .load("for i = 1, 40 do infinite.reportProgress(i, 40, 'Rows') end")
+            .exec()
+            .unwrap();
+        let progress = services.progress().unwrap();
+        assert_eq!((progress.current, progress.total, progress.label.as_str()), (40, 40, "Rows"));
+        assert_eq!(progress.fraction(), 1.0);
+
+        runtime.lua.load("infinite.reportProgress(-1, 0)").exec().unwrap();
+        assert_eq!(services.progress().unwrap().fraction(), 0.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mod_script_against_memory_output() {
+        let (runtime, output, fm) = runtime_with_files(&[
+            ("global/excel/misc.txt", "Name\tDesc\nGem\t\"Red, shiny\"\n"),
+            ("global/excel/skills.json", r#"{ "fire": { "level": 1 } }"#),
+        ]);
+        runtime
+            .lua
+            .load(
+                r#"
+                local misc = D2RMM.readTsv('global\\excel\\misc.txt')
+                misc.rows[1].Name = 'Big Gem'
+                D2RMM.writeTsv('global/excel/misc.txt', misc)
+
+                local skills = D2RMM.readJson('global/excel/skills.json')
+                skills.fire.level = skills.fire.level + 1
+                D2RMM.writeJson('global/excel/skills.json', skills)
+
+                -- Later reads see the earlier writes
+                assert(D2RMM.readTsv('global/excel/misc.txt').rows[1].Desc == 'Red, shiny')
+                "#,
+            )
+            .exec()
+            .unwrap();
+        fm.write().await.flush_cache().await.unwrap();
+
+        assert_eq!(
+            output.get_string("global/excel/misc.txt").unwrap(),
+            "Name\tDesc\nBig Gem\t\"Red, shiny\""
+        );
+        let skills: serde_json::Value =
+            serde_json::from_slice(&output.get("global/excel/skills.json").unwrap()).unwrap();
+        assert_eq!(skills["fire"]["level"], 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_json_key_order_survives_lua() {
+        let (runtime, output, fm) = runtime_with_files(&[
+            ("local/lng/strings/ui.json", r#"[{"id":7,"Key":"x","enUS":"Old","zhTW":"舊"},{"zebra":1,"apple":2}]"#),
+        ]);
+        runtime
+            .lua
+            .load(
+                r#"
+                local strings = infinite.readJson('local/lng/strings/ui.json')
+                strings[1].enUS = 'New'
+                strings[2].mango = 3
+                strings[2].banana = 4
+                infinite.writeJson('local/lng/strings/ui.json', strings, { compact = true })
+                "#,
+            )
+            .exec()
+            .unwrap();
+        fm.write().await.flush_cache().await.unwrap();
+
+        // Original keys keep their order, added keys follow sorted
+        assert_eq!(
+            output.get_string("local/lng/strings/ui.json").unwrap(),
+            r#"[{"id":7,"Key":"x","enUS":"New","zhTW":"舊"},{"zebra":1,"apple":2,"banana":4,"mango":3}]"#
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_merge_json() {
+        let (runtime, output, fm) = runtime_with_files(&[
+            ("global/ui/layouts/hud.json", r#"{"name":"hud","fields":{"width":800,"legacy":true},"children":[1,2]}"#),
+        ]);
+        runtime
+            .lua
+            .load(
+                r#"
+                -- Two mods layering edits on the same file
+                infinite.mergeJson('global/ui/layouts/hud.json', { fields = { width = 1024, legacy = infinite.null } })
+                infinite.mergeJson('global/ui/layouts/hud.json', { fields = { height = 600 }, children = { 3 } })
+                assert(not pcall(infinite.mergeJson, 'global/ui/layouts/hud.json', { 1, 2 }))
+                "#,
+            )
+            .exec()
+            .unwrap();
+        fm.write().await.flush_cache().await.unwrap();
+
+        let hud: serde_json::Value =
+            serde_json::from_slice(&output.get("global/ui/layouts/hud.json").unwrap()).unwrap();
+        assert_eq!(
+            hud,
+            serde_json::json!({ "name": "hud", "fields": { "width": 1024, "height": 600 }, "children": [3] })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_utf16_text_keeps_encoding() {
+        use crate::handlers::TextEncoding;
+
+        let (runtime, output, fm) = runtime_with_files(&[]);
+        fm.write().await.insert_game_file("local/strings.txt", TextEncoding::Utf16Le.encode("Key\tText\nhp1\tPotion\n"));
+        runtime
+            .lua
+            .load(
+                r#"
+                local text = infinite.readTxt('local/strings.txt')
+                infinite.writeTxt('local/strings.txt', (text:gsub('Potion', 'Elixir')))
+                infinite.writeTxt('local/new.txt', 'plain')
+                "#,
+            )
+            .exec()
+            .unwrap();
+        fm.write().await.flush_cache().await.unwrap();
+
+        assert_eq!(
+            output.get("local/strings.txt").unwrap(),
+            TextEncoding::Utf16Le.encode("Key\tText\nhp1\tElixir\n")
+        );
+        assert_eq!(output.get_string("local/new.txt").unwrap(), "plain");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_write_json_compact() {
+        let (runtime, output, fm) = runtime_with_files(&[]);
+        runtime
+            .lua
+            .load(
+                r#"
+                infinite.writeJson('pretty.json', { a = 1 })
+                infinite.writeJson('option.json', { a = 1 }, { compact = true })
+                infinite.writeJsonCompact('compact.json', { a = { 1, 2 } })
+                "#,
+            )
+            .exec()
+            .unwrap();
+        fm.write().await.flush_cache().await.unwrap();
+
+        assert_eq!(output.get_string("pretty.json").unwrap(), "{\n  \"a\": 1\n}");
+        assert_eq!(output.get_string("option.json").unwrap(), r#"{"a":1}"#);
+        assert_eq!(output.get_string("compact.json").unwrap(), r#"{"a":[1,2]}"#);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_hash_helpers() {
+        let (runtime, _, _) = runtime_with_files(&[("global/excel/misc.txt", "abc")]);
+        runtime
+            .lua
+            .load(
+                r#"
+                local abc = 'ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad'
+                assert(infinite.hashString('abc') == abc)
+                assert(infinite.hashFile('global/excel/misc.txt') == abc)
+
+                -- Hashes follow this build's writes
+                infinite.writeTxt('global/excel/misc.txt', 'abcd')
+                assert(infinite.hashFile('global/excel/misc.txt') == infinite.hashString('abcd'))
+                "#,
+            )
+            .exec()
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_is_game_file() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(mod_dir.path().join("icon.png"), b"png").unwrap();
+        let (runtime, _, _) = runtime_with_services(&[("global/excel/misc.txt", "Name\n")], |services| {
+            services.mod_path = mod_dir.path().to_path_buf();
+        });
+        runtime
+            .lua
+            .load(
+                r#"
+                assert(infinite.isGameFile('global\\excel\\Misc.txt'))
+                assert(not infinite.isGameFile('icon.png'))
+
+                -- Still a game file after a mod rewrote it; new files are not
+                infinite.writeTxt('global/excel/misc.txt', infinite.readTxt('global/excel/misc.txt') .. 'Gem\n')
+                infinite.copyFile('icon.png', 'hd/icon.png')
+                assert(infinite.isGameFile('global/excel/misc.txt'))
+                assert(not infinite.isGameFile('hd/icon.png'))
+                "#,
+            )
+            .exec()
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_delete_file() {
+        let (runtime, _, _) = runtime_with_files(&[("global/excel/misc.txt", "Name\n")]);
+        runtime
+            .lua
+            .load(
+                r#"
+                assert(infinite.deleteFile('hd/missing.json') == false)
+
+                infinite.writeTxt('global/excel/misc.txt', 'Changed\n')
+                assert(infinite.deleteFile('global/excel/misc.txt'))
+                assert(#infinite.listOutputFiles('global') == 0)
+                -- Reading it again gives the vanilla file
+                assert(infinite.readTxt('global/excel/misc.txt') == 'Name\n')
+                "#,
+            )
+            .exec()
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_extract_file_by_id_without_casc() {
+        let (runtime, _, _) = runtime_with_files(&[]);
+        let message: String = runtime
+            .lua
+            .load("local ok, err = pcall(infinite.extractFileById, 1234); assert(not ok); return tostring(err)")
+            .eval()
+            .unwrap();
+        assert!(message.contains("file data ID 1234"), "{}", message);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_for_each_tsv_row() {
+        let (runtime, output, fm) = runtime_with_files(&[
+            ("global/excel/weapons.txt", "name\tlevel\r\nSword\t5\r\n\"Great\nAxe\"\t 7\r\nBow\t3\r\n"),
+            ("global/excel/levels.json", r#"{ "Sword": 9, "Bow": 4 }"#),
+        ]);
+        runtime
+            .lua
+            .load(
+                r#"
+                local names = {}
+                local changed = infinite.forEachTsvRow('global/excel/weapons.txt', function(row)
+                    table.insert(names, row.name)
+                    -- Other API calls work inside the callback
+                    local level = infinite.readJson('global/excel/levels.json')[row.name]
+                    if level then
+                        row.level = level
+                    end
+                end)
+                assert(changed == 2)
+                -- A quoted field spanning lines is still one row
+                assert(table.concat(names, ',') == 'Sword,Great\nAxe,Bow')
+                assert(infinite.forEachTsvRow('global/excel/weapons.txt', function(row) end) == 0)
+
+                local ok, err = pcall(infinite.forEachTsvRow, 'global/excel/weapons.txt', function(row)
+                    error('bad row')
+                end)
+                assert(not ok and tostring(err):find('bad row'))
+                "#,
+            )
+            .exec()
+            .unwrap();
+        fm.write().await.flush_cache().await.unwrap();
+
+        assert_eq!(
+            output.get_string("global/excel/weapons.txt").unwrap(),
+            "name\tlevel\r\nSword\t9\r\n\"Great\nAxe\"\t 7\r\nBow\t4\r\n"
+        );
+    }
+}
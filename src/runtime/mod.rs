@@ -8,8 +8,8 @@ pub mod api;
 #[cfg(feature = "js-runtime")]
 pub mod js_runtime;
 
-pub use context::Context;
+pub use context::{Context, ScriptProgress, SharedProgress};
 pub use executor::ModExecutor;
-pub use script_runtime::{ScriptRuntime, ScriptType, ScriptServices, UserConfig, TsvData, TsvRow};
-pub use factory::RuntimeFactory;
-pub use api::{InfiniteApiCore, ConsoleApi};
+pub use script_runtime::{ScriptRuntime, ScriptType, ScriptServices, ScriptLimits, UserConfig, TsvData, TsvRow};
+pub use factory::{RuntimeFactory, ScriptRuntimeUnavailable};
+pub use api::{InfiniteApiCore, ConsoleApi, TsvChange, TsvCellChange, SCRIPT_API};
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -43,6 +43,34 @@ impl std::fmt::Display for ScriptType {
     }
 }
 
+/// 默认的脚本运行时间上限
+pub const DEFAULT_SCRIPT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// 脚本运行限制, 防止死循环卡住整个安装 (目前由 Lua 运行时执行)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptLimits {
+    /// 运行时间上限 (墙钟时间, 包括 API 调用的耗时); None 表示不限制
+    pub timeout: Option<std::time::Duration>,
+    /// 执行的指令数上限; None 表示不限制
+    pub max_instructions: Option<u64>,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Some(DEFAULT_SCRIPT_TIMEOUT),
+            max_instructions: None,
+        }
+    }
+}
+
+impl ScriptLimits {
+    /// 不限制
+    pub fn unlimited() -> Self {
+        Self { timeout: None, max_instructions: None }
+    }
+}
+
 /// 脚本服务 - 提供给所有运行时的核心功能
 #[derive(Clone)]
 pub struct ScriptServices {
@@ -50,8 +78,58 @@ pub struct ScriptServices {
     pub output_path: PathBuf,
     pub game_path: PathBuf,
     pub file_manager: std::sync::Arc<tokio::sync::RwLock<crate::file_system::FileManager>>,
+    /// 沙箱模式: 读取限制在 mod 目录, 写入限制在输出目录 (--sandbox)
+    pub sandbox: bool,
+    /// 构建级功能开关 (--feature key=value)
+    pub features: std::collections::HashMap<String, String>,
+    /// 当前 mod 的标识符 (字符串 ID 清单按 mod 分组)
+    pub mod_id: String,
+    /// 试运行: 写入和复制只记录日志, 不进入缓存 (--dry-run)
+    pub dry_run: bool,
+    /// 运行时间和指令数上限 (--script-timeout)
+    pub limits: ScriptLimits,
+    /// 字符串表语言列表缓存 (首次调用 get_languages 时扫描)
+    languages: std::sync::Arc<std::sync::Mutex<Option<Vec<String>>>>,
+    /// 整个构建共享的字符串 ID 分配器
+    string_ids: std::sync::Arc<std::sync::Mutex<crate::string_ids::StringIdAllocator>>,
+    /// 脚本上报的进度 (由 CLI/GUI 读取显示)
+    progress: super::SharedProgress,
 }
 
+/// listDirectory 递归的最大深度 (相对于被列出的目录)
+const LIST_DIRECTORY_MAX_DEPTH: usize = 16;
+
+/// 用于探测语言列表的字符串表文件 (按顺序尝试, 所有字符串表共用同一组语言列)
+const STRING_TABLE_PROBES: &[&str] = &[
+    "local/lng/strings/item-names.json",
+    "local/lng/strings/ui.json",
+    "local/lng/strings/item-modifiers.json",
+];
+
+/// 分配字符串 ID 前扫描的字符串表 (收集游戏已占用的 ID)
+const STRING_TABLE_FILES: &[&str] = &[
+    "local/lng/strings/bnet.json",
+    "local/lng/strings/commands.json",
+    "local/lng/strings/item-gems.json",
+    "local/lng/strings/item-modifiers.json",
+    "local/lng/strings/item-nameaffixes.json",
+    "local/lng/strings/item-names.json",
+    "local/lng/strings/item-runes.json",
+    "local/lng/strings/keybinds.json",
+    "local/lng/strings/levels.json",
+    "local/lng/strings/mercenaries.json",
+    "local/lng/strings/monsters.json",
+    "local/lng/strings/npcs.json",
+    "local/lng/strings/objects.json",
+    "local/lng/strings/presence-states.json",
+    "local/lng/strings/quests.json",
+    "local/lng/strings/shrines.json",
+    "local/lng/strings/skills.json",
+    "local/lng/strings/ui-controller.json",
+    "local/lng/strings/ui.json",
+    "local/lng/strings/vo.json",
+];
+
 impl ScriptServices {
     pub fn new(
         mod_path: PathBuf,
@@ -64,6 +142,14 @@ impl ScriptServices {
             output_path,
             game_path,
             file_manager,
+            sandbox: false,
+            features: Default::default(),
+            mod_id: String::new(),
+            dry_run: false,
+            limits: ScriptLimits::default(),
+            languages: Default::default(),
+            string_ids: Default::default(),
+            progress: Default::default(),
         }
     }
 
@@ -74,12 +160,49 @@ impl ScriptServices {
             output_path: context.output_path.clone(),
             game_path: context.game_path.clone(),
             file_manager: context.file_manager.clone(),
+            sandbox: context.sandbox,
+            features: context.features.clone(),
+            mod_id: context.mod_id.clone(),
+            dry_run: context.dry_run,
+            limits: context.limits,
+            languages: Default::default(),
+            string_ids: context.string_ids.clone(),
+            progress: context.progress.clone(),
+        }
+    }
+
+    /// 文件操作记录中的执行者 (mod id, 未设置时为 "script")
+    fn owner(&self) -> &str {
+        if self.mod_id.is_empty() {
+            "script"
+        } else {
+            &self.mod_id
+        }
+    }
+
+    /// 试运行时记录本应执行的写操作, 返回 true 表示调用方应直接返回 (与 `Context` 一致)
+    fn skip_in_dry_run(&self, what: &str, path: &str) -> bool {
+        if self.dry_run {
+            tracing::info!("[DRY RUN] Would {}: {}", what, path);
         }
+        self.dry_run
+    }
+
+    /// 沙箱模式下拒绝逃逸出基准目录的路径 (绝对路径、盘符、越界的 `..`), 并记录被拦截的操作
+    fn check_sandbox(&self, operation: &str, path: &str) -> Result<()> {
+        if !self.sandbox || is_contained_path(path) {
+            return Ok(());
+        }
+
+        tracing::warn!("Sandbox blocked {} of '{}' (path escapes its base directory)", operation, path);
+        anyhow::bail!("{} blocked by --sandbox: '{}' escapes its base directory", operation, path)
     }
 
     /// 读取 JSON 文件
     pub fn read_json(&self, path: &str) -> Result<JsonValue> {
+        self.check_sandbox("readJson", path)?;
         let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
         let path = path.to_string();
 
         // Use block_in_place to run async code in a sync context
@@ -87,48 +210,268 @@ impl ScriptServices {
             tokio::runtime::Handle::current().block_on(async {
                 let mut fm = file_manager.write().await;
 
-                // Try to read from cache first
-                if let Ok(content) = fm.read_file_with_cache(&path, "script").await {
-                    let value = crate::handlers::JsonHandler::parse_from_bytes(&content)
-                        .map_err(|e| anyhow::anyhow!("Failed to parse JSON: {}", e))?;
+                // 尚未序列化的缓存 JSON 直接返回, 免去序列化再解析
+                if let Some(value) = fm.read_cached_json(&path, &owner) {
                     return Ok(value);
                 }
 
+                // Try to read from cache first
+                if let Ok(content) = fm.read_file_with_cache(&path, &owner).await {
+                    return Self::parse_json_lenient(&path, &content)
+                        .map_err(|e| anyhow::anyhow!("Failed to parse JSON: {}", e));
+                }
+
                 // Extract from CASC if needed
-                let full_path = fm.ensure_extracted(&path, "script").await?;
+                let content = fm.ensure_extracted(&path, &owner).await?;
 
-                // Read the file
-                let value = crate::handlers::JsonHandler::read(&full_path).await
+                // Parse the file
+                let value = Self::parse_json_lenient(&path, &content)
                     .map_err(|e| anyhow::anyhow!("Failed to read JSON: {}", e))?;
 
-                fm.record_read(&path, "script");
+                fm.record_read(&path, &owner);
                 Ok(value)
             })
         })
     }
 
-    /// 写入 JSON 文件
+    /// 解析 JSON, 解析失败时去掉注释后重试 (社区模板中常见 `//` 与 `/* */` 注释)
+    fn parse_json_lenient(path: &str, content: &[u8]) -> Result<JsonValue> {
+        let (value, stripped) = crate::handlers::JsonHandler::parse_lenient(content)?;
+        if stripped {
+            tracing::info!("Stripped comments to parse '{}'", path);
+        }
+        Ok(value)
+    }
+
+    /// 写入 JSON 文件 (格式化输出)
     pub fn write_json(&self, path: &str, data: &JsonValue) -> Result<()> {
+        self.write_json_as(path, data, crate::handlers::JsonFormat::Pretty)
+    }
+
+    /// 按指定格式写入 JSON 文件 (紧凑格式可减小大文件体积)
+    pub fn write_json_as(&self, path: &str, data: &JsonValue, format: crate::handlers::JsonFormat) -> Result<()> {
+        self.check_sandbox("writeJson", path)?;
+        if self.skip_in_dry_run("write JSON", path) {
+            return Ok(());
+        }
         let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
         let path = path.to_string();
         let data = data.clone();
 
         tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(async {
-                let content = crate::handlers::JsonHandler::to_bytes(&data)
-                    .map_err(|e| anyhow::anyhow!("Failed to serialize JSON: {}", e))?;
-
+                // 序列化推迟到 flush (或按字节读取) 时, 同一文件的多次写入只序列化一次
                 let mut fm = file_manager.write().await;
-                fm.write_file_to_cache(&path, content, "script");
+                fm.write_json_to_cache(&path, data, format, &owner);
 
                 Ok(())
             })
         })
     }
 
+    /// 对 JSON 文件应用 RFC 6902 JSON Patch
+    pub fn json_patch(&self, path: &str, operations: &JsonValue) -> Result<()> {
+        let mut data = self.read_json(path)?;
+
+        crate::handlers::JsonHandler::apply_patch(&mut data, operations)
+            .map_err(|e| anyhow::anyhow!("jsonPatch failed for '{}': {}", path, e))?;
+
+        self.write_json(path, &data)
+    }
+
+    /// 将 RFC 7386 JSON Merge Patch 合并进 JSON 文件 (对象递归合并, null 删除键)
+    ///
+    /// 补丁须为对象: 其他值按 RFC 会替换整个文件, 这通常是写错了
+    pub fn merge_json(&self, path: &str, patch: &JsonValue) -> Result<()> {
+        if !patch.is_object() {
+            anyhow::bail!("mergeJson failed for '{}': the patch must be an object", path);
+        }
+        let data = self.read_json(path)?;
+        self.write_json(path, &crate::handlers::JsonHandler::merge(&data, patch))
+    }
+
+    /// 删除 JSON 文件中 JSON Pointer (RFC 6901) 指向的节点
+    pub fn remove_json_key(&self, path: &str, pointer: &str) -> Result<()> {
+        let mut data = self.read_json(path)?;
+
+        crate::handlers::JsonHandler::remove_pointer(&mut data, pointer)
+            .map_err(|e| anyhow::anyhow!("removeJsonKey failed for '{}': {}", path, e))?;
+
+        self.write_json(path, &data)
+    }
+
+    /// 获取构建级功能开关的值 (未设置时返回 None)
+    pub fn get_feature(&self, key: &str) -> Option<String> {
+        self.features.get(key).cloned()
+    }
+
+    /// 下一个未被字符串表占用的 ID (不写入清单, 每次构建可能不同)
+    pub fn next_string_id(&self) -> Result<u64> {
+        self.scan_string_ids()?;
+        Ok(self.string_ids.lock().unwrap().next_id())
+    }
+
+    /// 为当前 mod 的逻辑名称分配字符串 ID, 优先复用上次构建清单中的 ID
+    pub fn allocate_string_id(&self, name: &str) -> Result<u64> {
+        self.scan_string_ids()?;
+        Ok(self.string_ids.lock().unwrap().allocate(&self.mod_id, name))
+    }
+
+    /// 首次分配前收集字符串表中已占用的 ID (读取时不持有分配器锁)
+    fn scan_string_ids(&self) -> Result<()> {
+        if self.string_ids.lock().unwrap().has_used_ids() {
+            return Ok(());
+        }
+
+        let mut used = std::collections::BTreeSet::new();
+        let mut found = false;
+        for table in STRING_TABLE_FILES {
+            match self.read_json(table) {
+                Ok(JsonValue::Array(entries)) => {
+                    found = true;
+                    used.extend(entries.iter().filter_map(|entry| entry.get("id")?.as_u64()));
+                }
+                Ok(_) => {}
+                Err(e) => tracing::debug!("Skipping string table {}: {}", table, e),
+            }
+        }
+        if !found {
+            anyhow::bail!("Failed to allocate string ID: no string tables could be read");
+        }
+
+        self.string_ids.lock().unwrap().set_used_ids(used);
+        Ok(())
+    }
+
+    /// 上报脚本进度 (只更新共享状态, 不阻塞脚本)
+    pub fn report_progress(&self, current: u64, total: u64, label: &str) {
+        *self.progress.lock().unwrap() = Some(super::ScriptProgress {
+            mod_id: self.mod_id.clone(),
+            current,
+            total,
+            label: label.to_string(),
+        });
+    }
+
+    /// 最近一次上报的进度
+    pub fn progress(&self) -> Option<super::ScriptProgress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    /// 获取字符串表中可用的语言代码 (例如 "enUS", "zhCN")
+    ///
+    /// 首次调用时扫描字符串表, 结果缓存在本服务实例中
+    pub fn get_languages(&self) -> Result<Vec<String>> {
+        let mut cached = self.languages.lock().unwrap();
+        if let Some(languages) = cached.as_ref() {
+            return Ok(languages.clone());
+        }
+
+        let mut last_error = None;
+        for probe in STRING_TABLE_PROBES {
+            match self.read_json(probe) {
+                Ok(table) => {
+                    let languages = collect_languages(&table);
+                    if !languages.is_empty() {
+                        tracing::debug!("Detected string-table languages from {}: {:?}", probe, languages);
+                        *cached = Some(languages.clone());
+                        return Ok(languages);
+                    }
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let reason = last_error
+            .map(|e| e.to_string())
+            .unwrap_or_else(|| "no language columns found".to_string());
+        anyhow::bail!("Failed to detect string-table languages: {}", reason)
+    }
+
+    /// 列出输出目录中以 prefix 开头的文件 (包括尚未写入磁盘的缓存文件)
+    pub fn list_output_files(&self, prefix: &str) -> Result<Vec<String>> {
+        let file_manager = self.file_manager.clone();
+
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                file_manager.read().await.list_output_files(prefix)
+            })
+        })
+    }
+
+    /// 列出 mod 目录中匹配 glob 模式的文件 (相对路径, `/` 分隔, 已排序)
+    ///
+    /// 无论是否 --sandbox, 模式和匹配结果都限制在 mod 目录内
+    pub fn glob(&self, pattern: &str) -> Result<Vec<String>> {
+        if !is_contained_path(pattern) {
+            anyhow::bail!("glob pattern '{}' must stay inside the mod directory", pattern);
+        }
+        let mod_base = self.mod_path.canonicalize()
+            .with_context(|| format!("Failed to resolve mod directory: {}", self.mod_path.display()))?;
+        let full_pattern = format!(
+            "{}/{}",
+            glob::Pattern::escape(&mod_base.to_string_lossy()),
+            pattern.replace('\\', "/")
+        );
+
+        let mut files = Vec::new();
+        for entry in glob::glob(&full_pattern).with_context(|| format!("Invalid glob pattern '{}'", pattern))? {
+            let path = entry?;
+            if !path.is_file() {
+                continue;
+            }
+            // 符号链接可能指向 mod 目录之外
+            let Ok(relative) = path.canonicalize()?.strip_prefix(&mod_base).map(Path::to_path_buf) else {
+                tracing::warn!("glob skipped '{}' (resolves outside the mod directory)", path.display());
+                continue;
+            };
+            let relative: Vec<_> = relative.iter().map(|part| part.to_string_lossy()).collect();
+            files.push(relative.join("/"));
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    /// 递归列出 mod 目录下某个目录中的文件 (相对 mod 目录的路径, `/` 分隔, 已排序)
+    ///
+    /// 最多深入 [`LIST_DIRECTORY_MAX_DEPTH`] 层; 无论是否 --sandbox, 都不能离开 mod 目录
+    pub fn list_directory(&self, path: &str) -> Result<Vec<String>> {
+        if !is_contained_path(path) {
+            anyhow::bail!("listDirectory path '{}' must stay inside the mod directory", path);
+        }
+        let mod_base = self.mod_path.canonicalize()
+            .with_context(|| format!("Failed to resolve mod directory: {}", self.mod_path.display()))?;
+        let dir = mod_base.join(path.replace('\\', "/"));
+        if !dir.is_dir() {
+            anyhow::bail!("Directory not found in mod: {}", path);
+        }
+
+        let mut files = Vec::new();
+        for entry in walkdir::WalkDir::new(&dir).min_depth(1).max_depth(LIST_DIRECTORY_MAX_DEPTH).follow_links(true) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            // 符号链接可能指向 mod 目录之外
+            let Ok(relative) = entry.path().canonicalize()?.strip_prefix(&mod_base).map(Path::to_path_buf) else {
+                tracing::warn!("listDirectory skipped '{}' (resolves outside the mod directory)", entry.path().display());
+                continue;
+            };
+            let relative: Vec<_> = relative.iter().map(|part| part.to_string_lossy()).collect();
+            files.push(relative.join("/"));
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
     /// 读取 TSV 文件
     pub fn read_tsv(&self, path: &str) -> Result<TsvData> {
+        self.check_sandbox("readTsv", path)?;
         let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
         let path = path.to_string();
 
         tokio::task::block_in_place(|| {
@@ -136,18 +479,18 @@ impl ScriptServices {
                 let mut fm = file_manager.write().await;
 
                 // Try to read from cache first
-                if let Ok(content) = fm.read_file_with_cache(&path, "script").await {
+                if let Ok(content) = fm.read_file_with_cache(&path, &owner).await {
                     let rows = crate::handlers::TsvHandler::parse_from_bytes(&content)?;
                     return Self::tsv_rows_to_data(rows);
                 }
 
                 // Extract from CASC if needed
-                let full_path = fm.ensure_extracted(&path, "script").await?;
+                let content = fm.ensure_extracted(&path, &owner).await?;
 
-                // Read the file using TsvHandler
-                let rows = crate::handlers::TsvHandler::read(&full_path).await?;
+                // Parse the file using TsvHandler
+                let rows = crate::handlers::TsvHandler::parse_from_bytes(&content)?;
 
-                fm.record_read(&path, "script");
+                fm.record_read(&path, &owner);
                 Self::tsv_rows_to_data(rows)
             })
         })
@@ -186,9 +529,61 @@ impl ScriptServices {
         })
     }
 
+    /// 逐行遍历 TSV 文件 (不构建整张表), 回调可修改当前行; 有修改时整体写回一次
+    ///
+    /// 按记录而不是物理行遍历: 引号内跨行的字段仍属于同一行。回调中设为空 (如 Lua 中设为 nil) 的列写为空字符串。返回被修改的行数
+    pub fn for_each_tsv_row(&self, path: &str, mut visit: impl FnMut(&mut TsvRow) -> Result<()>) -> Result<usize> {
+        self.check_sandbox("forEachTsvRow", path)?;
+        let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
+        let path = path.to_string();
+
+        // 回调会调用脚本 (脚本可能再调用其他 API), 因此读取后先释放文件管理器的锁
+        let content = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut fm = file_manager.write().await;
+                if let Ok(content) = fm.read_file_with_cache(&path, &owner).await {
+                    return Ok::<_, anyhow::Error>(content);
+                }
+                let content = fm.ensure_extracted(&path, &owner).await?;
+                fm.record_read(&path, &owner);
+                Ok(content)
+            })
+        })?;
+
+        let patched = crate::handlers::TsvHandler::map_rows(&content, crate::handlers::TsvHandler::format_row, |headers, fields| {
+            let mut row = TsvRow {
+                data: headers.iter().cloned().zip(fields.iter().cloned()).collect(),
+            };
+            visit(&mut row)?;
+            for (header, field) in headers.iter().zip(fields.iter_mut()) {
+                *field = row.data.remove(header).unwrap_or_default();
+            }
+            Ok(())
+        })?;
+
+        let Some((patched, changed)) = patched else {
+            return Ok(0);
+        };
+        if self.skip_in_dry_run("write TSV", &path) {
+            return Ok(changed);
+        }
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                file_manager.write().await.write_file_to_cache(&path, patched, &owner);
+            })
+        });
+        Ok(changed)
+    }
+
     /// 写入 TSV 文件
     pub fn write_tsv(&self, path: &str, data: &TsvData) -> Result<()> {
+        self.check_sandbox("writeTsv", path)?;
+        if self.skip_in_dry_run("write TSV", path) {
+            return Ok(());
+        }
         let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
         let path = path.to_string();
         let data = data.clone();
 
@@ -205,35 +600,88 @@ impl ScriptServices {
                     rows.push(row_vec);
                 }
 
-                // Convert to TSV string manually
+                let mut fm = file_manager.write().await;
+
+                // 增量模式: 只重写有变化的行, 其余行保留原始字节
+                if fm.incremental_tsv() {
+                    if let Some(baseline) = fm.peek_file(&path)? {
+                        if let Some(patched) = crate::handlers::TsvHandler::patch_rows(&baseline, &rows, crate::handlers::TsvHandler::format_row)? {
+                            fm.write_file_to_cache(&path, patched, &owner);
+                            return Ok(());
+                        }
+                    }
+                }
+
                 let content = rows.iter()
-                    .map(|row| {
-                        row.iter()
-                            .map(|field| {
-                                // Quote fields containing commas
-                                if field.contains(',') {
-                                    format!("\"{}\"", field)
-                                } else {
-                                    field.clone()
-                                }
-                            })
-                            .collect::<Vec<_>>()
-                            .join("\t")
-                    })
+                    .map(|row| crate::handlers::TsvHandler::format_row(row))
                     .collect::<Vec<_>>()
                     .join("\n");
-
-                let mut fm = file_manager.write().await;
-                fm.write_file_to_cache(&path, content.into_bytes(), "script");
+                fm.write_file_to_cache(&path, content.into_bytes(), &owner);
 
                 Ok(())
             })
         })
     }
 
+    /// 文件是否为游戏文件 (CASC/游戏目录), 而不是 mod 自带或仅由 mod 写入的文件
+    pub fn is_game_file(&self, path: &str) -> Result<bool> {
+        self.check_sandbox("isGameFile", path)?;
+        let file_manager = self.file_manager.clone();
+
+        Ok(tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                file_manager.read().await.is_game_file(path)
+            })
+        }))
+    }
+
+    /// 一次提取多个游戏文件 (只获取一次 FileManager 写锁)
+    ///
+    /// 返回 `{ extracted: [路径...], failed: { 路径: 原因 } }`; 单个文件失败 (包括 --sandbox
+    /// 拒绝的路径) 不影响其余文件。
+    pub fn extract_files(&self, paths: &[String]) -> Result<JsonValue> {
+        let mut failed = serde_json::Map::new();
+        let mut allowed = Vec::new();
+        for path in paths {
+            match self.check_sandbox("extractFiles", path) {
+                Ok(()) => allowed.push(path.as_str()),
+                Err(e) => {
+                    failed.insert(path.clone(), JsonValue::String(format!("{:#}", e)));
+                }
+            }
+        }
+        let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
+
+        let batch = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                file_manager.write().await.ensure_extracted_many(&allowed, &owner).await
+            })
+        })?;
+        for (path, error) in batch.failed {
+            failed.insert(path, JsonValue::String(error));
+        }
+        Ok(serde_json::json!({ "extracted": batch.extracted, "failed": failed }))
+    }
+
+    /// 按 CASC file data ID 读取游戏文件 (文本, 需为 UTF-8)
+    pub fn extract_file_by_id(&self, fdid: u32) -> Result<String> {
+        let file_manager = self.file_manager.clone();
+
+        let content = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                file_manager.read().await.read_game_file_by_id(fdid)
+            })
+        })?;
+        crate::handlers::decode_utf8_owned(content)
+            .with_context(|| format!("Failed to parse file data ID {} as UTF-8", fdid))
+    }
+
     /// 读取文本文件
     pub fn read_txt(&self, path: &str) -> Result<String> {
+        self.check_sandbox("readTxt", path)?;
         let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
         let path = path.to_string();
 
         tokio::task::block_in_place(|| {
@@ -241,65 +689,135 @@ impl ScriptServices {
                 let mut fm = file_manager.write().await;
 
                 // Try to read from cache first
-                if let Ok(content) = fm.read_file_with_cache(&path, "script").await {
-                    return String::from_utf8(content)
-                        .map_err(|e| anyhow::anyhow!("Invalid UTF-8: {}", e));
+                if let Ok(content) = fm.read_file_with_cache(&path, &owner).await {
+                    return crate::handlers::decode_text(&content)
+                        .map(|(text, _)| text)
+                        .with_context(|| format!("Failed to decode cached text '{}'", path));
                 }
 
                 // Extract from CASC if needed
-                let full_path = fm.ensure_extracted(&path, "script").await?;
+                let content = fm.ensure_extracted(&path, &owner).await?;
+                let (content, _) = crate::handlers::decode_text(&content)
+                    .with_context(|| format!("Failed to read text file '{}'", path))?;
 
-                // Read the file
-                let content = crate::handlers::TextHandler::read(&full_path).await?;
-
-                fm.record_read(&path, "script");
+                fm.record_read(&path, &owner);
                 Ok(content)
             })
         })
     }
 
-    /// 写入文本文件
+    /// 文件当前内容 (本次构建中已写入的版本, 否则为原版) 的 SHA-256 十六进制摘要
+    pub fn hash_file(&self, path: &str) -> Result<String> {
+        self.check_sandbox("hashFile", path)?;
+        let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
+        let path = path.to_string();
+
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut fm = file_manager.write().await;
+
+                if let Ok(content) = fm.read_file_with_cache(&path, &owner).await {
+                    return Ok(crate::report::hash_bytes(&content));
+                }
+
+                let content = fm.ensure_extracted(&path, &owner).await
+                    .with_context(|| format!("Failed to hash file '{}'", path))?;
+                fm.record_read(&path, &owner);
+                Ok(crate::report::hash_bytes(&content))
+            })
+        })
+    }
+
+    /// 写入文本文件 (内容原样写入)
     pub fn write_txt(&self, path: &str, content: &str) -> Result<()> {
+        self.write_txt_as(path, content, crate::handlers::TrailingNewline::Preserve)
+    }
+
+    /// 按结尾换行策略写入文本文件
+    ///
+    /// 沿用该文件当前内容的编码 (按 BOM 判断, 如 UTF-16 的本地化文件), 新文件为 UTF-8
+    pub fn write_txt_as(&self, path: &str, content: &str, policy: crate::handlers::TrailingNewline) -> Result<()> {
+        self.check_sandbox("writeTxt", path)?;
+        if self.skip_in_dry_run("write text", path) {
+            return Ok(());
+        }
         let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
         let path = path.to_string();
-        let content = content.to_string();
+        let content = crate::handlers::TextHandler::normalize_end(content, policy);
 
         tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(async {
                 let mut fm = file_manager.write().await;
-                fm.write_file_to_cache(&path, content.as_bytes().to_vec(), "script");
+                let encoding = fm
+                    .peek_file(&path)?
+                    .map(|existing| crate::handlers::TextEncoding::detect(&existing))
+                    .unwrap_or_default();
+                fm.write_file_to_cache(&path, encoding.encode(&content), &owner);
                 Ok(())
             })
         })
     }
 
+    /// 从输出中删除文件; 输出中没有该文件时什么也不做, 返回 false
+    pub fn delete_file(&self, path: &str) -> Result<bool> {
+        self.check_sandbox("deleteFile", path)?;
+        if self.skip_in_dry_run("delete", path) {
+            return Ok(false);
+        }
+        let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
+        let path = path.to_string();
+
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                file_manager.write().await.delete_file(&path, &owner)
+            })
+        })
+    }
+
     /// 复制文件或目录
     pub fn copy_file(&self, src: &str, dst: &str, _overwrite: bool) -> Result<()> {
         let file_manager = self.file_manager.clone();
+        let owner = self.owner().to_string();
         let src = src.to_string();
         let dst = dst.to_string();
 
         // D2RMM's copyFile can copy directories from the mod folder
         // Source is relative to mod folder, destination is relative to output
         let mod_base = self.mod_path.clone();
-        let output_base = self.output_path.clone();
+
+        self.check_sandbox("copyFile source", &src)?;
+        self.check_sandbox("copyFile destination", &dst)?;
+        if self.skip_in_dry_run("copy", &format!("{} -> {}", src, dst)) {
+            return Ok(());
+        }
 
         let src_path = mod_base.join(&src);
-        let dst_path = output_base.join(&dst);
 
-        tracing::debug!("copyFile: {} -> {}", src_path.display(), dst_path.display());
+        // Symlinks inside the mod could still point outside of it
+        if self.sandbox && src_path.exists() {
+            let real_src = src_path.canonicalize()?;
+            if !real_src.starts_with(mod_base.canonicalize()?) {
+                tracing::warn!("Sandbox blocked copyFile of '{}' (resolves outside the mod directory)", src);
+                anyhow::bail!("copyFile blocked by --sandbox: '{}' resolves outside the mod directory", src);
+            }
+        }
+
+        tracing::debug!("copyFile: {} -> {}", src_path.display(), dst);
+
+        // mod 目录中的文件直接写入输出 (记录为写入, 以便构建报告知道由哪个 mod 生成)
+        let mut copied = Vec::new();
 
         if src_path.is_dir() {
             // Copy entire directory recursively
             tracing::debug!("Copying directory recursively");
-            copy_dir_recursive(&src_path, &dst_path)?;
+            collect_files_recursive(&src_path, dst.trim_end_matches(['/', '\\']), &mut copied)?;
         } else if src_path.is_file() {
             // Copy single file
             tracing::debug!("Copying single file");
-            if let Some(parent) = dst_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-            std::fs::copy(&src_path, &dst_path)?;
+            copied.push((src_path, dst));
         } else {
             // Maybe it's a CASC file path?
             let result: Result<()> = tokio::task::block_in_place(|| {
@@ -307,15 +825,14 @@ impl ScriptServices {
                     let mut fm = file_manager.write().await;
 
                     // Read source file (may extract from CASC)
-                    let content = if let Ok(cached) = fm.read_file_with_cache(&src, "script").await {
+                    let content = if let Ok(cached) = fm.read_file_with_cache(&src, &owner).await {
                         cached
                     } else {
-                        let full_path = fm.ensure_extracted(&src, "script").await?;
-                        tokio::fs::read(&full_path).await?
+                        fm.ensure_extracted(&src, &owner).await?
                     };
 
                     // Write to destination in cache
-                    fm.write_file_to_cache(&dst, content, "script");
+                    fm.write_file_to_cache(&dst, content, &owner);
 
                     Ok(())
                 })
@@ -323,25 +840,186 @@ impl ScriptServices {
             result?;
         }
 
+        if !copied.is_empty() {
+            tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(async {
+                    let mut fm = file_manager.write().await;
+                    for (src_path, dst) in &copied {
+                        let content = std::fs::read(src_path)?;
+                        fm.write_file_direct(dst, &content, &owner)?;
+                    }
+                    Ok::<_, anyhow::Error>(())
+                })
+            })?;
+        }
+
         Ok(())
     }
 }
 
-/// 递归复制目录
-fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
-    std::fs::create_dir_all(dst)?;
+/// 收集字符串表条目中的语言代码列 (排除 "id"/"Key" 等非语言列)
+fn collect_languages(table: &JsonValue) -> Vec<String> {
+    let mut languages: Vec<String> = table
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.as_object())
+        .flat_map(|entry| entry.keys())
+        .filter(|key| is_language_code(key))
+        .cloned()
+        .collect();
+    languages.sort();
+    languages.dedup();
+    languages
+}
+
+/// 语言代码形如 "enUS": 两个小写字母 + 两个大写字母
+fn is_language_code(key: &str) -> bool {
+    let bytes = key.as_bytes();
+    bytes.len() == 4
+        && bytes[..2].iter().all(u8::is_ascii_lowercase)
+        && bytes[2..].iter().all(u8::is_ascii_uppercase)
+}
+
+/// 相对路径是否停留在基准目录内 (无绝对路径/盘符, `..` 不越过根)
+fn is_contained_path(path: &str) -> bool {
+    let normalized = path.replace('\\', "/");
+    if normalized.starts_with('/') {
+        return false;
+    }
+
+    let mut depth = 0usize;
+    for part in normalized.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => match depth.checked_sub(1) {
+                Some(d) => depth = d,
+                None => return false,
+            },
+            // 盘符 (C:) 或其他前缀
+            p if p.contains(':') => return false,
+            _ => depth += 1,
+        }
+    }
+    true
+}
 
+/// 递归收集目录中的文件, (源文件路径, 输出中的目标路径) 追加到 `files`
+fn collect_files_recursive(src: &Path, dst: &str, files: &mut Vec<(PathBuf, String)>) -> Result<()> {
     for entry in std::fs::read_dir(src)? {
         let entry = entry?;
         let src_path = entry.path();
-        let dst_path = dst.join(entry.file_name());
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let dst_path = if dst.is_empty() { name } else { format!("{}/{}", dst, name) };
 
         if src_path.is_dir() {
-            copy_dir_recursive(&src_path, &dst_path)?;
+            collect_files_recursive(&src_path, &dst_path, files)?;
         } else {
-            std::fs::copy(&src_path, &dst_path)?;
+            files.push((src_path, dst_path));
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dry_run_skips_writes() {
+        use crate::file_system::OutputStore as _;
+
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(mod_dir.path().join("icon.png"), b"png").unwrap();
+
+        let output = crate::file_system::MemoryStore::new();
+        let mut fm = crate::file_system::FileManager::new();
+        fm.set_output_store(output.clone());
+        let fm = std::sync::Arc::new(tokio::sync::RwLock::new(fm));
+
+        let mut services =
+            ScriptServices::new(mod_dir.path().to_path_buf(), PathBuf::new(), PathBuf::new(), fm.clone());
+        services.dry_run = true;
+
+        services.write_json("a.json", &serde_json::json!({ "x": 1 })).unwrap();
+        services.write_txt("b.txt", "text").unwrap();
+        let tsv = TsvData { headers: vec!["Name".to_string()], rows: Vec::new() };
+        services.write_tsv("c.txt", &tsv).unwrap();
+        services.copy_file("icon.png", "d/icon.png", true).unwrap();
+
+        let mut fm = fm.write().await;
+        for path in ["a.json", "b.txt", "c.txt", "d/icon.png"] {
+            assert!(fm.peek_file(path).unwrap().is_none(), "{} was written", path);
+        }
+        fm.flush_cache().await.unwrap();
+        assert!(output.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_glob() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        for file in ["templates/a.json", "templates/nested/b.json", "templates/c.txt", "mod.lua"] {
+            let path = mod_dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "{}").unwrap();
+        }
+        let fm = std::sync::Arc::new(tokio::sync::RwLock::new(crate::file_system::FileManager::new()));
+        let services = ScriptServices::new(mod_dir.path().to_path_buf(), PathBuf::new(), PathBuf::new(), fm);
+
+        assert_eq!(services.glob("templates/*.json").unwrap(), vec!["templates/a.json"]);
+        assert_eq!(
+            services.glob("templates/**/*.json").unwrap(),
+            vec!["templates/a.json", "templates/nested/b.json"]
+        );
+        assert_eq!(services.glob("templates\\*.txt").unwrap(), vec!["templates/c.txt"]);
+        assert!(services.glob("*.png").unwrap().is_empty());
+        assert!(services.glob("../*").is_err());
+        assert!(services.glob("/etc/*").is_err());
+    }
+
+    #[test]
+    fn test_list_directory() {
+        let mod_dir = tempfile::TempDir::new().unwrap();
+        for file in ["sprites/a.dc6", "sprites/ui/b.dc6", "sprites/ui/c.json", "mod.lua"] {
+            let path = mod_dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let fm = std::sync::Arc::new(tokio::sync::RwLock::new(crate::file_system::FileManager::new()));
+        let services = ScriptServices::new(mod_dir.path().to_path_buf(), PathBuf::new(), PathBuf::new(), fm);
+
+        assert_eq!(
+            services.list_directory("sprites").unwrap(),
+            vec!["sprites/a.dc6", "sprites/ui/b.dc6", "sprites/ui/c.json"]
+        );
+        assert_eq!(services.list_directory("sprites\\ui/").unwrap().len(), 2);
+        assert_eq!(services.list_directory("").unwrap().len(), 4);
+        assert!(services.list_directory("missing").is_err());
+        assert!(services.list_directory("sprites/a.dc6").is_err());
+        assert!(services.list_directory("../").is_err());
+        assert!(services.list_directory("sprites/../..").is_err());
+    }
+
+    #[test]
+    fn test_collect_languages() {
+        let table = serde_json::json!([
+            { "id": 1, "Key": "hp1", "enUS": "Minor Healing Potion", "zhCN": "轻微治疗药剂", "deDE": "x" },
+            { "id": 2, "Key": "hp2", "enUS": "Light Healing Potion", "zhTW": "y" }
+        ]);
+
+        assert_eq!(collect_languages(&table), vec!["deDE", "enUS", "zhCN", "zhTW"]);
+        assert!(collect_languages(&serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_is_contained_path() {
+        assert!(is_contained_path("global\\excel\\misc.json"));
+        assert!(is_contained_path("assets/../data/./x.json"));
+        assert!(!is_contained_path("../other_mod/mod.lua"));
+        assert!(!is_contained_path("data/../../x.json"));
+        assert!(!is_contained_path("/etc/passwd"));
+        assert!(!is_contained_path("C:\\Windows\\win.ini"));
+        assert!(!is_contained_path("\\\\server\\share\\x"));
+    }
+}
@@ -0,0 +1,261 @@
+//! Persistent string ID assignments
+//!
+//! Mods that add string table entries need numeric IDs that don't clash with the
+//! game's. Handing out "max ID + 1" works within one build but drifts between builds
+//! (a different mod order or a game patch shifts every ID after it), which breaks
+//! anything that stored the old ID. The manifest remembers which ID each mod's
+//! logical string name received, and the allocator hands the same ID back on the
+//! next build as long as the game hasn't claimed it in the meantime.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest, written next to the output directory
+pub const MANIFEST_FILE_NAME: &str = "infinite-string-ids.json";
+
+/// One remembered assignment
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StringIdEntry {
+    pub id: u64,
+    /// Build counter value of the last build that requested this name
+    pub last_build: u64,
+}
+
+/// IDs assigned to (mod id, logical name) pairs across builds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StringIdManifest {
+    /// Incremented once per build that uses the manifest
+    pub build: u64,
+    /// Mod id -> logical name -> assignment
+    pub mods: BTreeMap<String, BTreeMap<String, StringIdEntry>>,
+}
+
+impl StringIdManifest {
+    /// Path of the manifest for a given output path (its parent directory, so clearing
+    /// the output doesn't lose it)
+    pub fn path_for(output_path: &Path) -> PathBuf {
+        output_path
+            .parent()
+            .unwrap_or(output_path)
+            .join(MANIFEST_FILE_NAME)
+    }
+
+    /// Load the manifest belonging to an output directory; missing means empty
+    pub fn load(output_path: &Path) -> Result<Self> {
+        let path = Self::path_for(output_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read string ID manifest: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse string ID manifest: {}", path.display()))
+    }
+
+    /// Load the manifest for a build that will save it again
+    ///
+    /// Unlike [`load`](Self::load) falling back to an empty manifest, a manifest that
+    /// can't be read or parsed stops the build: saving over it would forget every
+    /// remembered ID.
+    pub fn load_for_build(output_path: &Path) -> Result<Self> {
+        Self::load(output_path).with_context(|| {
+            format!(
+                "The string ID manifest is unreadable; fix or move away {} (a new one \
+                 assigns different IDs)",
+                Self::path_for(output_path).display()
+            )
+        })
+    }
+
+    /// Write the manifest next to the output directory
+    ///
+    /// Written atomically, so a crash mid-write leaves the previous manifest intact.
+    pub fn save(&self, output_path: &Path) -> Result<PathBuf> {
+        let path = Self::path_for(output_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        crate::file_system::write_atomic(&path, content.as_bytes())
+            .with_context(|| format!("Failed to write string ID manifest: {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Number of remembered assignments
+    pub fn len(&self) -> usize {
+        self.mods.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop assignments the most recent build didn't request; returns how many were removed
+    pub fn compact(&mut self) -> usize {
+        let build = self.build;
+        let before = self.len();
+        for names in self.mods.values_mut() {
+            names.retain(|_, entry| entry.last_build >= build);
+        }
+        self.mods.retain(|_, names| !names.is_empty());
+        before - self.len()
+    }
+
+    fn max_id(&self) -> Option<u64> {
+        self.mods
+            .values()
+            .flat_map(|names| names.values().map(|entry| entry.id))
+            .max()
+    }
+}
+
+/// Hands out string IDs for one build
+///
+/// The IDs already present in the string tables must be supplied with
+/// [`set_used_ids`](Self::set_used_ids) before the first allocation.
+#[derive(Debug)]
+pub struct StringIdAllocator {
+    manifest: StringIdManifest,
+    used: Option<BTreeSet<u64>>,
+    next: u64,
+}
+
+impl StringIdAllocator {
+    /// Start a build on top of a previously saved manifest
+    pub fn new(mut manifest: StringIdManifest) -> Self {
+        manifest.build += 1;
+        Self {
+            manifest,
+            used: None,
+            next: 0,
+        }
+    }
+
+    /// Whether the string tables have been scanned yet
+    pub fn has_used_ids(&self) -> bool {
+        self.used.is_some()
+    }
+
+    /// Record the IDs taken by the string tables; ignored once set
+    pub fn set_used_ids(&mut self, used: BTreeSet<u64>) {
+        if self.used.is_some() {
+            return;
+        }
+        let table_max = used.iter().next_back().copied().unwrap_or(0);
+        let manifest_max = self.manifest.max_id().unwrap_or(0);
+        self.next = table_max.max(manifest_max) + 1;
+        self.used = Some(used);
+    }
+
+    /// Next unused ID, not remembered across builds
+    pub fn next_id(&mut self) -> u64 {
+        let used = self.used.get_or_insert_with(BTreeSet::new);
+        while used.contains(&self.next) {
+            self.next += 1;
+        }
+        let id = self.next;
+        used.insert(id);
+        self.next += 1;
+        id
+    }
+
+    /// ID for `name` within `mod_id`, reusing the previous build's assignment when possible
+    pub fn allocate(&mut self, mod_id: &str, name: &str) -> u64 {
+        let build = self.manifest.build;
+        let previous = self
+            .manifest
+            .mods
+            .get(mod_id)
+            .and_then(|names| names.get(name))
+            .copied();
+
+        if let Some(entry) = previous {
+            if entry.last_build == build {
+                return entry.id;
+            }
+            let used = self.used.get_or_insert_with(BTreeSet::new);
+            if used.insert(entry.id) {
+                self.remember(mod_id, name, entry.id);
+                return entry.id;
+            }
+            tracing::warn!(
+                "String ID {} for '{}' in mod '{}' is now used by the string tables; assigning a new one",
+                entry.id,
+                name,
+                mod_id
+            );
+        }
+
+        let id = self.next_id();
+        self.remember(mod_id, name, id);
+        id
+    }
+
+    fn remember(&mut self, mod_id: &str, name: &str, id: u64) {
+        let last_build = self.manifest.build;
+        self.manifest
+            .mods
+            .entry(mod_id.to_string())
+            .or_default()
+            .insert(name.to_string(), StringIdEntry { id, last_build });
+    }
+
+    /// The manifest including this build's assignments
+    pub fn manifest(&self) -> &StringIdManifest {
+        &self.manifest
+    }
+}
+
+impl Default for StringIdAllocator {
+    fn default() -> Self {
+        Self::new(StringIdManifest::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_allocations_are_stable_across_builds() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("Infinite.mpq/data");
+
+        let mut first = StringIdAllocator::default();
+        first.set_used_ids([1, 2, 50].into_iter().collect());
+        assert_eq!(first.allocate("a", "greeting"), 51);
+        assert_eq!(first.next_id(), 52);
+        assert_eq!(first.allocate("b", "greeting"), 53);
+        assert_eq!(first.allocate("a", "greeting"), 51);
+        first.manifest().save(&output).unwrap();
+
+        // Mod "a" is gone and the game now uses 53: "b" moves, new names go above everything
+        let mut second = StringIdAllocator::new(StringIdManifest::load(&output).unwrap());
+        second.set_used_ids([1, 2, 50, 53].into_iter().collect());
+        assert_eq!(second.allocate("b", "greeting"), 54);
+        assert_eq!(second.allocate("b", "farewell"), 55);
+
+        let mut manifest = second.manifest().clone();
+        assert_eq!(manifest.len(), 3);
+        assert_eq!(manifest.compact(), 1);
+        assert!(!manifest.mods.contains_key("a"));
+        assert_eq!(manifest.mods["b"]["greeting"].id, 54);
+    }
+
+    #[test]
+    fn test_corrupt_manifest_is_not_replaced() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("Infinite.mpq/data");
+        assert!(StringIdManifest::load_for_build(&output).unwrap().is_empty());
+
+        let path = StringIdManifest::path_for(&output);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "{ \"build\": 3, \"mods\": {").unwrap();
+        let err = format!("{:#}", StringIdManifest::load_for_build(&output).unwrap_err());
+        assert!(err.contains("fix or move away") && err.contains("Failed to parse"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ \"build\": 3, \"mods\": {");
+    }
+}